@@ -1,9 +1,8 @@
 use std::fmt::{self, Debug, Formatter};
 use std::mem;
-use std::num::Int;
 use std::sync::Arc;
 
-use raw::{Allocator, RawIobuf};
+use raw::{Allocator, Prim, RawIobuf};
 use iobuf::{Iobuf};
 use impls::{AROIobuf};
 
@@ -324,7 +323,7 @@ impl<'a> AppendBuf<'a> {
   /// unsafe { assert_eq!(b.as_window_slice(), expected); }
   /// ```
   #[inline(always)]
-  pub fn poke_be<T: Int>(&self, pos: u32, t: T) -> Result<(), ()> { self.raw.poke_be(pos, t) }
+  pub fn poke_be<T: Prim>(&self, pos: u32, t: T) -> Result<(), ()> { self.raw.poke_be(pos, t) }
 
   /// Writes a little-endian primitive at a given offset from the beginning of
   /// the window.
@@ -345,7 +344,7 @@ impl<'a> AppendBuf<'a> {
   /// unsafe { assert_eq!(b.as_window_slice(), [ 4, 5, 5, 9, 8, 7, 6 ]); }
   /// ```
   #[inline(always)]
-  pub fn poke_le<T: Int>(&self, pos: u32, t: T) -> Result<(), ()> { self.raw.poke_le(pos, t) }
+  pub fn poke_le<T: Prim>(&self, pos: u32, t: T) -> Result<(), ()> { self.raw.poke_le(pos, t) }
 
   /// Writes bytes from the supplied buffer, starting from the front of the
   /// window. Either the entire buffer is copied, or an error is returned
@@ -396,7 +395,7 @@ impl<'a> AppendBuf<'a> {
   ///                      , 0x88, 0x77 ]); }
   /// ```
   #[inline(always)]
-  pub fn fill_be<T: Int>(&mut self, t: T) -> Result<(), ()> { self.raw.fill_be(t) }
+  pub fn fill_be<T: Prim>(&mut self, t: T) -> Result<(), ()> { self.raw.fill_be(t) }
 
   /// Writes a little-endian primitive into the beginning of the window.
   ///
@@ -422,7 +421,7 @@ impl<'a> AppendBuf<'a> {
   ///                      , 0x77, 0x88 ]); }
   /// ```
   #[inline(always)]
-  pub fn fill_le<T: Int>(&mut self, t: T) -> Result<(), ()> { self.raw.fill_le(t) }
+  pub fn fill_le<T: Prim>(&mut self, t: T) -> Result<(), ()> { self.raw.fill_le(t) }
 
   /// Advances the lower bound of the window by `len`. `Err(())` will be
   /// returned if you advance past the upper bound of the window.
@@ -0,0 +1,163 @@
+//! `quickcheck` support. Enabled with the `quickcheck` cargo feature.
+//!
+//! Generated Iobufs have random contents, random limits somewhere inside the
+//! backing buffer, and a random window somewhere inside those limits. Lots of
+//! bugs only show up when the lower limit isn't zero, so this is much more
+//! useful than wrapping a random `Vec<u8>`.
+
+use quickcheck::{Arbitrary, Gen, Shrinker};
+
+use iobuf::Iobuf;
+use impls::{ROIobuf, RWIobuf};
+
+/// Picks random limits and a random window for a buffer of `len` bytes.
+fn arbitrary_geometry<G: Gen>(g: &mut G, len: u32) -> ((u32, u32), (u32, u32)) {
+  let (a, b, c, d): (u32, u32, u32, u32) = Arbitrary::arbitrary(g);
+
+  let hi_max = a % (len + 1);
+  let lo_min = b % (hi_max + 1);
+  let hi     = lo_min + c % (hi_max - lo_min + 1);
+  let lo     = lo_min + d % (hi - lo_min + 1);
+
+  ((lo_min, hi_max), (lo, hi))
+}
+
+fn arbitrary_rw<G: Gen>(g: &mut G) -> RWIobuf<'static> {
+  let data: Vec<u8> = Arbitrary::arbitrary(g);
+  let (limits, window) = arbitrary_geometry(g, data.len() as u32);
+
+  let mut buf = RWIobuf::from_slice_copy(&data[]);
+  buf.set_limits_and_window(limits, window).unwrap();
+  buf
+}
+
+/// Pushes clones of `b` with narrower limits or smaller windows onto `v`.
+fn shrink_geometry<B: Iobuf>(b: &B, v: &mut Vec<B>) {
+  let (lo_min, lo, hi, hi_max) = (b.lo_min(), b.lo(), b.hi(), b.hi_max());
+
+  let mut push = |limits: (u32, u32), window: (u32, u32)| {
+    let mut c = b.clone();
+    c.set_limits_and_window(limits, window).unwrap();
+    v.push(c);
+  };
+
+  // Throw away the contents outside the window.
+  if lo > lo_min { push((lo, hi_max), (lo, hi)); }
+  if hi < hi_max { push((lo_min, hi), (lo, hi)); }
+
+  // Shrink the window itself.
+  if hi > lo {
+    push((lo_min, hi_max), (lo, lo + (hi - lo) / 2));
+    push((lo_min, hi_max), (lo + 1, hi));
+    push((lo_min, hi_max), (lo, hi - 1));
+  }
+}
+
+/// The offsets of the window, relative to the lower limit.
+fn relative_window<B: Iobuf>(b: &B) -> ((u32, u32), (u32, u32)) {
+  let lo_min = b.lo_min();
+  ((0, b.cap()), (b.lo() - lo_min, b.hi() - lo_min))
+}
+
+impl Arbitrary for RWIobuf<'static> {
+  fn arbitrary<G: Gen>(g: &mut G) -> RWIobuf<'static> {
+    arbitrary_rw(g)
+  }
+
+  fn shrink(&self) -> Box<Shrinker<RWIobuf<'static>>+'static> {
+    let mut v = vec!();
+
+    // Drop the parts of the backing buffer outside the limits.
+    if self.lo_min() != 0 {
+      let (limits, window) = relative_window(self);
+      let mut b = RWIobuf::from_slice_copy(unsafe { self.as_limit_slice() });
+      b.set_limits_and_window(limits, window).unwrap();
+      v.push(b);
+    }
+
+    shrink_geometry(self, &mut v);
+
+    Box::new(v.into_iter())
+  }
+}
+
+impl Arbitrary for ROIobuf<'static> {
+  fn arbitrary<G: Gen>(g: &mut G) -> ROIobuf<'static> {
+    arbitrary_rw(g).read_only()
+  }
+
+  fn shrink(&self) -> Box<Shrinker<ROIobuf<'static>>+'static> {
+    let mut v = vec!();
+
+    // Drop the parts of the backing buffer outside the limits.
+    if self.lo_min() != 0 {
+      let (limits, window) = relative_window(self);
+      let mut b = ROIobuf::from_slice_copy(unsafe { self.as_limit_slice() });
+      b.set_limits_and_window(limits, window).unwrap();
+      v.push(b);
+    }
+
+    shrink_geometry(self, &mut v);
+
+    Box::new(v.into_iter())
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::iter::repeat;
+
+  use quickcheck::quickcheck;
+
+  use iobuf::Iobuf;
+  use impls::{ROIobuf, RWIobuf};
+
+  #[test]
+  fn prop_valid_hi() {
+    fn test_hi(v: ROIobuf<'static>) -> bool {
+      v.hi() <= v.hi_max()
+    }
+    quickcheck(test_hi as fn(ROIobuf<'static>) -> bool)
+  }
+
+  #[test]
+  fn prop_valid_bounds() {
+    fn test_bounds(v: ROIobuf<'static>) -> bool {
+      v.lo() <= v.hi()
+    }
+    quickcheck(test_bounds as fn(ROIobuf<'static>) -> bool)
+  }
+
+  #[test]
+  fn prop_valid_lo() {
+    fn test_lo(v: ROIobuf<'static>) -> bool {
+      v.lo_min() <= v.lo()
+    }
+    quickcheck(test_lo as fn(ROIobuf<'static>) -> bool)
+  }
+
+  #[test]
+  fn prop_fill_flip_consume() {
+    fn fill_flip_consume(mut b: RWIobuf<'static>, data: Vec<u8>) -> bool {
+      let lo_space = b.lo_space();
+      let len      = b.len();
+
+      if b.fill(&data[]).is_err() {
+        // Failed fills must not touch the window.
+        return data.len() > len as usize && b.len() == len;
+      }
+
+      b.flip_lo();
+
+      // `flip_lo` brings back whatever was below the window, too.
+      if b.advance(lo_space).is_err() { return false; }
+
+      let mut out: Vec<u8> = repeat(0u8).take(data.len()).collect();
+
+      b.consume(&mut out[]) == Ok(())
+      && out == data
+      && b.is_empty()
+    }
+    quickcheck(fill_flip_consume as fn(RWIobuf<'static>, Vec<u8>) -> bool)
+  }
+}
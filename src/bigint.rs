@@ -0,0 +1,88 @@
+//! Arbitrary-length big-endian integers, as used by RSA and Diffie-Hellman
+//! fields. Enabled with the `num-bigint` cargo feature.
+
+use num_bigint::BigUint;
+
+use raw::RawIobuf;
+
+impl<'a> RawIobuf<'a> {
+  #[inline]
+  pub fn peek_biguint_be(&self, pos: u32, len: u32) -> Result<BigUint, ()> {
+    unsafe {
+      try!(self.check_range_u32(pos, len));
+      let s = self.as_window_slice();
+      Ok(BigUint::from_bytes_be(&s[pos as usize..(pos + len) as usize]))
+    }
+  }
+
+  #[inline]
+  pub fn consume_biguint_be(&mut self, len: u32) -> Result<BigUint, ()> {
+    let ret = try!(self.peek_biguint_be(0, len));
+    unsafe { self.unsafe_advance(len); }
+    Ok(ret)
+  }
+
+  pub fn poke_biguint_be(&self, pos: u32, n: &BigUint, len: u32) -> Result<(), ()> {
+    let bytes = n.to_bytes_be();
+    // `to_bytes_be` gives `[0]` for zero, and no leading zeros otherwise.
+    let skip = bytes.iter().take_while(|&&b| b == 0).count();
+    let bytes = &bytes[skip..];
+    if bytes.len() > len as usize { return Err(()) }
+    unsafe {
+      try!(self.check_range_u32(pos, len));
+      let s = &mut self.as_mut_window_slice()[pos as usize..(pos + len) as usize];
+      let pad = s.len() - bytes.len();
+      for x in s[..pad].iter_mut() { *x = 0; }
+      s[pad..].clone_from_slice(bytes);
+    }
+    Ok(())
+  }
+
+  #[inline]
+  pub fn fill_biguint_be(&mut self, n: &BigUint, len: u32) -> Result<(), ()> {
+    try!(self.poke_biguint_be(0, n, len));
+    unsafe { self.unsafe_advance(len); }
+    Ok(())
+  }
+}
+
+#[test]
+fn biguint_round_trips() {
+  use std::num::FromPrimitive;
+
+  use iobuf::Iobuf;
+  use impls::RWIobuf;
+
+  let one: BigUint = FromPrimitive::from_u64(1).unwrap();
+  let big = (one.clone() << 1000) - one.clone();    // 125 bytes of 0xFF.
+  let odd = (one.clone() << 520) + one.clone();     // 66 bytes, 0x01 ... 0x01.
+  let zero: BigUint = FromPrimitive::from_u64(0).unwrap();
+
+  for &(ref n, len) in [ (big.clone(), 125u32), (big.clone(), 256),
+                         (odd.clone(), 66), (odd.clone(), 128),
+                         (zero.clone(), 0), (zero.clone(), 32) ].iter() {
+    let mut b = RWIobuf::new(len as usize);
+    assert_eq!(b.fill_biguint_be(n, len), Ok(()));
+    assert!(b.is_empty());
+    b.flip_lo();
+    assert_eq!(b.len(), len);
+    assert_eq!(b.peek_biguint_be(0, len).as_ref(), Ok(n));
+    assert_eq!(b.consume_biguint_be(len).as_ref(), Ok(n));
+    assert!(b.is_empty());
+  }
+
+  // Too big for the requested length.
+  let mut b = RWIobuf::new(200);
+  assert_eq!(b.fill_biguint_be(&big, 124), Err(()));
+  assert_eq!(b.len(), 200);
+
+  // Not enough room in the window.
+  let mut b = RWIobuf::new(10);
+  assert_eq!(b.fill_biguint_be(&one, 11), Err(()));
+  assert_eq!(b.consume_biguint_be(11), Err(()));
+  assert_eq!(b.len(), 10);
+
+  // Leading zeros on the wire are dropped.
+  let b = RWIobuf::from_slice_copy(&[ 0, 0, 0, 1, 0 ]);
+  assert_eq!(b.peek_biguint_be(0, 5), Ok(FromPrimitive::from_u64(256).unwrap()));
+}
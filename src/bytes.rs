@@ -0,0 +1,110 @@
+use iobuf::Iobuf;
+
+/// An iterator which eats bytes off the front of an Iobuf's window. See
+/// `Iobuf::drain`.
+pub struct Drain<'b, Buf: 'b> {
+  buf:  &'b mut Buf,
+  // The next byte to hand out, and how many are left. The window is kept in
+  // step, but reading through this skips its bounds checks.
+  next: *const u8,
+  left: u32,
+}
+
+impl<'b, Buf: Iobuf> Drain<'b, Buf> {
+  /// Drains bytes from the front of `buf`'s window.
+  #[inline]
+  pub fn new(buf: &'b mut Buf) -> Drain<'b, Buf> {
+    let (next, left) = unsafe { (buf.as_window_slice().as_ptr(), buf.len()) };
+    Drain { buf: buf, next: next, left: left }
+  }
+}
+
+impl<'b, Buf: Iobuf> Iterator for Drain<'b, Buf> {
+  type Item = u8;
+
+  #[inline]
+  fn next(&mut self) -> Option<u8> {
+    if self.left == 0 { return None; }
+    unsafe {
+      let c = *self.next;
+      self.next = self.next.offset(1);
+      self.left -= 1;
+      self.buf.unsafe_advance(1);
+      Some(c)
+    }
+  }
+
+  #[inline]
+  fn size_hint(&self) -> (usize, Option<usize>) {
+    (self.left as usize, Some(self.left as usize))
+  }
+}
+
+/// An iterator which owns an Iobuf, and eats bytes off the front of its
+/// window. See `Iobuf::into_bytes`.
+pub struct IntoBytes<Buf> {
+  buf:  Buf,
+  // See `Drain`.
+  next: *const u8,
+  left: u32,
+}
+
+impl<Buf: Iobuf> IntoBytes<Buf> {
+  /// Iterates over the bytes in `buf`'s window.
+  #[inline]
+  pub fn new(buf: Buf) -> IntoBytes<Buf> {
+    let (next, left) = unsafe { (buf.as_window_slice().as_ptr(), buf.len()) };
+    IntoBytes { buf: buf, next: next, left: left }
+  }
+
+  /// Gives back the Iobuf. Its window holds the bytes that haven't been
+  /// iterated over yet.
+  #[inline(always)]
+  pub fn into_inner(self) -> Buf { self.buf }
+}
+
+impl<Buf: Iobuf> Iterator for IntoBytes<Buf> {
+  type Item = u8;
+
+  #[inline]
+  fn next(&mut self) -> Option<u8> {
+    if self.left == 0 { return None; }
+    unsafe {
+      let c = *self.next;
+      self.next = self.next.offset(1);
+      self.left -= 1;
+      self.buf.unsafe_advance(1);
+      Some(c)
+    }
+  }
+
+  #[inline]
+  fn size_hint(&self) -> (usize, Option<usize>) {
+    (self.left as usize, Some(self.left as usize))
+  }
+}
+
+#[test]
+fn drain_stops_early() {
+  use impls::ROIobuf;
+
+  let mut b = ROIobuf::from_str("abc:def");
+
+  let mut name = Vec::new();
+  for c in b.drain() {
+    if c == b':' { break; }
+    name.push(c);
+  }
+  assert_eq!(&name[], b"abc");
+  unsafe { assert_eq!(b.as_window_slice(), b"def"); }
+
+  assert_eq!(b.drain().size_hint(), (3, Some(3)));
+  assert_eq!(b.drain().count(), 3);
+  assert!(b.is_empty());
+  assert_eq!(b.drain().next(), None);
+
+  let mut it = ROIobuf::from_str("xyz").into_bytes();
+  assert_eq!(it.next(), Some(b'x'));
+  let rest = it.into_inner();
+  unsafe { assert_eq!(rest.as_window_slice(), b"yz"); }
+}
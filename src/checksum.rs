@@ -0,0 +1,302 @@
+use raw::{Prim, prim_len};
+use iobuf::Iobuf;
+use impls::RWIobuf;
+
+/// A running checksum, which bytes are fed into a piece at a time. See
+/// `Checksummed`.
+pub trait Summer {
+  /// Adds `bytes` to the end of the data checksummed so far.
+  fn update(&mut self, bytes: &[u8]);
+
+  /// The checksum of all the data added since the last `reset`.
+  fn sum(&self) -> u32;
+
+  /// Starts over, as if no data had been added.
+  fn reset(&mut self);
+}
+
+/// The Adler-32 checksum, as used by zlib.
+///
+/// ```rust
+/// use iobuf::{Adler32, Summer};
+///
+/// let mut s = Adler32::new();
+/// s.update(b"Wiki");
+/// s.update(b"pedia");
+/// assert_eq!(s.sum(), 0x11E60398);
+/// ```
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Adler32 {
+  a: u32,
+  b: u32,
+}
+
+const ADLER_MOD: u32 = 65521;
+
+// The most bytes which can be summed before `b` might overflow a u32.
+const ADLER_NMAX: usize = 5552;
+
+impl Adler32 {
+  /// The checksum of no data.
+  #[inline]
+  pub fn new() -> Adler32 { Adler32 { a: 1, b: 0 } }
+}
+
+impl Summer for Adler32 {
+  fn update(&mut self, bytes: &[u8]) {
+    for chunk in bytes.chunks(ADLER_NMAX) {
+      for &c in chunk.iter() {
+        self.a += c as u32;
+        self.b += self.a;
+      }
+      self.a %= ADLER_MOD;
+      self.b %= ADLER_MOD;
+    }
+  }
+
+  #[inline]
+  fn sum(&self) -> u32 { self.b << 16 | self.a }
+
+  #[inline]
+  fn reset(&mut self) { *self = Adler32::new() }
+}
+
+/// An Iobuf which keeps a running checksum of every byte consumed from it or
+/// filled into it, for protocols which checksum a whole stretch of traffic.
+///
+/// Peeks and pokes don't move the window, so by default they aren't
+/// checksummed. Use `include_peeks` to change that. The checksum is kept in
+/// this wrapper, so anything done through `get_mut` isn't checksummed, but
+/// moving the window or compacting through it is fine.
+///
+/// ```rust
+/// use iobuf::{Adler32, Checksummed, Iobuf, ROIobuf, Summer};
+///
+/// let mut b = Checksummed::new(ROIobuf::from_str("hdr:Wikipedia"), Adler32::new());
+///
+/// assert_eq!(b.get_mut().advance(4), Ok(())); // The header isn't included.
+/// assert_eq!(b.consume_be::<u32>(), Ok(0x57696b69)); // "Wiki"
+/// assert_eq!(b.advance(5), Ok(()));                    // "pedia"
+/// assert_eq!(b.sum(), 0x11E60398);
+/// ```
+pub struct Checksummed<Buf, S> {
+  buf:           Buf,
+  summer:        S,
+  include_peeks: bool,
+}
+
+impl<Buf: Iobuf, S: Summer> Checksummed<Buf, S> {
+  /// Starts checksumming the traffic through `buf` with `summer`.
+  #[inline]
+  pub fn new(buf: Buf, summer: S) -> Checksummed<Buf, S> {
+    Checksummed { buf: buf, summer: summer, include_peeks: false }
+  }
+
+  /// The checksum of everything consumed or filled so far.
+  #[inline(always)]
+  pub fn sum(&self) -> u32 { self.summer.sum() }
+
+  /// Starts the checksum over.
+  #[inline(always)]
+  pub fn reset_sum(&mut self) { self.summer.reset() }
+
+  /// Whether successful peeks and pokes are checksummed, too.
+  #[inline(always)]
+  pub fn include_peeks(&mut self, include: bool) { self.include_peeks = include }
+
+  /// The checksum so far.
+  #[inline(always)]
+  pub fn summer(&self) -> &S { &self.summer }
+
+  /// The underlying Iobuf.
+  #[inline(always)]
+  pub fn get_ref(&self) -> &Buf { &self.buf }
+
+  /// The underlying Iobuf. Nothing done through this is checksummed.
+  #[inline(always)]
+  pub fn get_mut(&mut self) -> &mut Buf { &mut self.buf }
+
+  /// Unwraps the underlying Iobuf, throwing the checksum away.
+  #[inline(always)]
+  pub fn into_inner(self) -> Buf { self.buf }
+
+  // Feeds the first `len` bytes of the window, starting at `pos`.
+  #[inline]
+  fn feed(&mut self, pos: u32, len: usize) {
+    unsafe {
+      let s = self.buf.as_window_slice();
+      self.summer.update(&s[pos as usize..pos as usize + len]);
+    }
+  }
+
+  /// `Iobuf::peek`, checksumming the bytes if peeks are included.
+  #[inline]
+  pub fn peek(&mut self, pos: u32, dst: &mut [u8]) -> Result<(), ()> {
+    try!(self.buf.peek(pos, dst));
+    if self.include_peeks { self.summer.update(dst) }
+    Ok(())
+  }
+
+  /// `Iobuf::peek_be`, checksumming the bytes if peeks are included.
+  #[inline]
+  pub fn peek_be<T: Prim>(&mut self, pos: u32) -> Result<T, ()> {
+    let t = try!(self.buf.peek_be(pos));
+    if self.include_peeks { self.feed(pos, prim_len::<T>()) }
+    Ok(t)
+  }
+
+  /// `Iobuf::peek_le`, checksumming the bytes if peeks are included.
+  #[inline]
+  pub fn peek_le<T: Prim>(&mut self, pos: u32) -> Result<T, ()> {
+    let t = try!(self.buf.peek_le(pos));
+    if self.include_peeks { self.feed(pos, prim_len::<T>()) }
+    Ok(t)
+  }
+
+  /// `Iobuf::advance`, checksumming the bytes skipped over.
+  #[inline]
+  pub fn advance(&mut self, len: u32) -> Result<(), ()> {
+    try!(self.buf.check_range(0, len));
+    self.feed(0, len as usize);
+    unsafe { self.buf.unsafe_advance(len) };
+    Ok(())
+  }
+
+  /// `Iobuf::consume`, checksumming the bytes consumed.
+  #[inline]
+  pub fn consume(&mut self, dst: &mut [u8]) -> Result<(), ()> {
+    try!(self.buf.consume(dst));
+    self.summer.update(dst);
+    Ok(())
+  }
+
+  /// `Iobuf::consume_be`, checksumming the bytes consumed.
+  #[inline]
+  pub fn consume_be<T: Prim>(&mut self) -> Result<T, ()> {
+    let t = try!(self.buf.peek_be(0));
+    self.feed(0, prim_len::<T>());
+    unsafe { self.buf.unsafe_advance(prim_len::<T>() as u32) };
+    Ok(t)
+  }
+
+  /// `Iobuf::consume_le`, checksumming the bytes consumed.
+  #[inline]
+  pub fn consume_le<T: Prim>(&mut self) -> Result<T, ()> {
+    let t = try!(self.buf.peek_le(0));
+    self.feed(0, prim_len::<T>());
+    unsafe { self.buf.unsafe_advance(prim_len::<T>() as u32) };
+    Ok(t)
+  }
+}
+
+impl<'a, S: Summer> Checksummed<RWIobuf<'a>, S> {
+  /// `RWIobuf::poke`, checksumming the bytes if pokes are included.
+  #[inline]
+  pub fn poke(&mut self, pos: u32, src: &[u8]) -> Result<(), ()> {
+    try!(self.buf.poke(pos, src));
+    if self.include_peeks { self.summer.update(src) }
+    Ok(())
+  }
+
+  /// `RWIobuf::poke_be`, checksumming the bytes if pokes are included.
+  #[inline]
+  pub fn poke_be<T: Prim>(&mut self, pos: u32, t: T) -> Result<(), ()> {
+    try!(self.buf.poke_be(pos, t));
+    if self.include_peeks { self.feed(pos, prim_len::<T>()) }
+    Ok(())
+  }
+
+  /// `RWIobuf::poke_le`, checksumming the bytes if pokes are included.
+  #[inline]
+  pub fn poke_le<T: Prim>(&mut self, pos: u32, t: T) -> Result<(), ()> {
+    try!(self.buf.poke_le(pos, t));
+    if self.include_peeks { self.feed(pos, prim_len::<T>()) }
+    Ok(())
+  }
+
+  /// `RWIobuf::fill`, checksumming the bytes written.
+  #[inline]
+  pub fn fill(&mut self, src: &[u8]) -> Result<(), ()> {
+    try!(self.buf.fill(src));
+    self.summer.update(src);
+    Ok(())
+  }
+
+  /// `RWIobuf::fill_be`, checksumming the bytes written.
+  #[inline]
+  pub fn fill_be<T: Prim>(&mut self, t: T) -> Result<(), ()> {
+    try!(self.buf.poke_be(0, t));
+    self.feed(0, prim_len::<T>());
+    unsafe { self.buf.unsafe_advance(prim_len::<T>() as u32) };
+    Ok(())
+  }
+
+  /// `RWIobuf::fill_le`, checksumming the bytes written.
+  #[inline]
+  pub fn fill_le<T: Prim>(&mut self, t: T) -> Result<(), ()> {
+    try!(self.buf.poke_le(0, t));
+    self.feed(0, prim_len::<T>());
+    unsafe { self.buf.unsafe_advance(prim_len::<T>() as u32) };
+    Ok(())
+  }
+
+  /// `RWIobuf::compact`. Nothing is checksummed.
+  #[inline(always)]
+  pub fn compact(&mut self) { self.buf.compact() }
+}
+
+#[test]
+fn matches_one_shot_across_refills() {
+  let traffic: Vec<u8> = range(0u32, 1000).map(|i| (i * 7 + i / 13) as u8).collect();
+
+  let mut oneshot = Adler32::new();
+  oneshot.update(&traffic[]);
+
+  // Write it all through a small buffer, compacting whenever it fills up.
+  let mut w = Checksummed::new(RWIobuf::new(64), Adler32::new());
+  let mut written = Vec::new();
+  for (i, pair) in traffic.chunks(2).enumerate() {
+    loop {
+      let r =
+        if i % 2 == 0 || pair.len() < 2 { w.fill(pair) }
+        else { w.fill_be((pair[0] as u16) << 8 | pair[1] as u16) };
+      if r.is_ok() { break }
+      let mut full = w.get_ref().clone();
+      full.flip_lo();
+      written.push_all(unsafe { full.as_window_slice() });
+      w.get_mut().reset();
+    }
+  }
+  w.get_mut().flip_lo();
+  written.push_all(unsafe { w.get_ref().as_window_slice() });
+  assert_eq!(written, traffic);
+  assert_eq!(w.sum(), oneshot.sum());
+
+  // Read it back through a small buffer, refilling it as it drains.
+  let mut r = Checksummed::new(RWIobuf::new(64), Adler32::new());
+  r.get_mut().flip_lo();
+  let mut src = &traffic[];
+  let mut n = 0u32;
+  loop {
+    let ok = match n % 3 {
+      0 => r.consume_be::<u32>().map(|_| ()),
+      1 => r.advance(5),
+      _ => { let mut d = [0u8; 3]; r.consume(&mut d) },
+    };
+    if ok.is_ok() { n += 1; continue }
+    if src.is_empty() { break }
+    r.compact();
+    let room = ::std::cmp::min(r.get_ref().len() as usize, src.len());
+    assert_eq!(r.get_mut().fill(&src[..room]), Ok(()));
+    src = &src[room..];
+    r.get_mut().flip_lo();
+  }
+
+  // Whatever's too short to consume in one piece is left over.
+  let left = r.get_ref().len();
+  assert_eq!(r.advance(left), Ok(()));
+  assert_eq!(r.sum(), oneshot.sum());
+
+  r.reset_sum();
+  assert_eq!(r.sum(), Adler32::new().sum());
+}
@@ -0,0 +1,129 @@
+use iobuf::Iobuf;
+
+/// An iterator over `size`-byte pieces of an Iobuf's window, from the back to
+/// the front. See `Iobuf::rchunks`.
+pub struct RChunks<'b, Buf: 'b> {
+  buf:  &'b Buf,
+  size: u32,
+  // The end of the next chunk, relative to the start of the window.
+  end:  u32,
+}
+
+impl<'b, Buf: Iobuf> RChunks<'b, Buf> {
+  /// Iterates over `size`-byte pieces of `buf`'s window, from the back.
+  #[inline]
+  pub fn new(buf: &'b Buf, size: u32) -> RChunks<'b, Buf> {
+    if size == 0 { panic!("Iobuf::rchunks: the chunk size must not be 0") }
+    RChunks { buf: buf, size: size, end: buf.len() }
+  }
+}
+
+impl<'b, Buf: Iobuf> Iterator for RChunks<'b, Buf> {
+  type Item = Buf;
+
+  #[inline]
+  fn next(&mut self) -> Option<Buf> {
+    if self.end == 0 { return None; }
+
+    let start = if self.end > self.size { self.end - self.size } else { 0 };
+
+    let mut ret = self.buf.clone();
+    unsafe { ret.unsafe_sub_window(start, self.end - start); }
+
+    self.end = start;
+    Some(ret)
+  }
+
+  #[inline]
+  fn size_hint(&self) -> (usize, Option<usize>) {
+    let n = ((self.end as u64 + self.size as u64 - 1) / self.size as u64) as usize;
+    (n, Some(n))
+  }
+}
+
+/// Splits `buf`'s window into `parts` pieces. See `Iobuf::split_into`.
+pub fn split_into<Buf: Iobuf>(buf: &Buf, parts: u32) -> Vec<Buf> {
+  if parts == 0 { panic!("Iobuf::split_into: the number of parts must not be 0") }
+
+  let len   = buf.len();
+  let small = len / parts;
+  // The first `big` pieces get one byte more than the rest.
+  let big   = len % parts;
+
+  let mut ret = Vec::with_capacity(parts as usize);
+  let mut pos = 0;
+  for i in range(0, parts) {
+    let n = if i < big { small + 1 } else { small };
+    let mut piece = buf.clone();
+    unsafe { piece.unsafe_sub(pos, n); }
+    ret.push(piece);
+    pos += n;
+  }
+  ret
+}
+
+#[test]
+fn rchunks() {
+  use impls::ROIobuf;
+
+  let mut b = ROIobuf::from_str("xx0123456789xx");
+  assert_eq!(b.sub_window(2, 10), Ok(()));
+
+  let chunks: Vec<Vec<u8>> =
+    b.rchunks(4).map(|c| unsafe { c.as_window_slice().to_vec() }).collect();
+  assert_eq!(chunks, vec!(b"6789".to_vec(), b"2345".to_vec(), b"01".to_vec()));
+
+  assert_eq!(b.rchunks(5).count(), 2);
+  assert_eq!(b.rchunks(10).count(), 1);
+  assert_eq!(b.rchunks(11).count(), 1);
+  assert_eq!(b.rchunks(3).size_hint(), (4, Some(4)));
+
+  // The chunks keep the original limits.
+  let last = b.rchunks(4).next().unwrap();
+  assert_eq!(last.lo_min(), 0);
+  assert_eq!(last.hi_max(), 14);
+
+  assert_eq!(ROIobuf::from_str("").rchunks(4).count(), 0);
+}
+
+#[test]
+#[should_fail]
+fn rchunks_zero() {
+  use impls::ROIobuf;
+  ROIobuf::from_str("abc").rchunks(0);
+}
+
+#[test]
+fn split_into_tiles() {
+  use impls::ROIobuf;
+
+  let mut b = ROIobuf::from_str("xx0123456789xx");
+  assert_eq!(b.sub_window(2, 10), Ok(()));
+
+  let lens: Vec<u32> = b.split_into(4).iter().map(|p| p.len()).collect();
+  assert_eq!(lens, vec!(3, 3, 2, 2));
+
+  let parts = b.split_into(3);
+  unsafe {
+    assert_eq!(parts[0].as_window_slice(), b"0123");
+    assert_eq!(parts[1].as_window_slice(), b"456");
+    assert_eq!(parts[2].as_window_slice(), b"789");
+  }
+  // The limits are narrowed to each piece.
+  let mut p = parts[1].clone();
+  p.reset();
+  assert_eq!(p.len(), 3);
+
+  // More parts than bytes leaves empty pieces at the end.
+  let lens: Vec<u32> = ROIobuf::from_str("ab").split_into(4).iter().map(|p| p.len()).collect();
+  assert_eq!(lens, vec!(1, 1, 0, 0));
+
+  assert_eq!(b.split_into(1).len(), 1);
+}
+
+#[test]
+#[should_fail]
+fn split_into_zero() {
+  use impls::ROIobuf;
+  ROIobuf::from_str("abc").split_into(0);
+}
@@ -0,0 +1,864 @@
+use std::cmp::Ordering;
+use std::ffi::CString;
+use std::hash;
+use std::io::net::ip::{IpAddr, SocketAddr};
+use std::sync::Arc;
+
+#[cfg(feature = "num-bigint")] use num_bigint::BigUint;
+
+use raw::{Allocator, EnumError, IntArray, IobufState, Prim, RawIobuf, TagError};
+use iobuf::{Iobuf, IobufWrite, MutIobuf, ReadFrom};
+use impls::{self, AROIobuf, ROIobuf, RWIobuf, UniqueIobuf};
+use words::WordIter;
+use chunks::{self, RChunks};
+use bytes::{Drain, IntoBytes};
+use records::{RecordIter, Records};
+use tlv::TlvIter;
+use transaction::BufTransaction;
+use window::PositionedWindow;
+use reader::IobufReader;
+use pool::IobufPool;
+
+/// An Iobuf which totals up how many bytes have been consumed from it and
+/// filled into it, for bandwidth accounting.
+///
+/// It's an `Iobuf` (and a `MutIobuf`, if the wrapped Iobuf is), so it can be
+/// handed to generic code in place of the Iobuf it wraps. Everything behaves
+/// exactly as it does on the wrapped Iobuf.
+///
+/// Operations which eat bytes off the front of the window are counted, by how
+/// far they moved the start of the window: `advance` and `advance_upto`, all
+/// of the `consume`s (including the `unsafe_` ones), `split_start_at`,
+/// `split_to_owned`, the `trim`s, and `drain`. `with_sub_window` counts the
+/// whole sub-window as consumed. The `fill`s count as filled. Failed
+/// operations aren't counted, and neither is anything rolled back by
+/// `with_rollback`. Everything else just moves the window around, and isn't
+/// counted: the `sub`s, `narrow`, `extend`, `resize`, `flip_lo`, `rewind`,
+/// and so on.
+///
+/// Clones, and the pieces from `split_at` and friends, start with a copy of
+/// the counters. Anything done through `get_mut` isn't counted.
+///
+/// ```rust
+/// use iobuf::{Counted, Iobuf, IobufWrite, RWIobuf};
+///
+/// let mut b = Counted::new(RWIobuf::new(10));
+/// assert_eq!(b.fill_be(0x1234u16), Ok(()));
+/// assert_eq!(b.fill(b"hello"), Ok(()));
+/// assert_eq!(b.fill(b"world"), Err(()));
+///
+/// b.flip_lo();
+/// assert_eq!(b.consume_be::<u16>(), Ok(0x1234));
+/// assert_eq!(b.advance(2), Ok(()));
+///
+/// assert_eq!(b.counters(), (4, 7));
+/// b.reset_counters();
+/// assert_eq!(b.counters(), (0, 0));
+/// ```
+#[derive(Clone, Debug)]
+pub struct Counted<Buf> {
+  buf:      Buf,
+  consumed: u64,
+  filled:   u64,
+}
+
+impl<Buf: Iobuf> Counted<Buf> {
+  /// Starts counting the traffic through `buf`, from zero.
+  #[inline]
+  pub fn new(buf: Buf) -> Counted<Buf> {
+    Counted { buf: buf, consumed: 0, filled: 0 }
+  }
+
+  /// The number of bytes consumed and filled so far, in that order.
+  #[inline(always)]
+  pub fn counters(&self) -> (u64, u64) { (self.consumed, self.filled) }
+
+  /// Sets both counters back to zero.
+  #[inline(always)]
+  pub fn reset_counters(&mut self) {
+    self.consumed = 0;
+    self.filled   = 0;
+  }
+
+  /// The underlying Iobuf.
+  #[inline(always)]
+  pub fn get_ref(&self) -> &Buf { &self.buf }
+
+  /// The underlying Iobuf. Nothing done through this is counted.
+  #[inline(always)]
+  pub fn get_mut(&mut self) -> &mut Buf { &mut self.buf }
+
+  /// Unwraps the underlying Iobuf, forgetting the counters.
+  #[inline(always)]
+  pub fn into_inner(self) -> Buf { self.buf }
+
+  // Runs `f` on the Iobuf, counting how far it moved the start of the window
+  // as consumed.
+  #[inline]
+  fn consuming<R, F: FnOnce(&mut Buf) -> R>(&mut self, f: F) -> R {
+    let lo = self.buf.lo();
+    let r = f(&mut self.buf);
+    self.consumed += (self.buf.lo() - lo) as u64;
+    r
+  }
+
+  // Runs `f` on the Iobuf, counting how far it moved the start of the window
+  // as filled.
+  #[inline]
+  fn filling<R, F: FnOnce(&mut Buf) -> R>(&mut self, f: F) -> R {
+    let lo = self.buf.lo();
+    let r = f(&mut self.buf);
+    self.filled += (self.buf.lo() - lo) as u64;
+    r
+  }
+
+  // Wraps an Iobuf split off of this one, with a copy of the counters.
+  #[inline(always)]
+  fn wrap(&self, buf: Buf) -> Counted<Buf> {
+    Counted { buf: buf, consumed: self.consumed, filled: self.filled }
+  }
+}
+
+impl<Buf: Iobuf> Iobuf for Counted<Buf> {
+  #[inline(always)]
+  fn deep_clone(&self) -> RWIobuf<'static> { self.buf.deep_clone() }
+
+  #[inline(always)]
+  fn deep_clone_with_allocator(&self, allocator: Arc<Box<Allocator>>) -> RWIobuf<'static> { self.buf.deep_clone_with_allocator(allocator) }
+
+  #[inline(always)]
+  fn deep_clone_from_pool(&self, pool: &mut IobufPool) -> RWIobuf<'static> { self.buf.deep_clone_from_pool(pool) }
+
+  #[inline(always)]
+  fn window_to_owned(&self) -> ROIobuf<'static> { self.buf.window_to_owned() }
+
+  #[inline]
+  fn split_to_owned(&mut self, n: u32) -> Result<RWIobuf<'static>, ()> { self.consuming(|b| b.split_to_owned(n)) }
+
+  #[inline(always)]
+  fn to_cstring(&self) -> Result<CString, ()> { self.buf.to_cstring() }
+
+  #[inline(always)]
+  unsafe fn as_cstr_ptr(&self) -> Result<*const u8, ()> { self.buf.as_cstr_ptr() }
+
+  #[inline]
+  fn unique(self) -> Result<UniqueIobuf, Self> {
+    let Counted { buf, consumed, filled } = self;
+    buf.unique().map_err(|buf| Counted { buf: buf, consumed: consumed, filled: filled })
+  }
+
+  #[inline]
+  fn atomic_read_only(self) -> Result<AROIobuf, Self> {
+    let Counted { buf, consumed, filled } = self;
+    buf.atomic_read_only().map_err(|buf| Counted { buf: buf, consumed: consumed, filled: filled })
+  }
+
+  #[inline(always)]
+  fn len(&self) -> u32 { self.buf.len() }
+
+  #[inline(always)]
+  fn cap(&self) -> u32 { self.buf.cap() }
+
+  #[inline(always)]
+  fn is_empty(&self) -> bool { self.buf.is_empty() }
+
+  #[inline(always)]
+  fn count_prims<T: Prim>(&self, from: u32) -> u32 { self.buf.count_prims::<T>(from) }
+
+  #[inline(always)]
+  unsafe fn as_window_slice<'b>(&'b self) -> &'b [u8] { self.buf.as_window_slice() }
+
+  #[inline(always)]
+  unsafe fn as_limit_slice<'b>(&'b self) -> &'b [u8] { self.buf.as_limit_slice() }
+
+  #[inline(always)]
+  unsafe fn window_as_slice<'b, T: Prim>(&'b self) -> Result<&'b [T], ()> { self.buf.window_as_slice() }
+
+  #[inline(always)]
+  fn sub_window(&mut self, pos: u32, len: u32) -> Result<(), ()> { self.buf.sub_window(pos, len) }
+
+  #[inline(always)]
+  fn sub_window_from(&mut self, pos: u32) -> Result<(), ()> { self.buf.sub_window_from(pos) }
+
+  #[inline(always)]
+  fn sub_window_to(&mut self, len: u32) -> Result<(), ()> { self.buf.sub_window_to(len) }
+
+  #[inline(always)]
+  unsafe fn unsafe_sub_window(&mut self, pos: u32, len: u32) { self.buf.unsafe_sub_window(pos, len) }
+
+  #[inline(always)]
+  unsafe fn unsafe_sub_window_from(&mut self, pos: u32) { self.buf.unsafe_sub_window_from(pos) }
+
+  #[inline(always)]
+  unsafe fn unsafe_sub_window_to(&mut self, pos: u32) { self.buf.unsafe_sub_window_to(pos) }
+
+  #[inline(always)]
+  fn sub(&mut self, pos: u32, len: u32) -> Result<(), ()> { self.buf.sub(pos, len) }
+
+  #[inline(always)]
+  fn sub_from(&mut self, pos: u32) -> Result<(), ()> { self.buf.sub_from(pos) }
+
+  #[inline(always)]
+  fn sub_to(&mut self, len: u32) -> Result<(), ()> { self.buf.sub_to(len) }
+
+  #[inline(always)]
+  unsafe fn unsafe_sub(&mut self, pos: u32, len: u32) { self.buf.unsafe_sub(pos, len) }
+
+  #[inline(always)]
+  unsafe fn unsafe_sub_from(&mut self, pos: u32) { self.buf.unsafe_sub_from(pos) }
+
+  #[inline(always)]
+  unsafe fn unsafe_sub_to(&mut self, len: u32) { self.buf.unsafe_sub_to(len) }
+
+  #[inline(always)]
+  fn set_limits_and_window(&mut self, limits: (u32, u32), window: (u32, u32)) -> Result<(), ()> { self.buf.set_limits_and_window(limits, window) }
+
+  #[inline(always)]
+  fn narrow(&mut self) { self.buf.narrow() }
+
+  #[inline]
+  fn advance(&mut self, len: u32) -> Result<(), ()> { self.consuming(|b| b.advance(len)) }
+
+  #[inline(always)]
+  fn advanced(mut self, len: u32) -> Result<Self, ()> { try!(self.advance(len)); Ok(self) }
+
+  #[inline(always)]
+  fn resized(mut self, len: u32) -> Result<Self, ()> { try!(self.resize(len)); Ok(self) }
+
+  #[inline(always)]
+  fn subbed(mut self, pos: u32, len: u32) -> Result<Self, ()> { try!(self.sub(pos, len)); Ok(self) }
+
+  #[inline(always)]
+  fn narrowed(mut self) -> Self { self.narrow(); self }
+
+  #[inline]
+  unsafe fn unsafe_advance(&mut self, len: u32) { self.consuming(|b| b.unsafe_advance(len)) }
+
+  #[inline]
+  fn advance_upto(&mut self, len: u32) -> u32 { self.consuming(|b| b.advance_upto(len)) }
+
+  #[inline(always)]
+  fn extend(&mut self, len: u32) -> Result<(), ()> { self.buf.extend(len) }
+
+  #[inline(always)]
+  unsafe fn unsafe_extend(&mut self, len: u32) { self.buf.unsafe_extend(len) }
+
+  #[inline(always)]
+  fn extend_upto(&mut self, len: u32) -> u32 { self.buf.extend_upto(len) }
+
+  #[inline(always)]
+  fn is_extended_by<B: Iobuf>(&self, other: &B) -> bool { self.buf.is_extended_by(other) }
+
+  #[inline(always)]
+  fn cmp_window<B: Iobuf>(&self, other: &B) -> Ordering { self.buf.cmp_window(other) }
+
+  #[inline(always)]
+  fn extend_with<B: Iobuf>(&mut self, other: &B) -> Result<(), ()> { self.buf.extend_with(other) }
+
+  #[inline(always)]
+  fn resize(&mut self, len: u32) -> Result<(), ()> { self.buf.resize(len) }
+
+  #[inline(always)]
+  fn resize_preserving(&mut self, len: u32) -> Result<(), ()> { self.buf.resize_preserving(len) }
+
+  #[inline(always)]
+  unsafe fn unsafe_resize(&mut self, len: u32) { self.buf.unsafe_resize(len) }
+
+  #[inline(always)]
+  fn resize_upto(&mut self, len: u32) -> u32 { self.buf.resize_upto(len) }
+
+  #[inline]
+  fn split_at(&self, pos: u32) -> Result<(Self, Self), ()> {
+    self.buf.split_at(pos).map(|(a, b)| (self.wrap(a), self.wrap(b)))
+  }
+
+  #[inline]
+  unsafe fn unsafe_split_at(&self, pos: u32) -> (Self, Self) {
+    let (a, b) = self.buf.unsafe_split_at(pos);
+    (self.wrap(a), self.wrap(b))
+  }
+
+  #[inline]
+  fn split_start_at(&mut self, pos: u32) -> Result<Self, ()> {
+    let b = try!(self.consuming(|b| b.split_start_at(pos)));
+    Ok(self.wrap(b))
+  }
+
+  #[inline]
+  unsafe fn unsafe_split_start_at(&mut self, pos: u32) -> Self {
+    let b = self.consuming(|b| b.unsafe_split_start_at(pos));
+    self.wrap(b)
+  }
+
+  #[inline(always)]
+  fn rewind(&mut self) { self.buf.rewind() }
+
+  #[inline(always)]
+  fn reset(&mut self) { self.buf.reset() }
+
+  #[inline(always)]
+  fn flip_lo(&mut self) { self.buf.flip_lo() }
+
+  #[inline(always)]
+  fn flip_hi(&mut self) { self.buf.flip_hi() }
+
+  #[inline(always)]
+  fn lo_space(&self) -> u32 { self.buf.lo_space() }
+
+  #[inline(always)]
+  fn hi_space(&self) -> u32 { self.buf.hi_space() }
+
+  #[inline(always)]
+  fn peek(&self, pos: u32, dst: &mut [u8]) -> Result<(), ()> { self.buf.peek(pos, dst) }
+
+  #[inline(always)]
+  unsafe fn peek_into_uninit(&self, pos: u32, dst: *mut u8, len: u32) -> Result<(), ()> { self.buf.peek_into_uninit(pos, dst, len) }
+
+  #[inline(always)]
+  fn peek_vectored(&self, pos: u32, dsts: &mut [&mut [u8]]) -> Result<(), ()> { self.buf.peek_vectored(pos, dsts) }
+
+  #[inline]
+  fn consume_vectored(&mut self, dsts: &mut [&mut [u8]]) -> Result<(), ()> { self.consuming(|b| b.consume_vectored(dsts)) }
+
+  #[inline(always)]
+  fn peek_all_into(&self, dst: &mut Vec<u8>) { self.buf.peek_all_into(dst) }
+
+  #[inline]
+  fn consume_all_into(&mut self, dst: &mut Vec<u8>) { self.consuming(|b| b.consume_all_into(dst)) }
+
+  #[inline(always)]
+  fn peek_be<T: Prim>(&self, pos: u32) -> Result<T, ()> { self.buf.peek_be(pos) }
+
+  #[inline(always)]
+  fn peek_le<T: Prim>(&self, pos: u32) -> Result<T, ()> { self.buf.peek_le(pos) }
+
+  #[inline]
+  fn consume(&mut self, dst: &mut [u8]) -> Result<(), ()> { self.consuming(|b| b.consume(dst)) }
+
+  #[inline]
+  fn consume_be<T: Prim>(&mut self) -> Result<T, ()> { self.consuming(|b| b.consume_be()) }
+
+  #[inline]
+  fn consume_le<T: Prim>(&mut self) -> Result<T, ()> { self.consuming(|b| b.consume_le()) }
+
+  #[cfg(feature = "num-bigint")]
+  #[inline(always)]
+  fn peek_biguint_be(&self, pos: u32, len: u32) -> Result<BigUint, ()> { self.buf.peek_biguint_be(pos, len) }
+
+  #[cfg(feature = "num-bigint")]
+  #[inline]
+  fn consume_biguint_be(&mut self, len: u32) -> Result<BigUint, ()> { self.consuming(|b| b.consume_biguint_be(len)) }
+
+  #[inline]
+  fn consume_be_opt<T: Prim>(&mut self) -> Option<T> { self.consuming(|b| b.consume_be_opt()) }
+
+  #[inline]
+  fn consume_le_opt<T: Prim>(&mut self) -> Option<T> { self.consuming(|b| b.consume_le_opt()) }
+
+  #[inline]
+  fn consume_hex_u64(&mut self) -> Result<u64, ()> { self.consuming(|b| b.consume_hex_u64()) }
+
+  #[inline]
+  fn consume_decimal_u64(&mut self) -> Result<u64, ()> { self.consuming(|b| b.consume_decimal_u64()) }
+
+  #[inline]
+  fn consume_int_be(&mut self, width: u8) -> Result<i64, ()> { self.consuming(|b| b.consume_int_be(width)) }
+
+  #[inline]
+  fn consume_int_le(&mut self, width: u8) -> Result<i64, ()> { self.consuming(|b| b.consume_int_le(width)) }
+
+  #[inline(always)]
+  fn peek_fixed_be(&self, pos: u32, int_bits: u8, frac_bits: u8) -> Result<f64, ()> { self.buf.peek_fixed_be(pos, int_bits, frac_bits) }
+
+  #[inline]
+  fn consume_fixed_be(&mut self, int_bits: u8, frac_bits: u8) -> Result<f64, ()> { self.consuming(|b| b.consume_fixed_be(int_bits, frac_bits)) }
+
+  #[inline]
+  fn consume_q16_16_be(&mut self) -> Result<f64, ()> { self.consuming(|b| b.consume_q16_16_be()) }
+
+  #[inline(always)]
+  fn peek_ipv4(&self, pos: u32) -> Result<IpAddr, ()> { self.buf.peek_ipv4(pos) }
+
+  #[inline(always)]
+  fn peek_ipv6(&self, pos: u32) -> Result<IpAddr, ()> { self.buf.peek_ipv6(pos) }
+
+  #[inline(always)]
+  fn peek_socket_addr_v4(&self, pos: u32) -> Result<SocketAddr, ()> { self.buf.peek_socket_addr_v4(pos) }
+
+  #[inline]
+  fn consume_ipv4(&mut self) -> Result<IpAddr, ()> { self.consuming(|b| b.consume_ipv4()) }
+
+  #[inline]
+  fn consume_ipv6(&mut self) -> Result<IpAddr, ()> { self.consuming(|b| b.consume_ipv6()) }
+
+  #[inline]
+  fn consume_socket_addr_v4(&mut self) -> Result<SocketAddr, ()> { self.consuming(|b| b.consume_socket_addr_v4()) }
+
+  #[inline]
+  fn consume_be_enum<T: Prim, E, F: FnOnce(T) -> Option<E>>(&mut self, validate: F) -> Result<E, EnumError<T>> { self.consuming(|b| b.consume_be_enum(validate)) }
+
+  #[inline]
+  fn consume_le_enum<T: Prim, E, F: FnOnce(T) -> Option<E>>(&mut self, validate: F) -> Result<E, EnumError<T>> { self.consuming(|b| b.consume_le_enum(validate)) }
+
+  #[inline(always)]
+  fn peek_prim<T: Prim>(&self, pos: u32, big_endian: bool) -> Result<T, ()> { self.buf.peek_prim(pos, big_endian) }
+
+  #[inline]
+  fn consume_prim<T: Prim>(&mut self, big_endian: bool) -> Result<T, ()> { self.consuming(|b| b.consume_prim(big_endian)) }
+
+  #[inline]
+  fn consume_be_array<A: IntArray>(&mut self) -> Result<A, ()> { self.consuming(|b| b.consume_be_array()) }
+
+  #[inline]
+  fn consume_le_array<A: IntArray>(&mut self) -> Result<A, ()> { self.consuming(|b| b.consume_le_array()) }
+
+  #[inline(always)]
+  fn eq_ignore_ascii_case(&self, other: &[u8]) -> bool { self.buf.eq_ignore_ascii_case(other) }
+
+  #[inline(always)]
+  fn starts_with_ignore_ascii_case(&self, prefix: &[u8]) -> bool { self.buf.starts_with_ignore_ascii_case(prefix) }
+
+  #[inline]
+  fn consume_tag(&mut self, tag: &[u8]) -> Result<(), TagError> { self.consuming(|b| b.consume_tag(tag)) }
+
+  #[inline]
+  fn consume_tag_ignore_ascii_case(&mut self, tag: &[u8]) -> Result<(), TagError> { self.consuming(|b| b.consume_tag_ignore_ascii_case(tag)) }
+
+  #[inline(always)]
+  fn owns_slice(&self, s: &[u8]) -> bool { self.buf.owns_slice(s) }
+
+  #[inline(always)]
+  fn rchunks<'b>(&'b self, size: u32) -> RChunks<'b, Self> { RChunks::new(self, size) }
+
+  #[inline(always)]
+  fn split_into(&self, parts: u32) -> Vec<Self> { chunks::split_into(self, parts) }
+
+  #[inline(always)]
+  fn drain<'b>(&'b mut self) -> Drain<'b, Self> { Drain::new(self) }
+
+  #[inline(always)]
+  fn into_bytes(self) -> IntoBytes<Self> { IntoBytes::new(self) }
+
+  #[inline(always)]
+  fn into_reader(self) -> IobufReader<Self> { IobufReader::new(self) }
+
+  #[inline]
+  fn split_once(&self, delim: u8) -> Option<(Self, Self)> {
+    self.buf.split_once(delim).map(|(a, b)| (self.wrap(a), self.wrap(b)))
+  }
+
+  #[inline(always)]
+  fn rfind(&self, byte: u8) -> Option<u32> { self.buf.rfind(byte) }
+
+  #[inline(always)]
+  fn rfind_slice(&self, needle: &[u8]) -> Option<u32> { self.buf.rfind_slice(needle) }
+
+  #[inline(always)]
+  fn iter_be<'b, T: Prim>(&'b self) -> WordIter<'b, T> { self.buf.iter_be() }
+
+  #[inline(always)]
+  fn iter_le<'b, T: Prim>(&'b self) -> WordIter<'b, T> { self.buf.iter_le() }
+
+  #[inline(always)]
+  fn fold<B, F: FnMut(B, u8) -> B>(&self, init: B, f: F) -> B { self.buf.fold(init, f) }
+
+  #[inline(always)]
+  fn all_bytes<F: FnMut(u8) -> bool>(&self, pred: F) -> bool { self.buf.all_bytes(pred) }
+
+  #[inline(always)]
+  fn is_ascii(&self) -> bool { self.buf.is_ascii() }
+
+  #[inline(always)]
+  fn is_ascii_printable(&self) -> bool { self.buf.is_ascii_printable() }
+
+  #[inline(always)]
+  fn first_non_ascii(&self) -> Option<u32> { self.buf.first_non_ascii() }
+
+  #[inline]
+  fn trim_matches<F: FnMut(u8) -> bool>(&mut self, pred: F) { self.consuming(|b| b.trim_matches(pred)) }
+
+  #[inline]
+  fn trim_ascii_whitespace(&mut self) { self.consuming(|b| b.trim_ascii_whitespace()) }
+
+  #[inline(always)]
+  fn partition_point<F: FnMut(u8) -> bool>(&self, pred: F) -> u32 { self.buf.partition_point(pred) }
+
+  #[inline(always)]
+  fn partition_point_records<F: FnMut(&[u8]) -> bool>(&self, size: u32, pred: F) -> Result<u32, ()> { self.buf.partition_point_records(size, pred) }
+
+  #[inline(always)]
+  fn byte_histogram(&self) -> [u32; 256] { self.buf.byte_histogram() }
+
+  #[inline(always)]
+  fn shannon_entropy(&self) -> f64 { self.buf.shannon_entropy() }
+
+  #[inline(always)]
+  fn byte_min(&self) -> Option<u8> { self.buf.byte_min() }
+
+  #[inline(always)]
+  fn byte_max(&self) -> Option<u8> { self.buf.byte_max() }
+
+  #[inline(always)]
+  fn byte_sum(&self) -> u64 { self.buf.byte_sum() }
+
+  #[inline(always)]
+  fn crc32c(&self) -> u32 { self.buf.crc32c() }
+
+  #[inline(always)]
+  fn crc32c_from(&self, pos: u32, len: u32) -> Result<u32, ()> { self.buf.crc32c_from(pos, len) }
+
+  #[inline(always)]
+  fn hash_window<H: hash::Writer>(&self, hasher: &mut H) { self.buf.hash_window(hasher) }
+
+  #[inline]
+  fn with_rollback<R, E, F: FnOnce(&mut Self) -> Result<R, E>>(&mut self, f: F) -> Result<R, E> {
+    // Putting back a clone puts back the counters, too.
+    let saved = self.clone();
+    let ret = f(self);
+    if ret.is_err() {
+      *self = saved;
+    }
+    ret
+  }
+
+  #[inline(always)]
+  fn transaction<'b>(&'b mut self) -> BufTransaction<'b, Self> { BufTransaction::new(self) }
+
+  #[inline(always)]
+  unsafe fn window_guard_positioned<'b>(&'b mut self) -> PositionedWindow<'b, Self> { PositionedWindow::new(self) }
+
+  #[inline(always)]
+  fn peek_header<H: ReadFrom>(&self) -> Result<H, ()> { self.buf.peek_header() }
+
+  #[inline(always)]
+  fn read_records<'b, H: ReadFrom>(&'b mut self) -> RecordIter<'b, Self, H> { RecordIter::new(self) }
+
+  #[inline(always)]
+  fn consume_records<'b>(&'b mut self, size: u32) -> Records<'b, Self> { Records::new(self, size) }
+
+  #[inline(always)]
+  fn tlv_iter<'b>(&'b mut self, type_width: u8, len_width: u8, big_endian: bool) -> TlvIter<'b, Self> { TlvIter::new(self, type_width, len_width, big_endian) }
+
+  #[inline(always)]
+  fn lookahead<'b, R, F: FnOnce(&mut ROIobuf<'b>) -> R>(&'b self, f: F) -> R { self.buf.lookahead(f) }
+
+  #[inline]
+  fn with_sub_window<R, F: FnOnce(&mut Self) -> R>(&mut self, pos: u32, len: u32, f: F) -> Result<R, ()> {
+    let saved = self.buf.clone();
+    let consumed = self.consumed;
+    try!(self.buf.sub(pos, len));
+    self.buf.narrow();
+    let ret = f(self);
+    // Whatever `f` did, the whole sub-window counts as consumed, once.
+    self.buf = saved;
+    self.consumed = consumed + (pos + len) as u64;
+    unsafe { self.buf.unsafe_advance(pos + len) }
+    Ok(ret)
+  }
+
+  #[inline(always)]
+  fn consume_repeat<R, E, F: FnMut(&mut Self) -> Result<R, E>>(&mut self, count: u32, f: F) -> Result<Vec<R>, E> { impls::consume_repeat(self, count, f) }
+
+  #[inline(always)]
+  fn consume_repeat_until_empty<R, E, F: FnMut(&mut Self) -> Result<R, E>>(&mut self, f: F) -> Result<Vec<R>, E> { impls::consume_repeat_until_empty(self, f) }
+
+  #[inline(always)]
+  fn check_range(&self, pos: u32, len: u32) -> Result<(), ()> { self.buf.check_range(pos, len) }
+
+  #[inline(always)]
+  fn check_range_usize(&self, pos: u32, len: usize) -> Result<(), ()> { self.buf.check_range_usize(pos, len) }
+
+  #[inline(always)]
+  fn check_range_fail(&self, pos: u32, len: u32) { self.buf.check_range_fail(pos, len) }
+
+  #[inline(always)]
+  fn check_range_usize_fail(&self, pos: u32, len: usize) { self.buf.check_range_usize_fail(pos, len) }
+
+  #[inline(always)]
+  unsafe fn unsafe_peek(&self, pos: u32, dst: &mut [u8]) { self.buf.unsafe_peek(pos, dst) }
+
+  #[inline(always)]
+  unsafe fn unsafe_peek_be<T: Prim>(&self, pos: u32) -> T { self.buf.unsafe_peek_be(pos) }
+
+  #[inline(always)]
+  unsafe fn unsafe_peek_le<T: Prim>(&self, pos: u32) -> T { self.buf.unsafe_peek_le(pos) }
+
+  #[inline]
+  unsafe fn unsafe_consume(&mut self, dst: &mut [u8]) { self.consuming(|b| b.unsafe_consume(dst)) }
+
+  #[inline]
+  unsafe fn unsafe_consume_be<T: Prim>(&mut self) -> T { self.consuming(|b| b.unsafe_consume_be()) }
+
+  #[inline]
+  unsafe fn unsafe_consume_le<T: Prim>(&mut self) -> T { self.consuming(|b| b.unsafe_consume_le()) }
+
+  #[inline(always)]
+  unsafe fn as_raw<'b>(&'b self) -> &RawIobuf<'b> { self.buf.as_raw() }
+
+  #[inline(always)]
+  fn ptr(&self) -> *mut u8 { self.buf.ptr() }
+
+  #[inline(always)]
+  fn is_owned(&self) -> bool { self.buf.is_owned() }
+
+  #[inline(always)]
+  fn debug_state(&self) -> IobufState { self.buf.debug_state() }
+
+  #[inline(always)]
+  fn lo_min(&self) -> u32 { self.buf.lo_min() }
+
+  #[inline(always)]
+  fn lo(&self) -> u32 { self.buf.lo() }
+
+  #[inline(always)]
+  fn hi(&self) -> u32 { self.buf.hi() }
+
+  #[inline(always)]
+  fn hi_max(&self) -> u32 { self.buf.hi_max() }
+}
+
+impl<Buf: MutIobuf> IobufWrite for Counted<Buf> {
+  #[inline(always)]
+  fn poke(&self, pos: u32, src: &[u8]) -> Result<(), ()> { self.buf.poke(pos, src) }
+
+  #[inline(always)]
+  fn poke_be<T: Prim>(&self, pos: u32, t: T) -> Result<(), ()> { self.buf.poke_be(pos, t) }
+
+  #[inline(always)]
+  fn poke_le<T: Prim>(&self, pos: u32, t: T) -> Result<(), ()> { self.buf.poke_le(pos, t) }
+
+  #[inline]
+  fn fill(&mut self, src: &[u8]) -> Result<(), ()> { self.filling(|b| b.fill(src)) }
+
+  #[inline]
+  fn fill_be<T: Prim>(&mut self, t: T) -> Result<(), ()> { self.filling(|b| b.fill_be(t)) }
+
+  #[inline]
+  fn fill_le<T: Prim>(&mut self, t: T) -> Result<(), ()> { self.filling(|b| b.fill_le(t)) }
+}
+
+impl<Buf: MutIobuf> MutIobuf for Counted<Buf> {
+  #[inline(always)]
+  unsafe fn as_mut_window_slice<'b>(&'b self) -> &'b mut [u8] { self.buf.as_mut_window_slice() }
+
+  #[inline(always)]
+  unsafe fn as_mut_limit_slice<'b>(&'b self) -> &'b mut [u8] { self.buf.as_mut_limit_slice() }
+
+  #[inline(always)]
+  unsafe fn unsafe_poke(&self, pos: u32, src: &[u8]) { self.buf.unsafe_poke(pos, src) }
+
+  #[inline(always)]
+  unsafe fn unsafe_poke_be<T: Prim>(&self, pos: u32, t: T) { self.buf.unsafe_poke_be(pos, t) }
+
+  #[inline(always)]
+  unsafe fn unsafe_poke_le<T: Prim>(&self, pos: u32, t: T) { self.buf.unsafe_poke_le(pos, t) }
+
+  #[inline]
+  unsafe fn unsafe_fill(&mut self, src: &[u8]) { self.filling(|b| b.unsafe_fill(src)) }
+
+  #[inline]
+  unsafe fn unsafe_fill_be<T: Prim>(&mut self, t: T) { self.filling(|b| b.unsafe_fill_be(t)) }
+
+  #[inline]
+  unsafe fn unsafe_fill_le<T: Prim>(&mut self, t: T) { self.filling(|b| b.unsafe_fill_le(t)) }
+}
+
+impl<'a> Counted<RWIobuf<'a>> {
+  /// `RWIobuf::fill_vectored`, counting the bytes written.
+  #[inline]
+  pub fn fill_vectored(&mut self, srcs: &[&[u8]]) -> Result<(), ()> {
+    self.filling(|b| b.fill_vectored(srcs))
+  }
+
+  /// `RWIobuf::fill_reporting`, counting the bytes written.
+  #[inline]
+  pub fn fill_reporting(&mut self, src: &[u8]) -> Result<(), u32> {
+    self.filling(|b| b.fill_reporting(src))
+  }
+
+  /// `RWIobuf::fill_json_escaped`, counting the bytes written.
+  #[inline]
+  pub fn fill_json_escaped(&mut self, s: &str) -> Result<(), ()> {
+    self.filling(|b| b.fill_json_escaped(s))
+  }
+
+  /// `RWIobuf::fill_fixed_be`, counting the bytes written.
+  #[inline]
+  pub fn fill_fixed_be(&mut self, x: f64, int_bits: u8, frac_bits: u8) -> Result<(), ()> {
+    self.filling(|b| b.fill_fixed_be(x, int_bits, frac_bits))
+  }
+
+  /// `RWIobuf::fill_q16_16_be`, counting the bytes written.
+  #[inline]
+  pub fn fill_q16_16_be(&mut self, x: f64) -> Result<(), ()> {
+    self.filling(|b| b.fill_q16_16_be(x))
+  }
+
+  /// `RWIobuf::fill_ip`, counting the bytes written.
+  #[inline]
+  pub fn fill_ip(&mut self, ip: IpAddr) -> Result<(), ()> {
+    self.filling(|b| b.fill_ip(ip))
+  }
+
+  /// `RWIobuf::fill_socket_addr`, counting the bytes written.
+  #[inline]
+  pub fn fill_socket_addr(&mut self, addr: SocketAddr) -> Result<(), ()> {
+    self.filling(|b| b.fill_socket_addr(addr))
+  }
+
+  /// `RWIobuf::compact`. Not counted.
+  #[inline(always)]
+  pub fn compact(&mut self) { self.buf.compact() }
+}
+
+#[test]
+fn same_results_as_unwrapped() {
+  // The `Iobuf::consume` and `RWIobuf::fill` examples, run both ways.
+  let mut plain = RWIobuf::new(10);
+  let mut counted = Counted::new(RWIobuf::new(10));
+
+  assert_eq!(plain.fill(&[1,2,3,4,5,6,7,8]), counted.fill(&[1,2,3,4,5,6,7,8]));
+  assert_eq!(plain.fill_le(0x0A09u16), counted.fill_le(0x0A09u16));
+  assert_eq!(plain.fill_be(0u8), counted.fill_be(0u8));
+  assert_eq!(plain.len(), counted.len());
+
+  plain.flip_lo();
+  counted.flip_lo();
+
+  let mut a = [0u8; 4];
+  let mut b = [0u8; 4];
+  assert_eq!(plain.consume(&mut a), counted.consume(&mut b));
+  assert_eq!(a, b);
+  assert_eq!(plain.peek_be::<u32>(0), counted.peek_be::<u32>(0));
+  assert_eq!(plain.consume_le::<u32>(), counted.consume_le::<u32>());
+  assert_eq!(plain.advance(3), counted.advance(3));
+  assert_eq!(plain.consume_be::<u16>(), counted.consume_be::<u16>());
+  assert_eq!(plain.is_empty(), counted.is_empty());
+
+  plain.compact();
+  counted.compact();
+  assert_eq!(plain.len(), counted.len());
+
+  assert_eq!(counted.counters(), (10, 10));
+  assert_eq!(counted.get_ref().len(), 10);
+}
+
+#[test]
+fn doc_scenarios_through_the_wrapper() {
+  // `Iobuf::advance_upto`.
+  let mut b = Counted::new(ROIobuf::from_str("hello"));
+  assert_eq!(b.advance_upto(3), 3);
+  assert_eq!(b.advance_upto(3), 2);
+  assert!(b.is_empty());
+  assert_eq!(b.advance_upto(3), 0);
+  assert_eq!(b.counters(), (5, 0));
+
+  // `Iobuf::consume_vectored`.
+  let mut b = Counted::new(ROIobuf::from_slice_copy(&[ 1, 2, 3, 4, 5, 6, 7, 8, 9 ]));
+  let mut mac  = [0u8; 6];
+  let mut kind = [0u8; 2];
+  assert_eq!(b.consume_vectored(&mut [ &mut mac, &mut kind ]), Ok(()));
+  assert_eq!(mac, [ 1, 2, 3, 4, 5, 6 ]);
+  assert_eq!(kind, [ 7, 8 ]);
+  assert_eq!(b.len(), 1);
+  assert_eq!(b.counters(), (8, 0));
+
+  // `Iobuf::consume_all_into`.
+  let mut v = Vec::new();
+  let mut b = Counted::new(ROIobuf::from_str("hello"));
+  assert_eq!(b.advance(1), Ok(()));
+  b.consume_all_into(&mut v);
+  assert_eq!(&v[], b"ello");
+  assert!(b.is_empty());
+  assert_eq!(b.counters(), (5, 0));
+
+  // `Iobuf::consume_tag`.
+  let mut b = Counted::new(ROIobuf::from_str("\r\nabc\r"));
+  assert_eq!(b.consume_tag(b"\r\n"), Ok(()));
+  assert_eq!(b.consume_tag(b"\r\n"), Err(TagError::Mismatch(0)));
+  assert_eq!(b.consume_tag(b"abd"),  Err(TagError::Mismatch(2)));
+  assert_eq!(b.consume_tag(b"abc"),  Ok(()));
+  assert_eq!(b.consume_tag(b"\r\n"), Err(TagError::Incomplete));
+  assert_eq!(b.len(), 1);
+  assert_eq!(b.counters(), (5, 0));
+
+  // `Iobuf::split_start_at`.
+  let mut b = Counted::new(ROIobuf::from_str("helloworld"));
+  let c = b.split_start_at(5).unwrap();
+  unsafe {
+    assert_eq!(b.get_ref().as_window_slice(), b"world");
+    assert_eq!(c.as_window_slice(), b"hello");
+  }
+  assert!(b.split_start_at(10000).is_err());
+  assert_eq!(b.counters(), (5, 0));
+
+  // `RWIobuf::fill_vectored`.
+  let mut b = Counted::new(RWIobuf::new(8));
+  assert_eq!(b.fill_vectored(&[ b"head", b"body" ]), Ok(()));
+  assert_eq!(b.fill_vectored(&[ b"x" ]), Err(()));
+  b.flip_lo();
+  unsafe { assert_eq!(b.get_ref().as_window_slice(), b"headbody"); }
+  assert_eq!(b.counters(), (0, 8));
+}
+
+#[test]
+fn generic_code_counts_through_the_wrapper() {
+  // A length-prefixed list of whitespace-padded names, then a trailer.
+  fn parse<B: Iobuf>(b: &mut B) -> Result<(Vec<Vec<u8>>, RWIobuf<'static>), ()> {
+    let n = try!(b.consume_be::<u8>()) as u32;
+    let names = try!(b.consume_repeat(n, |b| {
+      let len = try!(b.consume_be::<u8>()) as u32;
+      b.with_sub_window(0, len, |b| {
+        b.trim_ascii_whitespace();
+        let mut v = Vec::new();
+        b.consume_all_into(&mut v);
+        v
+      })
+    }));
+    let trailer = try!(b.split_to_owned(2));
+    Ok((names, trailer))
+  }
+
+  let data = b"\x02\x05 abc \x03de \xAA\xBB\xCC";
+
+  let mut plain = ROIobuf::from_slice_copy(data);
+  let mut counted = Counted::new(ROIobuf::from_slice_copy(data));
+  let (names, trailer) = parse(&mut plain).unwrap();
+  let (cnames, ctrailer) = parse(&mut counted).unwrap();
+  assert_eq!(names, vec![ b"abc".to_vec(), b"de".to_vec() ]);
+  assert_eq!(names, cnames);
+  unsafe { assert_eq!(trailer.as_window_slice(), ctrailer.as_window_slice()); }
+  assert_eq!(plain.lo(), counted.lo());
+  assert_eq!(counted.counters(), (13, 0));
+
+  // A failed `consume_repeat` rolls back the window and the counters.
+  let mut b = Counted::new(ROIobuf::from_slice_copy(b"\x03\x01a\x01b"));
+  assert_eq!(b.advance(1), Ok(()));
+  assert_eq!(b.consume_repeat(3, |b| b.consume_be::<u16>()), Err(()));
+  assert_eq!(b.len(), 4);
+  assert_eq!(b.counters(), (1, 0));
+
+  // So does a dropped transaction.
+  {
+    let mut t = b.transaction();
+    assert_eq!(t.consume_be::<u16>(), Ok(0x0161));
+  }
+  assert_eq!(b.counters(), (1, 0));
+
+  // `Iobuf::drain`, and pieces carrying a copy of the counters.
+  let (x, mut y) = b.split_at(2).unwrap();
+  assert_eq!(x.counters(), (1, 0));
+  assert_eq!(y.drain().collect::<Vec<u8>>(), b"\x01b".to_vec());
+  assert_eq!(y.counters(), (3, 0));
+  assert_eq!(b.counters(), (1, 0));
+
+  // Fills through generic code.
+  fn put<B: MutIobuf>(b: &mut B) -> Result<(), ()> {
+    try!(b.fill_be(0xCAFEu16));
+    b.fill(b"hi")
+  }
+  let mut b = Counted::new(RWIobuf::new(5));
+  assert_eq!(put(&mut b), Ok(()));
+  assert_eq!(put(&mut b), Err(()));
+  assert_eq!(b.counters(), (0, 4));
+}
@@ -0,0 +1,185 @@
+use std::cell::Cell;
+use std::num::Int;
+use std::u32;
+
+use raw::{Prim, prim_len};
+use iobuf::IobufWrite;
+
+/// Stands in for a `RWIobuf` when all you want to know is how many bytes
+/// would be written. Nothing is actually written anywhere, and none of the
+/// writes ever fail.
+///
+/// Run a serializer against a `CountingIobuf` first, then allocate a `RWIobuf`
+/// of exactly `written()` bytes, and run it again for real. Write the
+/// serializer against `IobufWrite` to use the same code for both.
+///
+/// Like a `RWIobuf` with an endless window, `fill`s move the front of the
+/// window forwards, and `poke`s are relative to the front of the window.
+/// Counts stop at `u32::MAX`, which is already far more than fits in an
+/// Iobuf.
+///
+/// ```rust
+/// use iobuf::{CountingIobuf, Iobuf, RWIobuf};
+///
+/// let mut c = CountingIobuf::new();
+/// assert_eq!(c.fill_be(0u32), Ok(()));
+/// assert_eq!(c.fill(b"hello"), Ok(()));
+/// assert_eq!(c.written(), 9);
+///
+/// // Pokes past the front of the window count too.
+/// assert_eq!(c.poke_le(4, 0u16), Ok(()));
+/// assert_eq!(c.written(), 15);
+///
+/// let b = RWIobuf::new(c.written() as usize);
+/// assert_eq!(b.len(), 15);
+/// ```
+#[derive(Clone, PartialEq, Debug)]
+pub struct CountingIobuf {
+  lo:  u32,
+  // Pokes only take `&self`, like `RWIobuf`'s.
+  max: Cell<u32>,
+}
+
+/// The length of `s`, or `u32::MAX` if it's even longer than that.
+#[inline(always)]
+fn slice_len(s: &[u8]) -> u32 {
+  if s.len() > u32::MAX as usize { u32::MAX } else { s.len() as u32 }
+}
+
+impl CountingIobuf {
+  /// A new counter, with nothing written yet.
+  #[inline]
+  pub fn new() -> CountingIobuf {
+    CountingIobuf { lo: 0, max: Cell::new(0) }
+  }
+
+  /// The size of a buffer which would fit everything written so far: the
+  /// furthest any `fill` or `poke` reached.
+  #[inline(always)]
+  pub fn written(&self) -> u32 { self.max.get() }
+
+  /// How far the front of the window has been moved by `fill`s.
+  #[inline(always)]
+  pub fn filled(&self) -> u32 { self.lo }
+
+  #[inline]
+  fn touch(&self, pos: u32, len: u32) {
+    let end = self.lo.checked_add(pos).and_then(|e| e.checked_add(len)).unwrap_or(u32::MAX);
+    if end > self.max.get() { self.max.set(end) }
+  }
+
+  /// Counts a `RWIobuf::poke`.
+  #[inline]
+  pub fn poke(&self, pos: u32, src: &[u8]) -> Result<(), ()> {
+    self.touch(pos, slice_len(src));
+    Ok(())
+  }
+
+  /// Counts a `RWIobuf::poke_be`.
+  #[inline]
+  pub fn poke_be<T: Prim>(&self, pos: u32, _: T) -> Result<(), ()> {
+    self.touch(pos, prim_len::<T>() as u32);
+    Ok(())
+  }
+
+  /// Counts a `RWIobuf::poke_le`.
+  #[inline]
+  pub fn poke_le<T: Prim>(&self, pos: u32, _: T) -> Result<(), ()> {
+    self.touch(pos, prim_len::<T>() as u32);
+    Ok(())
+  }
+
+  /// Counts a `RWIobuf::fill`.
+  #[inline]
+  pub fn fill(&mut self, src: &[u8]) -> Result<(), ()> {
+    self.advance(slice_len(src))
+  }
+
+  /// Counts a `RWIobuf::fill_be`.
+  #[inline]
+  pub fn fill_be<T: Prim>(&mut self, _: T) -> Result<(), ()> {
+    self.advance(prim_len::<T>() as u32)
+  }
+
+  /// Counts a `RWIobuf::fill_le`.
+  #[inline]
+  pub fn fill_le<T: Prim>(&mut self, _: T) -> Result<(), ()> {
+    self.advance(prim_len::<T>() as u32)
+  }
+
+  /// Counts skipping over `len` bytes, without writing them.
+  #[inline]
+  pub fn advance(&mut self, len: u32) -> Result<(), ()> {
+    self.touch(0, len);
+    self.lo = self.lo.checked_add(len).unwrap_or(u32::MAX);
+    Ok(())
+  }
+}
+
+impl IobufWrite for CountingIobuf {
+  #[inline(always)]
+  fn poke(&self, pos: u32, src: &[u8]) -> Result<(), ()> { CountingIobuf::poke(self, pos, src) }
+
+  #[inline(always)]
+  fn poke_be<T: Prim>(&self, pos: u32, t: T) -> Result<(), ()> { CountingIobuf::poke_be(self, pos, t) }
+
+  #[inline(always)]
+  fn poke_le<T: Prim>(&self, pos: u32, t: T) -> Result<(), ()> { CountingIobuf::poke_le(self, pos, t) }
+
+  #[inline(always)]
+  fn fill(&mut self, src: &[u8]) -> Result<(), ()> { CountingIobuf::fill(self, src) }
+
+  #[inline(always)]
+  fn fill_be<T: Prim>(&mut self, t: T) -> Result<(), ()> { CountingIobuf::fill_be(self, t) }
+
+  #[inline(always)]
+  fn fill_le<T: Prim>(&mut self, t: T) -> Result<(), ()> { CountingIobuf::fill_le(self, t) }
+}
+
+#[test]
+fn counting_matches_real_size() {
+  use iobuf::Iobuf;
+  use impls::RWIobuf;
+
+  struct Message<'a> { id: u32, flags: u16, name: &'a str }
+
+  fn encode<B: IobufWrite>(m: &Message, out: &mut B) -> Result<(), ()> {
+    let name = m.name.as_bytes();
+    try!(out.fill_be(m.id));
+    // The flags go after the name, which hasn't been written yet.
+    try!(out.poke_be(2 + name.len() as u32, m.flags));
+    try!(out.fill_le(name.len() as u16));
+    out.fill(name)
+  }
+
+  let m = Message { id: 7, flags: 0xF00F, name: "iobuf" };
+
+  let mut c = CountingIobuf::new();
+  assert_eq!(encode(&m, &mut c), Ok(()));
+  assert_eq!(c.written(), 13);
+  assert_eq!(c.filled(), 11);
+
+  let mut b = RWIobuf::new(c.written() as usize);
+  assert_eq!(encode(&m, &mut b), Ok(()));
+  assert_eq!(b.len(), 2);
+
+  unsafe {
+    assert_eq!(b.as_limit_slice(),
+               b"\x00\x00\x00\x07\x05\x00iobuf\xF0\x0F");
+  }
+
+  // A buffer any smaller doesn't fit it.
+  let mut b = RWIobuf::new(c.written() as usize - 1);
+  assert_eq!(encode(&m, &mut b), Err(()));
+}
+
+#[test]
+fn counting_saturates() {
+  let mut c = CountingIobuf::new();
+  assert_eq!(c.advance(u32::MAX - 1), Ok(()));
+  assert_eq!(c.poke_be(1, 0u32), Ok(()));
+  assert_eq!(c.written(), u32::MAX);
+  assert_eq!(c.fill_be(0u64), Ok(()));
+  assert_eq!(c.filled(), u32::MAX);
+  assert_eq!(c.written(), u32::MAX);
+}
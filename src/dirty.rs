@@ -0,0 +1,159 @@
+use std::cmp;
+
+use raw::{Prim, prim_len};
+use iobuf::Iobuf;
+use impls::RWIobuf;
+
+/// A `RWIobuf` which keeps track of which bytes have been written, so that
+/// only those need to be flushed back to wherever the buffer came from (an
+/// `mmap`ed file, say).
+///
+/// Tracking is opt-in: wrap the buffer (with `RWIobuf::enable_dirty_tracking`,
+/// say) when you want it. It costs a couple of comparisons per write. The
+/// range is kept in this wrapper, not in the buffer, so writes through other
+/// Iobufs over the same memory, or through `get_mut`, aren't tracked.
+///
+/// It's a wrapper, rather than a mode of `RWIobuf` itself, so that Iobufs
+/// stay 24 bytes, and writes through Iobufs which aren't tracked don't pay for
+/// checking whether they are.
+///
+/// ```rust
+/// use iobuf::{DirtyIobuf, Iobuf, RWIobuf};
+///
+/// let mut b = DirtyIobuf::new(RWIobuf::new(100));
+/// assert_eq!(b.dirty_range(), None);
+///
+/// assert_eq!(b.poke_be(50, 0u32), Ok(()));
+/// assert_eq!(b.poke(10, b"hi"), Ok(()));
+/// assert_eq!(b.dirty_range(), Some((10, 54)));
+///
+/// b.clear_dirty();
+/// assert_eq!(b.get_mut().advance(20), Ok(()));
+/// assert_eq!(b.fill(b"abc"), Ok(()));
+/// assert_eq!(b.dirty_range(), Some((20, 23)));
+/// ```
+pub struct DirtyIobuf<'a> {
+  buf:   RWIobuf<'a>,
+  dirty: Option<(u32, u32)>,
+}
+
+impl<'a> DirtyIobuf<'a> {
+  /// Starts tracking writes to `buf`. Nothing is dirty yet.
+  #[inline]
+  pub fn new(buf: RWIobuf<'a>) -> DirtyIobuf<'a> {
+    DirtyIobuf { buf: buf, dirty: None }
+  }
+
+  /// The range of bytes written since tracking started, or since the last
+  /// `clear_dirty`, as `[lo, hi)` offsets into the buffer returned by
+  /// `ptr()`, the same as `lo()` and `hi()`. This may include bytes in between
+  /// writes which weren't written.
+  #[inline(always)]
+  pub fn dirty_range(&self) -> Option<(u32, u32)> { self.dirty }
+
+  /// Forgets about everything written so far. Call this after flushing.
+  #[inline(always)]
+  pub fn clear_dirty(&mut self) { self.dirty = None }
+
+  /// The underlying Iobuf.
+  #[inline(always)]
+  pub fn get_ref(&self) -> &RWIobuf<'a> { &self.buf }
+
+  /// The underlying Iobuf. Writes made through this aren't tracked, but
+  /// moving the window is fine.
+  #[inline(always)]
+  pub fn get_mut(&mut self) -> &mut RWIobuf<'a> { &mut self.buf }
+
+  /// Stops tracking, and unwraps the underlying Iobuf.
+  #[inline(always)]
+  pub fn into_inner(self) -> RWIobuf<'a> { self.buf }
+
+  #[inline]
+  fn mark<T>(&mut self, pos: u32, len: usize, r: Result<T, ()>) -> Result<T, ()> {
+    if r.is_ok() && len != 0 {
+      let lo = self.buf.lo() + pos;
+      let hi = lo + len as u32;
+      self.dirty = Some(match self.dirty {
+        None           => (lo, hi),
+        Some((l, h))   => (cmp::min(l, lo), cmp::max(h, hi)),
+      });
+    }
+    r
+  }
+
+  /// `RWIobuf::poke`, tracking the bytes written.
+  #[inline]
+  pub fn poke(&mut self, pos: u32, src: &[u8]) -> Result<(), ()> {
+    let r = self.buf.poke(pos, src);
+    self.mark(pos, src.len(), r)
+  }
+
+  /// `RWIobuf::poke_be`, tracking the bytes written.
+  #[inline]
+  pub fn poke_be<T: Prim>(&mut self, pos: u32, t: T) -> Result<(), ()> {
+    let r = self.buf.poke_be(pos, t);
+    self.mark(pos, prim_len::<T>(), r)
+  }
+
+  /// `RWIobuf::poke_le`, tracking the bytes written.
+  #[inline]
+  pub fn poke_le<T: Prim>(&mut self, pos: u32, t: T) -> Result<(), ()> {
+    let r = self.buf.poke_le(pos, t);
+    self.mark(pos, prim_len::<T>(), r)
+  }
+
+  /// `RWIobuf::fill`, tracking the bytes written.
+  #[inline]
+  pub fn fill(&mut self, src: &[u8]) -> Result<(), ()> {
+    let r = self.buf.poke(0, src);
+    try!(self.mark(0, src.len(), r));
+    unsafe { self.buf.unsafe_advance(src.len() as u32) };
+    Ok(())
+  }
+
+  /// `RWIobuf::fill_be`, tracking the bytes written.
+  #[inline]
+  pub fn fill_be<T: Prim>(&mut self, t: T) -> Result<(), ()> {
+    try!(self.poke_be(0, t));
+    unsafe { self.buf.unsafe_advance(prim_len::<T>() as u32) };
+    Ok(())
+  }
+
+  /// `RWIobuf::fill_le`, tracking the bytes written.
+  #[inline]
+  pub fn fill_le<T: Prim>(&mut self, t: T) -> Result<(), ()> {
+    try!(self.poke_le(0, t));
+    unsafe { self.buf.unsafe_advance(prim_len::<T>() as u32) };
+    Ok(())
+  }
+}
+
+#[test]
+fn dirty_scattered_pokes() {
+  let mut b = RWIobuf::new(64);
+  assert_eq!(b.advance(8), Ok(()));
+  let mut b = DirtyIobuf::new(b);
+
+  // Failed writes don't count.
+  assert_eq!(b.poke_be(60, 0u64), Err(()));
+  assert_eq!(b.poke(0, b""), Ok(()));
+  assert_eq!(b.dirty_range(), None);
+
+  assert_eq!(b.poke_le(30, 1u16), Ok(()));
+  assert_eq!(b.dirty_range(), Some((38, 40)));
+  assert_eq!(b.poke(4, b"x"), Ok(()));
+  assert_eq!(b.dirty_range(), Some((12, 40)));
+  assert_eq!(b.poke_be(52, 7u32), Ok(()));
+  assert_eq!(b.dirty_range(), Some((12, 64)));
+  assert_eq!(b.poke(20, b"inside"), Ok(()));
+  assert_eq!(b.dirty_range(), Some((12, 64)));
+
+  b.clear_dirty();
+  assert_eq!(b.fill_be(0u32), Ok(()));
+  assert_eq!(b.fill_le(0u8), Ok(()));
+  assert_eq!(b.dirty_range(), Some((8, 13)));
+  assert_eq!(b.get_ref().lo(), 13);
+
+  let b = b.into_inner();
+  assert_eq!(b.peek_be::<u32>(47), Ok(7));
+}
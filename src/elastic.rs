@@ -0,0 +1,209 @@
+use std::fmt::{self, Debug, Formatter};
+
+use raw::{Prim, prim_len};
+use iobuf::{Iobuf, IobufWrite};
+use impls::{ROIobuf, RWIobuf};
+
+/// A writer which fills a preallocated `RWIobuf` while it has room, and then
+/// spills over into a `Vec<u8>`, so that writes never fail.
+///
+/// Size the buffer for the common case, and the fast path is just a `fill`
+/// into it. Only messages that turn out to be bigger pay for the spill, and
+/// for the copy in `finish` that puts everything back together.
+///
+/// Once anything has spilled, everything after it is spilled too, to keep the
+/// bytes in order.
+///
+/// It's also an `IobufWrite`, so generic serializers can write into it. The
+/// `fill`s there never fail either. `poke`s only reach bytes still in the
+/// buffer's window, so they fail once anything has spilled.
+///
+/// ```rust
+/// use iobuf::{ElasticWriter, Iobuf, RWIobuf};
+///
+/// let mut w = ElasticWriter::new(RWIobuf::new(4));
+/// w.fill_be(0xAABBu16);
+/// w.fill_str("hello");
+/// assert_eq!(w.written(), 7);
+/// assert_eq!(w.spilled(), 5);
+///
+/// let b = w.finish();
+/// unsafe { assert_eq!(b.as_window_slice(), b"\xAA\xBBhello"); }
+/// ```
+pub struct ElasticWriter {
+  buf:   RWIobuf<'static>,
+  spill: Vec<u8>,
+}
+
+impl ElasticWriter {
+  /// Writes into the window of `buf` first. Everything outside of the window
+  /// is left alone.
+  #[inline]
+  pub fn new(buf: RWIobuf<'static>) -> ElasticWriter {
+    let mut buf = buf;
+    buf.narrow();
+    ElasticWriter { buf: buf, spill: Vec::new() }
+  }
+
+  /// The number of bytes written so far, including any that spilled.
+  #[inline]
+  pub fn written(&self) -> u32 {
+    self.buf.lo_space() + self.spill.len() as u32
+  }
+
+  /// The number of bytes that didn't fit in the buffer.
+  #[inline(always)]
+  pub fn spilled(&self) -> u32 { self.spill.len() as u32 }
+
+  /// Writes `src`, spilling if it doesn't fit.
+  #[inline]
+  pub fn fill(&mut self, src: &[u8]) {
+    if !self.spill.is_empty() || self.buf.fill(src).is_err() {
+      self.spill.push_all(src);
+    }
+  }
+
+  /// Writes the bytes of `s`, spilling if they don't fit.
+  #[inline]
+  pub fn fill_str(&mut self, s: &str) {
+    self.fill(s.as_bytes())
+  }
+
+  /// Writes a big-endian primitive, spilling if it doesn't fit.
+  #[inline]
+  pub fn fill_be<T: Prim>(&mut self, t: T) {
+    if !self.spill.is_empty() || self.buf.fill_be(t).is_err() {
+      self.spill_prim(t, true);
+    }
+  }
+
+  /// Writes a little-endian primitive, spilling if it doesn't fit.
+  #[inline]
+  pub fn fill_le<T: Prim>(&mut self, t: T) {
+    if !self.spill.is_empty() || self.buf.fill_le(t).is_err() {
+      self.spill_prim(t, false);
+    }
+  }
+
+  #[cold]
+  fn spill_prim<T: Prim>(&mut self, t: T, big_endian: bool) {
+    let mut tmp = [0u8; 8];
+    let len = prim_len::<T>();
+    {
+      let b = RWIobuf::from_slice(&mut tmp[..len]);
+      b.poke_prim(0, t, big_endian).unwrap();
+    }
+    self.spill.push_all(&tmp[..len]);
+  }
+
+  /// Everything written, in one contiguous buffer. If nothing spilled, this
+  /// is the original buffer, with its window set to the written bytes.
+  /// Otherwise, it's a new buffer.
+  pub fn finish(self) -> ROIobuf<'static> {
+    let mut buf = self.buf;
+    buf.flip_lo();
+
+    if self.spill.is_empty() {
+      return buf.read_only();
+    }
+
+    let mut b = RWIobuf::new((buf.len() + self.spilled()) as usize);
+    unsafe {
+      b.unsafe_fill(buf.as_window_slice());
+      b.unsafe_fill(&self.spill[]);
+    }
+    b.flip_lo();
+    b.read_only()
+  }
+}
+
+impl IobufWrite for ElasticWriter {
+  #[inline]
+  fn poke(&self, pos: u32, src: &[u8]) -> Result<(), ()> {
+    if !self.spill.is_empty() { return Err(()) }
+    self.buf.poke(pos, src)
+  }
+
+  #[inline]
+  fn poke_be<T: Prim>(&self, pos: u32, t: T) -> Result<(), ()> {
+    if !self.spill.is_empty() { return Err(()) }
+    self.buf.poke_be(pos, t)
+  }
+
+  #[inline]
+  fn poke_le<T: Prim>(&self, pos: u32, t: T) -> Result<(), ()> {
+    if !self.spill.is_empty() { return Err(()) }
+    self.buf.poke_le(pos, t)
+  }
+
+  #[inline]
+  fn fill(&mut self, src: &[u8]) -> Result<(), ()> { ElasticWriter::fill(self, src); Ok(()) }
+
+  #[inline]
+  fn fill_be<T: Prim>(&mut self, t: T) -> Result<(), ()> { ElasticWriter::fill_be(self, t); Ok(()) }
+
+  #[inline]
+  fn fill_le<T: Prim>(&mut self, t: T) -> Result<(), ()> { ElasticWriter::fill_le(self, t); Ok(()) }
+}
+
+impl Debug for ElasticWriter {
+  fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+    write!(f, "ElasticWriter {{ written: {}, spilled: {} }}", self.written(), self.spilled())
+  }
+}
+
+#[test]
+fn elastic_spill_order() {
+  let mut b = RWIobuf::new(16);
+  assert_eq!(b.advance(2), Ok(()));
+  assert_eq!(b.resize(7), Ok(()));
+
+  let mut w = ElasticWriter::new(b);
+  w.fill(b"abc");
+  w.fill_le(0x0201u16);
+  assert_eq!(w.spilled(), 0);
+
+  // Doesn't fit, even though there's room in the buffer past the window.
+  w.fill_be(0x03040506u32);
+  assert_eq!(w.spilled(), 4);
+
+  // Would fit, but has to go after what's already spilled.
+  w.fill(b"z");
+  assert_eq!(w.spilled(), 5);
+  assert_eq!(w.written(), 10);
+
+  let b = w.finish();
+  unsafe { assert_eq!(b.as_window_slice(), b"abc\x01\x02\x03\x04\x05\x06z"); }
+
+  // No spill, no copy.
+  let b = RWIobuf::new(8);
+  let p = b.ptr();
+  let mut w = ElasticWriter::new(b);
+  w.fill_str("hi");
+  let b = w.finish();
+  assert_eq!(b.ptr(), p);
+  assert_eq!(b.len(), 2);
+}
+
+#[test]
+fn elastic_generic_writes() {
+  fn encode<B: IobufWrite>(out: &mut B) -> Result<(), ()> {
+    try!(out.fill_le(0x0201u16));
+    out.fill(b"xyz")
+  }
+
+  let mut w = ElasticWriter::new(RWIobuf::new(4));
+
+  // Before anything spills, pokes land in the buffer.
+  assert_eq!(IobufWrite::poke_be(&w, 3, 0u8), Ok(()));
+  assert_eq!(IobufWrite::poke_be(&w, 4, 0u8), Err(()));
+
+  assert_eq!(encode(&mut w), Ok(()));
+  assert_eq!(w.spilled(), 3);
+
+  // After, there's nothing in the window left to poke into.
+  assert_eq!(IobufWrite::poke(&w, 0, b"!"), Err(()));
+
+  let b = w.finish();
+  unsafe { assert_eq!(b.as_window_slice(), b"\x01\x02xyz"); }
+}
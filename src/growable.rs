@@ -0,0 +1,181 @@
+use std::cmp;
+use std::fmt::{self, Debug, Formatter};
+
+use raw::{Prim, prim_len};
+use iobuf::Iobuf;
+use impls::{ROIobuf, RWIobuf};
+
+/// Growable Read-Write Iobuf
+///
+/// A `RWIobuf` that reallocates itself when it runs out of room, like a
+/// `Vec<u8>`. This is handy as a serialization sink when the final size isn't
+/// known up front.
+///
+/// The window is the space that can still be written into without growing.
+/// Everything between the lower limit and the window has already been written.
+pub struct GrowableRWIobuf {
+  buf: RWIobuf<'static>,
+}
+
+impl GrowableRWIobuf {
+  /// Creates a new growable Iobuf, with room for `cap` bytes before the first
+  /// reallocation.
+  ///
+  /// ```rust
+  /// use iobuf::{GrowableRWIobuf, Iobuf};
+  ///
+  /// let b = GrowableRWIobuf::new(10);
+  /// assert_eq!(b.cap(), 10);
+  /// assert_eq!(b.written(), 0);
+  /// ```
+  #[inline]
+  pub fn new(cap: usize) -> GrowableRWIobuf {
+    GrowableRWIobuf { buf: RWIobuf::new(cap) }
+  }
+
+  /// The number of bytes written so far.
+  #[inline(always)]
+  pub fn written(&self) -> u32 { self.buf.lo_space() }
+
+  /// The number of bytes that can be written before the next reallocation.
+  #[inline(always)]
+  pub fn len(&self) -> u32 { self.buf.len() }
+
+  /// The size of the current backing buffer.
+  #[inline(always)]
+  pub fn cap(&self) -> u32 { self.buf.cap() }
+
+  /// Makes sure at least `additional` bytes can be written without another
+  /// reallocation. If the buffer has to grow, its capacity is at least doubled,
+  /// the written bytes are copied into the new buffer, and the window is reset
+  /// to cover all the remaining space.
+  ///
+  /// Growing reallocates, so it invalidates any slices or pointers previously
+  /// taken from the window.
+  #[inline]
+  pub fn reserve(&mut self, additional: u32) {
+    if self.buf.len() < additional {
+      self.grow(additional as usize)
+    }
+  }
+
+  #[cold]
+  fn grow(&mut self, additional: usize) {
+    // `RWIobuf::new` will panic if this is too big to fit in an Iobuf.
+    let written = self.written() as usize;
+    let new_cap = cmp::max(written + additional, 2 * self.cap() as usize);
+
+    let mut new_buf = RWIobuf::new(new_cap);
+    unsafe {
+      new_buf.unsafe_fill(&self.buf.as_limit_slice()[..written]);
+    }
+
+    self.buf = new_buf;
+  }
+
+  /// Writes `src` into the front of the window, and advances the window past
+  /// it. If there isn't enough room, the buffer grows first, so this always
+  /// succeeds (unless we run out of memory). This is the growable counterpart
+  /// to `fill`.
+  ///
+  /// This can reallocate, and therefore invalidates any slices or pointers
+  /// previously taken from the window.
+  ///
+  /// ```rust
+  /// use iobuf::{GrowableRWIobuf, Iobuf};
+  ///
+  /// let mut b = GrowableRWIobuf::new(4);
+  ///
+  /// b.fill_growing(b"hello");
+  /// b.fill_growing(b", world");
+  ///
+  /// assert_eq!(b.written(), 12);
+  /// assert!(b.cap() >= 12);
+  ///
+  /// let b = b.into_read_only();
+  /// unsafe { assert_eq!(b.as_window_slice(), b"hello, world"); }
+  /// ```
+  #[inline]
+  pub fn fill_growing(&mut self, src: &[u8]) {
+    let len = src.len();
+    if len > self.buf.len() as usize {
+      self.grow(len);
+    }
+    unsafe { self.buf.unsafe_fill(src) }
+  }
+
+  /// Writes a big-endian primitive into the front of the window, growing the
+  /// buffer first if there isn't room. The growable counterpart to `fill_be`.
+  ///
+  /// ```rust
+  /// use iobuf::{GrowableRWIobuf, Iobuf};
+  ///
+  /// let mut b = GrowableRWIobuf::new(1);
+  /// b.fill_be_growing(0x0102u16);
+  /// b.fill_le_growing(0x0304u16);
+  ///
+  /// let b = b.into_read_only();
+  /// unsafe { assert_eq!(b.as_window_slice(), [ 1, 2, 4, 3 ]); }
+  /// ```
+  #[inline]
+  pub fn fill_be_growing<T: Prim>(&mut self, t: T) {
+    self.reserve(prim_len::<T>() as u32);
+    unsafe { self.buf.unsafe_fill_be(t) }
+  }
+
+  /// Writes a little-endian primitive into the front of the window, growing
+  /// the buffer first if there isn't room. The growable counterpart to
+  /// `fill_le`.
+  #[inline]
+  pub fn fill_le_growing<T: Prim>(&mut self, t: T) {
+    self.reserve(prim_len::<T>() as u32);
+    unsafe { self.buf.unsafe_fill_le(t) }
+  }
+
+  /// Gets at the underlying Iobuf, to `peek` at or `poke` into it.
+  ///
+  /// Only a shared reference is handed out, so the limits and window can't be
+  /// moved out from under the bookkeeping that decides when to grow. Use the
+  /// `_growing` functions to write and advance.
+  #[inline(always)]
+  pub fn as_iobuf(&self) -> &RWIobuf<'static> { &self.buf }
+
+  /// Returns the underlying Iobuf, with its window set to the written bytes.
+  #[inline]
+  pub fn into_iobuf(self) -> RWIobuf<'static> {
+    let mut buf = self.buf;
+    buf.flip_lo();
+    buf
+  }
+
+  /// Returns a read-only Iobuf, with its window set to the written bytes.
+  #[inline]
+  pub fn into_read_only(self) -> ROIobuf<'static> {
+    self.into_iobuf().read_only()
+  }
+}
+
+impl Debug for GrowableRWIobuf {
+  #[inline]
+  fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+    self.buf.fmt(f)
+  }
+}
+
+#[test]
+fn fill_growing_past_capacity() {
+  let mut b = GrowableRWIobuf::new(3);
+
+  for i in range(0u8, 100) {
+    b.fill_growing(&[ i, i ]);
+  }
+
+  assert_eq!(b.written(), 200);
+  assert!(b.cap() >= 200);
+
+  let b = b.into_iobuf();
+  assert_eq!(b.len(), 200);
+  for i in range(0u32, 200) {
+    assert_eq!(b.peek_be(i), Ok((i / 2) as u8));
+  }
+}
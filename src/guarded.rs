@@ -0,0 +1,137 @@
+//! A debugging allocator which puts every buffer (up to its alignment) right
+//! before an inaccessible guard page, so that running off the end of one (with the `unsafe_`
+//! functions, say) faults immediately instead of silently corrupting the
+//! neighbouring allocation.
+//!
+//! This is slow and wastes lots of memory. Only use it while debugging.
+//! Enabled with the `guard-pages` cargo feature.
+
+use std::os;
+use std::ptr;
+
+use raw::Allocator;
+
+const PROT_NONE:   i32 = 0;
+const PROT_READ:   i32 = 1;
+const PROT_WRITE:  i32 = 2;
+const MAP_PRIVATE: i32 = 2;
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+const MAP_ANON: i32 = 0x20;
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+const MAP_ANON: i32 = 0x1000;
+
+extern {
+  fn mmap(addr: *mut u8, len: usize, prot: i32, flags: i32, fd: i32, offset: i64) -> *mut u8;
+  fn munmap(addr: *mut u8, len: usize) -> i32;
+  fn mprotect(addr: *mut u8, len: usize, prot: i32) -> i32;
+}
+
+/// Rounds `len` up to a whole number of pages.
+#[inline]
+fn round_to_pages(len: usize) -> usize {
+  let page = os::page_size();
+  (len + page - 1) / page * page
+}
+
+/// Rounds `len` up to a multiple of `align`.
+#[inline]
+fn round_to_align(len: usize, align: usize) -> usize {
+  (len + align - 1) / align * align
+}
+
+/// Maps enough pages for each allocation, followed by a `PROT_NONE` page. The
+/// allocation is placed as close to the end of its pages as its alignment
+/// allows, so there are fewer than `align` bytes of slack before the guard
+/// page. Overruns into that slack aren't caught; `RWIobuf::new_guarded` pads
+/// its buffers to a whole number of words so that there isn't any.
+pub struct GuardedAllocator;
+
+impl Allocator for GuardedAllocator {
+  fn allocate(&self, len: usize, align: usize) -> *mut u8 {
+    let padded   = round_to_align(len, align);
+    let data_len = round_to_pages(padded);
+    unsafe {
+      let base = mmap(ptr::null_mut(), data_len + os::page_size(),
+                      PROT_READ | PROT_WRITE, MAP_PRIVATE | MAP_ANON, -1, 0);
+      if base as isize == -1 {
+        panic!("iobuf: mmap failed for a guarded buffer of {} bytes", len);
+      }
+      if mprotect(base.offset(data_len as isize), os::page_size(), PROT_NONE) != 0 {
+        panic!("iobuf: couldn't protect the guard page");
+      }
+      base.offset((data_len - padded) as isize)
+    }
+  }
+
+  fn deallocate(&self, ptr: *mut u8, len: usize, align: usize) {
+    let padded   = round_to_align(len, align);
+    let data_len = round_to_pages(padded);
+    unsafe {
+      let base = ptr.offset(padded as isize - data_len as isize);
+      munmap(base, data_len + os::page_size());
+    }
+  }
+}
+
+#[test]
+fn guarded_in_bounds() {
+  use iobuf::Iobuf;
+  use impls::RWIobuf;
+
+  for len in range(0us, 20) {
+    let mut b = RWIobuf::new_guarded(len);
+    assert_eq!(b.len(), len as u32);
+    assert_eq!(b.cap(), len as u32);
+
+    // Every byte in the buffer is writeable, right up to the guard page.
+    for i in range(0u32, len as u32) {
+      unsafe { b.unsafe_poke_be(i, i as u8); }
+    }
+    for i in range(0u32, len as u32) {
+      assert_eq!(b.peek_be(i), Ok(i as u8));
+    }
+
+    b.flip_hi();
+    assert!(b.is_empty());
+  }
+}
+
+#[test]
+fn guarded_allocations_are_aligned() {
+  for &align in [1us, 4, 8, 16, 64].iter() {
+    for len in range(1us, 40) {
+      let p = GuardedAllocator.allocate(len, align);
+      assert_eq!(p as usize % align, 0);
+      unsafe { *p.offset(len as isize - 1) = 0xFF; }
+      GuardedAllocator.deallocate(p, len, align);
+    }
+  }
+}
+
+#[test]
+#[ignore]
+fn guarded_overrun_child() {
+  use impls::RWIobuf;
+
+  let b = RWIobuf::new_guarded(13);
+  unsafe {
+    let p = b.ptr().offset(b.hi() as isize);
+    *p = 0xFF;
+  }
+}
+
+// The overrun kills the process, so it has to happen in a child.
+#[test]
+fn guarded_overrun_faults() {
+  use std::io::Command;
+
+  let out =
+    Command::new(os::self_exe_name().unwrap())
+      .arg("--ignored")
+      .arg("guarded_overrun_child")
+      .output()
+      .unwrap();
+
+  assert!(!out.status.success());
+}
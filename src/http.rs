@@ -0,0 +1,212 @@
+use std::u32;
+
+use iobuf::Iobuf;
+
+/// One element of a body sent with the HTTP/1.1 "chunked" transfer-coding.
+#[derive(Debug)]
+pub enum ChunkEvent<Buf> {
+  /// The payload of one chunk. This shares the buffer it was parsed out of,
+  /// and its limits are narrowed to the payload.
+  Data(Buf),
+  /// The terminating zero-size chunk. This holds the trailer fields, each
+  /// ending in a CRLF, but not the empty line that ends the body. It's empty if
+  /// there were no trailer fields.
+  Done(Buf),
+}
+
+/// The reasons `consume_http_chunk` can fail.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum ChunkError {
+  /// The window does not (yet) hold a complete element. Nothing was consumed,
+  /// so `compact`, read more data, and try again.
+  Incomplete,
+  /// The chunk size wasn't a hexadecimal number, or didn't fit in a `u32`.
+  BadSize,
+  /// A CRLF was expected, but something else was found.
+  MissingCrlf,
+}
+
+#[inline]
+fn hex_digit(c: u8) -> Option<u32> {
+  match c {
+    b'0' ... b'9' => Some((c - b'0') as u32),
+    b'a' ... b'f' => Some((c - b'a' + 10) as u32),
+    b'A' ... b'F' => Some((c - b'A' + 10) as u32),
+    _             => None,
+  }
+}
+
+/// Returns the index of the next CRLF at or after `from`.
+fn find_crlf(s: &[u8], from: usize) -> Result<usize, ChunkError> {
+  match s[from..].iter().position(|&c| c == b'\r') {
+    None => Err(ChunkError::Incomplete),
+    Some(off) => {
+      let i = from + off;
+      if i + 1 >= s.len()   { Err(ChunkError::Incomplete)  }
+      else if s[i+1] != b'\n' { Err(ChunkError::MissingCrlf) }
+      else                  { Ok(i) }
+    }
+  }
+}
+
+/// Parses the element at the front of `s`. Returns whether it's the last
+/// chunk, the position and length of its payload (or trailer), and the total
+/// length of the element.
+fn parse_chunk(s: &[u8]) -> Result<(bool, u32, u32, u32), ChunkError> {
+  let mut i    = 0us;
+  let mut size = 0u64;
+
+  while i < s.len() {
+    match hex_digit(s[i]) {
+      None => break,
+      Some(d) => {
+        size = size * 16 + d as u64;
+        if size > u32::MAX as u64 { return Err(ChunkError::BadSize); }
+        i += 1;
+      }
+    }
+  }
+
+  if i == s.len() { return Err(ChunkError::Incomplete); }
+  if i == 0       { return Err(ChunkError::BadSize);    }
+
+  // Anything between the size and the CRLF is a chunk extension, which we
+  // skip over.
+  match s[i] {
+    b';' | b' ' | b'\t' | b'\r' => {},
+    _ => return Err(ChunkError::BadSize),
+  }
+
+  let data_start = try!(find_crlf(s, i)) + 2;
+
+  if size == 0 {
+    // The trailer is a list of header fields, ended by an empty line.
+    let mut p = data_start;
+    loop {
+      let eol = try!(find_crlf(s, p));
+      if eol == p {
+        return Ok((true, data_start as u32, (p - data_start) as u32, (p + 2) as u32));
+      }
+      p = eol + 2;
+    }
+  }
+
+  let data_end = data_start as u64 + size;
+
+  if data_end + 2 > s.len() as u64 { return Err(ChunkError::Incomplete); }
+
+  let data_end = data_end as usize;
+
+  if &s[data_end .. data_end + 2] != b"\r\n" { return Err(ChunkError::MissingCrlf); }
+
+  Ok((false, data_start as u32, size as u32, (data_end + 2) as u32))
+}
+
+/// Decodes one element of a chunked HTTP/1.1 body from the front of the
+/// window.
+///
+/// The window is only advanced if a whole element (the size line, the payload,
+/// and its CRLF, or the last chunk and its trailer) is available. Otherwise,
+/// `Err(ChunkError::Incomplete)` is returned and nothing is consumed. Chunk
+/// extensions are skipped over. No bytes are copied: the payloads share the
+/// original buffer.
+///
+/// ```rust
+/// use iobuf::{ROIobuf, Iobuf, ChunkEvent, ChunkError, consume_http_chunk};
+///
+/// let mut b = ROIobuf::from_str("4\r\nWiki\r\n5;ext=1\r\npedia\r\n0\r\n\r\n");
+/// let mut body = vec!();
+///
+/// loop {
+///   match consume_http_chunk(&mut b) {
+///     Ok(ChunkEvent::Data(c)) => body.push_all(unsafe { c.as_window_slice() }),
+///     Ok(ChunkEvent::Done(t)) => { assert!(t.is_empty()); break },
+///     Err(e) => panic!("unexpected error: {:?}", e),
+///   }
+/// }
+///
+/// assert_eq!(&body[], b"Wikipedia");
+/// assert!(b.is_empty());
+///
+/// // Half a chunk isn't enough.
+/// let mut b = ROIobuf::from_str("4\r\nWi");
+/// assert_eq!(consume_http_chunk(&mut b).err(), Some(ChunkError::Incomplete));
+/// assert_eq!(b.len(), 5);
+/// ```
+pub fn consume_http_chunk<Buf: Iobuf>(buf: &mut Buf) -> Result<ChunkEvent<Buf>, ChunkError> {
+  let (is_last, pos, len, total) = try!(parse_chunk(unsafe { buf.as_window_slice() }));
+
+  let mut ret = buf.clone();
+  unsafe {
+    ret.unsafe_sub(pos, len);
+    buf.unsafe_advance(total);
+  }
+
+  Ok(if is_last { ChunkEvent::Done(ret) } else { ChunkEvent::Data(ret) })
+}
+
+#[cfg(test)]
+fn decode_all(s: &str) -> Result<(Vec<u8>, Vec<u8>), ChunkError> {
+  use impls::ROIobuf;
+
+  let mut b = ROIobuf::from_str(s);
+  let mut body = vec!();
+
+  loop {
+    match try!(consume_http_chunk(&mut b)) {
+      ChunkEvent::Data(c) => body.push_all(unsafe { c.as_window_slice() }),
+      ChunkEvent::Done(t) => {
+        assert!(b.is_empty());
+        return Ok((body, unsafe { t.as_window_slice().to_vec() }));
+      }
+    }
+  }
+}
+
+#[test]
+fn rfc7230_example() {
+  // The example body from RFC 7230 section 4.1 and its errata, with
+  // extensions and a trailer.
+  let s = "4\r\nWiki\r\n5\r\npedia\r\nE\r\n in\r\n\r\nchunks.\r\n0\r\n\r\n";
+  assert_eq!(decode_all(s), Ok((b"Wikipedia in\r\n\r\nchunks.".to_vec(), vec!())));
+
+  let s = "1a; name=value\r\nabcdefghijklmnopqrstuvwxyz\r\n0;last\r\nExpires: never\r\nX-Foo: bar\r\n\r\n";
+  assert_eq!(decode_all(s),
+             Ok((b"abcdefghijklmnopqrstuvwxyz".to_vec(),
+                 b"Expires: never\r\nX-Foo: bar\r\n".to_vec())));
+}
+
+#[test]
+fn chunk_errors() {
+  assert_eq!(decode_all("zz\r\n"),           Err(ChunkError::BadSize));
+  assert_eq!(decode_all("4x\r\nWiki\r\n"),   Err(ChunkError::BadSize));
+  assert_eq!(decode_all("100000000\r\n"),    Err(ChunkError::BadSize));
+  assert_eq!(decode_all("4\rWiki\r\n"),      Err(ChunkError::MissingCrlf));
+  assert_eq!(decode_all("4\r\nWikiXX"),      Err(ChunkError::MissingCrlf));
+  assert_eq!(decode_all("0\r\nX: y\rz"),     Err(ChunkError::MissingCrlf));
+}
+
+#[test]
+fn chunk_incomplete() {
+  use impls::ROIobuf;
+
+  let full = "4\r\nWiki\r\n0\r\nX: y\r\n\r\n";
+
+  // Every strict prefix is either incomplete, or decodes the first chunk and
+  // then is incomplete.
+  for n in range(0, full.len()) {
+    let mut b = ROIobuf::from_str(&full[..n]);
+    loop {
+      let len = b.len();
+      match consume_http_chunk(&mut b) {
+        Ok(ChunkEvent::Data(_)) => {},
+        Ok(ChunkEvent::Done(_)) => panic!("done after {} bytes", n),
+        Err(e) => {
+          assert_eq!(e, ChunkError::Incomplete);
+          assert_eq!(b.len(), len);
+          break;
+        }
+      }
+    }
+  }
+}
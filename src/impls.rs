@@ -1,11 +1,29 @@
+use std::cmp::{self, Ordering};
+use std::ffi::CString;
 use std::fmt::{self, Debug, Formatter};
+use std::hash;
 use std::mem;
-use std::num::Int;
 use std::sync::Arc;
-
-
-use raw::{Allocator, RawIobuf};
-use iobuf::Iobuf;
+use std::io::{self, Reader};
+use std::io::net::ip::{IpAddr, SocketAddr};
+#[cfg(unix)] use std::io::IoResult;
+
+#[cfg(feature = "num-bigint")] use num_bigint::BigUint;
+
+use raw::{Allocator, EnumError, IntArray, IobufState, Prim, RawIobuf, TagError};
+use iobuf::{Iobuf, IobufAlloc, IobufWrite, MutIobuf, ReadFrom};
+use words::WordIter;
+use chunks::{self, RChunks};
+use bytes::{Drain, IntoBytes};
+use records::{RecordIter, Records};
+use tlv::TlvIter;
+use transaction::BufTransaction;
+use window::PositionedWindow;
+use reader::IobufReader;
+use pool::IobufPool;
+use dirty::DirtyIobuf;
+#[cfg(unix)] use locked::LockedAllocator;
+#[cfg(all(unix, feature = "guard-pages"))] use guarded::GuardedAllocator;
 
 /// Read-Only Iobuf
 ///
@@ -233,6 +251,17 @@ impl Drop for UniqueIobuf {
   fn drop(&mut self) { unsafe { self.raw.drop_nonatomic() } }
 }
 
+/// Turns a unique Iobuf back into a writeable one, with the limits and window
+/// widened to the whole buffer. Used to recycle buffers in an `IobufPool`.
+#[inline]
+pub fn reclaim_unique(b: UniqueIobuf) -> RWIobuf<'static> {
+  unsafe {
+    let mut b: RWIobuf<'static> = mem::transmute(b);
+    b.raw.reset_to_allocation();
+    b
+  }
+}
+
 impl<'a> ROIobuf<'a> {
   /// Constructs a trivially empty Iobuf, limits and window are 0, and there's
   /// an empty backing buffer. This will not allocate.
@@ -299,6 +328,13 @@ impl<'a> ROIobuf<'a> {
   /// slice will be copied, so prefer to use the other constructors whenever
   /// possible.
   ///
+  /// This is the way to keep a transient slice around: exactly `s.len()` bytes
+  /// are allocated, and the data is copied once, with no intermediate `Vec`.
+  /// The limits and window cover the whole copy. An empty slice gives an
+  /// `ROIobuf::empty()`, which doesn't allocate. Like any empty Iobuf, that
+  /// doesn't own a buffer: `is_owned` is `false`, and `unique` and
+  /// `atomic_read_only` fail for it.
+  ///
   /// ```rust
   /// use iobuf::{ROIobuf,Iobuf};
   ///
@@ -306,15 +342,35 @@ impl<'a> ROIobuf<'a> {
   /// v[1] = 20;
   ///
   /// let mut b = ROIobuf::from_slice_copy(&v[]);
+  /// drop(v);
   ///
   /// let expected = [ 1,20,3,4,5,6 ];
   /// unsafe { assert_eq!(b.as_window_slice(), &expected[]); }
+  /// assert_eq!(b.cap(), 6);
+  ///
+  /// let e = ROIobuf::from_slice_copy(&[]);
+  /// assert!(e.is_empty());
+  /// assert_eq!(e.cap(), 0);
   /// ```
   #[inline(always)]
   pub fn from_slice_copy(s: &[u8]) -> ROIobuf<'static> {
     ROIobuf { raw: RawIobuf::from_slice_copy(s) }
   }
 
+  /// Constructs a new read-only Iobuf of size `len`, where the byte at offset
+  /// `i` is `f(i)`. See `RWIobuf::from_fn`.
+  ///
+  /// ```rust
+  /// use iobuf::{ROIobuf,Iobuf};
+  ///
+  /// let b = ROIobuf::from_fn(256, |i| i as u8);
+  /// assert_eq!(b.peek_be(200), Ok(200u8));
+  /// ```
+  #[inline]
+  pub fn from_fn<F: FnMut(u32) -> u8>(len: usize, f: F) -> ROIobuf<'static> {
+    RWIobuf::from_fn(len, f).read_only()
+  }
+
   /// Copies a byte vector into a new read-only Iobuf, whose memory comes from
   /// the given allocator.
   #[inline(always)]
@@ -337,10 +393,64 @@ impl<'a> ROIobuf<'a> {
   /// assert_eq!(s[1], 2); // we can still use the slice!
   /// assert_eq!(b.peek_be(1), Ok(0x0304u16)); // ...and the Iobuf!
   /// ```
+  ///
+  /// This is also the way to look at data that's already shared some other
+  /// way, like an `Rc<Vec<u8>>`, without copying it. An Iobuf can't take a
+  /// share of an `Rc` itself. Owned Iobufs keep their refcount in a header
+  /// right in front of the data, and there's no room for one in front of a
+  /// `Vec`'s data. Custom allocators have to be `Send` and `Sync`, which `Rc`
+  /// isn't, so one can't hold the `Rc` either. So either borrow the data while
+  /// a clone of the `Rc` keeps it alive, or copy it with `from_slice_copy` if
+  /// the Iobuf has to outlive it:
+  ///
+  /// ```rust
+  /// use std::rc::Rc;
+  /// use iobuf::{ROIobuf,Iobuf};
+  ///
+  /// let rc = Rc::new(vec!(1u8, 2, 3, 4));
+  ///
+  /// {
+  ///   let b = ROIobuf::from_slice(&rc[]);
+  ///   assert_eq!(b.ptr() as *const u8, rc.as_ptr());
+  /// }
+  ///
+  /// let owned = ROIobuf::from_slice_copy(&rc[]);
+  /// drop(rc);
+  /// assert_eq!(owned.peek_be(0), Ok(0x01020304u32));
+  /// ```
   #[inline(always)]
   pub fn from_slice(s: &'a [u8]) -> ROIobuf<'a> {
     ROIobuf { raw: RawIobuf::from_slice(s) }
   }
+
+  /// Gets a version of this Iobuf that isn't tied to any borrow, so it can be
+  /// stored for as long as you like.
+  ///
+  /// If the buffer is already owned, this is as cheap as `clone`: the two
+  /// Iobufs share the buffer. If it's borrowed (from `from_slice`, say), the
+  /// data between the limits is copied into a new buffer. Either way, the
+  /// limits and window are the same, relative to each other. Borrowed
+  /// buffers with empty limits come back as `ROIobuf::empty()`, which isn't
+  /// owned either, but is `'static` all the same.
+  ///
+  /// ```rust
+  /// use iobuf::{ROIobuf,Iobuf};
+  ///
+  /// let owned = {
+  ///   let s = [ 1, 2, 3, 4 ];
+  ///   let mut b = ROIobuf::from_slice(&s);
+  ///   assert_eq!(b.advance(1), Ok(()));
+  ///   b.to_owned()
+  /// };
+  ///
+  /// assert!(owned.is_owned());
+  /// assert_eq!(owned.cap(), 4);
+  /// unsafe { assert_eq!(owned.as_window_slice(), [ 2, 3, 4 ]); }
+  /// ```
+  #[inline(always)]
+  pub fn to_owned(&self) -> ROIobuf<'static> {
+    ROIobuf { raw: unsafe { self.raw.to_owned_nonatomic() } }
+  }
 }
 
 impl<'a> RWIobuf<'a> {
@@ -388,6 +498,219 @@ impl<'a> RWIobuf<'a> {
     RWIobuf { raw: RawIobuf::new_with_allocator(len, allocator) }
   }
 
+  /// Constructs a new Iobuf with a buffer of size `len`, where the byte at
+  /// offset `i` is `f(i)`. The limits and window are set to the full range of
+  /// the buffer.
+  ///
+  /// `f` is called exactly `len` times, in order, starting from 0, so it's
+  /// fine for it to keep state between calls.
+  ///
+  /// ```rust
+  /// use iobuf::{RWIobuf,Iobuf};
+  ///
+  /// let b = RWIobuf::from_fn(5, |i| b'a' + i as u8);
+  /// unsafe { assert_eq!(b.as_window_slice(), b"abcde"); }
+  ///
+  /// let mut x = 1u8;
+  /// let b = RWIobuf::from_fn(4, |_| { x *= 3; x });
+  /// unsafe { assert_eq!(b.as_window_slice(), [ 3, 9, 27, 81 ]); }
+  /// ```
+  #[inline]
+  pub fn from_fn<F: FnMut(u32) -> u8>(len: usize, mut f: F) -> RWIobuf<'static> {
+    let b = RWIobuf::new(len);
+    unsafe {
+      for (i, x) in b.as_mut_window_slice().iter_mut().enumerate() {
+        *x = f(i as u32);
+      }
+    }
+    b
+  }
+
+  /// Constructs a new Iobuf with a buffer of size `len` and undefined
+  /// contents. The limits are set to the full range of the buffer, and the
+  /// window to the `window.1` bytes starting at `window.0`, like `sub_window`.
+  ///
+  /// Returns `Err(())` if the window doesn't fit in the buffer. That is checked
+  /// before anything is allocated.
+  ///
+  /// ```rust
+  /// use iobuf::{RWIobuf,Iobuf};
+  ///
+  /// // Leave room for a 4-byte header in front of the payload.
+  /// let mut b = RWIobuf::with_window(12, (4, 8)).unwrap();
+  /// assert_eq!(b.len(), 8);
+  /// assert_eq!(b.cap(), 12);
+  ///
+  /// b.reset();
+  /// assert_eq!(b.len(), 12);
+  ///
+  /// assert!(RWIobuf::with_window(12, (4, 9)).is_err());
+  /// ```
+  #[inline]
+  pub fn with_window(len: usize, window: (u32, u32)) -> Result<RWIobuf<'static>, ()> {
+    let (pos, wlen) = window;
+    if pos as u64 + wlen as u64 > len as u64 { return Err(()) }
+    let mut b = RWIobuf::new(len);
+    try!(b.sub_window(pos, wlen));
+    Ok(b)
+  }
+
+  /// Constructs a new Iobuf with a buffer of size `len` and undefined
+  /// contents, with both the limits and the window set to the `limits.1` bytes
+  /// starting at `limits.0`, like `sub`. Nothing outside of them can be seen,
+  /// even with `reset`.
+  ///
+  /// Returns `Err(())` if the range doesn't fit in the buffer. That is checked
+  /// before anything is allocated.
+  ///
+  /// ```rust
+  /// use iobuf::{RWIobuf,Iobuf};
+  ///
+  /// let mut b = RWIobuf::with_limits(12, (4, 8)).unwrap();
+  /// assert_eq!(b.len(), 8);
+  /// assert_eq!(b.cap(), 8);
+  ///
+  /// b.reset();
+  /// assert_eq!(b.len(), 8);
+  ///
+  /// assert!(RWIobuf::with_limits(12, (13, 0)).is_err());
+  /// ```
+  #[inline]
+  pub fn with_limits(len: usize, limits: (u32, u32)) -> Result<RWIobuf<'static>, ()> {
+    let (pos, llen) = limits;
+    if pos as u64 + llen as u64 > len as u64 { return Err(()) }
+    let mut b = RWIobuf::new(len);
+    try!(b.sub(pos, llen));
+    Ok(b)
+  }
+
+  /// Builds a refcounted Iobuf out of memory that someone else allocated,
+  /// like a shared-memory segment, or a buffer handed over by a C library.
+  /// This is the building block for custom Iobuf flavors.
+  ///
+  /// The first `RWIobuf::foreign_header_len()` bytes of the memory hold the
+  /// refcount, and the rest become the buffer. The limits and window are set
+  /// to the full range of the buffer. When the last Iobuf referencing the
+  /// memory is dropped, `release.deallocate(mem, len, align)` is called to give
+  /// it back. `release.allocate` is never called.
+  ///
+  /// This is unsafe because `mem` must be valid for reads and writes of `len`
+  /// bytes, aligned to a `usize`, and not used by anything else until it's
+  /// released.
+  ///
+  /// ```rust
+  /// use std::sync::Arc;
+  /// use std::sync::atomic::{AtomicUint, Ordering, ATOMIC_UINT_INIT};
+  /// use iobuf::{Allocator, Iobuf, RWIobuf};
+  ///
+  /// static RELEASED: AtomicUint = ATOMIC_UINT_INIT;
+  ///
+  /// // A pretend shared-memory segment, with its own release protocol.
+  /// struct Segment;
+  ///
+  /// impl Allocator for Segment {
+  ///   fn allocate(&self, _len: usize, _align: usize) -> *mut u8 { unreachable!() }
+  ///   fn deallocate(&self, _ptr: *mut u8, len: usize, _align: usize) {
+  ///     RELEASED.fetch_add(len, Ordering::SeqCst);
+  ///   }
+  /// }
+  ///
+  /// let mut mem = [ 0u64; 8 ];
+  ///
+  /// {
+  ///   let release = Arc::new(Box::new(Segment) as Box<Allocator>);
+  ///   let b = unsafe {
+  ///     RWIobuf::from_foreign(mem.as_mut_ptr() as *mut u8, 64, release)
+  ///   };
+  ///   assert_eq!(b.len() as usize, 64 - RWIobuf::foreign_header_len());
+  ///   assert_eq!(b.poke_be(0, 0xAABBCCDDu32), Ok(()));
+  ///
+  ///   let c = b.clone();
+  ///   drop(b);
+  ///   assert_eq!(RELEASED.load(Ordering::SeqCst), 0);
+  ///   assert_eq!(c.peek_be(0), Ok(0xAABBCCDDu32));
+  /// }
+  ///
+  /// assert_eq!(RELEASED.load(Ordering::SeqCst), 64);
+  /// ```
+  #[inline]
+  pub unsafe fn from_foreign(mem: *mut u8, len: usize, release: Arc<Box<Allocator>>) -> RWIobuf<'static> {
+    RWIobuf { raw: RawIobuf::from_foreign(mem, len, release) }
+  }
+
+  /// The number of bytes at the front of the memory passed to `from_foreign`
+  /// which are used for bookkeeping, rather than as part of the buffer.
+  #[inline(always)]
+  pub fn foreign_header_len() -> usize {
+    RawIobuf::foreign_header_len()
+  }
+
+  /// Constructs a new Iobuf with a buffer of size `len`, undefined contents,
+  /// and the limits and window set to the full range of the buffer. The memory
+  /// is locked into RAM with `mlock`, so it will never be swapped out to disk.
+  /// This is useful for holding secrets, like key material.
+  ///
+  /// Clones share the locked memory. When the last one is dropped, the memory
+  /// is zeroed, unlocked, and freed. Note that `deep_clone` makes an ordinary,
+  /// unlocked copy. To copy secrets, `fill` a new locked Iobuf instead.
+  ///
+  /// Locked memory is rounded up to a whole number of pages, so prefer a few
+  /// big locked buffers over lots of small ones. An error is returned if the
+  /// memory couldn't be locked, usually because the `RLIMIT_MEMLOCK` budget has
+  /// been exceeded.
+  ///
+  /// ```rust
+  /// use iobuf::{RWIobuf,Iobuf};
+  ///
+  /// match RWIobuf::new_locked(32) {
+  ///   Ok(key) => {
+  ///     assert_eq!(key.len(), 32);
+  ///     assert_eq!(key.poke_be(0, 0xDEADBEEFu32), Ok(()));
+  ///   }
+  ///   Err(e) => println!("couldn't lock memory: {}", e),
+  /// }
+  /// ```
+  #[cfg(unix)]
+  pub fn new_locked(len: usize) -> IoResult<RWIobuf<'static>> {
+    let allocator = Arc::new(Box::new(LockedAllocator) as Box<Allocator>);
+    let b = RWIobuf::new_with_allocator(len, allocator);
+    try!(unsafe { b.raw.lock_allocation() });
+    Ok(b)
+  }
+
+  /// Constructs a new Iobuf with a buffer of size `len`, undefined contents,
+  /// and the limits and window set to the full range of the buffer. The buffer
+  /// is placed right before an inaccessible guard page, so reading or writing
+  /// past its end (with the `unsafe_` functions, for example) will crash the
+  /// program immediately, instead of corrupting some other allocation.
+  ///
+  /// Each buffer gets its own pages, so this is slow and wasteful. It's only
+  /// meant for tracking down bugs. Enable the `guard-pages` cargo feature to
+  /// use it.
+  ///
+  /// ```rust
+  /// use iobuf::{RWIobuf,Iobuf};
+  ///
+  /// let b = RWIobuf::new_guarded(10);
+  /// assert_eq!(b.len(), 10);
+  /// assert_eq!(b.poke_be(6, 0xDEADBEEFu32), Ok(()));
+  /// ```
+  #[cfg(all(unix, feature = "guard-pages"))]
+  pub fn new_guarded(len: usize) -> RWIobuf<'static> {
+    // The allocation header has to stay aligned, so the buffer is padded out
+    // to a whole number of words. The limits exclude the padding at the
+    // front, so the end of the buffer is still flush against the guard page.
+    let word   = mem::size_of::<usize>();
+    let padded = (len + word - 1) / word * word;
+
+    let allocator = Arc::new(Box::new(GuardedAllocator) as Box<Allocator>);
+    let mut b = RWIobuf::new_with_allocator(padded, allocator);
+
+    let lim = ((padded - len) as u32, padded as u32);
+    b.set_limits_and_window(lim, lim).unwrap();
+    b
+  }
+
   /// Copies a `str` into a writeable Iobuf. The contents of the `str` will be
   /// copied, so prefer to use the non-copying constructors whenever possible.
   ///
@@ -439,10 +762,33 @@ impl<'a> RWIobuf<'a> {
     RWIobuf { raw: RawIobuf::from_slice(s) }
   }
 
+  /// Gets a version of this Iobuf that isn't tied to any borrow. See
+  /// `ROIobuf::to_owned`.
+  ///
+  /// If the buffer is already owned, the two Iobufs share it, so writes through
+  /// one are visible through the other, just like with `clone`.
+  ///
+  /// ```rust
+  /// use iobuf::{RWIobuf,Iobuf};
+  ///
+  /// let mut s = [ 1, 2, 3 ];
+  /// let owned = RWIobuf::from_slice(&mut s).to_owned();
+  ///
+  /// assert_eq!(owned.poke_be(0, 10u8), Ok(()));
+  /// assert_eq!(s[0], 1);
+  /// ```
+  #[inline(always)]
+  pub fn to_owned(&self) -> RWIobuf<'static> {
+    RWIobuf { raw: unsafe { self.raw.to_owned_nonatomic() } }
+  }
+
   /// Copies a byte vector into a new, writeable Iobuf. The contents of the
   /// slice will be copied, so prefer to use the other constructors whenever
   /// possible.
   ///
+  /// Like `ROIobuf::from_slice_copy`, an empty slice gives an
+  /// `RWIobuf::empty()`, which doesn't allocate, and doesn't own a buffer.
+  ///
   /// ```rust
   /// use iobuf::{RWIobuf,Iobuf};
   ///
@@ -521,6 +867,39 @@ impl<'a> RWIobuf<'a> {
     self.raw.as_mut_limit_slice()
   }
 
+  /// Splits the window into two non-overlapping mutable slices, covering
+  /// `[0, mid)` and `[mid, len)` of the window. This is the Iobuf version of
+  /// `split_at_mut` on slices.
+  ///
+  /// This is unsafe for the same reason `as_mut_window_slice` is. The slices
+  /// borrow this Iobuf mutably, but clones of it, and `read_only` views of
+  /// it, share the same buffer and can still read and write those bytes.
+  /// Make sure nothing touches the window through any other Iobuf while the
+  /// slices are alive.
+  ///
+  /// Returns `Err(())` if `mid` is past the end of the window.
+  ///
+  /// ```rust
+  /// use iobuf::{RWIobuf, Iobuf};
+  ///
+  /// let mut b = RWIobuf::from_str_copy("abcdef");
+  ///
+  /// {
+  ///   let (x, y) = unsafe { b.window_split_at_mut(2).unwrap() };
+  ///   assert_eq!(x, b"ab");
+  ///   assert_eq!(y, b"cdef");
+  ///   x[0] = y[3];
+  /// }
+  ///
+  /// unsafe { assert_eq!(b.as_window_slice(), b"fbcdef"); }
+  /// assert!(unsafe { b.window_split_at_mut(7) }.is_err());
+  /// ```
+  #[inline]
+  pub unsafe fn window_split_at_mut<'b>(&'b mut self, mid: u32) -> Result<(&'b mut [u8], &'b mut [u8]), ()> {
+    try!(self.raw.check_range_u32(mid, 0));
+    Ok(self.raw.as_mut_window_slice().split_at_mut(mid as usize))
+  }
+
   /// Gets a read-only copy of this Iobuf. This is a very cheap operation, as
   /// the backing buffers are shared. This can be useful for interfacing with
   /// code that only accepts read-only Iobufs.
@@ -595,6 +974,160 @@ impl<'a> RWIobuf<'a> {
   #[inline(always)]
   pub fn compact(&mut self) { self.raw.compact() }
 
+  /// Like `compact`, but copies with `memcpy` instead of `memmove`, and
+  /// doesn't skip the copy when there's nothing to move.
+  ///
+  /// This is undefined unless the window's contents and their destination
+  /// don't overlap: the window must be no longer than the space between the
+  /// lower limit and the start of the window. That's true whenever at least
+  /// half of the data has been consumed.
+  ///
+  /// ```rust
+  /// use iobuf::{RWIobuf,Iobuf};
+  ///
+  /// let mut b = RWIobuf::from_str_copy("consumed_left");
+  /// assert_eq!(b.advance(9), Ok(()));
+  ///
+  /// unsafe { b.unsafe_compact(); }
+  ///
+  /// b.flip_lo();
+  /// unsafe { assert_eq!(b.as_window_slice(), b"left"); }
+  /// ```
+  #[inline(always)]
+  pub unsafe fn unsafe_compact(&mut self) { self.raw.unsafe_compact() }
+
+  /// Moves the window's contents to the very front of the buffer, and widens
+  /// the limits to the whole buffer, so that the data starts at offset 0 and
+  /// there's as much room as possible after it. The window covers the moved
+  /// data. This keeps a long-lived streaming buffer from shrinking as its
+  /// limits get narrowed.
+  ///
+  /// The limits are only widened if this Iobuf is the only one referencing
+  /// the buffer, so that other Iobufs' data can't be overwritten. Otherwise,
+  /// the data is moved to the front of the current limits, which stay as they
+  /// are.
+  ///
+  /// ```rust
+  /// use iobuf::{RWIobuf,Iobuf};
+  ///
+  /// let mut b = RWIobuf::new(16);
+  /// assert_eq!(b.sub(4, 8), Ok(()));
+  /// assert_eq!(b.fill(b"done:rest"), Err(()));
+  /// assert_eq!(b.fill(b"done:abc"), Ok(()));
+  /// b.flip_lo();
+  /// assert_eq!(b.advance(5), Ok(()));
+  ///
+  /// b.reset_to_front();
+  /// assert_eq!(b.lo_min(), 0);
+  /// assert_eq!(b.len(), 3);
+  /// assert_eq!(b.hi_space(), 13);
+  /// unsafe { assert_eq!(b.as_window_slice(), b"abc"); }
+  /// ```
+  #[inline(always)]
+  pub fn reset_to_front(&mut self) { self.raw.reset_to_front() }
+
+  /// Like `resize`, but if the window grows, the newly exposed bytes are
+  /// zeroed. If it shrinks, nothing is written. The window is left untouched
+  /// if it would exceed the limits.
+  ///
+  /// ```rust
+  /// use iobuf::{RWIobuf,Iobuf};
+  ///
+  /// let mut b = RWIobuf::from_str_copy("hello");
+  ///
+  /// assert_eq!(b.resize_zeroed(2), Ok(()));
+  /// unsafe { assert_eq!(b.as_window_slice(), b"he"); }
+  ///
+  /// assert_eq!(b.resize_zeroed(4), Ok(()));
+  /// unsafe { assert_eq!(b.as_window_slice(), b"he\0\0"); }
+  ///
+  /// assert_eq!(b.resize_zeroed(6), Err(()));
+  /// unsafe { assert_eq!(b.as_limit_slice(), b"he\0\0o"); }
+  /// ```
+  #[inline(always)]
+  pub fn resize_zeroed(&mut self, len: u32) -> Result<(), ()> { self.raw.resize_zeroed(len) }
+
+  /// Removes consecutive repeated bytes from the window, keeping the first of
+  /// each run, like `Vec::dedup`. The kept bytes are moved towards the front,
+  /// and the window is shrunk to fit them. Returns the new length of the
+  /// window.
+  ///
+  /// Nothing is allocated. The bytes after the new end of the window are left
+  /// as they were.
+  ///
+  /// ```rust
+  /// use iobuf::{RWIobuf,Iobuf};
+  ///
+  /// let mut b = RWIobuf::from_slice_copy(&[ 1, 1, 2, 2, 2, 3 ]);
+  ///
+  /// assert_eq!(b.dedup(), 3);
+  /// assert_eq!(b.len(), 3);
+  /// unsafe { assert_eq!(b.as_window_slice(), [ 1, 2, 3 ]); }
+  /// ```
+  #[inline]
+  pub fn dedup(&mut self) -> u32 { self.raw.dedup_by(|a, b| a == b) }
+
+  /// Like `dedup`, but `same(kept, next)` decides whether `next` repeats the
+  /// last byte that was kept, and should be removed.
+  ///
+  /// ```rust
+  /// use iobuf::{RWIobuf,Iobuf};
+  ///
+  /// // Squeeze runs of whitespace down to their first byte.
+  /// let mut b = RWIobuf::from_str_copy("a  \t b\n\nc");
+  ///
+  /// let ws = |c: u8| c == b' ' || c == b'\t' || c == b'\n';
+  /// assert_eq!(b.dedup_by(|x, y| ws(x) && ws(y)), 5);
+  /// unsafe { assert_eq!(b.as_window_slice(), b"a b\nc"); }
+  /// ```
+  #[inline]
+  pub fn dedup_by<F: FnMut(u8, u8) -> bool>(&mut self, same: F) -> u32 { self.raw.dedup_by(same) }
+
+  /// Runs `compact`, but only if `should` returns `true`. `should` is given
+  /// the buffer, so it can make its decision based on `lo_space`, `len`, etc.
+  /// Returns whether the buffer was compacted.
+  ///
+  /// ```rust
+  /// use iobuf::{RWIobuf,Iobuf};
+  ///
+  /// let mut b = RWIobuf::from_str_copy("hello, world");
+  /// assert_eq!(b.advance(7), Ok(()));
+  ///
+  /// assert!(!b.compact_if(|b| b.len() < 2));
+  /// assert_eq!(b.lo_space(), 7);
+  ///
+  /// assert!(b.compact_if(|b| b.len() < 7));
+  /// assert_eq!(b.lo_space(), 5);
+  /// ```
+  #[inline]
+  pub fn compact_if<F: FnOnce(&RWIobuf<'a>) -> bool>(&mut self, should: F) -> bool {
+    if should(self) {
+      self.compact();
+      true
+    } else {
+      false
+    }
+  }
+
+  /// Runs `compact`, but only if more than `threshold` bytes have been
+  /// consumed from the front of the buffer. This avoids shuffling data around
+  /// just to reclaim a few bytes. Returns whether the buffer was compacted.
+  ///
+  /// ```rust
+  /// use iobuf::{RWIobuf,Iobuf};
+  ///
+  /// let mut b = RWIobuf::from_str_copy("hello, world");
+  /// assert_eq!(b.advance(7), Ok(()));
+  ///
+  /// assert!(!b.compact_if_lo_space_exceeds(7));
+  /// assert!(b.compact_if_lo_space_exceeds(6));
+  /// assert_eq!(b.lo_space(), 5);
+  /// ```
+  #[inline]
+  pub fn compact_if_lo_space_exceeds(&mut self, threshold: u32) -> bool {
+    self.compact_if(|b| b.lo_space() > threshold)
+  }
+
   /// Writes the bytes at a given offset from the beginning of the window, into
   /// the supplied buffer. Either the entire buffer is copied, or an error is
   /// returned because bytes outside of the window would be written.
@@ -637,7 +1170,7 @@ impl<'a> RWIobuf<'a> {
   /// unsafe { assert_eq!(b.as_window_slice(), expected); }
   /// ```
   #[inline(always)]
-  pub fn poke_be<T: Int>(&self, pos: u32, t: T) -> Result<(), ()> { self.raw.poke_be(pos, t) }
+  pub fn poke_be<T: Prim>(&self, pos: u32, t: T) -> Result<(), ()> { self.raw.poke_be(pos, t) }
 
   /// Writes a little-endian primitive at a given offset from the beginning of
   /// the window.
@@ -658,7 +1191,7 @@ impl<'a> RWIobuf<'a> {
   /// unsafe { assert_eq!(b.as_window_slice(), [ 4, 5, 5, 9, 8, 7, 6 ]); }
   /// ```
   #[inline(always)]
-  pub fn poke_le<T: Int>(&self, pos: u32, t: T) -> Result<(), ()> { self.raw.poke_le(pos, t) }
+  pub fn poke_le<T: Prim>(&self, pos: u32, t: T) -> Result<(), ()> { self.raw.poke_le(pos, t) }
 
   /// Writes bytes from the supplied buffer, starting from the front of the
   /// window. Either the entire buffer is copied, or an error is returned
@@ -685,179 +1218,265 @@ impl<'a> RWIobuf<'a> {
   #[inline(always)]
   pub fn fill(&mut self, src: &[u8]) -> Result<(), ()> { self.raw.fill(src) }
 
-  /// Writes a big-endian primitive into the beginning of the window.
+  /// Advances the window past the next `len` bytes, and returns them as a
+  /// mutable slice. Fill in the slice (say, with a `Reader`, or a C function),
+  /// and the bytes will be in the right place in the buffer. This is `fill`,
+  /// for when something else does the writing.
   ///
-  /// After the primitive has been written, the window will be moved such that
-  /// it is no longer included.
+  /// The slice starts out holding whatever was in the buffer before, which
+  /// for a freshly allocated buffer is uninitialized memory. Returns `Err(())`,
+  /// without touching the window, if it's shorter than `len`.
   ///
-  /// An error is returned if bytes outside of the window were requested.
+  /// This is unsafe for the same reasons as `as_mut_window_slice`: other
+  /// clones of this Iobuf may share the buffer, and nothing stops them from
+  /// reading or writing the bytes behind the slice while it's alive. It may
+  /// only be used if no other Iobuf touches those bytes until the slice is
+  /// gone, and if the caller doesn't read from the slice before writing to it.
   ///
   /// ```rust
   /// use iobuf::{RWIobuf,Iobuf};
   ///
   /// let mut b = RWIobuf::new(10);
   ///
-  /// assert_eq!(b.fill_be(0x12345678u32), Ok(()));
-  /// assert_eq!(b.fill_be(0x11223344u32), Ok(()));
-  /// assert_eq!(b.fill_be(0x54321123u32), Err(()));
-  /// assert_eq!(b.fill_be(0x8877u16), Ok(()));
+  /// unsafe {
+  ///   let s = b.fill_uninit(4).unwrap();
+  ///   for (i, x) in s.iter_mut().enumerate() { *x = i as u8; }
+  /// }
   ///
-  /// b.flip_lo();
+  /// assert_eq!(b.len(), 6);
+  /// unsafe { assert!(b.fill_uninit(7).is_err()); }
+  /// assert_eq!(b.len(), 6);
   ///
-  /// unsafe { assert_eq!(b.as_window_slice(), [ 0x12, 0x34, 0x56, 0x78
-  ///                                          , 0x11, 0x22, 0x33, 0x44
-  ///                                          , 0x88, 0x77 ]); }
+  /// b.flip_lo();
+  /// unsafe { assert_eq!(b.as_window_slice(), [ 0, 1, 2, 3 ]); }
   /// ```
-  #[inline(always)]
-  pub fn fill_be<T: Int>(&mut self, t: T) -> Result<(), ()> { self.raw.fill_be(t) }
+  #[inline]
+  pub unsafe fn fill_uninit<'b>(&'b mut self, len: u32) -> Result<&'b mut [u8], ()> {
+    try!(self.raw.check_range_u32(0, len));
+    let s: &'b mut [u8] = mem::transmute(&mut self.raw.as_mut_window_slice()[..len as usize]);
+    self.raw.unsafe_advance(len);
+    Ok(s)
+  }
 
-  /// Writes a little-endian primitive into the beginning of the window.
+  /// Like `fill_uninit`, but passes the slice to `f` instead of returning it.
   ///
-  /// After the primitive has been written, the window will be moved such that
-  /// it is no longer included.
-  ///
-  /// An error is returned if bytes outside of the window were requested.
+  /// This is unsafe for the same reasons as `fill_uninit`: `f` must not read
+  /// from the slice before writing to it, and no other Iobuf sharing this
+  /// buffer may touch those bytes while `f` runs.
   ///
   /// ```rust
   /// use iobuf::{RWIobuf,Iobuf};
   ///
   /// let mut b = RWIobuf::new(10);
   ///
-  /// assert_eq!(b.fill_le(0x12345678u32), Ok(()));
-  /// assert_eq!(b.fill_le(0x11223344u32), Ok(()));
-  /// assert_eq!(b.fill_le(0x54321123u32), Err(()));
-  /// assert_eq!(b.fill_le(0x8877u16), Ok(()));
+  /// unsafe {
+  ///   assert_eq!(b.fill_with(3, |s| { s[0] = b'a'; s[1] = b'b'; s[2] = b'c'; }), Ok(()));
+  ///   assert_eq!(b.fill_with(8, |_| panic!("not enough room")), Err(()));
+  /// }
   ///
   /// b.flip_lo();
-  ///
-  /// unsafe { assert_eq!(b.as_window_slice(), [ 0x78, 0x56, 0x34, 0x12
-  ///                                          , 0x44, 0x33, 0x22, 0x11
-  ///                                          , 0x77, 0x88 ]); }
+  /// unsafe { assert_eq!(b.as_window_slice(), b"abc"); }
   /// ```
-  #[inline(always)]
-  pub fn fill_le<T: Int>(&mut self, t: T) -> Result<(), ()> { self.raw.fill_le(t) }
+  #[inline]
+  pub unsafe fn fill_with<F: FnOnce(&mut [u8])>(&mut self, len: u32, f: F) -> Result<(), ()> {
+    let s = try!(self.fill_uninit(len));
+    f(s);
+    Ok(())
+  }
 
-  /// Writes the bytes at a given offset from the beginning of the window, into
-  /// the supplied buffer. It is undefined behavior to write outside the iobuf
-  /// window.
+  /// Sets aside the next `len` bytes of the window to be written later, and
+  /// advances the window past them.
+  ///
+  /// The returned Iobuf shares this one's buffer, and its limits are exactly
+  /// the reserved bytes, so it can't be used to write anywhere else. This is
+  /// handy for length or checksum fields that can only be filled in once the
+  /// rest of the message has been written.
+  ///
+  /// An error is returned, and the window is left untouched, if there are
+  /// fewer than `len` bytes in the window.
   ///
   /// ```rust
   /// use iobuf::{RWIobuf,Iobuf};
   ///
-  /// let data = [ 1,2,3,4 ];
+  /// let mut b = RWIobuf::new(8);
   ///
-  /// let mut b = RWIobuf::new(10);
+  /// let len_field = b.reserve_iobuf(2).unwrap();
+  /// assert_eq!(b.fill(b"hello"), Ok(()));
+  /// assert_eq!(len_field.poke_be(0, 5u16), Ok(()));
+  /// assert_eq!(len_field.poke_be(1, 5u16), Err(()));
   ///
-  /// unsafe {
-  ///   b.check_range_fail(1, 7);
-  ///
-  ///   b.unsafe_advance(1);
-  ///   b.narrow();
-  ///
-  ///   b.unsafe_poke(0, &data);
-  ///   b.unsafe_poke(3, &data);
-  ///   b.unsafe_advance(7);
-  /// }
+  /// assert!(b.reserve_iobuf(2).is_err());
+  /// assert_eq!(b.len(), 1);
   ///
   /// b.flip_lo();
+  /// unsafe { assert_eq!(b.as_window_slice(), b"\x00\x05hello"); }
+  /// ```
+  #[inline]
+  pub fn reserve_iobuf(&mut self, len: u32) -> Result<RWIobuf<'a>, ()> {
+    let mut r = self.clone();
+    try!(r.raw.sub_window_to(len));
+    r.raw.narrow();
+    unsafe { self.raw.unsafe_advance(len) };
+    Ok(r)
+  }
+
+  /// Starts keeping track of which bytes get written, for flushing only those
+  /// back to wherever the buffer came from. Tracking is opt-in, and costs a
+  /// couple of comparisons per write. See `DirtyIobuf`.
   ///
-  /// unsafe { assert_eq!(b.as_window_slice(), [ 1,2,3,1,2,3,4 ]); }
+  /// ```rust
+  /// use iobuf::{RWIobuf,Iobuf};
+  ///
+  /// let mut b = RWIobuf::new(100).enable_dirty_tracking();
+  /// assert_eq!(b.poke_be(90, 0u16), Ok(()));
+  /// assert_eq!(b.poke_be(30, 0u8), Ok(()));
+  /// assert_eq!(b.dirty_range(), Some((30, 92)));
   /// ```
   #[inline(always)]
-  pub unsafe fn unsafe_poke(&self, pos: u32, src: &[u8]) { self.raw.unsafe_poke(pos, src) }
+  pub fn enable_dirty_tracking(self) -> DirtyIobuf<'a> {
+    DirtyIobuf::new(self)
+  }
 
-  /// Writes a big-endian primitive at a given offset from the beginning of the
-  /// window. It is undefined behavior to write outside the iobuf window.
+  /// Hands the buffer to `f` once the last Iobuf referring to it is dropped,
+  /// instead of releasing its memory. `f` gets the only reference to the
+  /// buffer, with the limits and window set to the whole thing, not just the
+  /// limits of the Iobuf the hook was set on. It can keep the buffer (to put it
+  /// back in an external pool, say), or drop it, which releases the memory
+  /// for real.
+  ///
+  /// `f` runs exactly once, no matter which clone goes last. Buffers that get
+  /// recycled (with an `IobufPool`, say) aren't released, so the hook stays
+  /// attached until the buffer is finally dropped. Setting a second hook runs
+  /// both, newest first: the older one gets the buffer once the newer one
+  /// drops it.
+  ///
+  /// An error is returned if the buffer isn't owned by an Iobuf, since then
+  /// there's nothing for us to hand over. Set the hook before sharing the
+  /// buffer with other threads.
   ///
   /// ```rust
+  /// use std::sync::{Arc, Mutex};
   /// use iobuf::{RWIobuf,Iobuf};
   ///
-  /// let mut b = RWIobuf::new(10);
+  /// let reclaimed = Arc::new(Mutex::new(Vec::new()));
+  /// let r = reclaimed.clone();
   ///
-  /// unsafe {
-  ///   b.check_range_fail(0, 7);
+  /// let mut a = RWIobuf::new(10);
+  /// assert_eq!(a.advance(4), Ok(()));
+  /// assert_eq!(a.on_last_drop(move |buf: RWIobuf<'static>| {
+  ///   r.lock().unwrap().push(buf);
+  /// }), Ok(()));
   ///
-  ///   b.unsafe_poke_be(0, 0x0304u16);
-  ///   b.unsafe_poke_be(1, 0x0505u16);
-  ///   b.unsafe_poke_be(3, 0x06070809u32);
-  /// }
+  /// let b = a.clone();
+  /// drop(a);
+  /// assert_eq!(reclaimed.lock().unwrap().len(), 0);
+  /// drop(b);
   ///
-  /// assert_eq!(b.resize(7), Ok(()));
+  /// let buf = reclaimed.lock().unwrap().pop().unwrap();
+  /// assert_eq!(buf.len(), 10);
+  /// assert_eq!(buf.debug_state().refcount, 1);
   ///
-  /// unsafe { assert_eq!(b.as_window_slice(), [ 3, 5, 5, 6, 7, 8, 9 ]); }
+  /// let mut s = [ 1, 2, 3 ];
+  /// assert!(RWIobuf::from_slice(&mut s).on_last_drop(|_: RWIobuf<'static>| {}).is_err());
   /// ```
-  #[inline(always)]
-  pub unsafe fn unsafe_poke_be<T: Int>(&self, pos: u32, t: T) { self.raw.unsafe_poke_be(pos, t) }
+  #[inline]
+  pub fn on_last_drop<F: FnOnce(RWIobuf<'static>) + Send + 'static>(&mut self, f: F) -> Result<(), ()> {
+    self.raw.on_last_drop(move |raw: RawIobuf<'static>| f(RWIobuf { raw: raw }))
+  }
 
-  /// Writes a little-endian primitive at a given offset from the beginning of
-  /// the window. It is undefined behavior to write outside the iobuf window.
+  /// Like `poke`, but on failure, reports how many bytes the write would have
+  /// reached past the end of the window. Nothing is written on failure.
+  ///
+  /// This is exactly how much the window would have to grow for the write to
+  /// succeed, which is handy when deciding how much more space to allocate.
   ///
   /// ```rust
   /// use iobuf::{RWIobuf,Iobuf};
   ///
-  /// let mut b = RWIobuf::new(10);
+  /// let b = RWIobuf::new(6);
   ///
-  /// unsafe {
-  ///   b.check_range_fail(0, 7);
+  /// assert_eq!(b.poke_reporting(2, &[ 1, 2, 3, 4 ]), Ok(()));
+  /// assert_eq!(b.poke_reporting(4, &[ 1, 2, 3, 4 ]), Err(2));
+  /// assert_eq!(b.poke_reporting(8, &[ 1, 2 ]),       Err(4));
+  /// ```
+  #[inline(always)]
+  pub fn poke_reporting(&self, pos: u32, src: &[u8]) -> Result<(), u32> { self.raw.poke_reporting(pos, src) }
+
+  /// Like `fill`, but on failure, reports how many bytes of `src` didn't fit
+  /// in the window. Nothing is written, and the window is left untouched, on
+  /// failure.
   ///
-  ///   b.unsafe_poke_le(0, 0x0304u16);
-  ///   b.unsafe_poke_le(1, 0x0505u16);
-  ///   b.unsafe_poke_le(3, 0x06070809u32);
-  /// }
+  /// ```rust
+  /// use iobuf::{RWIobuf,Iobuf};
   ///
-  /// assert_eq!(b.resize(7), Ok(()));
+  /// let data = [ 0, 1, 2, 3, 4, 5, 6, 7, 8, 9 ];
   ///
-  /// unsafe { assert_eq!(b.as_window_slice(), [ 4, 5, 5, 9, 8, 7, 6 ]); }
+  /// let mut b = RWIobuf::new(6);
+  ///
+  /// assert_eq!(b.fill_reporting(&data[]), Err(4));
+  /// assert_eq!(b.len(), 6);
+  /// assert_eq!(b.lo_space(), 0);
+  ///
+  /// assert_eq!(b.fill_reporting(&data[..6]), Ok(()));
+  /// assert!(b.is_empty());
   /// ```
   #[inline(always)]
-  pub unsafe fn unsafe_poke_le<T: Int>(&self, pos: u32, t: T) { self.raw.unsafe_poke_le(pos, t) }
+  pub fn fill_reporting(&mut self, src: &[u8]) -> Result<(), u32> { self.raw.fill_reporting(src) }
 
-  /// Writes bytes from the supplied buffer, starting from the front of the
-  /// window. It is undefined behavior to write outside the iobuf window.
+  /// XORs the window with a repeating 4-byte `key`, like WebSocket masking.
+  /// The first byte of the window is XORed with `key[offset % 4]`, so a
+  /// payload split across several buffers can be masked piece by piece.
+  /// Masking twice with the same key and offset gives back the original.
   ///
-  /// After the bytes have been written, the window will be moved to no longer
-  /// include then.
+  /// The whole window is masked, several bytes at a time.
   ///
   /// ```rust
   /// use iobuf::{RWIobuf,Iobuf};
   ///
-  /// let data = [ 1, 2, 3, 4 ];
+  /// let b = RWIobuf::from_str_copy("Hello");
+  /// let key = [ 0x37, 0xfa, 0x21, 0x3d ];
   ///
-  /// let mut b = RWIobuf::new(10);
+  /// b.mask(key, 0);
+  /// unsafe { assert_eq!(b.as_window_slice(), [ 0x7f, 0x9f, 0x4d, 0x51, 0x58 ]); }
   ///
-  /// unsafe {
-  ///   b.check_range_fail(0, 8);
+  /// b.mask(key, 0);
+  /// unsafe { assert_eq!(b.as_window_slice(), b"Hello"); }
+  /// ```
+  #[inline(always)]
+  pub fn mask(&self, key: [u8; 4], offset: u32) { self.raw.mask(key, offset) }
+
+  /// Replaces every `from` byte in the window with `to`, in place, and returns
+  /// how many were replaced. This doesn't allocate, and makes one pass over
+  /// the window.
   ///
-  ///   b.unsafe_fill(&data[]);
-  ///   b.unsafe_fill(&data[]);
-  /// }
+  /// ```rust
+  /// use iobuf::{RWIobuf,Iobuf};
   ///
-  /// b.flip_lo();
+  /// let b = RWIobuf::from_str_copy("a file name.txt");
   ///
-  /// unsafe { assert_eq!(b.as_window_slice(), [ 1,2,3,4,1,2,3,4 ]); }
+  /// assert_eq!(b.replace_byte(b' ', b'_'), 2);
+  /// unsafe { assert_eq!(b.as_window_slice(), b"a_file_name.txt"); }
+  /// assert_eq!(b.replace_byte(b' ', b'_'), 0);
   /// ```
   #[inline(always)]
-  pub unsafe fn unsafe_fill(&mut self, src: &[u8]) { self.raw.unsafe_fill(src) }
+  pub fn replace_byte(&self, from: u8, to: u8) -> u32 { self.raw.replace_byte(from, to) }
 
-  /// Writes a big-endian primitive into the beginning of the window. It is
-  /// undefined behavior to write outside the iobuf window.
+  /// Writes a big-endian primitive into the beginning of the window.
   ///
   /// After the primitive has been written, the window will be moved such that
   /// it is no longer included.
   ///
+  /// An error is returned if bytes outside of the window were requested.
+  ///
   /// ```rust
   /// use iobuf::{RWIobuf,Iobuf};
   ///
   /// let mut b = RWIobuf::new(10);
   ///
-  /// unsafe {
-  ///   b.check_range_fail(0, 10);
-  ///
-  ///   b.unsafe_fill_be(0x12345678u32);
-  ///   b.unsafe_fill_be(0x11223344u32);
-  ///   // b.unsafe_fill_be(0x54321123u32); DO NOT DO THIS. Undefined behavior.
-  ///   b.unsafe_fill_be(0x8877u16);
-  /// }
+  /// assert_eq!(b.fill_be(0x12345678u32), Ok(()));
+  /// assert_eq!(b.fill_be(0x11223344u32), Ok(()));
+  /// assert_eq!(b.fill_be(0x54321123u32), Err(()));
+  /// assert_eq!(b.fill_be(0x8877u16), Ok(()));
   ///
   /// b.flip_lo();
   ///
@@ -866,256 +1485,1600 @@ impl<'a> RWIobuf<'a> {
   ///                                          , 0x88, 0x77 ]); }
   /// ```
   #[inline(always)]
-  pub unsafe fn unsafe_fill_be<T: Int>(&mut self, t: T) { self.raw.unsafe_fill_be(t) }
+  pub fn fill_be<T: Prim>(&mut self, t: T) -> Result<(), ()> { self.raw.fill_be(t) }
+
+  /// Writes a little-endian primitive into the beginning of the window.
+  ///
+  /// After the primitive has been written, the window will be moved such that
+  /// it is no longer included.
+  ///
+  /// An error is returned if bytes outside of the window were requested.
+  ///
+  /// ```rust
+  /// use iobuf::{RWIobuf,Iobuf};
+  ///
+  /// let mut b = RWIobuf::new(10);
+  ///
+  /// assert_eq!(b.fill_le(0x12345678u32), Ok(()));
+  /// assert_eq!(b.fill_le(0x11223344u32), Ok(()));
+  /// assert_eq!(b.fill_le(0x54321123u32), Err(()));
+  /// assert_eq!(b.fill_le(0x8877u16), Ok(()));
+  ///
+  /// b.flip_lo();
+  ///
+  /// unsafe { assert_eq!(b.as_window_slice(), [ 0x78, 0x56, 0x34, 0x12
+  ///                                          , 0x44, 0x33, 0x22, 0x11
+  ///                                          , 0x77, 0x88 ]); }
+  /// ```
+  #[inline(always)]
+  pub fn fill_le<T: Prim>(&mut self, t: T) -> Result<(), ()> { self.raw.fill_le(t) }
+
+  /// Copies the memory of a slice of primitives into the front of the window,
+  /// as is, and advances the window past it. This is one `memcpy`, with no
+  /// byte swapping, so the primitives are written in the host's byte order.
+  /// Data written this way should only be read back on a host with the same
+  /// byte order, like with `window_as_slice`.
+  ///
+  /// Returns `Err(())`, without writing anything, if the slice doesn't fit in
+  /// the window, or if `T` is a `Prim` whose size in memory is different from
+  /// its size in an Iobuf.
+  ///
+  /// ```rust
+  /// use iobuf::{RWIobuf,Iobuf};
+  ///
+  /// let samples = [ 1u16, 2, 3, 0xFFFF ];
+  ///
+  /// let mut b = RWIobuf::new(8);
+  /// assert_eq!(b.fill_native_slice(&samples), Ok(()));
+  /// assert!(b.is_empty());
+  ///
+  /// b.flip_lo();
+  /// if cfg!(target_endian = "little") {
+  ///   assert_eq!(b.peek_le::<u16>(2), Ok(2));
+  /// } else {
+  ///   assert_eq!(b.peek_be::<u16>(2), Ok(2));
+  /// }
+  /// ```
+  #[inline(always)]
+  pub fn fill_native_slice<T: Prim>(&mut self, src: &[T]) -> Result<(), ()> { self.raw.fill_native_slice(src) }
+
+  /// Writes several slices back to back, starting `pos` bytes into the window.
+  /// There's only one bounds check, for all of them together. If they don't
+  /// all fit, nothing is written, and `Err(())` is returned.
+  ///
+  /// ```rust
+  /// use iobuf::{RWIobuf,Iobuf};
+  ///
+  /// let b = RWIobuf::from_str_copy("[......]");
+  ///
+  /// assert_eq!(b.poke_vectored(1, &[ b"ab", b"cd" ]), Ok(()));
+  /// assert_eq!(b.poke_vectored(5, &[ b"ef", b"gh" ]), Err(()));
+  /// unsafe { assert_eq!(b.as_window_slice(), b"[abcd..]"); }
+  /// ```
+  #[inline]
+  pub fn poke_vectored(&self, pos: u32, srcs: &[&[u8]]) -> Result<(), ()> {
+    self.raw.poke_vectored(pos, srcs).map(|_| ())
+  }
+
+  /// Writes several slices back to back at the front of the window, like a
+  /// header, a body, and a trailer, and advances the window past all of them.
+  /// There's only one bounds check, for all of them together. If they don't
+  /// all fit, nothing is written, the window isn't moved, and `Err(())` is
+  /// returned.
+  ///
+  /// ```rust
+  /// use iobuf::{RWIobuf,Iobuf};
+  ///
+  /// let mut b = RWIobuf::new(9);
+  ///
+  /// assert_eq!(b.fill_vectored(&[ b"\x00\x05", b"hello", b"\n" ]), Ok(()));
+  /// assert_eq!(b.fill_vectored(&[ b"\x00", b"\x01" ]), Err(()));
+  /// assert_eq!(b.len(), 1);
+  ///
+  /// b.flip_lo();
+  /// unsafe { assert_eq!(b.as_window_slice(), b"\x00\x05hello\n"); }
+  /// ```
+  #[inline(always)]
+  pub fn fill_vectored(&mut self, srcs: &[&[u8]]) -> Result<(), ()> { self.raw.fill_vectored(srcs) }
+
+  /// Writes `s` to the front of the window, escaped for the inside of a JSON
+  /// string, and advances the window past it. The surrounding quotes aren't
+  /// written.
+  ///
+  /// Quotes, backslashes, and control characters below U+0020 are escaped,
+  /// and so is everything outside of ASCII, as `\uXXXX`. DEL is passed
+  /// through, since JSON allows it. Characters above U+FFFF are written as
+  /// UTF-16 surrogate pairs, as JSON requires. The output is always plain
+  /// ASCII.
+  ///
+  /// If the escaped string doesn't fit in the window, nothing is written, the
+  /// window isn't moved, and `Err(())` is returned.
+  ///
+  /// ```rust
+  /// use iobuf::{RWIobuf,Iobuf};
+  ///
+  /// let mut b = RWIobuf::new(20);
+  ///
+  /// assert_eq!(b.fill(b"\""), Ok(()));
+  /// assert_eq!(b.fill_json_escaped("say \"hi\"\n"), Ok(()));
+  /// assert_eq!(b.fill(b"\""), Ok(()));
+  /// assert_eq!(b.fill_json_escaped("too long"), Err(()));
+  ///
+  /// b.flip_lo();
+  /// unsafe { assert_eq!(b.as_window_slice(), b"\"say \\\"hi\\\"\\n\""); }
+  /// ```
+  #[inline(always)]
+  pub fn fill_json_escaped(&mut self, s: &str) -> Result<(), ()> { self.raw.fill_json_escaped(s) }
+
+  /// Writes `x` as a big-endian signed fixed-point number `pos` bytes into the
+  /// window. See `Iobuf::peek_fixed_be` for the format.
+  ///
+  /// `x` is rounded to the nearest representable value. Values exactly
+  /// halfway between two are rounded away from zero.
+  ///
+  /// An error is returned, and nothing is written, if the rounded value is out
+  /// of the format's range, if `x` is NaN, or if the window is too short.
+  ///
+  /// Panics if the number isn't a whole number of bytes, up to 8.
+  ///
+  /// ```rust
+  /// use iobuf::{RWIobuf,Iobuf};
+  ///
+  /// let b = RWIobuf::new(2);
+  ///
+  /// assert_eq!(b.poke_fixed_be(0, -1.5, 8, 8), Ok(()));
+  /// assert_eq!(b.peek_be::<u16>(0), Ok(0xFE80));
+  ///
+  /// assert_eq!(b.poke_fixed_be(0, 128.0, 8, 8), Err(()));
+  /// ```
+  #[inline(always)]
+  pub fn poke_fixed_be(&self, pos: u32, x: f64, int_bits: u8, frac_bits: u8) -> Result<(), ()> {
+    self.raw.poke_fixed_be(pos, x, int_bits, frac_bits)
+  }
+
+  /// Writes `x` as a big-endian signed fixed-point number to the front of the
+  /// window, and advances past it. See `poke_fixed_be`.
+  ///
+  /// ```rust
+  /// use iobuf::{RWIobuf,Iobuf};
+  ///
+  /// let mut b = RWIobuf::new(3);
+  ///
+  /// assert_eq!(b.fill_fixed_be(0.75, 4, 4), Ok(()));
+  /// assert_eq!(b.fill_fixed_be(0.75, 4, 12), Ok(()));
+  /// assert_eq!(b.fill_fixed_be(0.75, 4, 4), Err(()));
+  ///
+  /// b.flip_lo();
+  /// unsafe { assert_eq!(b.as_window_slice(), [ 0x0C, 0x0C, 0x00 ]); }
+  /// ```
+  #[inline(always)]
+  pub fn fill_fixed_be(&mut self, x: f64, int_bits: u8, frac_bits: u8) -> Result<(), ()> {
+    self.raw.fill_fixed_be(x, int_bits, frac_bits)
+  }
+
+  /// Writes `x` as a big-endian Q16.16 fixed-point number to the front of the
+  /// window, and advances past it. This is `fill_fixed_be(x, 16, 16)`.
+  ///
+  /// ```rust
+  /// use iobuf::{RWIobuf,Iobuf};
+  ///
+  /// let mut b = RWIobuf::new(4);
+  ///
+  /// assert_eq!(b.fill_q16_16_be(-0.5), Ok(()));
+  ///
+  /// b.flip_lo();
+  /// assert_eq!(b.peek_be::<u32>(0), Ok(0xFFFF8000));
+  /// ```
+  #[inline(always)]
+  pub fn fill_q16_16_be(&mut self, x: f64) -> Result<(), ()> { self.raw.fill_fixed_be(x, 16, 16) }
+
+  /// Writes an IP address `pos` bytes into the window, in network byte order.
+  /// That's 4 bytes for an IPv4 address, and 16 for an IPv6 one.
+  ///
+  /// An error is returned, and nothing is written, if it doesn't fit.
+  ///
+  /// ```rust
+  /// use std::io::net::ip::Ipv4Addr;
+  /// use iobuf::{RWIobuf,Iobuf};
+  ///
+  /// let b = RWIobuf::new(5);
+  ///
+  /// assert_eq!(b.poke_ip(1, Ipv4Addr(127, 0, 0, 1)), Ok(()));
+  /// assert_eq!(b.poke_ip(0, "::1".parse().unwrap()), Err(()));
+  /// assert_eq!(b.peek_be::<u32>(1), Ok(0x7F000001));
+  /// ```
+  #[inline]
+  pub fn poke_ip(&self, pos: u32, ip: IpAddr) -> Result<(), ()> {
+    self.raw.poke_ip(pos, ip).map(|_| ())
+  }
+
+  /// Writes an IP address to the front of the window, in network byte order,
+  /// and advances past it. See `poke_ip`.
+  ///
+  /// ```rust
+  /// use iobuf::{RWIobuf,Iobuf};
+  ///
+  /// let mut b = RWIobuf::new(20);
+  ///
+  /// assert_eq!(b.fill_ip("2001:db8::1".parse().unwrap()), Ok(()));
+  /// assert_eq!(b.fill_ip("192.0.2.1".parse().unwrap()), Ok(()));
+  /// assert_eq!(b.fill_ip("192.0.2.2".parse().unwrap()), Err(()));
+  ///
+  /// b.flip_lo();
+  /// assert_eq!(b.consume_ipv6(), Ok("2001:db8::1".parse().unwrap()));
+  /// assert_eq!(b.consume_ipv4(), Ok("192.0.2.1".parse().unwrap()));
+  /// ```
+  #[inline(always)]
+  pub fn fill_ip(&mut self, ip: IpAddr) -> Result<(), ()> { self.raw.fill_ip(ip) }
+
+  /// Writes a socket address `pos` bytes into the window: the IP address, as
+  /// with `poke_ip`, followed by the port as a big-endian u16.
+  ///
+  /// ```rust
+  /// use iobuf::{RWIobuf,Iobuf};
+  ///
+  /// let b = RWIobuf::new(6);
+  ///
+  /// assert_eq!(b.poke_socket_addr(0, "10.0.0.1:1080".parse().unwrap()), Ok(()));
+  /// assert_eq!(b.peek_be::<u16>(4), Ok(1080));
+  /// ```
+  #[inline]
+  pub fn poke_socket_addr(&self, pos: u32, addr: SocketAddr) -> Result<(), ()> {
+    self.raw.poke_socket_addr(pos, addr).map(|_| ())
+  }
+
+  /// Writes a socket address to the front of the window, and advances past
+  /// it. See `poke_socket_addr`.
+  ///
+  /// ```rust
+  /// use iobuf::{RWIobuf,Iobuf};
+  ///
+  /// let mut b = RWIobuf::new(6);
+  ///
+  /// assert_eq!(b.fill_socket_addr("10.0.0.1:1080".parse().unwrap()), Ok(()));
+  /// assert!(b.is_empty());
+  /// ```
+  #[inline(always)]
+  pub fn fill_socket_addr(&mut self, addr: SocketAddr) -> Result<(), ()> { self.raw.fill_socket_addr(addr) }
+
+  /// Reads exactly `n` bytes from `r` into the front of the window, calling
+  /// `read` as many times as it takes, and advances the window past them. If
+  /// `r` hits EOF first, the window is advanced past what was read. Returns
+  /// the number of bytes read, which is only less than `n` on EOF.
+  ///
+  /// This is for reading a complete fixed-size header off of a socket, which
+  /// may hand it over a few bytes at a time.
+  ///
+  /// An error is returned, and nothing is read, if the window is shorter than
+  /// `n`. If `r` fails with anything other than EOF, the window is advanced
+  /// past what was read before the failure, and an error is returned.
+  ///
+  /// ```rust
+  /// use std::io::MemReader;
+  /// use iobuf::{RWIobuf,Iobuf};
+  ///
+  /// let mut r = MemReader::new(vec!(1, 2, 3, 4, 5, 6));
+  /// let mut b = RWIobuf::new(8);
+  ///
+  /// assert_eq!(b.fill_exact_from_reader(&mut r, 9), Err(()));
+  /// assert_eq!(b.fill_exact_from_reader(&mut r, 4), Ok(4));
+  /// assert_eq!(b.fill_exact_from_reader(&mut r, 4), Ok(2));
+  /// assert_eq!(b.fill_exact_from_reader(&mut r, 2), Ok(0));
+  ///
+  /// b.flip_lo();
+  /// unsafe { assert_eq!(b.as_window_slice(), [ 1, 2, 3, 4, 5, 6 ]); }
+  /// ```
+  pub fn fill_exact_from_reader<R: Reader>(&mut self, r: &mut R, n: u32) -> Result<u32, ()> {
+    try!(self.check_range(0, n));
+    let mut read = 0;
+    let mut ret = Ok(());
+    {
+      let dst = unsafe { &mut self.as_mut_window_slice()[..n as usize] };
+      while read < dst.len() {
+        match r.read(&mut dst[read..]) {
+          Ok(k) => read += k,
+          Err(ref e) if e.kind == io::EndOfFile => break,
+          Err(_) => { ret = Err(()); break }
+        }
+      }
+    }
+    unsafe { self.raw.unsafe_advance(read as u32) };
+    ret.map(|()| read as u32)
+  }
+
+  /// Writes a primitive `pos` bytes into the window, big-endian if
+  /// `big_endian` is set, and little-endian otherwise.
+  ///
+  /// An error is returned if bytes outside of the window were requested.
+  ///
+  /// ```rust
+  /// use iobuf::{RWIobuf,Iobuf};
+  ///
+  /// let b = RWIobuf::new(4);
+  ///
+  /// assert_eq!(b.poke_prim(0, 0x0102u16, true),  Ok(()));
+  /// assert_eq!(b.poke_prim(2, 0x0102u16, false), Ok(()));
+  /// assert_eq!(b.poke_prim(3, 0x0102u16, false), Err(()));
+  ///
+  /// unsafe { assert_eq!(b.as_window_slice(), [ 0x01, 0x02, 0x02, 0x01 ]); }
+  /// ```
+  #[inline(always)]
+  pub fn poke_prim<T: Prim>(&self, pos: u32, t: T, big_endian: bool) -> Result<(), ()> { self.raw.poke_prim(pos, t, big_endian) }
+
+  /// Writes a primitive into the beginning of the window, big-endian if
+  /// `big_endian` is set, and little-endian otherwise.
+  ///
+  /// After the primitive has been written, the window will be moved such that
+  /// it is no longer included.
+  ///
+  /// An error is returned if bytes outside of the window were requested.
+  ///
+  /// ```rust
+  /// use iobuf::{RWIobuf,Iobuf};
+  ///
+  /// let mut b = RWIobuf::new(4);
+  ///
+  /// assert_eq!(b.fill_prim(0x0102u16, false), Ok(()));
+  /// assert_eq!(b.fill_prim(0x0102u16, true),  Ok(()));
+  /// assert_eq!(b.fill_prim(0x01u8, true),     Err(()));
+  ///
+  /// b.flip_lo();
+  /// unsafe { assert_eq!(b.as_window_slice(), [ 0x02, 0x01, 0x01, 0x02 ]); }
+  /// ```
+  #[inline(always)]
+  pub fn fill_prim<T: Prim>(&mut self, t: T, big_endian: bool) -> Result<(), ()> { self.raw.fill_prim(t, big_endian) }
+
+  /// Writes `n` as a `len`-byte big-endian unsigned integer at `pos` bytes
+  /// into the window, padded with leading zeros. Only available with the
+  /// `num-bigint` feature.
+  ///
+  /// Returns `Err(())` without writing anything if `n` doesn't fit in `len`
+  /// bytes, or if the bytes don't fit in the window.
+  #[cfg(feature = "num-bigint")]
+  #[inline(always)]
+  pub fn poke_biguint_be(&self, pos: u32, n: &BigUint, len: u32) -> Result<(), ()> { self.raw.poke_biguint_be(pos, n, len) }
+
+  /// Writes `n` as a `len`-byte big-endian unsigned integer at the beginning
+  /// of the window, padded with leading zeros, and advances past it. Only
+  /// available with the `num-bigint` feature.
+  ///
+  /// Returns `Err(())` without writing anything if `n` doesn't fit in `len`
+  /// bytes, or if the bytes don't fit in the window.
+  ///
+  /// ```rust
+  /// extern crate iobuf;
+  /// extern crate "num-bigint" as num_bigint;
+  ///
+  /// use std::num::FromPrimitive;
+  /// use num_bigint::BigUint;
+  /// use iobuf::{RWIobuf,Iobuf};
+  ///
+  /// fn main() {
+  ///   let n: BigUint = FromPrimitive::from_u64(0x010001).unwrap();
+  ///   let mut b = RWIobuf::new(4);
+  ///
+  ///   assert_eq!(b.fill_biguint_be(&n, 4), Ok(()));
+  ///   b.flip_lo();
+  ///   unsafe { assert_eq!(b.as_window_slice(), [ 0x00, 0x01, 0x00, 0x01 ]); }
+  ///
+  ///   assert_eq!(b.fill_biguint_be(&n, 2), Err(()));
+  /// }
+  /// ```
+  #[cfg(feature = "num-bigint")]
+  #[inline(always)]
+  pub fn fill_biguint_be(&mut self, n: &BigUint, len: u32) -> Result<(), ()> { self.raw.fill_biguint_be(n, len) }
+
+  /// Writes the bytes at a given offset from the beginning of the window, into
+  /// the supplied buffer. It is undefined behavior to write outside the iobuf
+  /// window.
+  ///
+  /// ```rust
+  /// use iobuf::{RWIobuf,Iobuf};
+  ///
+  /// let data = [ 1,2,3,4 ];
+  ///
+  /// let mut b = RWIobuf::new(10);
+  ///
+  /// unsafe {
+  ///   b.check_range_fail(1, 7);
+  ///
+  ///   b.unsafe_advance(1);
+  ///   b.narrow();
+  ///
+  ///   b.unsafe_poke(0, &data);
+  ///   b.unsafe_poke(3, &data);
+  ///   b.unsafe_advance(7);
+  /// }
+  ///
+  /// b.flip_lo();
+  ///
+  /// unsafe { assert_eq!(b.as_window_slice(), [ 1,2,3,1,2,3,4 ]); }
+  /// ```
+  #[inline(always)]
+  pub unsafe fn unsafe_poke(&self, pos: u32, src: &[u8]) { self.raw.unsafe_poke(pos, src) }
+
+  /// Writes a big-endian primitive at a given offset from the beginning of the
+  /// window. It is undefined behavior to write outside the iobuf window.
+  ///
+  /// ```rust
+  /// use iobuf::{RWIobuf,Iobuf};
+  ///
+  /// let mut b = RWIobuf::new(10);
+  ///
+  /// unsafe {
+  ///   b.check_range_fail(0, 7);
+  ///
+  ///   b.unsafe_poke_be(0, 0x0304u16);
+  ///   b.unsafe_poke_be(1, 0x0505u16);
+  ///   b.unsafe_poke_be(3, 0x06070809u32);
+  /// }
+  ///
+  /// assert_eq!(b.resize(7), Ok(()));
+  ///
+  /// unsafe { assert_eq!(b.as_window_slice(), [ 3, 5, 5, 6, 7, 8, 9 ]); }
+  /// ```
+  #[inline(always)]
+  pub unsafe fn unsafe_poke_be<T: Prim>(&self, pos: u32, t: T) { self.raw.unsafe_poke_be(pos, t) }
+
+  /// Writes a little-endian primitive at a given offset from the beginning of
+  /// the window. It is undefined behavior to write outside the iobuf window.
+  ///
+  /// ```rust
+  /// use iobuf::{RWIobuf,Iobuf};
+  ///
+  /// let mut b = RWIobuf::new(10);
+  ///
+  /// unsafe {
+  ///   b.check_range_fail(0, 7);
+  ///
+  ///   b.unsafe_poke_le(0, 0x0304u16);
+  ///   b.unsafe_poke_le(1, 0x0505u16);
+  ///   b.unsafe_poke_le(3, 0x06070809u32);
+  /// }
+  ///
+  /// assert_eq!(b.resize(7), Ok(()));
+  ///
+  /// unsafe { assert_eq!(b.as_window_slice(), [ 4, 5, 5, 9, 8, 7, 6 ]); }
+  /// ```
+  #[inline(always)]
+  pub unsafe fn unsafe_poke_le<T: Prim>(&self, pos: u32, t: T) { self.raw.unsafe_poke_le(pos, t) }
+
+  /// Writes bytes from the supplied buffer, starting from the front of the
+  /// window. It is undefined behavior to write outside the iobuf window.
+  ///
+  /// After the bytes have been written, the window will be moved to no longer
+  /// include then.
+  ///
+  /// ```rust
+  /// use iobuf::{RWIobuf,Iobuf};
+  ///
+  /// let data = [ 1, 2, 3, 4 ];
+  ///
+  /// let mut b = RWIobuf::new(10);
+  ///
+  /// unsafe {
+  ///   b.check_range_fail(0, 8);
+  ///
+  ///   b.unsafe_fill(&data[]);
+  ///   b.unsafe_fill(&data[]);
+  /// }
+  ///
+  /// b.flip_lo();
+  ///
+  /// unsafe { assert_eq!(b.as_window_slice(), [ 1,2,3,4,1,2,3,4 ]); }
+  /// ```
+  #[inline(always)]
+  pub unsafe fn unsafe_fill(&mut self, src: &[u8]) { self.raw.unsafe_fill(src) }
+
+  /// Writes a big-endian primitive into the beginning of the window. It is
+  /// undefined behavior to write outside the iobuf window.
+  ///
+  /// After the primitive has been written, the window will be moved such that
+  /// it is no longer included.
+  ///
+  /// ```rust
+  /// use iobuf::{RWIobuf,Iobuf};
+  ///
+  /// let mut b = RWIobuf::new(10);
+  ///
+  /// unsafe {
+  ///   b.check_range_fail(0, 10);
+  ///
+  ///   b.unsafe_fill_be(0x12345678u32);
+  ///   b.unsafe_fill_be(0x11223344u32);
+  ///   // b.unsafe_fill_be(0x54321123u32); DO NOT DO THIS. Undefined behavior.
+  ///   b.unsafe_fill_be(0x8877u16);
+  /// }
+  ///
+  /// b.flip_lo();
+  ///
+  /// unsafe { assert_eq!(b.as_window_slice(), [ 0x12, 0x34, 0x56, 0x78
+  ///                                          , 0x11, 0x22, 0x33, 0x44
+  ///                                          , 0x88, 0x77 ]); }
+  /// ```
+  #[inline(always)]
+  pub unsafe fn unsafe_fill_be<T: Prim>(&mut self, t: T) { self.raw.unsafe_fill_be(t) }
+
+  /// Writes a little-endian primitive into the beginning of the window. It is
+  /// undefined behavior to write outside the iobuf window.
+  ///
+  /// After the primitive has been written, the window will be moved such that
+  /// it is no longer included.
+  ///
+  /// ```rust
+  /// use iobuf::{RWIobuf,Iobuf};
+  ///
+  /// let mut b = RWIobuf::new(10);
+  ///
+  /// unsafe {
+  ///   b.check_range_fail(0, 10);
+  ///
+  ///   b.unsafe_fill_le(0x12345678u32);
+  ///   b.unsafe_fill_le(0x11223344u32);
+  ///   // b.unsafe_fill_le(0x54321123u32); DO NOT DO THIS. Undefined behavior.
+  ///   b.unsafe_fill_le(0x8877u16);
+  /// }
+  ///
+  /// b.flip_lo();
+  ///
+  /// unsafe { assert_eq!(b.as_window_slice(), [ 0x78, 0x56, 0x34, 0x12
+  ///                                          , 0x44, 0x33, 0x22, 0x11
+  ///                                          , 0x77, 0x88 ]); }
+  /// ```
+  #[inline(always)]
+  pub unsafe fn unsafe_fill_le<T: Prim>(&mut self, t: T) { self.raw.unsafe_fill_le(t) }
+}
+
+impl AROIobuf {
+  /// Stops atomically reference counting a unique buffer. This method returns
+  /// `Ok` if the `AROIobuf` is the last of its kind, and `Err` if it's not.
+  ///
+  /// ```rust
+  /// use iobuf::{AROIobuf, ROIobuf, Iobuf};
+  ///
+  /// let buf: ROIobuf<'static> = ROIobuf::from_str_copy("hello, world!");
+  /// let a_buf: AROIobuf = buf.atomic_read_only().ok().unwrap();
+  /// unsafe { assert_eq!(a_buf.as_window_slice(), b"hello, world!"); }
+  ///
+  /// let buf: ROIobuf<'static> = a_buf.read_only().ok().unwrap();
+  ///
+  /// // TA-DA!
+  /// unsafe { assert_eq!(buf.as_window_slice(), b"hello, world!"); }
+  /// ```
+  #[inline(always)]
+  pub fn read_only(self) -> Result<ROIobuf<'static>, AROIobuf> {
+    unsafe {
+      if self.raw.is_unique_atomic() {
+        Ok(mem::transmute(self))
+      } else {
+        Err(self)
+      }
+    }
+  }
+
+  /// Stops atomically reference counting a unique buffer. This method returns
+  /// `Ok` if the `AROIobuf` is the last of its kind, and `Err` if it's not.
+  ///
+  /// ```rust
+  /// use iobuf::{AROIobuf, ROIobuf, RWIobuf, Iobuf};
+  ///
+  /// let buf: ROIobuf<'static> = ROIobuf::from_str_copy("hello, world!");
+  /// let a_buf: AROIobuf = buf.atomic_read_only().ok().unwrap();
+  /// unsafe { assert_eq!(a_buf.as_window_slice(), b"hello, world!"); }
+  ///
+  /// let buf: RWIobuf<'static> = a_buf.read_write().ok().unwrap();
+  ///
+  /// // TA-DA!
+  /// unsafe { assert_eq!(buf.as_window_slice(), b"hello, world!"); }
+  /// ```
+  #[inline(always)]
+  pub fn read_write(self) -> Result<RWIobuf<'static>, AROIobuf> {
+    unsafe {
+      if self.raw.is_unique_atomic() {
+        Ok(mem::transmute(self))
+      } else {
+        Err(self)
+      }
+    }
+  }
+}
+
+/// The Iobufs which are nothing but a `RawIobuf`, so that the trait methods
+/// which take closures over `Self` can share one generic implementation.
+trait HasRaw<'a> {
+  fn raw_mut(&mut self) -> &mut RawIobuf<'a>;
+}
+
+impl<'a> HasRaw<'a> for ROIobuf<'a> {
+  #[inline(always)]
+  fn raw_mut(&mut self) -> &mut RawIobuf<'a> { &mut self.raw }
+}
+
+impl HasRaw<'static> for AROIobuf {
+  #[inline(always)]
+  fn raw_mut(&mut self) -> &mut RawIobuf<'static> { &mut self.raw }
+}
+
+impl<'a> HasRaw<'a> for RWIobuf<'a> {
+  #[inline(always)]
+  fn raw_mut(&mut self) -> &mut RawIobuf<'a> { &mut self.raw }
+}
+
+#[inline]
+fn with_rollback<'a, B: HasRaw<'a>, R, E, F: FnOnce(&mut B) -> Result<R, E>>(b: &mut B, f: F) -> Result<R, E> {
+  let bounds = b.raw_mut().bounds();
+  let ret = f(b);
+  if ret.is_err() {
+    unsafe { b.raw_mut().restore_bounds(bounds) }
+  }
+  ret
+}
+
+/// `Iobuf::consume_repeat`, for any Iobuf. Also used by the wrappers.
+#[inline]
+pub fn consume_repeat<B: Iobuf, R, E, F: FnMut(&mut B) -> Result<R, E>>(b: &mut B, count: u32, mut f: F) -> Result<Vec<R>, E> {
+  b.with_rollback(|b| {
+    // `count` often comes off the wire, so don't let it size the allocation.
+    let mut ret = Vec::with_capacity(cmp::min(count, b.len()) as usize);
+    for _ in range(0, count) {
+      ret.push(try!(f(b)));
+    }
+    Ok(ret)
+  })
+}
+
+/// `Iobuf::consume_repeat_until_empty`, for any Iobuf. Also used by the
+/// wrappers.
+#[inline]
+pub fn consume_repeat_until_empty<B: Iobuf, R, E, F: FnMut(&mut B) -> Result<R, E>>(b: &mut B, mut f: F) -> Result<Vec<R>, E> {
+  b.with_rollback(|b| {
+    let mut ret = Vec::new();
+    while !b.is_empty() {
+      ret.push(try!(f(b)));
+    }
+    Ok(ret)
+  })
+}
+
+#[inline]
+fn with_sub_window<'a, B: HasRaw<'a>, R, F: FnOnce(&mut B) -> R>(b: &mut B, pos: u32, len: u32, f: F) -> Result<R, ()> {
+  let bounds = b.raw_mut().bounds();
+  try!(b.raw_mut().sub(pos, len));
+  b.raw_mut().narrow();
+  let ret = f(b);
+  unsafe {
+    let raw = b.raw_mut();
+    raw.restore_bounds(bounds);
+    raw.unsafe_advance(pos + len);
+  }
+  Ok(ret)
+}
+
+impl<'a> Iobuf for ROIobuf<'a> {
+  #[inline(always)]
+  fn deep_clone(&self) -> RWIobuf<'static> { RWIobuf { raw: self.raw.deep_clone() } }
+
+  #[inline(always)]
+  fn deep_clone_with_allocator(&self, allocator: Arc<Box<Allocator>>) -> RWIobuf<'static> {
+    RWIobuf { raw: self.raw.deep_clone_with_allocator(allocator) }
+  }
+
+  #[inline(always)]
+  fn deep_clone_from_pool(&self, pool: &mut IobufPool) -> RWIobuf<'static> { pool.deep_clone(self) }
+
+  #[inline(always)]
+  fn window_to_owned(&self) -> ROIobuf<'static> { ROIobuf { raw: self.raw.window_to_owned() } }
+
+  #[inline(always)]
+  fn split_to_owned(&mut self, n: u32) -> Result<RWIobuf<'static>, ()> { self.raw.split_to_owned(n).map(|raw| RWIobuf { raw: raw }) }
+
+  #[inline(always)]
+  fn to_cstring(&self) -> Result<CString, ()> { self.raw.to_cstring() }
+
+  #[inline(always)]
+  unsafe fn as_cstr_ptr(&self) -> Result<*const u8, ()> { self.raw.as_cstr_ptr() }
+
+  #[inline(always)]
+  fn unique(self) -> Result<UniqueIobuf, ROIobuf<'a>> {
+    unsafe {
+      if self.raw.is_unique_nonatomic() {
+        Ok(mem::transmute(self))
+      } else {
+        Err(self)
+      }
+    }
+  }
+
+  #[inline(always)]
+  fn atomic_read_only(self) -> Result<AROIobuf, ROIobuf<'a>> {
+    unsafe {
+      if self.raw.is_unique_nonatomic() {
+        Ok(mem::transmute(self))
+      } else {
+        Err(self)
+      }
+    }
+  }
+
+  #[inline(always)]
+  fn len(&self) -> u32 { self.raw.len() }
+
+  #[inline(always)]
+  fn cap(&self) -> u32 { self.raw.cap() }
+
+  #[inline(always)]
+  fn is_empty(&self) -> bool { self.raw.is_empty() }
+
+  #[inline(always)]
+  fn count_prims<T: Prim>(&self, from: u32) -> u32 { self.raw.count_prims::<T>(from) }
+
+  #[inline(always)]
+  unsafe fn as_window_slice<'b>(&'b self) -> &'b [u8] { self.raw.as_window_slice() }
+
+  #[inline(always)]
+  unsafe fn as_limit_slice<'b>(&'b self) -> &'b [u8] { self.raw.as_limit_slice() }
+
+  #[inline(always)]
+  unsafe fn window_as_slice<'b, T: Prim>(&'b self) -> Result<&'b [T], ()> { self.raw.window_as_slice::<T>() }
+
+  #[inline(always)]
+  fn sub_window(&mut self, pos: u32, len: u32) -> Result<(), ()> { self.raw.sub_window(pos, len) }
+
+  #[inline(always)]
+  fn sub_window_from(&mut self, pos: u32) -> Result<(), ()> { self.raw.sub_window_from(pos) }
+
+  #[inline(always)]
+  fn sub_window_to(&mut self, len: u32) -> Result<(), ()> { self.raw.sub_window_to(len) }
+
+  #[inline(always)]
+  unsafe fn unsafe_sub_window(&mut self, pos: u32, len: u32) { self.raw.unsafe_sub_window(pos, len) }
+
+  #[inline(always)]
+  unsafe fn unsafe_sub_window_from(&mut self, pos: u32) { self.raw.unsafe_sub_window_from(pos) }
+
+  #[inline(always)]
+  unsafe fn unsafe_sub_window_to(&mut self, len: u32) { self.raw.unsafe_sub_window_to(len) }
+
+  #[inline(always)]
+  fn sub(&mut self, pos: u32, len: u32) -> Result<(), ()> { self.raw.sub(pos, len) }
+
+  #[inline(always)]
+  fn sub_from(&mut self, pos: u32) -> Result<(), ()> { self.raw.sub_from(pos) }
+
+  #[inline(always)]
+  fn sub_to(&mut self, len: u32) -> Result<(), ()> { self.raw.sub_to(len) }
+
+  #[inline(always)]
+  unsafe fn unsafe_sub(&mut self, pos: u32, len: u32) { self.raw.unsafe_sub(pos, len) }
+
+  #[inline(always)]
+  unsafe fn unsafe_sub_from(&mut self, pos: u32) { self.raw.unsafe_sub_from(pos) }
+
+  #[inline(always)]
+  unsafe fn unsafe_sub_to(&mut self, len: u32) { self.raw.unsafe_sub_to(len) }
+
+  #[inline(always)]
+  fn set_limits_and_window(&mut self, limits: (u32, u32), window: (u32, u32)) -> Result<(), ()> { self.raw.set_limits_and_window(limits, window) }
+
+  #[inline(always)]
+  fn narrow(&mut self) { self.raw.narrow() }
+
+  #[inline(always)]
+  fn advance(&mut self, len: u32) -> Result<(), ()> { self.raw.advance(len) }
+
+  #[inline(always)]
+  fn advanced(mut self, len: u32) -> Result<Self, ()> { try!(self.raw.advance(len)); Ok(self) }
+
+  #[inline(always)]
+  fn resized(mut self, len: u32) -> Result<Self, ()> { try!(self.raw.resize(len)); Ok(self) }
+
+  #[inline(always)]
+  fn subbed(mut self, pos: u32, len: u32) -> Result<Self, ()> { try!(self.raw.sub(pos, len)); Ok(self) }
+
+  #[inline(always)]
+  fn narrowed(mut self) -> Self { self.raw.narrow(); self }
+
+  #[inline(always)]
+  unsafe fn unsafe_advance(&mut self, len: u32) { self.raw.unsafe_advance(len) }
+
+  #[inline(always)]
+  fn advance_upto(&mut self, len: u32) -> u32 { self.raw.advance_upto(len) }
+
+  #[inline(always)]
+  fn extend(&mut self, len: u32) -> Result<(), ()> { self.raw.extend(len) }
+
+  #[inline(always)]
+  unsafe fn unsafe_extend(&mut self, len: u32) { self.raw.unsafe_extend(len) }
+
+  #[inline(always)]
+  fn extend_upto(&mut self, len: u32) -> u32 { self.raw.extend_upto(len) }
+
+  #[inline(always)]
+  fn is_extended_by<Buf: Iobuf>(&self, other: &Buf) -> bool { unsafe { self.raw.is_extended_by(other.as_raw()) } }
+
+  #[inline(always)]
+  fn cmp_window<Buf: Iobuf>(&self, other: &Buf) -> Ordering { unsafe { self.raw.cmp_window(other.as_raw()) } }
+
+  #[inline(always)]
+  fn extend_with<Buf: Iobuf>(&mut self, other: &Buf) -> Result<(), ()> { unsafe { self.raw.extend_with(other.as_raw()) } }
+
+  #[inline(always)]
+  fn resize(&mut self, len: u32) -> Result<(), ()> { self.raw.resize(len) }
+
+  #[inline(always)]
+  fn resize_preserving(&mut self, len: u32) -> Result<(), ()> { self.raw.resize(len) }
+
+  #[inline(always)]
+  unsafe fn unsafe_resize(&mut self, len: u32) { self.raw.unsafe_resize(len) }
+
+  #[inline(always)]
+  fn resize_upto(&mut self, len: u32) -> u32 { self.raw.resize_upto(len) }
+
+  #[inline(always)]
+  fn split_at(&self, pos: u32) -> Result<(ROIobuf<'a>, ROIobuf<'a>), ()> {
+    self.raw.split_at_nonatomic(pos).map(|(a, b)| (ROIobuf { raw: a }, ROIobuf { raw: b }))
+  }
+
+  #[inline(always)]
+  unsafe fn unsafe_split_at(&self, pos: u32) -> (ROIobuf<'a>, ROIobuf<'a>) {
+    let (a, b) = self.raw.unsafe_split_at_nonatomic(pos);
+    (ROIobuf { raw: a }, ROIobuf { raw: b })
+  }
+
+  #[inline(always)]
+  fn split_start_at(&mut self, pos: u32) -> Result<ROIobuf<'a>, ()> {
+    self.raw.split_start_at_nonatomic(pos).map(|b| ROIobuf { raw: b })
+  }
+
+  #[inline(always)]
+  unsafe fn unsafe_split_start_at(&mut self, pos: u32) -> ROIobuf<'a> {
+    ROIobuf { raw: self.raw.unsafe_split_start_at_nonatomic(pos) }
+  }
+
+  #[inline(always)]
+  fn rewind(&mut self) { self.raw.rewind() }
+
+  #[inline(always)]
+  fn reset(&mut self) { self.raw.reset() }
+
+  #[inline(always)]
+  fn flip_lo(&mut self) { self.raw.flip_lo() }
+
+  #[inline(always)]
+  fn flip_hi(&mut self) { self.raw.flip_hi() }
+
+  #[inline(always)]
+  fn lo_space(&self) -> u32 { self.raw.lo_space() }
+
+  #[inline(always)]
+  fn hi_space(&self) -> u32 { self.raw.hi_space() }
+
+  #[inline(always)]
+  fn peek(&self, pos: u32, dst: &mut [u8]) -> Result<(), ()> { self.raw.peek(pos, dst) }
+
+  #[inline(always)]
+  unsafe fn peek_into_uninit(&self, pos: u32, dst: *mut u8, len: u32) -> Result<(), ()> { self.raw.peek_into_uninit(pos, dst, len) }
+
+  #[inline]
+  fn peek_vectored(&self, pos: u32, dsts: &mut [&mut [u8]]) -> Result<(), ()> { self.raw.peek_vectored(pos, dsts).map(|_| ()) }
+
+  #[inline(always)]
+  fn consume_vectored(&mut self, dsts: &mut [&mut [u8]]) -> Result<(), ()> { self.raw.consume_vectored(dsts) }
+
+  #[inline(always)]
+  fn peek_all_into(&self, dst: &mut Vec<u8>) { self.raw.peek_all_into(dst) }
+
+  #[inline(always)]
+  fn consume_all_into(&mut self, dst: &mut Vec<u8>) { self.raw.consume_all_into(dst) }
+  #[inline(always)]
+  fn peek_be<T: Prim>(&self, pos: u32) -> Result<T, ()> { self.raw.peek_be(pos) }
+  #[inline(always)]
+  fn peek_le<T: Prim>(&self, pos: u32) -> Result<T, ()> { self.raw.peek_le(pos) }
+
+  #[inline(always)]
+  fn consume(&mut self, dst: &mut [u8]) -> Result<(), ()> { self.raw.consume(dst) }
+  #[inline(always)]
+  fn consume_be<T: Prim>(&mut self) -> Result<T, ()> { self.raw.consume_be::<T>() }
+  #[inline(always)]
+  fn consume_le<T: Prim>(&mut self) -> Result<T, ()> { self.raw.consume_le::<T>() }
+
+  #[cfg(feature = "num-bigint")]
+  #[inline(always)]
+  fn peek_biguint_be(&self, pos: u32, len: u32) -> Result<BigUint, ()> { self.raw.peek_biguint_be(pos, len) }
+
+  #[cfg(feature = "num-bigint")]
+  #[inline(always)]
+  fn consume_biguint_be(&mut self, len: u32) -> Result<BigUint, ()> { self.raw.consume_biguint_be(len) }
+
+  #[inline(always)]
+  fn consume_be_opt<T: Prim>(&mut self) -> Option<T> { self.raw.consume_be_opt::<T>() }
+
+  #[inline(always)]
+  fn consume_le_opt<T: Prim>(&mut self) -> Option<T> { self.raw.consume_le_opt::<T>() }
+
+  #[inline(always)]
+  fn consume_hex_u64(&mut self) -> Result<u64, ()> { self.raw.consume_digits_u64(16) }
+
+  #[inline(always)]
+  fn consume_decimal_u64(&mut self) -> Result<u64, ()> { self.raw.consume_digits_u64(10) }
+
+  #[inline(always)]
+  fn consume_int_be(&mut self, width: u8) -> Result<i64, ()> { self.raw.consume_int(width, true) }
+
+  #[inline(always)]
+  fn consume_int_le(&mut self, width: u8) -> Result<i64, ()> { self.raw.consume_int(width, false) }
+
+  #[inline(always)]
+  fn peek_fixed_be(&self, pos: u32, int_bits: u8, frac_bits: u8) -> Result<f64, ()> { self.raw.peek_fixed_be(pos, int_bits, frac_bits) }
+
+  #[inline(always)]
+  fn consume_fixed_be(&mut self, int_bits: u8, frac_bits: u8) -> Result<f64, ()> { self.raw.consume_fixed_be(int_bits, frac_bits) }
+
+  #[inline(always)]
+  fn consume_q16_16_be(&mut self) -> Result<f64, ()> { self.raw.consume_fixed_be(16, 16) }
+
+  #[inline(always)]
+  fn peek_ipv4(&self, pos: u32) -> Result<IpAddr, ()> { self.raw.peek_ipv4(pos) }
+
+  #[inline(always)]
+  fn peek_ipv6(&self, pos: u32) -> Result<IpAddr, ()> { self.raw.peek_ipv6(pos) }
+
+  #[inline(always)]
+  fn peek_socket_addr_v4(&self, pos: u32) -> Result<SocketAddr, ()> { self.raw.peek_socket_addr_v4(pos) }
+
+  #[inline(always)]
+  fn consume_ipv4(&mut self) -> Result<IpAddr, ()> { self.raw.consume_ipv4() }
+
+  #[inline(always)]
+  fn consume_ipv6(&mut self) -> Result<IpAddr, ()> { self.raw.consume_ipv6() }
+
+  #[inline(always)]
+  fn consume_socket_addr_v4(&mut self) -> Result<SocketAddr, ()> { self.raw.consume_socket_addr_v4() }
+
+  #[inline(always)]
+  fn consume_be_enum<T: Prim, E, F: FnOnce(T) -> Option<E>>(&mut self, validate: F) -> Result<E, EnumError<T>> { self.raw.consume_enum(validate, true) }
+
+  #[inline(always)]
+  fn consume_le_enum<T: Prim, E, F: FnOnce(T) -> Option<E>>(&mut self, validate: F) -> Result<E, EnumError<T>> { self.raw.consume_enum(validate, false) }
+
+  #[inline(always)]
+  fn peek_prim<T: Prim>(&self, pos: u32, big_endian: bool) -> Result<T, ()> { self.raw.peek_prim(pos, big_endian) }
+
+  #[inline(always)]
+  fn consume_prim<T: Prim>(&mut self, big_endian: bool) -> Result<T, ()> { self.raw.consume_prim::<T>(big_endian) }
+
+  #[inline(always)]
+  fn consume_be_array<A: IntArray>(&mut self) -> Result<A, ()> { self.raw.consume_be_array::<A>() }
+
+  #[inline(always)]
+  fn consume_le_array<A: IntArray>(&mut self) -> Result<A, ()> { self.raw.consume_le_array::<A>() }
+
+  #[inline(always)]
+  fn eq_ignore_ascii_case(&self, other: &[u8]) -> bool { self.raw.eq_ignore_ascii_case(other) }
+
+  #[inline(always)]
+  fn starts_with_ignore_ascii_case(&self, prefix: &[u8]) -> bool { self.raw.starts_with_ignore_ascii_case(prefix) }
+
+  #[inline(always)]
+  fn consume_tag(&mut self, tag: &[u8]) -> Result<(), TagError> { self.raw.consume_tag(tag) }
+
+  #[inline(always)]
+  fn consume_tag_ignore_ascii_case(&mut self, tag: &[u8]) -> Result<(), TagError> { self.raw.consume_tag_ignore_ascii_case(tag) }
+
+  #[inline(always)]
+  fn owns_slice(&self, s: &[u8]) -> bool { self.raw.owns_slice(s) }
+
+  #[inline(always)]
+  fn rchunks<'b>(&'b self, size: u32) -> RChunks<'b, Self> { RChunks::new(self, size) }
+
+  #[inline]
+  fn split_into(&self, parts: u32) -> Vec<Self> { chunks::split_into(self, parts) }
+
+  #[inline(always)]
+  fn drain<'b>(&'b mut self) -> Drain<'b, Self> { Drain::new(self) }
+
+  #[inline(always)]
+  fn into_bytes(self) -> IntoBytes<Self> { IntoBytes::new(self) }
+
+  #[inline(always)]
+  fn into_reader(self) -> IobufReader<Self> { IobufReader::new(self) }
+
+  #[inline(always)]
+  fn rfind(&self, byte: u8) -> Option<u32> { self.raw.rposition(byte) }
+
+  #[inline(always)]
+  fn rfind_slice(&self, needle: &[u8]) -> Option<u32> { self.raw.rfind_slice(needle) }
+
+  #[inline]
+  fn split_once(&self, delim: u8) -> Option<(Self, Self)> {
+    self.raw.position(delim).map(|i| {
+      let mut head = self.clone();
+      let mut tail = self.clone();
+      unsafe {
+        head.raw.unsafe_resize(i);
+        tail.raw.unsafe_advance(i + 1);
+      }
+      (head, tail)
+    })
+  }
+
+  #[inline(always)]
+  fn iter_be<'b, T: Prim>(&'b self) -> WordIter<'b, T> { self.raw.iter_be::<T>() }
+
+  #[inline(always)]
+  fn iter_le<'b, T: Prim>(&'b self) -> WordIter<'b, T> { self.raw.iter_le::<T>() }
+
+  #[inline(always)]
+  fn fold<B, F: FnMut(B, u8) -> B>(&self, init: B, f: F) -> B { self.raw.fold(init, f) }
+
+  #[inline(always)]
+  fn all_bytes<F: FnMut(u8) -> bool>(&self, pred: F) -> bool { self.raw.all_bytes(pred) }
+
+  #[inline(always)]
+  fn is_ascii(&self) -> bool { self.raw.is_ascii() }
+
+  #[inline(always)]
+  fn is_ascii_printable(&self) -> bool { self.raw.is_ascii_printable() }
+
+  #[inline(always)]
+  fn first_non_ascii(&self) -> Option<u32> { self.raw.first_non_ascii() }
+
+  #[inline(always)]
+  fn trim_matches<F: FnMut(u8) -> bool>(&mut self, pred: F) { self.raw.trim_matches(pred) }
+
+  #[inline(always)]
+  fn trim_ascii_whitespace(&mut self) { self.raw.trim_ascii_whitespace() }
+
+  #[inline(always)]
+  fn partition_point<F: FnMut(u8) -> bool>(&self, pred: F) -> u32 { self.raw.partition_point(pred) }
+
+  #[inline(always)]
+  fn partition_point_records<F: FnMut(&[u8]) -> bool>(&self, size: u32, pred: F) -> Result<u32, ()> { self.raw.partition_point_records(size, pred) }
+
+  #[inline(always)]
+  fn byte_histogram(&self) -> [u32; 256] { self.raw.byte_histogram() }
+
+  #[inline(always)]
+  fn shannon_entropy(&self) -> f64 { self.raw.shannon_entropy() }
+
+  #[inline(always)]
+  fn byte_min(&self) -> Option<u8> { self.raw.byte_min() }
+
+  #[inline(always)]
+  fn byte_max(&self) -> Option<u8> { self.raw.byte_max() }
+
+  #[inline(always)]
+  fn byte_sum(&self) -> u64 { self.raw.byte_sum() }
+
+  #[inline(always)]
+  fn crc32c(&self) -> u32 { self.raw.crc32c() }
+
+  #[inline(always)]
+  fn crc32c_from(&self, pos: u32, len: u32) -> Result<u32, ()> { self.raw.crc32c_from(pos, len) }
+
+  #[inline(always)]
+  fn hash_window<H: hash::Writer>(&self, hasher: &mut H) { unsafe { hasher.write(self.raw.as_window_slice()) } }
+
+  #[inline(always)]
+  fn with_rollback<R, E, F: FnOnce(&mut Self) -> Result<R, E>>(&mut self, f: F) -> Result<R, E> { with_rollback(self, f) }
+
+  #[inline(always)]
+  fn transaction<'b>(&'b mut self) -> BufTransaction<'b, Self> { BufTransaction::new(self) }
+
+  #[inline(always)]
+  unsafe fn window_guard_positioned<'b>(&'b mut self) -> PositionedWindow<'b, Self> { PositionedWindow::new(self) }
+
+  #[inline(always)]
+  fn read_records<'b, H: ReadFrom>(&'b mut self) -> RecordIter<'b, Self, H> { RecordIter::new(self) }
+
+  #[inline(always)]
+  fn consume_records<'b>(&'b mut self, size: u32) -> Records<'b, Self> { Records::new(self, size) }
+
+  #[inline(always)]
+  fn tlv_iter<'b>(&'b mut self, type_width: u8, len_width: u8, big_endian: bool) -> TlvIter<'b, Self> { TlvIter::new(self, type_width, len_width, big_endian) }
+
+  #[inline]
+  fn peek_header<H: ReadFrom>(&self) -> Result<H, ()> { ReadFrom::read_from(&mut self.clone()) }
+
+  #[inline]
+  fn lookahead<'b, R, F: FnOnce(&mut ROIobuf<'b>) -> R>(&'b self, f: F) -> R {
+    f(&mut ROIobuf { raw: self.raw.borrowed_view() })
+  }
+
+  #[inline(always)]
+  fn consume_repeat<R, E, F: FnMut(&mut Self) -> Result<R, E>>(&mut self, count: u32, f: F) -> Result<Vec<R>, E> { consume_repeat(self, count, f) }
+
+  #[inline(always)]
+  fn consume_repeat_until_empty<R, E, F: FnMut(&mut Self) -> Result<R, E>>(&mut self, f: F) -> Result<Vec<R>, E> { consume_repeat_until_empty(self, f) }
+
+  #[inline(always)]
+  fn with_sub_window<R, F: FnOnce(&mut Self) -> R>(&mut self, pos: u32, len: u32, f: F) -> Result<R, ()> { with_sub_window(self, pos, len, f) }
+
+  #[inline(always)]
+  fn check_range(&self, pos: u32, len: u32) -> Result<(), ()> { self.raw.check_range_u32(pos, len) }
+
+  #[inline(always)]
+  fn check_range_usize(&self, pos: u32, len: usize) -> Result<(), ()> { self.raw.check_range_usize(pos, len) }
+
+  #[inline(always)]
+  fn check_range_fail(&self, pos: u32, len: u32) { self.raw.check_range_u32_fail(pos, len) }
+
+  #[inline(always)]
+  fn check_range_usize_fail(&self, pos: u32, len: usize) { self.raw.check_range_usize_fail(pos, len) }
+
+  #[inline(always)]
+  unsafe fn unsafe_peek(&self, pos: u32, dst: &mut [u8]) { self.raw.unsafe_peek(pos, dst) }
+  #[inline(always)]
+  unsafe fn unsafe_peek_be<T: Prim>(&self, pos: u32) -> T { self.raw.unsafe_peek_be(pos) }
+  #[inline(always)]
+  unsafe fn unsafe_peek_le<T: Prim>(&self, pos: u32) -> T { self.raw.unsafe_peek_le(pos) }
+
+  #[inline(always)]
+  unsafe fn unsafe_consume(&mut self, dst: &mut [u8]) { self.raw.unsafe_consume(dst) }
+  #[inline(always)]
+  unsafe fn unsafe_consume_be<T: Prim>(&mut self) -> T { self.raw.unsafe_consume_be::<T>() }
+  #[inline(always)]
+  unsafe fn unsafe_consume_le<T: Prim>(&mut self) -> T { self.raw.unsafe_consume_le::<T>() }
+
+  #[inline(always)]
+  unsafe fn as_raw<'b>(&'b self) -> &RawIobuf<'b> { mem::transmute(&self.raw) }
+
+  #[inline(always)]
+  fn ptr(&self) -> *mut u8 { self.raw.ptr() }
+  #[inline(always)]
+  fn is_owned(&self) -> bool { self.raw.is_owned() }
+
+  #[inline(always)]
+  fn debug_state(&self) -> IobufState { self.raw.debug_state() }
+  #[inline(always)]
+  fn lo_min(&self) -> u32 { self.raw.lo_min() }
+  #[inline(always)]
+  fn lo(&self) -> u32 { self.raw.lo() }
+  #[inline(always)]
+  fn hi(&self) -> u32 { self.raw.hi() }
+  #[inline(always)]
+  fn hi_max(&self) -> u32 { self.raw.hi_max() }
+}
+
+impl Iobuf for AROIobuf {
+  #[inline(always)]
+  fn deep_clone(&self) -> RWIobuf<'static> {
+    RWIobuf { raw: self.raw.deep_clone() }
+  }
+
+  #[inline(always)]
+  fn deep_clone_with_allocator(&self, allocator: Arc<Box<Allocator>>) -> RWIobuf<'static> {
+    RWIobuf { raw: self.raw.deep_clone_with_allocator(allocator) }
+  }
+
+  #[inline(always)]
+  fn deep_clone_from_pool(&self, pool: &mut IobufPool) -> RWIobuf<'static> { pool.deep_clone(self) }
+
+  #[inline(always)]
+  fn window_to_owned(&self) -> ROIobuf<'static> { ROIobuf { raw: self.raw.window_to_owned() } }
+
+  #[inline(always)]
+  fn split_to_owned(&mut self, n: u32) -> Result<RWIobuf<'static>, ()> { self.raw.split_to_owned(n).map(|raw| RWIobuf { raw: raw }) }
+
+  #[inline(always)]
+  fn to_cstring(&self) -> Result<CString, ()> { self.raw.to_cstring() }
+
+  #[inline(always)]
+  unsafe fn as_cstr_ptr(&self) -> Result<*const u8, ()> { self.raw.as_cstr_ptr() }
+
+  #[inline(always)]
+  fn unique(self) -> Result<UniqueIobuf, AROIobuf> {
+    unsafe {
+      if self.raw.is_unique_atomic() {
+        Ok(mem::transmute(self))
+      } else {
+        Err(self)
+      }
+    }
+  }
+
+  #[inline(always)]
+  fn atomic_read_only(self) -> Result<AROIobuf, AROIobuf> {
+    Ok(self)
+  }
+
+  #[inline(always)]
+  fn len(&self) -> u32 { self.raw.len() }
+
+  #[inline(always)]
+  fn cap(&self) -> u32 { self.raw.cap() }
+
+  #[inline(always)]
+  fn is_empty(&self) -> bool { self.raw.is_empty() }
+
+  #[inline(always)]
+  fn count_prims<T: Prim>(&self, from: u32) -> u32 { self.raw.count_prims::<T>(from) }
+
+  #[inline(always)]
+  unsafe fn as_window_slice<'b>(&'b self) -> &'b [u8] { self.raw.as_window_slice() }
+
+  #[inline(always)]
+  unsafe fn as_limit_slice<'b>(&'b self) -> &'b [u8] { self.raw.as_limit_slice() }
+
+  #[inline(always)]
+  unsafe fn window_as_slice<'b, T: Prim>(&'b self) -> Result<&'b [T], ()> { self.raw.window_as_slice::<T>() }
+
+  #[inline(always)]
+  fn sub_window(&mut self, pos: u32, len: u32) -> Result<(), ()> { self.raw.sub_window(pos, len) }
+
+  #[inline(always)]
+  fn sub_window_from(&mut self, pos: u32) -> Result<(), ()> { self.raw.sub_window_from(pos) }
+
+  #[inline(always)]
+  fn sub_window_to(&mut self, len: u32) -> Result<(), ()> { self.raw.sub_window_to(len) }
+
+  #[inline(always)]
+  unsafe fn unsafe_sub_window(&mut self, pos: u32, len: u32) { self.raw.unsafe_sub_window(pos, len) }
+
+  #[inline(always)]
+  unsafe fn unsafe_sub_window_from(&mut self, pos: u32) { self.raw.unsafe_sub_window_from(pos) }
+
+  #[inline(always)]
+  unsafe fn unsafe_sub_window_to(&mut self, len: u32) { self.raw.unsafe_sub_window_to(len) }
+
+  #[inline(always)]
+  fn sub(&mut self, pos: u32, len: u32) -> Result<(), ()> { self.raw.sub(pos, len) }
+
+  #[inline(always)]
+  fn sub_from(&mut self, pos: u32) -> Result<(), ()> { self.raw.sub_from(pos) }
+
+  #[inline(always)]
+  fn sub_to(&mut self, len: u32) -> Result<(), ()> { self.raw.sub_to(len) }
+
+  #[inline(always)]
+  unsafe fn unsafe_sub(&mut self, pos: u32, len: u32) { self.raw.unsafe_sub(pos, len) }
+
+  #[inline(always)]
+  unsafe fn unsafe_sub_from(&mut self, pos: u32) { self.raw.unsafe_sub_from(pos) }
+
+  #[inline(always)]
+  unsafe fn unsafe_sub_to(&mut self, len: u32) { self.raw.unsafe_sub_to(len) }
+
+  #[inline(always)]
+  fn set_limits_and_window(&mut self, limits: (u32, u32), window: (u32, u32)) -> Result<(), ()> { self.raw.set_limits_and_window(limits, window) }
+
+  #[inline(always)]
+  fn narrow(&mut self) { self.raw.narrow() }
+
+  #[inline(always)]
+  fn advance(&mut self, len: u32) -> Result<(), ()> { self.raw.advance(len) }
+
+  #[inline(always)]
+  fn advanced(mut self, len: u32) -> Result<Self, ()> { try!(self.raw.advance(len)); Ok(self) }
+
+  #[inline(always)]
+  fn resized(mut self, len: u32) -> Result<Self, ()> { try!(self.raw.resize(len)); Ok(self) }
+
+  #[inline(always)]
+  fn subbed(mut self, pos: u32, len: u32) -> Result<Self, ()> { try!(self.raw.sub(pos, len)); Ok(self) }
+
+  #[inline(always)]
+  fn narrowed(mut self) -> Self { self.raw.narrow(); self }
+
+  #[inline(always)]
+  unsafe fn unsafe_advance(&mut self, len: u32) { self.raw.unsafe_advance(len) }
+
+  #[inline(always)]
+  fn advance_upto(&mut self, len: u32) -> u32 { self.raw.advance_upto(len) }
+
+  #[inline(always)]
+  fn extend(&mut self, len: u32) -> Result<(), ()> { self.raw.extend(len) }
+
+  #[inline(always)]
+  unsafe fn unsafe_extend(&mut self, len: u32) { self.raw.unsafe_extend(len) }
+
+  #[inline(always)]
+  fn extend_upto(&mut self, len: u32) -> u32 { self.raw.extend_upto(len) }
+
+  #[inline(always)]
+  fn is_extended_by<Buf: Iobuf>(&self, other: &Buf) -> bool { unsafe { self.raw.is_extended_by(other.as_raw()) } }
+
+  #[inline(always)]
+  fn cmp_window<Buf: Iobuf>(&self, other: &Buf) -> Ordering { unsafe { self.raw.cmp_window(other.as_raw()) } }
+
+  #[inline(always)]
+  fn extend_with<Buf: Iobuf>(&mut self, other: &Buf) -> Result<(), ()> { unsafe { self.raw.extend_with(other.as_raw()) } }
+
+  #[inline(always)]
+  fn resize(&mut self, len: u32) -> Result<(), ()> { self.raw.resize(len) }
+
+  #[inline(always)]
+  fn resize_preserving(&mut self, len: u32) -> Result<(), ()> { self.raw.resize(len) }
+
+  #[inline(always)]
+  unsafe fn unsafe_resize(&mut self, len: u32) { self.raw.unsafe_resize(len) }
+
+  #[inline(always)]
+  fn resize_upto(&mut self, len: u32) -> u32 { self.raw.resize_upto(len) }
+
+  #[inline(always)]
+  fn split_at(&self, pos: u32) -> Result<(AROIobuf, AROIobuf), ()> {
+    self.raw.split_at_atomic(pos).map(
+      |(a, b)| (AROIobuf { raw: a },
+                AROIobuf { raw: b }))
+  }
+
+  #[inline(always)]
+  unsafe fn unsafe_split_at(&self, pos: u32) -> (AROIobuf, AROIobuf) {
+    let (a, b) = self.raw.unsafe_split_at_atomic(pos);
+    (AROIobuf { raw: a },
+     AROIobuf { raw: b })
+  }
+
+  #[inline(always)]
+  fn split_start_at(&mut self, pos: u32) -> Result<AROIobuf, ()> {
+    self.raw.split_start_at_atomic(pos).map(
+      |b| AROIobuf { raw: b })
+  }
+
+  #[inline(always)]
+  unsafe fn unsafe_split_start_at(&mut self, pos: u32) -> AROIobuf {
+    AROIobuf { raw: self.raw.unsafe_split_start_at_atomic(pos) }
+  }
+
+  #[inline(always)]
+  fn rewind(&mut self) { self.raw.rewind() }
+
+  #[inline(always)]
+  fn reset(&mut self) { self.raw.reset() }
+
+  #[inline(always)]
+  fn flip_lo(&mut self) { self.raw.flip_lo() }
+
+  #[inline(always)]
+  fn flip_hi(&mut self) { self.raw.flip_hi() }
+
+  #[inline(always)]
+  fn lo_space(&self) -> u32 { self.raw.lo_space() }
+
+  #[inline(always)]
+  fn hi_space(&self) -> u32 { self.raw.hi_space() }
+
+  #[inline(always)]
+  fn peek(&self, pos: u32, dst: &mut [u8]) -> Result<(), ()> { self.raw.peek(pos, dst) }
+
+  #[inline(always)]
+  unsafe fn peek_into_uninit(&self, pos: u32, dst: *mut u8, len: u32) -> Result<(), ()> { self.raw.peek_into_uninit(pos, dst, len) }
+
+  #[inline]
+  fn peek_vectored(&self, pos: u32, dsts: &mut [&mut [u8]]) -> Result<(), ()> { self.raw.peek_vectored(pos, dsts).map(|_| ()) }
+
+  #[inline(always)]
+  fn consume_vectored(&mut self, dsts: &mut [&mut [u8]]) -> Result<(), ()> { self.raw.consume_vectored(dsts) }
+
+  #[inline(always)]
+  fn peek_all_into(&self, dst: &mut Vec<u8>) { self.raw.peek_all_into(dst) }
+
+  #[inline(always)]
+  fn consume_all_into(&mut self, dst: &mut Vec<u8>) { self.raw.consume_all_into(dst) }
+  #[inline(always)]
+  fn peek_be<T: Prim>(&self, pos: u32) -> Result<T, ()> { self.raw.peek_be(pos) }
+  #[inline(always)]
+  fn peek_le<T: Prim>(&self, pos: u32) -> Result<T, ()> { self.raw.peek_le(pos) }
+
+  #[inline(always)]
+  fn consume(&mut self, dst: &mut [u8]) -> Result<(), ()> { self.raw.consume(dst) }
+  #[inline(always)]
+  fn consume_be<T: Prim>(&mut self) -> Result<T, ()> { self.raw.consume_be::<T>() }
+  #[inline(always)]
+  fn consume_le<T: Prim>(&mut self) -> Result<T, ()> { self.raw.consume_le::<T>() }
+
+  #[cfg(feature = "num-bigint")]
+  #[inline(always)]
+  fn peek_biguint_be(&self, pos: u32, len: u32) -> Result<BigUint, ()> { self.raw.peek_biguint_be(pos, len) }
+
+  #[cfg(feature = "num-bigint")]
+  #[inline(always)]
+  fn consume_biguint_be(&mut self, len: u32) -> Result<BigUint, ()> { self.raw.consume_biguint_be(len) }
 
-  /// Writes a little-endian primitive into the beginning of the window. It is
-  /// undefined behavior to write outside the iobuf window.
-  ///
-  /// After the primitive has been written, the window will be moved such that
-  /// it is no longer included.
-  ///
-  /// ```rust
-  /// use iobuf::{RWIobuf,Iobuf};
-  ///
-  /// let mut b = RWIobuf::new(10);
-  ///
-  /// unsafe {
-  ///   b.check_range_fail(0, 10);
-  ///
-  ///   b.unsafe_fill_le(0x12345678u32);
-  ///   b.unsafe_fill_le(0x11223344u32);
-  ///   // b.unsafe_fill_le(0x54321123u32); DO NOT DO THIS. Undefined behavior.
-  ///   b.unsafe_fill_le(0x8877u16);
-  /// }
-  ///
-  /// b.flip_lo();
-  ///
-  /// unsafe { assert_eq!(b.as_window_slice(), [ 0x78, 0x56, 0x34, 0x12
-  ///                                          , 0x44, 0x33, 0x22, 0x11
-  ///                                          , 0x77, 0x88 ]); }
-  /// ```
   #[inline(always)]
-  pub unsafe fn unsafe_fill_le<T: Int>(&mut self, t: T) { self.raw.unsafe_fill_le(t) }
-}
+  fn consume_be_opt<T: Prim>(&mut self) -> Option<T> { self.raw.consume_be_opt::<T>() }
 
-impl AROIobuf {
-  /// Stops atomically reference counting a unique buffer. This method returns
-  /// `Ok` if the `AROIobuf` is the last of its kind, and `Err` if it's not.
-  ///
-  /// ```rust
-  /// use iobuf::{AROIobuf, ROIobuf, Iobuf};
-  ///
-  /// let buf: ROIobuf<'static> = ROIobuf::from_str_copy("hello, world!");
-  /// let a_buf: AROIobuf = buf.atomic_read_only().ok().unwrap();
-  /// unsafe { assert_eq!(a_buf.as_window_slice(), b"hello, world!"); }
-  ///
-  /// let buf: ROIobuf<'static> = a_buf.read_only().ok().unwrap();
-  ///
-  /// // TA-DA!
-  /// unsafe { assert_eq!(buf.as_window_slice(), b"hello, world!"); }
-  /// ```
   #[inline(always)]
-  pub fn read_only(self) -> Result<ROIobuf<'static>, AROIobuf> {
-    unsafe {
-      if self.raw.is_unique_atomic() {
-        Ok(mem::transmute(self))
-      } else {
-        Err(self)
-      }
-    }
-  }
+  fn consume_le_opt<T: Prim>(&mut self) -> Option<T> { self.raw.consume_le_opt::<T>() }
 
-  /// Stops atomically reference counting a unique buffer. This method returns
-  /// `Ok` if the `AROIobuf` is the last of its kind, and `Err` if it's not.
-  ///
-  /// ```rust
-  /// use iobuf::{AROIobuf, ROIobuf, RWIobuf, Iobuf};
-  ///
-  /// let buf: ROIobuf<'static> = ROIobuf::from_str_copy("hello, world!");
-  /// let a_buf: AROIobuf = buf.atomic_read_only().ok().unwrap();
-  /// unsafe { assert_eq!(a_buf.as_window_slice(), b"hello, world!"); }
-  ///
-  /// let buf: RWIobuf<'static> = a_buf.read_write().ok().unwrap();
-  ///
-  /// // TA-DA!
-  /// unsafe { assert_eq!(buf.as_window_slice(), b"hello, world!"); }
-  /// ```
   #[inline(always)]
-  pub fn read_write(self) -> Result<RWIobuf<'static>, AROIobuf> {
-    unsafe {
-      if self.raw.is_unique_atomic() {
-        Ok(mem::transmute(self))
-      } else {
-        Err(self)
-      }
-    }
-  }
-}
+  fn consume_hex_u64(&mut self) -> Result<u64, ()> { self.raw.consume_digits_u64(16) }
 
-impl<'a> Iobuf for ROIobuf<'a> {
   #[inline(always)]
-  fn deep_clone(&self) -> RWIobuf<'static> { RWIobuf { raw: self.raw.deep_clone() } }
+  fn consume_decimal_u64(&mut self) -> Result<u64, ()> { self.raw.consume_digits_u64(10) }
 
   #[inline(always)]
-  fn deep_clone_with_allocator(&self, allocator: Arc<Box<Allocator>>) -> RWIobuf<'static> {
-    RWIobuf { raw: self.raw.deep_clone_with_allocator(allocator) }
-  }
+  fn consume_int_be(&mut self, width: u8) -> Result<i64, ()> { self.raw.consume_int(width, true) }
 
   #[inline(always)]
-  fn unique(self) -> Result<UniqueIobuf, ROIobuf<'a>> {
-    unsafe {
-      if self.raw.is_unique_nonatomic() {
-        Ok(mem::transmute(self))
-      } else {
-        Err(self)
-      }
-    }
-  }
+  fn consume_int_le(&mut self, width: u8) -> Result<i64, ()> { self.raw.consume_int(width, false) }
 
   #[inline(always)]
-  fn atomic_read_only(self) -> Result<AROIobuf, ROIobuf<'a>> {
-    unsafe {
-      if self.raw.is_unique_nonatomic() {
-        Ok(mem::transmute(self))
-      } else {
-        Err(self)
-      }
-    }
-  }
+  fn peek_fixed_be(&self, pos: u32, int_bits: u8, frac_bits: u8) -> Result<f64, ()> { self.raw.peek_fixed_be(pos, int_bits, frac_bits) }
 
   #[inline(always)]
-  fn len(&self) -> u32 { self.raw.len() }
+  fn consume_fixed_be(&mut self, int_bits: u8, frac_bits: u8) -> Result<f64, ()> { self.raw.consume_fixed_be(int_bits, frac_bits) }
 
   #[inline(always)]
-  fn cap(&self) -> u32 { self.raw.cap() }
+  fn consume_q16_16_be(&mut self) -> Result<f64, ()> { self.raw.consume_fixed_be(16, 16) }
 
   #[inline(always)]
-  fn is_empty(&self) -> bool { self.raw.is_empty() }
+  fn peek_ipv4(&self, pos: u32) -> Result<IpAddr, ()> { self.raw.peek_ipv4(pos) }
 
   #[inline(always)]
-  unsafe fn as_window_slice<'b>(&'b self) -> &'b [u8] { self.raw.as_window_slice() }
+  fn peek_ipv6(&self, pos: u32) -> Result<IpAddr, ()> { self.raw.peek_ipv6(pos) }
 
   #[inline(always)]
-  unsafe fn as_limit_slice<'b>(&'b self) -> &'b [u8] { self.raw.as_limit_slice() }
+  fn peek_socket_addr_v4(&self, pos: u32) -> Result<SocketAddr, ()> { self.raw.peek_socket_addr_v4(pos) }
 
   #[inline(always)]
-  fn sub_window(&mut self, pos: u32, len: u32) -> Result<(), ()> { self.raw.sub_window(pos, len) }
+  fn consume_ipv4(&mut self) -> Result<IpAddr, ()> { self.raw.consume_ipv4() }
 
   #[inline(always)]
-  fn sub_window_from(&mut self, pos: u32) -> Result<(), ()> { self.raw.sub_window_from(pos) }
+  fn consume_ipv6(&mut self) -> Result<IpAddr, ()> { self.raw.consume_ipv6() }
 
   #[inline(always)]
-  fn sub_window_to(&mut self, len: u32) -> Result<(), ()> { self.raw.sub_window_to(len) }
+  fn consume_socket_addr_v4(&mut self) -> Result<SocketAddr, ()> { self.raw.consume_socket_addr_v4() }
 
   #[inline(always)]
-  unsafe fn unsafe_sub_window(&mut self, pos: u32, len: u32) { self.raw.unsafe_sub_window(pos, len) }
+  fn consume_be_enum<T: Prim, E, F: FnOnce(T) -> Option<E>>(&mut self, validate: F) -> Result<E, EnumError<T>> { self.raw.consume_enum(validate, true) }
 
   #[inline(always)]
-  unsafe fn unsafe_sub_window_from(&mut self, pos: u32) { self.raw.unsafe_sub_window_from(pos) }
+  fn consume_le_enum<T: Prim, E, F: FnOnce(T) -> Option<E>>(&mut self, validate: F) -> Result<E, EnumError<T>> { self.raw.consume_enum(validate, false) }
 
   #[inline(always)]
-  unsafe fn unsafe_sub_window_to(&mut self, len: u32) { self.raw.unsafe_sub_window_to(len) }
+  fn peek_prim<T: Prim>(&self, pos: u32, big_endian: bool) -> Result<T, ()> { self.raw.peek_prim(pos, big_endian) }
 
   #[inline(always)]
-  fn sub(&mut self, pos: u32, len: u32) -> Result<(), ()> { self.raw.sub(pos, len) }
+  fn consume_prim<T: Prim>(&mut self, big_endian: bool) -> Result<T, ()> { self.raw.consume_prim::<T>(big_endian) }
 
   #[inline(always)]
-  fn sub_from(&mut self, pos: u32) -> Result<(), ()> { self.raw.sub_from(pos) }
+  fn consume_be_array<A: IntArray>(&mut self) -> Result<A, ()> { self.raw.consume_be_array::<A>() }
 
   #[inline(always)]
-  fn sub_to(&mut self, len: u32) -> Result<(), ()> { self.raw.sub_to(len) }
+  fn consume_le_array<A: IntArray>(&mut self) -> Result<A, ()> { self.raw.consume_le_array::<A>() }
 
   #[inline(always)]
-  unsafe fn unsafe_sub(&mut self, pos: u32, len: u32) { self.raw.unsafe_sub(pos, len) }
+  fn eq_ignore_ascii_case(&self, other: &[u8]) -> bool { self.raw.eq_ignore_ascii_case(other) }
 
   #[inline(always)]
-  unsafe fn unsafe_sub_from(&mut self, pos: u32) { self.raw.unsafe_sub_from(pos) }
+  fn starts_with_ignore_ascii_case(&self, prefix: &[u8]) -> bool { self.raw.starts_with_ignore_ascii_case(prefix) }
 
   #[inline(always)]
-  unsafe fn unsafe_sub_to(&mut self, len: u32) { self.raw.unsafe_sub_to(len) }
+  fn consume_tag(&mut self, tag: &[u8]) -> Result<(), TagError> { self.raw.consume_tag(tag) }
 
   #[inline(always)]
-  fn set_limits_and_window(&mut self, limits: (u32, u32), window: (u32, u32)) -> Result<(), ()> { self.raw.set_limits_and_window(limits, window) }
+  fn consume_tag_ignore_ascii_case(&mut self, tag: &[u8]) -> Result<(), TagError> { self.raw.consume_tag_ignore_ascii_case(tag) }
 
   #[inline(always)]
-  fn narrow(&mut self) { self.raw.narrow() }
+  fn owns_slice(&self, s: &[u8]) -> bool { self.raw.owns_slice(s) }
 
   #[inline(always)]
-  fn advance(&mut self, len: u32) -> Result<(), ()> { self.raw.advance(len) }
+  fn rchunks<'b>(&'b self, size: u32) -> RChunks<'b, Self> { RChunks::new(self, size) }
+
+  #[inline]
+  fn split_into(&self, parts: u32) -> Vec<Self> { chunks::split_into(self, parts) }
 
   #[inline(always)]
-  unsafe fn unsafe_advance(&mut self, len: u32) { self.raw.unsafe_advance(len) }
+  fn drain<'b>(&'b mut self) -> Drain<'b, Self> { Drain::new(self) }
 
   #[inline(always)]
-  fn extend(&mut self, len: u32) -> Result<(), ()> { self.raw.extend(len) }
+  fn into_bytes(self) -> IntoBytes<Self> { IntoBytes::new(self) }
 
   #[inline(always)]
-  unsafe fn unsafe_extend(&mut self, len: u32) { self.raw.unsafe_extend(len) }
+  fn into_reader(self) -> IobufReader<Self> { IobufReader::new(self) }
 
   #[inline(always)]
-  fn is_extended_by<Buf: Iobuf>(&self, other: &Buf) -> bool { unsafe { self.raw.is_extended_by(other.as_raw()) } }
+  fn rfind(&self, byte: u8) -> Option<u32> { self.raw.rposition(byte) }
 
   #[inline(always)]
-  fn extend_with<Buf: Iobuf>(&mut self, other: &Buf) -> Result<(), ()> { unsafe { self.raw.extend_with(other.as_raw()) } }
+  fn rfind_slice(&self, needle: &[u8]) -> Option<u32> { self.raw.rfind_slice(needle) }
+
+  #[inline]
+  fn split_once(&self, delim: u8) -> Option<(Self, Self)> {
+    self.raw.position(delim).map(|i| {
+      let mut head = self.clone();
+      let mut tail = self.clone();
+      unsafe {
+        head.raw.unsafe_resize(i);
+        tail.raw.unsafe_advance(i + 1);
+      }
+      (head, tail)
+    })
+  }
 
   #[inline(always)]
-  fn resize(&mut self, len: u32) -> Result<(), ()> { self.raw.resize(len) }
+  fn iter_be<'b, T: Prim>(&'b self) -> WordIter<'b, T> { self.raw.iter_be::<T>() }
 
   #[inline(always)]
-  unsafe fn unsafe_resize(&mut self, len: u32) { self.raw.unsafe_resize(len) }
+  fn iter_le<'b, T: Prim>(&'b self) -> WordIter<'b, T> { self.raw.iter_le::<T>() }
 
   #[inline(always)]
-  fn split_at(&self, pos: u32) -> Result<(ROIobuf<'a>, ROIobuf<'a>), ()> {
-    self.raw.split_at_nonatomic(pos).map(|(a, b)| (ROIobuf { raw: a }, ROIobuf { raw: b }))
-  }
+  fn fold<B, F: FnMut(B, u8) -> B>(&self, init: B, f: F) -> B { self.raw.fold(init, f) }
 
   #[inline(always)]
-  unsafe fn unsafe_split_at(&self, pos: u32) -> (ROIobuf<'a>, ROIobuf<'a>) {
-    let (a, b) = self.raw.unsafe_split_at_nonatomic(pos);
-    (ROIobuf { raw: a }, ROIobuf { raw: b })
-  }
+  fn all_bytes<F: FnMut(u8) -> bool>(&self, pred: F) -> bool { self.raw.all_bytes(pred) }
 
   #[inline(always)]
-  fn split_start_at(&mut self, pos: u32) -> Result<ROIobuf<'a>, ()> {
-    self.raw.split_start_at_nonatomic(pos).map(|b| ROIobuf { raw: b })
-  }
+  fn is_ascii(&self) -> bool { self.raw.is_ascii() }
 
   #[inline(always)]
-  unsafe fn unsafe_split_start_at(&mut self, pos: u32) -> ROIobuf<'a> {
-    ROIobuf { raw: self.raw.unsafe_split_start_at_nonatomic(pos) }
-  }
+  fn is_ascii_printable(&self) -> bool { self.raw.is_ascii_printable() }
 
   #[inline(always)]
-  fn rewind(&mut self) { self.raw.rewind() }
+  fn first_non_ascii(&self) -> Option<u32> { self.raw.first_non_ascii() }
 
   #[inline(always)]
-  fn reset(&mut self) { self.raw.reset() }
+  fn trim_matches<F: FnMut(u8) -> bool>(&mut self, pred: F) { self.raw.trim_matches(pred) }
 
   #[inline(always)]
-  fn flip_lo(&mut self) { self.raw.flip_lo() }
+  fn trim_ascii_whitespace(&mut self) { self.raw.trim_ascii_whitespace() }
 
   #[inline(always)]
-  fn flip_hi(&mut self) { self.raw.flip_hi() }
+  fn partition_point<F: FnMut(u8) -> bool>(&self, pred: F) -> u32 { self.raw.partition_point(pred) }
 
   #[inline(always)]
-  fn lo_space(&self) -> u32 { self.raw.lo_space() }
+  fn partition_point_records<F: FnMut(&[u8]) -> bool>(&self, size: u32, pred: F) -> Result<u32, ()> { self.raw.partition_point_records(size, pred) }
 
   #[inline(always)]
-  fn hi_space(&self) -> u32 { self.raw.hi_space() }
+  fn byte_histogram(&self) -> [u32; 256] { self.raw.byte_histogram() }
 
   #[inline(always)]
-  fn peek(&self, pos: u32, dst: &mut [u8]) -> Result<(), ()> { self.raw.peek(pos, dst) }
+  fn shannon_entropy(&self) -> f64 { self.raw.shannon_entropy() }
+
+  #[inline(always)]
+  fn byte_min(&self) -> Option<u8> { self.raw.byte_min() }
+
   #[inline(always)]
-  fn peek_be<T: Int>(&self, pos: u32) -> Result<T, ()> { self.raw.peek_be(pos) }
+  fn byte_max(&self) -> Option<u8> { self.raw.byte_max() }
+
   #[inline(always)]
-  fn peek_le<T: Int>(&self, pos: u32) -> Result<T, ()> { self.raw.peek_le(pos) }
+  fn byte_sum(&self) -> u64 { self.raw.byte_sum() }
 
   #[inline(always)]
-  fn consume(&mut self, dst: &mut [u8]) -> Result<(), ()> { self.raw.consume(dst) }
+  fn crc32c(&self) -> u32 { self.raw.crc32c() }
+
   #[inline(always)]
-  fn consume_be<T: Int>(&mut self) -> Result<T, ()> { self.raw.consume_be::<T>() }
+  fn crc32c_from(&self, pos: u32, len: u32) -> Result<u32, ()> { self.raw.crc32c_from(pos, len) }
+
+  #[inline(always)]
+  fn hash_window<H: hash::Writer>(&self, hasher: &mut H) { unsafe { hasher.write(self.raw.as_window_slice()) } }
+
+  #[inline(always)]
+  fn with_rollback<R, E, F: FnOnce(&mut Self) -> Result<R, E>>(&mut self, f: F) -> Result<R, E> { with_rollback(self, f) }
+
+  #[inline(always)]
+  fn transaction<'b>(&'b mut self) -> BufTransaction<'b, Self> { BufTransaction::new(self) }
+
+  #[inline(always)]
+  unsafe fn window_guard_positioned<'b>(&'b mut self) -> PositionedWindow<'b, Self> { PositionedWindow::new(self) }
+
+  #[inline(always)]
+  fn read_records<'b, H: ReadFrom>(&'b mut self) -> RecordIter<'b, Self, H> { RecordIter::new(self) }
+
+  #[inline(always)]
+  fn consume_records<'b>(&'b mut self, size: u32) -> Records<'b, Self> { Records::new(self, size) }
+
+  #[inline(always)]
+  fn tlv_iter<'b>(&'b mut self, type_width: u8, len_width: u8, big_endian: bool) -> TlvIter<'b, Self> { TlvIter::new(self, type_width, len_width, big_endian) }
+
+  #[inline]
+  fn peek_header<H: ReadFrom>(&self) -> Result<H, ()> { ReadFrom::read_from(&mut self.clone()) }
+
+  #[inline]
+  fn lookahead<'b, R, F: FnOnce(&mut ROIobuf<'b>) -> R>(&'b self, f: F) -> R {
+    f(&mut ROIobuf { raw: self.raw.borrowed_view() })
+  }
+
+  #[inline(always)]
+  fn consume_repeat<R, E, F: FnMut(&mut Self) -> Result<R, E>>(&mut self, count: u32, f: F) -> Result<Vec<R>, E> { consume_repeat(self, count, f) }
+
+  #[inline(always)]
+  fn consume_repeat_until_empty<R, E, F: FnMut(&mut Self) -> Result<R, E>>(&mut self, f: F) -> Result<Vec<R>, E> { consume_repeat_until_empty(self, f) }
+
   #[inline(always)]
-  fn consume_le<T: Int>(&mut self) -> Result<T, ()> { self.raw.consume_le::<T>() }
+  fn with_sub_window<R, F: FnOnce(&mut Self) -> R>(&mut self, pos: u32, len: u32, f: F) -> Result<R, ()> { with_sub_window(self, pos, len, f) }
 
   #[inline(always)]
   fn check_range(&self, pos: u32, len: u32) -> Result<(), ()> { self.raw.check_range_u32(pos, len) }
@@ -1132,16 +3095,16 @@ impl<'a> Iobuf for ROIobuf<'a> {
   #[inline(always)]
   unsafe fn unsafe_peek(&self, pos: u32, dst: &mut [u8]) { self.raw.unsafe_peek(pos, dst) }
   #[inline(always)]
-  unsafe fn unsafe_peek_be<T: Int>(&self, pos: u32) -> T { self.raw.unsafe_peek_be(pos) }
+  unsafe fn unsafe_peek_be<T: Prim>(&self, pos: u32) -> T { self.raw.unsafe_peek_be(pos) }
   #[inline(always)]
-  unsafe fn unsafe_peek_le<T: Int>(&self, pos: u32) -> T { self.raw.unsafe_peek_le(pos) }
+  unsafe fn unsafe_peek_le<T: Prim>(&self, pos: u32) -> T { self.raw.unsafe_peek_le(pos) }
 
   #[inline(always)]
   unsafe fn unsafe_consume(&mut self, dst: &mut [u8]) { self.raw.unsafe_consume(dst) }
   #[inline(always)]
-  unsafe fn unsafe_consume_be<T: Int>(&mut self) -> T { self.raw.unsafe_consume_be::<T>() }
+  unsafe fn unsafe_consume_be<T: Prim>(&mut self) -> T { self.raw.unsafe_consume_be::<T>() }
   #[inline(always)]
-  unsafe fn unsafe_consume_le<T: Int>(&mut self) -> T { self.raw.unsafe_consume_le::<T>() }
+  unsafe fn unsafe_consume_le<T: Prim>(&mut self) -> T { self.raw.unsafe_consume_le::<T>() }
 
   #[inline(always)]
   unsafe fn as_raw<'b>(&'b self) -> &RawIobuf<'b> { mem::transmute(&self.raw) }
@@ -1150,6 +3113,9 @@ impl<'a> Iobuf for ROIobuf<'a> {
   fn ptr(&self) -> *mut u8 { self.raw.ptr() }
   #[inline(always)]
   fn is_owned(&self) -> bool { self.raw.is_owned() }
+
+  #[inline(always)]
+  fn debug_state(&self) -> IobufState { self.raw.debug_state() }
   #[inline(always)]
   fn lo_min(&self) -> u32 { self.raw.lo_min() }
   #[inline(always)]
@@ -1160,11 +3126,9 @@ impl<'a> Iobuf for ROIobuf<'a> {
   fn hi_max(&self) -> u32 { self.raw.hi_max() }
 }
 
-impl Iobuf for AROIobuf {
+impl<'a> Iobuf for RWIobuf<'a> {
   #[inline(always)]
-  fn deep_clone(&self) -> RWIobuf<'static> {
-    RWIobuf { raw: self.raw.deep_clone() }
-  }
+  fn deep_clone(&self) -> RWIobuf<'static> { RWIobuf { raw: self.raw.deep_clone() } }
 
   #[inline(always)]
   fn deep_clone_with_allocator(&self, allocator: Arc<Box<Allocator>>) -> RWIobuf<'static> {
@@ -1172,7 +3136,22 @@ impl Iobuf for AROIobuf {
   }
 
   #[inline(always)]
-  fn unique(self) -> Result<UniqueIobuf, AROIobuf> {
+  fn deep_clone_from_pool(&self, pool: &mut IobufPool) -> RWIobuf<'static> { pool.deep_clone(self) }
+
+  #[inline(always)]
+  fn window_to_owned(&self) -> ROIobuf<'static> { ROIobuf { raw: self.raw.window_to_owned() } }
+
+  #[inline(always)]
+  fn split_to_owned(&mut self, n: u32) -> Result<RWIobuf<'static>, ()> { self.raw.split_to_owned(n).map(|raw| RWIobuf { raw: raw }) }
+
+  #[inline(always)]
+  fn to_cstring(&self) -> Result<CString, ()> { self.raw.to_cstring() }
+
+  #[inline(always)]
+  unsafe fn as_cstr_ptr(&self) -> Result<*const u8, ()> { self.raw.as_cstr_ptr() }
+
+  #[inline(always)]
+  fn unique(self) -> Result<UniqueIobuf, RWIobuf<'a>> {
     unsafe {
       if self.raw.is_unique_atomic() {
         Ok(mem::transmute(self))
@@ -1183,8 +3162,14 @@ impl Iobuf for AROIobuf {
   }
 
   #[inline(always)]
-  fn atomic_read_only(self) -> Result<AROIobuf, AROIobuf> {
-    Ok(self)
+  fn atomic_read_only(self) -> Result<AROIobuf, RWIobuf<'a>> {
+    unsafe {
+      if self.raw.is_unique_nonatomic() {
+        Ok(mem::transmute(self))
+      } else {
+        Err(self)
+      }
+    }
   }
 
   #[inline(always)]
@@ -1196,12 +3181,18 @@ impl Iobuf for AROIobuf {
   #[inline(always)]
   fn is_empty(&self) -> bool { self.raw.is_empty() }
 
+  #[inline(always)]
+  fn count_prims<T: Prim>(&self, from: u32) -> u32 { self.raw.count_prims::<T>(from) }
+
   #[inline(always)]
   unsafe fn as_window_slice<'b>(&'b self) -> &'b [u8] { self.raw.as_window_slice() }
 
   #[inline(always)]
   unsafe fn as_limit_slice<'b>(&'b self) -> &'b [u8] { self.raw.as_limit_slice() }
 
+  #[inline(always)]
+  unsafe fn window_as_slice<'b, T: Prim>(&'b self) -> Result<&'b [T], ()> { self.raw.window_as_slice::<T>() }
+
   #[inline(always)]
   fn sub_window(&mut self, pos: u32, len: u32) -> Result<(), ()> { self.raw.sub_window(pos, len) }
 
@@ -1247,50 +3238,73 @@ impl Iobuf for AROIobuf {
   #[inline(always)]
   fn advance(&mut self, len: u32) -> Result<(), ()> { self.raw.advance(len) }
 
+  #[inline(always)]
+  fn advanced(mut self, len: u32) -> Result<Self, ()> { try!(self.raw.advance(len)); Ok(self) }
+
+  #[inline(always)]
+  fn resized(mut self, len: u32) -> Result<Self, ()> { try!(self.raw.resize(len)); Ok(self) }
+
+  #[inline(always)]
+  fn subbed(mut self, pos: u32, len: u32) -> Result<Self, ()> { try!(self.raw.sub(pos, len)); Ok(self) }
+
+  #[inline(always)]
+  fn narrowed(mut self) -> Self { self.raw.narrow(); self }
+
   #[inline(always)]
   unsafe fn unsafe_advance(&mut self, len: u32) { self.raw.unsafe_advance(len) }
 
+  #[inline(always)]
+  fn advance_upto(&mut self, len: u32) -> u32 { self.raw.advance_upto(len) }
+
   #[inline(always)]
   fn extend(&mut self, len: u32) -> Result<(), ()> { self.raw.extend(len) }
 
   #[inline(always)]
   unsafe fn unsafe_extend(&mut self, len: u32) { self.raw.unsafe_extend(len) }
 
+  #[inline(always)]
+  fn extend_upto(&mut self, len: u32) -> u32 { self.raw.extend_upto(len) }
+
   #[inline(always)]
   fn is_extended_by<Buf: Iobuf>(&self, other: &Buf) -> bool { unsafe { self.raw.is_extended_by(other.as_raw()) } }
 
+  #[inline(always)]
+  fn cmp_window<Buf: Iobuf>(&self, other: &Buf) -> Ordering { unsafe { self.raw.cmp_window(other.as_raw()) } }
+
   #[inline(always)]
   fn extend_with<Buf: Iobuf>(&mut self, other: &Buf) -> Result<(), ()> { unsafe { self.raw.extend_with(other.as_raw()) } }
 
   #[inline(always)]
   fn resize(&mut self, len: u32) -> Result<(), ()> { self.raw.resize(len) }
 
+  #[inline(always)]
+  fn resize_preserving(&mut self, len: u32) -> Result<(), ()> { self.raw.resize(len) }
+
   #[inline(always)]
   unsafe fn unsafe_resize(&mut self, len: u32) { self.raw.unsafe_resize(len) }
 
   #[inline(always)]
-  fn split_at(&self, pos: u32) -> Result<(AROIobuf, AROIobuf), ()> {
-    self.raw.split_at_atomic(pos).map(
-      |(a, b)| (AROIobuf { raw: a },
-                AROIobuf { raw: b }))
+  fn resize_upto(&mut self, len: u32) -> u32 { self.raw.resize_upto(len) }
+
+  #[inline(always)]
+  fn split_at(&self, pos: u32) -> Result<(RWIobuf<'a>, RWIobuf<'a>), ()> {
+    self.raw.split_at_nonatomic(pos).map(|(a, b)| (RWIobuf { raw: a }, RWIobuf { raw: b }))
   }
 
   #[inline(always)]
-  unsafe fn unsafe_split_at(&self, pos: u32) -> (AROIobuf, AROIobuf) {
-    let (a, b) = self.raw.unsafe_split_at_atomic(pos);
-    (AROIobuf { raw: a },
-     AROIobuf { raw: b })
+  unsafe fn unsafe_split_at(&self, pos: u32) -> (RWIobuf<'a>, RWIobuf<'a>) {
+    let (a, b) = self.raw.unsafe_split_at_nonatomic(pos);
+    (RWIobuf { raw: a }, RWIobuf { raw: b })
   }
 
   #[inline(always)]
-  fn split_start_at(&mut self, pos: u32) -> Result<AROIobuf, ()> {
-    self.raw.split_start_at_atomic(pos).map(
-      |b| AROIobuf { raw: b })
+  fn split_start_at(&mut self, pos: u32) -> Result<RWIobuf<'a>, ()> {
+    self.raw.split_start_at_nonatomic(pos).map(|b| RWIobuf { raw: b })
   }
 
   #[inline(always)]
-  unsafe fn unsafe_split_start_at(&mut self, pos: u32) -> AROIobuf {
-    AROIobuf { raw: self.raw.unsafe_split_start_at_atomic(pos) }
+  unsafe fn unsafe_split_start_at(&mut self, pos: u32) -> RWIobuf<'a> {
+    RWIobuf { raw: self.raw.unsafe_split_start_at_nonatomic(pos) }
   }
 
   #[inline(always)]
@@ -1313,225 +3327,244 @@ impl Iobuf for AROIobuf {
 
   #[inline(always)]
   fn peek(&self, pos: u32, dst: &mut [u8]) -> Result<(), ()> { self.raw.peek(pos, dst) }
+
   #[inline(always)]
-  fn peek_be<T: Int>(&self, pos: u32) -> Result<T, ()> { self.raw.peek_be(pos) }
+  unsafe fn peek_into_uninit(&self, pos: u32, dst: *mut u8, len: u32) -> Result<(), ()> { self.raw.peek_into_uninit(pos, dst, len) }
+
+  #[inline]
+  fn peek_vectored(&self, pos: u32, dsts: &mut [&mut [u8]]) -> Result<(), ()> { self.raw.peek_vectored(pos, dsts).map(|_| ()) }
+
   #[inline(always)]
-  fn peek_le<T: Int>(&self, pos: u32) -> Result<T, ()> { self.raw.peek_le(pos) }
+  fn consume_vectored(&mut self, dsts: &mut [&mut [u8]]) -> Result<(), ()> { self.raw.consume_vectored(dsts) }
 
   #[inline(always)]
-  fn consume(&mut self, dst: &mut [u8]) -> Result<(), ()> { self.raw.consume(dst) }
+  fn peek_all_into(&self, dst: &mut Vec<u8>) { self.raw.peek_all_into(dst) }
+
   #[inline(always)]
-  fn consume_be<T: Int>(&mut self) -> Result<T, ()> { self.raw.consume_be::<T>() }
+  fn consume_all_into(&mut self, dst: &mut Vec<u8>) { self.raw.consume_all_into(dst) }
   #[inline(always)]
-  fn consume_le<T: Int>(&mut self) -> Result<T, ()> { self.raw.consume_le::<T>() }
-
+  fn peek_be<T: Prim>(&self, pos: u32) -> Result<T, ()> { self.raw.peek_be(pos) }
   #[inline(always)]
-  fn check_range(&self, pos: u32, len: u32) -> Result<(), ()> { self.raw.check_range_u32(pos, len) }
+  fn peek_le<T: Prim>(&self, pos: u32) -> Result<T, ()> { self.raw.peek_le(pos) }
 
   #[inline(always)]
-  fn check_range_usize(&self, pos: u32, len: usize) -> Result<(), ()> { self.raw.check_range_usize(pos, len) }
+  fn consume(&mut self, dst: &mut [u8]) -> Result<(), ()> { self.raw.consume(dst) }
+  #[inline(always)]
+  fn consume_be<T: Prim>(&mut self) -> Result<T, ()> { self.raw.consume_be::<T>() }
+  #[inline(always)]
+  fn consume_le<T: Prim>(&mut self) -> Result<T, ()> { self.raw.consume_le::<T>() }
 
+  #[cfg(feature = "num-bigint")]
   #[inline(always)]
-  fn check_range_fail(&self, pos: u32, len: u32) { self.raw.check_range_u32_fail(pos, len) }
+  fn peek_biguint_be(&self, pos: u32, len: u32) -> Result<BigUint, ()> { self.raw.peek_biguint_be(pos, len) }
 
+  #[cfg(feature = "num-bigint")]
   #[inline(always)]
-  fn check_range_usize_fail(&self, pos: u32, len: usize) { self.raw.check_range_usize_fail(pos, len) }
+  fn consume_biguint_be(&mut self, len: u32) -> Result<BigUint, ()> { self.raw.consume_biguint_be(len) }
 
   #[inline(always)]
-  unsafe fn unsafe_peek(&self, pos: u32, dst: &mut [u8]) { self.raw.unsafe_peek(pos, dst) }
+  fn consume_be_opt<T: Prim>(&mut self) -> Option<T> { self.raw.consume_be_opt::<T>() }
+
   #[inline(always)]
-  unsafe fn unsafe_peek_be<T: Int>(&self, pos: u32) -> T { self.raw.unsafe_peek_be(pos) }
+  fn consume_le_opt<T: Prim>(&mut self) -> Option<T> { self.raw.consume_le_opt::<T>() }
+
   #[inline(always)]
-  unsafe fn unsafe_peek_le<T: Int>(&self, pos: u32) -> T { self.raw.unsafe_peek_le(pos) }
+  fn consume_hex_u64(&mut self) -> Result<u64, ()> { self.raw.consume_digits_u64(16) }
 
   #[inline(always)]
-  unsafe fn unsafe_consume(&mut self, dst: &mut [u8]) { self.raw.unsafe_consume(dst) }
+  fn consume_decimal_u64(&mut self) -> Result<u64, ()> { self.raw.consume_digits_u64(10) }
+
   #[inline(always)]
-  unsafe fn unsafe_consume_be<T: Int>(&mut self) -> T { self.raw.unsafe_consume_be::<T>() }
+  fn consume_int_be(&mut self, width: u8) -> Result<i64, ()> { self.raw.consume_int(width, true) }
+
   #[inline(always)]
-  unsafe fn unsafe_consume_le<T: Int>(&mut self) -> T { self.raw.unsafe_consume_le::<T>() }
+  fn consume_int_le(&mut self, width: u8) -> Result<i64, ()> { self.raw.consume_int(width, false) }
 
   #[inline(always)]
-  unsafe fn as_raw<'b>(&'b self) -> &RawIobuf<'b> { mem::transmute(&self.raw) }
+  fn peek_fixed_be(&self, pos: u32, int_bits: u8, frac_bits: u8) -> Result<f64, ()> { self.raw.peek_fixed_be(pos, int_bits, frac_bits) }
 
   #[inline(always)]
-  fn ptr(&self) -> *mut u8 { self.raw.ptr() }
+  fn consume_fixed_be(&mut self, int_bits: u8, frac_bits: u8) -> Result<f64, ()> { self.raw.consume_fixed_be(int_bits, frac_bits) }
+
   #[inline(always)]
-  fn is_owned(&self) -> bool { self.raw.is_owned() }
+  fn consume_q16_16_be(&mut self) -> Result<f64, ()> { self.raw.consume_fixed_be(16, 16) }
+
   #[inline(always)]
-  fn lo_min(&self) -> u32 { self.raw.lo_min() }
+  fn peek_ipv4(&self, pos: u32) -> Result<IpAddr, ()> { self.raw.peek_ipv4(pos) }
+
   #[inline(always)]
-  fn lo(&self) -> u32 { self.raw.lo() }
+  fn peek_ipv6(&self, pos: u32) -> Result<IpAddr, ()> { self.raw.peek_ipv6(pos) }
+
   #[inline(always)]
-  fn hi(&self) -> u32 { self.raw.hi() }
+  fn peek_socket_addr_v4(&self, pos: u32) -> Result<SocketAddr, ()> { self.raw.peek_socket_addr_v4(pos) }
+
   #[inline(always)]
-  fn hi_max(&self) -> u32 { self.raw.hi_max() }
-}
+  fn consume_ipv4(&mut self) -> Result<IpAddr, ()> { self.raw.consume_ipv4() }
 
-impl<'a> Iobuf for RWIobuf<'a> {
   #[inline(always)]
-  fn deep_clone(&self) -> RWIobuf<'static> { RWIobuf { raw: self.raw.deep_clone() } }
+  fn consume_ipv6(&mut self) -> Result<IpAddr, ()> { self.raw.consume_ipv6() }
 
   #[inline(always)]
-  fn deep_clone_with_allocator(&self, allocator: Arc<Box<Allocator>>) -> RWIobuf<'static> {
-    RWIobuf { raw: self.raw.deep_clone_with_allocator(allocator) }
-  }
+  fn consume_socket_addr_v4(&mut self) -> Result<SocketAddr, ()> { self.raw.consume_socket_addr_v4() }
 
   #[inline(always)]
-  fn unique(self) -> Result<UniqueIobuf, RWIobuf<'a>> {
-    unsafe {
-      if self.raw.is_unique_atomic() {
-        Ok(mem::transmute(self))
-      } else {
-        Err(self)
-      }
-    }
-  }
+  fn consume_be_enum<T: Prim, E, F: FnOnce(T) -> Option<E>>(&mut self, validate: F) -> Result<E, EnumError<T>> { self.raw.consume_enum(validate, true) }
 
   #[inline(always)]
-  fn atomic_read_only(self) -> Result<AROIobuf, RWIobuf<'a>> {
-    unsafe {
-      if self.raw.is_unique_nonatomic() {
-        Ok(mem::transmute(self))
-      } else {
-        Err(self)
-      }
-    }
-  }
+  fn consume_le_enum<T: Prim, E, F: FnOnce(T) -> Option<E>>(&mut self, validate: F) -> Result<E, EnumError<T>> { self.raw.consume_enum(validate, false) }
 
   #[inline(always)]
-  fn len(&self) -> u32 { self.raw.len() }
+  fn peek_prim<T: Prim>(&self, pos: u32, big_endian: bool) -> Result<T, ()> { self.raw.peek_prim(pos, big_endian) }
 
   #[inline(always)]
-  fn cap(&self) -> u32 { self.raw.cap() }
+  fn consume_prim<T: Prim>(&mut self, big_endian: bool) -> Result<T, ()> { self.raw.consume_prim::<T>(big_endian) }
 
   #[inline(always)]
-  fn is_empty(&self) -> bool { self.raw.is_empty() }
+  fn consume_be_array<A: IntArray>(&mut self) -> Result<A, ()> { self.raw.consume_be_array::<A>() }
 
   #[inline(always)]
-  unsafe fn as_window_slice<'b>(&'b self) -> &'b [u8] { self.raw.as_window_slice() }
+  fn consume_le_array<A: IntArray>(&mut self) -> Result<A, ()> { self.raw.consume_le_array::<A>() }
 
   #[inline(always)]
-  unsafe fn as_limit_slice<'b>(&'b self) -> &'b [u8] { self.raw.as_limit_slice() }
+  fn eq_ignore_ascii_case(&self, other: &[u8]) -> bool { self.raw.eq_ignore_ascii_case(other) }
 
   #[inline(always)]
-  fn sub_window(&mut self, pos: u32, len: u32) -> Result<(), ()> { self.raw.sub_window(pos, len) }
+  fn starts_with_ignore_ascii_case(&self, prefix: &[u8]) -> bool { self.raw.starts_with_ignore_ascii_case(prefix) }
 
   #[inline(always)]
-  fn sub_window_from(&mut self, pos: u32) -> Result<(), ()> { self.raw.sub_window_from(pos) }
+  fn consume_tag(&mut self, tag: &[u8]) -> Result<(), TagError> { self.raw.consume_tag(tag) }
 
   #[inline(always)]
-  fn sub_window_to(&mut self, len: u32) -> Result<(), ()> { self.raw.sub_window_to(len) }
+  fn consume_tag_ignore_ascii_case(&mut self, tag: &[u8]) -> Result<(), TagError> { self.raw.consume_tag_ignore_ascii_case(tag) }
 
   #[inline(always)]
-  unsafe fn unsafe_sub_window(&mut self, pos: u32, len: u32) { self.raw.unsafe_sub_window(pos, len) }
+  fn owns_slice(&self, s: &[u8]) -> bool { self.raw.owns_slice(s) }
 
   #[inline(always)]
-  unsafe fn unsafe_sub_window_from(&mut self, pos: u32) { self.raw.unsafe_sub_window_from(pos) }
+  fn rchunks<'b>(&'b self, size: u32) -> RChunks<'b, Self> { RChunks::new(self, size) }
+
+  #[inline]
+  fn split_into(&self, parts: u32) -> Vec<Self> { chunks::split_into(self, parts) }
 
   #[inline(always)]
-  unsafe fn unsafe_sub_window_to(&mut self, len: u32) { self.raw.unsafe_sub_window_to(len) }
+  fn drain<'b>(&'b mut self) -> Drain<'b, Self> { Drain::new(self) }
 
   #[inline(always)]
-  fn sub(&mut self, pos: u32, len: u32) -> Result<(), ()> { self.raw.sub(pos, len) }
+  fn into_bytes(self) -> IntoBytes<Self> { IntoBytes::new(self) }
 
   #[inline(always)]
-  fn sub_from(&mut self, pos: u32) -> Result<(), ()> { self.raw.sub_from(pos) }
+  fn into_reader(self) -> IobufReader<Self> { IobufReader::new(self) }
 
   #[inline(always)]
-  fn sub_to(&mut self, len: u32) -> Result<(), ()> { self.raw.sub_to(len) }
+  fn rfind(&self, byte: u8) -> Option<u32> { self.raw.rposition(byte) }
 
   #[inline(always)]
-  unsafe fn unsafe_sub(&mut self, pos: u32, len: u32) { self.raw.unsafe_sub(pos, len) }
+  fn rfind_slice(&self, needle: &[u8]) -> Option<u32> { self.raw.rfind_slice(needle) }
+
+  #[inline]
+  fn split_once(&self, delim: u8) -> Option<(Self, Self)> {
+    self.raw.position(delim).map(|i| {
+      let mut head = self.clone();
+      let mut tail = self.clone();
+      unsafe {
+        head.raw.unsafe_resize(i);
+        tail.raw.unsafe_advance(i + 1);
+      }
+      (head, tail)
+    })
+  }
 
   #[inline(always)]
-  unsafe fn unsafe_sub_from(&mut self, pos: u32) { self.raw.unsafe_sub_from(pos) }
+  fn iter_be<'b, T: Prim>(&'b self) -> WordIter<'b, T> { self.raw.iter_be::<T>() }
 
   #[inline(always)]
-  unsafe fn unsafe_sub_to(&mut self, len: u32) { self.raw.unsafe_sub_to(len) }
+  fn iter_le<'b, T: Prim>(&'b self) -> WordIter<'b, T> { self.raw.iter_le::<T>() }
 
   #[inline(always)]
-  fn set_limits_and_window(&mut self, limits: (u32, u32), window: (u32, u32)) -> Result<(), ()> { self.raw.set_limits_and_window(limits, window) }
+  fn fold<B, F: FnMut(B, u8) -> B>(&self, init: B, f: F) -> B { self.raw.fold(init, f) }
 
   #[inline(always)]
-  fn narrow(&mut self) { self.raw.narrow() }
+  fn all_bytes<F: FnMut(u8) -> bool>(&self, pred: F) -> bool { self.raw.all_bytes(pred) }
 
   #[inline(always)]
-  fn advance(&mut self, len: u32) -> Result<(), ()> { self.raw.advance(len) }
+  fn is_ascii(&self) -> bool { self.raw.is_ascii() }
 
   #[inline(always)]
-  unsafe fn unsafe_advance(&mut self, len: u32) { self.raw.unsafe_advance(len) }
+  fn is_ascii_printable(&self) -> bool { self.raw.is_ascii_printable() }
 
   #[inline(always)]
-  fn extend(&mut self, len: u32) -> Result<(), ()> { self.raw.extend(len) }
+  fn first_non_ascii(&self) -> Option<u32> { self.raw.first_non_ascii() }
 
   #[inline(always)]
-  unsafe fn unsafe_extend(&mut self, len: u32) { self.raw.unsafe_extend(len) }
+  fn trim_matches<F: FnMut(u8) -> bool>(&mut self, pred: F) { self.raw.trim_matches(pred) }
 
   #[inline(always)]
-  fn is_extended_by<Buf: Iobuf>(&self, other: &Buf) -> bool { unsafe { self.raw.is_extended_by(other.as_raw()) } }
+  fn trim_ascii_whitespace(&mut self) { self.raw.trim_ascii_whitespace() }
 
   #[inline(always)]
-  fn extend_with<Buf: Iobuf>(&mut self, other: &Buf) -> Result<(), ()> { unsafe { self.raw.extend_with(other.as_raw()) } }
+  fn partition_point<F: FnMut(u8) -> bool>(&self, pred: F) -> u32 { self.raw.partition_point(pred) }
 
   #[inline(always)]
-  fn resize(&mut self, len: u32) -> Result<(), ()> { self.raw.resize(len) }
+  fn partition_point_records<F: FnMut(&[u8]) -> bool>(&self, size: u32, pred: F) -> Result<u32, ()> { self.raw.partition_point_records(size, pred) }
 
   #[inline(always)]
-  unsafe fn unsafe_resize(&mut self, len: u32) { self.raw.unsafe_resize(len) }
+  fn byte_histogram(&self) -> [u32; 256] { self.raw.byte_histogram() }
 
   #[inline(always)]
-  fn split_at(&self, pos: u32) -> Result<(RWIobuf<'a>, RWIobuf<'a>), ()> {
-    self.raw.split_at_nonatomic(pos).map(|(a, b)| (RWIobuf { raw: a }, RWIobuf { raw: b }))
-  }
+  fn shannon_entropy(&self) -> f64 { self.raw.shannon_entropy() }
 
   #[inline(always)]
-  unsafe fn unsafe_split_at(&self, pos: u32) -> (RWIobuf<'a>, RWIobuf<'a>) {
-    let (a, b) = self.raw.unsafe_split_at_nonatomic(pos);
-    (RWIobuf { raw: a }, RWIobuf { raw: b })
-  }
+  fn byte_min(&self) -> Option<u8> { self.raw.byte_min() }
 
   #[inline(always)]
-  fn split_start_at(&mut self, pos: u32) -> Result<RWIobuf<'a>, ()> {
-    self.raw.split_start_at_nonatomic(pos).map(|b| RWIobuf { raw: b })
-  }
+  fn byte_max(&self) -> Option<u8> { self.raw.byte_max() }
 
   #[inline(always)]
-  unsafe fn unsafe_split_start_at(&mut self, pos: u32) -> RWIobuf<'a> {
-    RWIobuf { raw: self.raw.unsafe_split_start_at_nonatomic(pos) }
-  }
+  fn byte_sum(&self) -> u64 { self.raw.byte_sum() }
 
   #[inline(always)]
-  fn rewind(&mut self) { self.raw.rewind() }
+  fn crc32c(&self) -> u32 { self.raw.crc32c() }
 
   #[inline(always)]
-  fn reset(&mut self) { self.raw.reset() }
+  fn crc32c_from(&self, pos: u32, len: u32) -> Result<u32, ()> { self.raw.crc32c_from(pos, len) }
 
   #[inline(always)]
-  fn flip_lo(&mut self) { self.raw.flip_lo() }
+  fn hash_window<H: hash::Writer>(&self, hasher: &mut H) { unsafe { hasher.write(self.raw.as_window_slice()) } }
 
   #[inline(always)]
-  fn flip_hi(&mut self) { self.raw.flip_hi() }
+  fn with_rollback<R, E, F: FnOnce(&mut Self) -> Result<R, E>>(&mut self, f: F) -> Result<R, E> { with_rollback(self, f) }
 
   #[inline(always)]
-  fn lo_space(&self) -> u32 { self.raw.lo_space() }
+  fn transaction<'b>(&'b mut self) -> BufTransaction<'b, Self> { BufTransaction::new(self) }
 
   #[inline(always)]
-  fn hi_space(&self) -> u32 { self.raw.hi_space() }
+  unsafe fn window_guard_positioned<'b>(&'b mut self) -> PositionedWindow<'b, Self> { PositionedWindow::new(self) }
 
   #[inline(always)]
-  fn peek(&self, pos: u32, dst: &mut [u8]) -> Result<(), ()> { self.raw.peek(pos, dst) }
+  fn read_records<'b, H: ReadFrom>(&'b mut self) -> RecordIter<'b, Self, H> { RecordIter::new(self) }
+
   #[inline(always)]
-  fn peek_be<T: Int>(&self, pos: u32) -> Result<T, ()> { self.raw.peek_be(pos) }
+  fn consume_records<'b>(&'b mut self, size: u32) -> Records<'b, Self> { Records::new(self, size) }
+
   #[inline(always)]
-  fn peek_le<T: Int>(&self, pos: u32) -> Result<T, ()> { self.raw.peek_le(pos) }
+  fn tlv_iter<'b>(&'b mut self, type_width: u8, len_width: u8, big_endian: bool) -> TlvIter<'b, Self> { TlvIter::new(self, type_width, len_width, big_endian) }
+
+  #[inline]
+  fn peek_header<H: ReadFrom>(&self) -> Result<H, ()> { ReadFrom::read_from(&mut self.clone()) }
+
+  #[inline]
+  fn lookahead<'b, R, F: FnOnce(&mut ROIobuf<'b>) -> R>(&'b self, f: F) -> R {
+    f(&mut ROIobuf { raw: self.raw.borrowed_view() })
+  }
 
   #[inline(always)]
-  fn consume(&mut self, dst: &mut [u8]) -> Result<(), ()> { self.raw.consume(dst) }
+  fn consume_repeat<R, E, F: FnMut(&mut Self) -> Result<R, E>>(&mut self, count: u32, f: F) -> Result<Vec<R>, E> { consume_repeat(self, count, f) }
+
   #[inline(always)]
-  fn consume_be<T: Int>(&mut self) -> Result<T, ()> { self.raw.consume_be::<T>() }
+  fn consume_repeat_until_empty<R, E, F: FnMut(&mut Self) -> Result<R, E>>(&mut self, f: F) -> Result<Vec<R>, E> { consume_repeat_until_empty(self, f) }
+
   #[inline(always)]
-  fn consume_le<T: Int>(&mut self) -> Result<T, ()> { self.raw.consume_le::<T>() }
+  fn with_sub_window<R, F: FnOnce(&mut Self) -> R>(&mut self, pos: u32, len: u32, f: F) -> Result<R, ()> { with_sub_window(self, pos, len, f) }
 
   #[inline(always)]
   fn check_range(&self, pos: u32, len: u32) -> Result<(), ()> { self.raw.check_range_u32(pos, len) }
@@ -1548,16 +3581,16 @@ impl<'a> Iobuf for RWIobuf<'a> {
   #[inline(always)]
   unsafe fn unsafe_peek(&self, pos: u32, dst: &mut [u8]) { self.raw.unsafe_peek(pos, dst) }
   #[inline(always)]
-  unsafe fn unsafe_peek_be<T: Int>(&self, pos: u32) -> T { self.raw.unsafe_peek_be(pos) }
+  unsafe fn unsafe_peek_be<T: Prim>(&self, pos: u32) -> T { self.raw.unsafe_peek_be(pos) }
   #[inline(always)]
-  unsafe fn unsafe_peek_le<T: Int>(&self, pos: u32) -> T { self.raw.unsafe_peek_le(pos) }
+  unsafe fn unsafe_peek_le<T: Prim>(&self, pos: u32) -> T { self.raw.unsafe_peek_le(pos) }
 
   #[inline(always)]
   unsafe fn unsafe_consume(&mut self, dst: &mut [u8]) { self.raw.unsafe_consume(dst) }
   #[inline(always)]
-  unsafe fn unsafe_consume_be<T: Int>(&mut self) -> T { self.raw.unsafe_consume_be::<T>() }
+  unsafe fn unsafe_consume_be<T: Prim>(&mut self) -> T { self.raw.unsafe_consume_be::<T>() }
   #[inline(always)]
-  unsafe fn unsafe_consume_le<T: Int>(&mut self) -> T { self.raw.unsafe_consume_le::<T>() }
+  unsafe fn unsafe_consume_le<T: Prim>(&mut self) -> T { self.raw.unsafe_consume_le::<T>() }
 
   #[inline(always)]
   unsafe fn as_raw<'b>(&'b self) -> &'b RawIobuf<'b> { mem::transmute(&self.raw) }
@@ -1566,6 +3599,9 @@ impl<'a> Iobuf for RWIobuf<'a> {
   fn ptr(&self) -> *mut u8 { self.raw.ptr() }
   #[inline(always)]
   fn is_owned(&self) -> bool { self.raw.is_owned() }
+
+  #[inline(always)]
+  fn debug_state(&self) -> IobufState { self.raw.debug_state() }
   #[inline(always)]
   fn lo_min(&self) -> u32 { self.raw.lo_min() }
   #[inline(always)]
@@ -1576,6 +3612,96 @@ impl<'a> Iobuf for RWIobuf<'a> {
   fn hi_max(&self) -> u32 { self.raw.hi_max() }
 }
 
+impl<'a> IobufWrite for RWIobuf<'a> {
+  #[inline(always)]
+  fn poke(&self, pos: u32, src: &[u8]) -> Result<(), ()> { self.raw.poke(pos, src) }
+
+  #[inline(always)]
+  fn poke_be<T: Prim>(&self, pos: u32, t: T) -> Result<(), ()> { self.raw.poke_be(pos, t) }
+
+  #[inline(always)]
+  fn poke_le<T: Prim>(&self, pos: u32, t: T) -> Result<(), ()> { self.raw.poke_le(pos, t) }
+
+  #[inline(always)]
+  fn fill(&mut self, src: &[u8]) -> Result<(), ()> { self.raw.fill(src) }
+
+  #[inline(always)]
+  fn fill_be<T: Prim>(&mut self, t: T) -> Result<(), ()> { self.raw.fill_be(t) }
+
+  #[inline(always)]
+  fn fill_le<T: Prim>(&mut self, t: T) -> Result<(), ()> { self.raw.fill_le(t) }
+}
+
+impl<'a> MutIobuf for RWIobuf<'a> {
+  #[inline(always)]
+  unsafe fn as_mut_window_slice<'b>(&'b self) -> &'b mut [u8] { self.raw.as_mut_window_slice() }
+
+  #[inline(always)]
+  unsafe fn as_mut_limit_slice<'b>(&'b self) -> &'b mut [u8] { self.raw.as_mut_limit_slice() }
+
+  #[inline(always)]
+  unsafe fn unsafe_poke(&self, pos: u32, src: &[u8]) { self.raw.unsafe_poke(pos, src) }
+
+  #[inline(always)]
+  unsafe fn unsafe_poke_be<T: Prim>(&self, pos: u32, t: T) { self.raw.unsafe_poke_be(pos, t) }
+
+  #[inline(always)]
+  unsafe fn unsafe_poke_le<T: Prim>(&self, pos: u32, t: T) { self.raw.unsafe_poke_le(pos, t) }
+
+  #[inline(always)]
+  unsafe fn unsafe_fill(&mut self, src: &[u8]) { self.raw.unsafe_fill(src) }
+
+  #[inline(always)]
+  unsafe fn unsafe_fill_be<T: Prim>(&mut self, t: T) { self.raw.unsafe_fill_be(t) }
+
+  #[inline(always)]
+  unsafe fn unsafe_fill_le<T: Prim>(&mut self, t: T) { self.raw.unsafe_fill_le(t) }
+}
+
+impl<'a> IobufAlloc for ROIobuf<'a> {
+  type Owned = ROIobuf<'static>;
+
+  #[inline]
+  fn alloc(len: usize) -> ROIobuf<'static> { ROIobuf::from_fn(len, |_| 0) }
+
+  #[inline]
+  fn owned_from_vec(v: Vec<u8>) -> ROIobuf<'static> { ROIobuf::from_slice_copy(&v[]) }
+}
+
+impl<'a> IobufAlloc for RWIobuf<'a> {
+  type Owned = RWIobuf<'static>;
+
+  #[inline]
+  fn alloc(len: usize) -> RWIobuf<'static> { RWIobuf::new(len) }
+
+  #[inline]
+  fn owned_from_vec(v: Vec<u8>) -> RWIobuf<'static> { RWIobuf::from_slice_copy(&v[]) }
+}
+
+impl IobufAlloc for AROIobuf {
+  type Owned = AROIobuf;
+
+  #[inline]
+  fn alloc(len: usize) -> AROIobuf { freeze(RWIobuf::from_fn(len, |_| 0)) }
+
+  #[inline]
+  fn owned_from_vec(v: Vec<u8>) -> AROIobuf {
+    // Not `from_slice_copy`: an empty copy wouldn't own a buffer, so it
+    // couldn't be made atomic.
+    freeze(RWIobuf::from_fn(v.len(), |i| v[i as usize]))
+  }
+}
+
+// A freshly allocated buffer has nothing else pointing at it, so this can't
+// fail.
+#[inline]
+fn freeze(b: RWIobuf<'static>) -> AROIobuf {
+  match b.atomic_read_only() {
+    Ok(b)  => b,
+    Err(_) => unreachable!(),
+  }
+}
+
 impl<'a> Debug for ROIobuf<'a> {
   #[inline]
   fn fmt(&self, f: &mut Formatter) -> fmt::Result {
@@ -1604,74 +3730,471 @@ impl Debug for UniqueIobuf {
   }
 }
 
-#[cfg(never)]
-mod test {
-  use impls::AROIobuf;
-  use iobuf::Iobuf;
+/// The default is `ROIobuf::empty()`, which doesn't allocate.
+impl Default for ROIobuf<'static> {
+  #[inline(always)]
+  fn default() -> ROIobuf<'static> { ROIobuf::empty() }
+}
+
+/// The default is `RWIobuf::empty()`, which doesn't allocate.
+impl Default for RWIobuf<'static> {
+  #[inline(always)]
+  fn default() -> RWIobuf<'static> { RWIobuf::empty() }
+}
 
-  use quickcheck::{quickcheck, Arbitrary, Gen, Shrinker};
+#[test]
+fn window_split_at_mut_disjoint() {
+  let mut b = RWIobuf::new(8);
+  assert_eq!(b.advance(1), Ok(()));
+  assert_eq!(b.resize(6), Ok(()));
+
+  {
+    let (x, y) = unsafe { b.window_split_at_mut(2).unwrap() };
+    assert_eq!(x.len(), 2);
+    assert_eq!(y.len(), 4);
+    for (i, v) in x.iter_mut().enumerate() { *v = i as u8; }
+    for (i, v) in y.iter_mut().enumerate() { *v = 0x10 + i as u8; }
+  }
 
-    impl Arbitrary for AROIobuf {
-      fn arbitrary<G: Gen>(g: &mut G) -> AROIobuf {
-        let data: Vec<u8> = Arbitrary::arbitrary(g);
+  unsafe { assert_eq!(b.as_window_slice(), [ 0x00, 0x01, 0x10, 0x11, 0x12, 0x13 ]); }
 
-        assert!(data.len() < u32::MAX as usize);
+  {
+    let (x, y) = unsafe { b.window_split_at_mut(6).unwrap() };
+    assert_eq!(x.len(), 6);
+    assert!(y.is_empty());
+  }
 
-        let (a, b, c, d): (u32, u32, u32, u32) = Arbitrary::arbitrary(g);
+  assert!(unsafe { b.window_split_at_mut(7) }.is_err());
+}
 
-        let hi_max = a % (data.len() as u32 + 1);
-        let hi     = b % (hi_max + 1);
-        let lo     = c % (hi + 1);
-        let lo_min = d % (lo + 1);
+#[test]
+fn compact_if_skips() {
+  let mut b = RWIobuf::from_str_copy("0123456789");
+  assert_eq!(b.advance(4), Ok(()));
+  assert_eq!(b.resize(3), Ok(()));
+
+  assert!(!b.compact_if(|_| false));
+  assert!(!b.compact_if_lo_space_exceeds(4));
+  assert_eq!(b.lo(), 4);
+  assert_eq!(b.len(), 3);
+  unsafe { assert_eq!(b.as_limit_slice(), b"0123456789"); }
+
+  assert!(b.compact_if_lo_space_exceeds(3));
+  assert_eq!(b.lo(), 3);
+  assert_eq!(b.len(), 7);
+  unsafe { assert_eq!(&b.as_limit_slice()[..3], b"456"); }
+}
 
-        let mut buf = ROIobuf::from_slice_copy(data[]);
-        buf.set_limits_and_window((lo_min, hi_max), (lo, hi)).unwrap();
+#[test]
+fn reserve_iobuf_deferred_checksum() {
+  let mut b = RWIobuf::new(16);
+
+  assert_eq!(b.fill_be(0xCAFEu16), Ok(()));
+  let mut sum = b.reserve_iobuf(4).unwrap();
+  assert_eq!(sum.cap(), 4);
+  assert_eq!(b.fill(b"payload"), Ok(()));
+
+  let mut payload = b.clone();
+  payload.flip_lo();
+  assert_eq!(payload.advance(6), Ok(()));
+  let checksum = payload.fold(0u32, |acc, x| (acc << 5 | acc >> 27) ^ x as u32);
+
+  assert_eq!(sum.fill_be(checksum), Ok(()));
+  assert!(sum.is_empty());
+  assert_eq!(sum.fill_be(0u8), Err(()));
+
+  b.flip_lo();
+  assert_eq!(b.len(), 13);
+  assert_eq!(b.consume_be::<u16>(), Ok(0xCAFE));
+  assert_eq!(b.consume_be::<u32>(), Ok(checksum));
+  unsafe { assert_eq!(b.as_window_slice(), b"payload"); }
+}
 
-        buf.atomic_read_only().unwrap()
-      }
+#[test]
+fn on_last_drop_runs_once() {
+  use std::sync::Mutex;
+  use std::sync::atomic::{AtomicUint, Ordering, ATOMIC_UINT_INIT};
 
-      fn shrink(&self) -> Box<Shrinker<AROIobuf>+'static> {
-        let mut v: Vec<AROIobuf> = vec!();
-
-        // explore every possible subset of limits and bounds
-        for hi_max in range(0, self.raw.hi_max()) {
-          for hi in range(0, min(hi_max, self.raw.hi())) {
-            for lo in range(self.raw.lo() + 1, hi + 1) {
-              for lo_min in range(self.raw.lo_min() + 1, lo + 1) {
-                let mut new_buf: AROIobuf = (*self).clone();
-                new_buf.set_limits_and_window((lo_min, hi_max), (lo, hi)).unwrap();
-                v.push(new_buf);
-              }
-            }
-          }
-        }
+  let reclaimed = Arc::new(Mutex::new(Vec::new()));
 
-        box v.into_iter()
-      }
+  let mut a = RWIobuf::new(16);
+  let p = a.ptr();
+
+  {
+    let reclaimed = reclaimed.clone();
+    assert_eq!(a.on_last_drop(move |buf: RWIobuf<'static>| {
+      reclaimed.lock().unwrap().push(buf);
+    }), Ok(()));
+  }
+
+  let mut clones: Vec<ROIobuf<'static>> = range(0u32, 8).map(|_| a.read_only()).collect();
+  let mut c = a.clone();
+  assert_eq!(c.advance(3), Ok(()));
+  assert_eq!(c.resize(5), Ok(()));
+  drop(a);
+
+  for i in [ 5us, 0, 3, 4, 1, 2, 1, 0 ].iter() {
+    clones.remove(*i);
+    assert_eq!(reclaimed.lock().unwrap().len(), 0);
+  }
+
+  drop(c);
+
+  // The hook gets the very same buffer, whole, with nobody else sharing it.
+  let mut b = reclaimed.lock().unwrap().pop().unwrap();
+  assert_eq!(reclaimed.lock().unwrap().len(), 0);
+  assert_eq!(b.ptr(), p);
+  assert_eq!(b.lo_min(), 0);
+  assert_eq!(b.len(), 16);
+  assert_eq!(b.debug_state().refcount, 1);
+  assert_eq!(b.fill_be(0xDEADBEEFu32), Ok(()));
+
+  // It's been handed over, so dropping it now really releases it.
+  drop(b);
+  assert_eq!(reclaimed.lock().unwrap().len(), 0);
+
+  // Hooks chain, and a custom allocator still gets its memory back.
+  static RELEASED: AtomicUint = ATOMIC_UINT_INIT;
+
+  struct MyAllocator;
+
+  impl Allocator for MyAllocator {
+    fn allocate(&self, size: usize, align: usize) -> *mut u8 {
+      unsafe { ::alloc::heap::allocate(size, align) }
+    }
+
+    fn deallocate(&self, ptr: *mut u8, len: usize, align: usize) {
+      RELEASED.fetch_add(1, Ordering::SeqCst);
+      unsafe { ::alloc::heap::deallocate(ptr, len, align) }
+    }
+  }
+
+  let order = Arc::new(Mutex::new(Vec::new()));
+  let mut b = RWIobuf::new_with_allocator(4, Arc::new(Box::new(MyAllocator) as Box<Allocator>));
+  for i in range(0u32, 2) {
+    let order = order.clone();
+    assert_eq!(b.on_last_drop(move |buf: RWIobuf<'static>| {
+      assert_eq!(buf.len(), 4);
+      order.lock().unwrap().push(i);
+    }), Ok(()));
+  }
+  drop(b);
+  assert_eq!(*order.lock().unwrap(), vec!(1, 0));
+  assert_eq!(RELEASED.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn with_rollback_nested() {
+  let mut b = ROIobuf::from_str("0123456789");
+
+  let r: Result<(), ()> = b.with_rollback(|b| {
+    try!(b.advance(2));
+    assert_eq!(b.with_rollback(|b| { try!(b.advance(3)); b.advance(10) }), Err(()));
+    assert_eq!(b.lo(), 2);
+
+    assert_eq!(b.with_rollback(|b| { b.narrow(); b.advance(3) }), Ok(()));
+    assert_eq!(b.lo(), 5);
+    assert_eq!(b.lo_min(), 2);
+
+    Err(())
+  });
+
+  assert_eq!(r, Err(()));
+  assert_eq!(b.lo_min(), 0);
+  assert_eq!(b.lo(), 0);
+  assert_eq!(b.len(), 10);
+
+  assert_eq!(b.with_rollback(|b| b.consume_be::<u8>()), Ok(b'0'));
+  assert_eq!(b.lo(), 1);
+}
+
+#[test]
+fn mask_in_pieces() {
+  let data: Vec<u8> = range(0u32, 37).map(|i| (i * 7) as u8).collect();
+  let key = [ 0x01, 0x20, 0x0F, 0xF0 ];
+
+  let whole = RWIobuf::from_slice_copy(&data[]);
+  whole.mask(key, 0);
+
+  // Masking in uneven pieces, continuing through the key, gives the same result.
+  let mut offset = 0;
+  let mut pieces = RWIobuf::from_slice_copy(&data[]);
+  for &n in [ 3u32, 9, 1, 16, 8 ].iter() {
+    let mut piece = pieces.clone();
+    assert_eq!(piece.resize(n), Ok(()));
+    piece.mask(key, offset);
+    offset += n;
+    assert_eq!(pieces.advance(n), Ok(()));
+  }
+  pieces.reset();
+
+  unsafe {
+    assert_eq!(pieces.as_window_slice(), whole.as_window_slice());
+    for (i, (&x, &y)) in whole.as_window_slice().iter().zip(data.iter()).enumerate() {
+      assert_eq!(x, y ^ key[i % 4]);
     }
+  }
+
+  whole.mask(key, 0);
+  unsafe { assert_eq!(whole.as_window_slice(), &data[]); }
+}
+
+#[test]
+fn with_sub_window_restores() {
+  let mut b = RWIobuf::from_str_copy("0123456789");
+  assert_eq!(b.advance(1), Ok(()));
+
+  assert_eq!(b.with_sub_window(2, 4, |b| {
+    assert_eq!(b.lo_min(), 3);
+    assert_eq!(b.hi_max(), 7);
+    b.reset();
+    assert_eq!(b.len(), 4);
+    assert_eq!(b.fill(b"abcd"), Ok(()));
+    assert_eq!(b.fill(b"e"), Err(()));
+    b.flip_hi();
+  }), Ok(()));
+
+  assert_eq!(b.lo_min(), 0);
+  assert_eq!(b.hi_max(), 10);
+  assert_eq!(b.lo(), 7);
+  assert_eq!(b.len(), 3);
+
+  b.reset();
+  unsafe { assert_eq!(b.as_window_slice(), b"012abcd789"); }
+
+  assert_eq!(b.with_sub_window(8, 3, |_| panic!("out of range")), Err(()));
+  assert_eq!(b.lo(), 0);
+}
 
+#[test]
+fn split_once_edges() {
+  let mut b = ROIobuf::from_str("[=ab=]");
+  assert_eq!(b.advance(1), Ok(()));
+  assert_eq!(b.resize(4), Ok(()));
+
+  let (head, tail) = b.split_once(b'=').unwrap();
+  assert!(head.is_empty());
+  assert_eq!(head.lo(), 1);
+  unsafe { assert_eq!(tail.as_window_slice(), b"ab="); }
+
+  let (head, tail) = tail.split_once(b'=').unwrap();
+  unsafe { assert_eq!(head.as_window_slice(), b"ab"); }
+  assert!(tail.is_empty());
+  assert_eq!(tail.lo(), 5);
+
+  // The ']' is outside the window.
+  assert!(b.split_once(b']').is_none());
+}
+
+#[test]
+fn consume_repeat_rolls_back() {
+  let data = [ 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07 ];
+  let mut b = ROIobuf::from_slice(&data);
+  assert_eq!(b.advance(1), Ok(()));
+
+  // The third record is bad.
+  let mut calls = 0u32;
+  let r = b.consume_repeat(4, |b| {
+    calls += 1;
+    let x = try!(b.consume_be::<u8>().map_err(|_| 0xFFu8));
+    if x == 0x04 { Err(x) } else { Ok(x) }
+  });
+  assert_eq!(r, Err(0x04));
+  assert_eq!(calls, 3);
+  assert_eq!(b.lo(), 1);
+
+  assert_eq!(b.consume_repeat(0, |b| b.consume_be::<u8>()), Ok(vec!()));
+  assert_eq!(b.consume_repeat(3, |b| b.consume_be::<u16>()), Ok(vec!(0x0203, 0x0405, 0x0607)));
+  assert!(b.is_empty());
+
+  assert_eq!(b.consume_repeat_until_empty(|b| b.consume_be::<u8>()), Ok(vec!()));
+}
 
-  #[test]
-  fn prop_valid_hi() {
-    fn test_hi(v: AROIobuf) -> bool {
-         v.hi() <= v.hi_max()
+#[test]
+fn generic_encoder() {
+  fn encode<B: MutIobuf>(out: &mut B, xs: &[u16]) -> Result<(), ()> {
+    // Leave room for the count, and fill it in at the end.
+    let mut body = out.clone();
+    try!(body.advance(1));
+    for &x in xs.iter() {
+      try!(body.fill_be(x));
     }
-    quickcheck(test_hi as fn(AROIobuf) -> bool)
+    try!(out.poke_be(0, xs.len() as u8));
+    unsafe { out.unsafe_advance(1 + 2 * xs.len() as u32) };
+    Ok(())
+  }
+
+  let mut b = RWIobuf::new(8);
+  assert_eq!(encode(&mut b, &[ 0x0102, 0x0304 ]), Ok(()));
+  assert_eq!(b.len(), 3);
+  assert_eq!(encode(&mut b, &[ 0x0506, 0x0708 ]), Err(()));
+  assert_eq!(b.len(), 3);
+  assert_eq!(encode(&mut b, &[ 0x0506 ]), Ok(()));
+  assert!(b.is_empty());
+
+  b.flip_lo();
+  unsafe { assert_eq!(b.as_window_slice(), [ 2, 1, 2, 3, 4, 1, 5, 6 ]); }
+}
+
+#[test]
+fn default_in_a_derived_struct() {
+  #[derive(Default)]
+  struct Conn {
+    name:  ROIobuf<'static>,
+    out:   RWIobuf<'static>,
+    reads: u32,
   }
 
-  #[test]
-  fn prop_valid_bounds() {
-    fn test_bounds(v: AROIobuf) -> bool {
-      v.lo() <= v.hi()
+  let c: Conn = Default::default();
+  assert!(c.name.is_empty());
+  assert_eq!(c.name.cap(), 0);
+  assert!(c.out.is_empty());
+  assert_eq!(c.out.cap(), 0);
+  assert_eq!(c.reads, 0);
+
+  // Cloning and dropping the defaults is fine, since there's no refcount.
+  let d = c.out.clone();
+  drop(c);
+  assert_eq!(d.len(), 0);
+}
+
+#[test]
+fn fill_exact_from_a_trickling_reader() {
+  use std::cmp;
+  use std::io::IoResult;
+
+  // Hands out at most 3 bytes per read, then fails once `fail_at` is reached.
+  struct Trickle { data: Vec<u8>, pos: usize, fail_at: usize }
+
+  impl Reader for Trickle {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+      if self.pos == self.fail_at { return Err(io::standard_error(io::OtherIoError)) }
+      if self.pos == self.data.len() { return Err(io::standard_error(io::EndOfFile)) }
+      let n = cmp::min(3, cmp::min(buf.len(), cmp::min(self.data.len(), self.fail_at) - self.pos));
+      buf[..n].clone_from_slice(&self.data[self.pos..self.pos + n]);
+      self.pos += n;
+      Ok(n)
     }
-    quickcheck(test_bounds as fn(AROIobuf) -> bool)
   }
 
-  #[test]
-  fn prop_valid_lo() {
-    fn test_lo(v: AROIobuf) -> bool {
-      v.lo_min() <= v.lo()
+  let data: Vec<u8> = range(0u8, 20).collect();
+
+  let mut r = Trickle { data: data.clone(), pos: 0, fail_at: 100 };
+  let mut b = RWIobuf::new(32);
+  assert_eq!(b.fill_exact_from_reader(&mut r, 10), Ok(10));
+  assert_eq!(b.fill_exact_from_reader(&mut r, 16), Ok(10));
+  assert_eq!(b.fill_exact_from_reader(&mut r, 13), Err(()));
+  assert_eq!(b.fill_exact_from_reader(&mut r, 12), Ok(0));
+  b.flip_lo();
+  unsafe { assert_eq!(b.as_window_slice(), &data[]); }
+
+  let mut r = Trickle { data: data.clone(), pos: 0, fail_at: 7 };
+  let mut b = RWIobuf::new(32);
+  assert_eq!(b.fill_exact_from_reader(&mut r, 10), Err(()));
+  assert_eq!(b.len(), 25);
+}
+
+#[test]
+fn split_to_owned_outlives_the_slice() {
+  let mut kept = Vec::new();
+  {
+    let data: Vec<u8> = range(0u8, 10).collect();
+    let mut b = ROIobuf::from_slice(&data[]);
+    while let Ok(h) = b.split_to_owned(3) {
+      kept.push(h);
     }
-    quickcheck(test_lo as fn(AROIobuf) -> bool)
+    assert_eq!(b.len(), 1);
+    assert_eq!(b.split_to_owned(0).map(|h| h.cap()), Ok(0));
+    assert_eq!(b.len(), 1);
+  }
+
+  assert_eq!(kept.len(), 3);
+  for (i, h) in kept.iter().enumerate() {
+    // Each one is its own buffer, so writing to one doesn't touch the others.
+    assert_eq!(h.poke_be(0, 0xFFu8), Ok(()));
+    assert_eq!(h.cap(), 3);
+    assert_eq!(h.peek_be::<u16>(1), Ok(((3 * i as u16 + 1) << 8) | (3 * i as u16 + 2)));
   }
 }
+
+#[test]
+fn generic_alloc_round_trip() {
+  // Reverses the window into a new buffer of the same flavor.
+  fn reversed<B: IobufAlloc>(b: &B) -> B::Owned {
+    let len = b.len();
+    let v: Vec<u8> = range(0, len).rev().map(|i| b.peek_be::<u8>(i).unwrap()).collect();
+    B::owned_from_vec(v)
+  }
+
+  let r = reversed(&ROIobuf::from_str("abcd"));
+  unsafe { assert_eq!(r.as_window_slice(), b"dcba"); }
+  let rr = reversed(&r);
+  unsafe { assert_eq!(rr.as_window_slice(), b"abcd"); }
+
+  let w = reversed(&RWIobuf::from_str_copy("xyz"));
+  assert_eq!(w.poke_be(0, b'Z'), Ok(()));
+  unsafe { assert_eq!(w.as_window_slice(), b"Zyx"); }
+
+  assert_eq!(reversed(&ROIobuf::empty()).len(), 0);
+
+  let z = <ROIobuf as IobufAlloc>::alloc(5);
+  assert_eq!(z.cap(), 5);
+  unsafe { assert_eq!(z.as_window_slice(), [ 0; 5 ]); }
+  let mut a = <RWIobuf as IobufAlloc>::alloc(3);
+  assert_eq!(a.fill(b"abc"), Ok(()));
+  assert!(a.is_empty());
+
+  let at = reversed(&ROIobuf::from_str_copy("wxyz").atomic_read_only().ok().unwrap());
+  unsafe { assert_eq!(at.as_window_slice(), b"zyxw"); }
+  let z = <AROIobuf as IobufAlloc>::alloc(2);
+  unsafe { assert_eq!(z.as_window_slice(), [ 0; 2 ]); }
+  let e = <AROIobuf as IobufAlloc>::owned_from_vec(Vec::new());
+  assert!(e.is_empty());
+  assert!(e.is_owned());
+}
+
+#[test]
+fn to_owned_shares_or_copies() {
+  use raw::Ownership;
+
+  // Already owned: no new buffer, just another reference.
+  let mut b = RWIobuf::from_str_copy("hello world");
+  assert_eq!(b.sub(2, 7), Ok(()));
+  assert_eq!(b.sub_window(2, 3), Ok(()));
+  let o = b.to_owned();
+  assert_eq!(o.ptr(), b.ptr());
+  assert_eq!(b.debug_state().refcount, 2);
+  let mut expected = b.debug_state();
+  assert_eq!(o.debug_state(), expected);
+  assert_eq!(o.poke_be(0, b'L'), Ok(()));
+  assert_eq!(b.peek_be::<u8>(0), Ok(b'L'));
+  drop(o);
+  assert_eq!(b.debug_state().refcount, 1);
+
+  let r = ROIobuf::from_str_copy("abc");
+  let ro = r.to_owned();
+  assert_eq!(ro.ptr(), r.ptr());
+
+  // Borrowed: a fresh copy of everything between the limits.
+  let mut s = b"hello world".to_vec();
+  let mut o = {
+    let mut b = RWIobuf::from_slice(&mut s[]);
+    assert_eq!(b.sub(2, 7), Ok(()));
+    assert_eq!(b.sub_window(2, 3), Ok(()));
+    expected = b.debug_state();
+    b.to_owned()
+  };
+  assert!(o.ptr() != s.as_ptr() as *mut u8);
+  let state = o.debug_state();
+  assert_eq!(state.ownership, Ownership::Heap);
+  assert_eq!(state.refcount, 1);
+  assert_eq!(state.hi_max - state.lo_min, expected.hi_max - expected.lo_min);
+  assert_eq!(state.lo - state.lo_min, expected.lo - expected.lo_min);
+  assert_eq!(state.hi - state.lo_min, expected.hi - expected.lo_min);
+  unsafe { assert_eq!(o.as_window_slice(), b"o w"); }
+
+  assert_eq!(o.poke_be(0, b'O'), Ok(()));
+  assert_eq!(s[4], b'o');
+  o.reset();
+  unsafe { assert_eq!(o.as_window_slice(), b"llO wor"); }
+}
@@ -1,9 +1,23 @@
+use std::cmp::Ordering;
+use std::ffi::CString;
 use std::fmt::Debug;
-use std::num::Int;
+use std::hash;
+use std::io::net::ip::{IpAddr, SocketAddr};
 use std::sync::Arc;
 
-use raw::{Allocator, RawIobuf};
-use impls::{AROIobuf, RWIobuf, UniqueIobuf};
+#[cfg(feature = "num-bigint")] use num_bigint::BigUint;
+
+use raw::{Allocator, EnumError, IntArray, IobufState, Prim, RawIobuf, TagError};
+use impls::{AROIobuf, ROIobuf, RWIobuf, UniqueIobuf};
+use words::WordIter;
+use chunks::RChunks;
+use bytes::{Drain, IntoBytes};
+use records::{RecordIter, Records};
+use tlv::TlvIter;
+use transaction::BufTransaction;
+use window::PositionedWindow;
+use reader::IobufReader;
+use pool::IobufPool;
 
 /// Input/Output Buffer
 ///
@@ -46,6 +60,120 @@ pub trait Iobuf: Clone + Debug {
   /// share the buffer with the original Iobuf.
   fn deep_clone_with_allocator(&self, allocator: Arc<Box<Allocator>>) -> RWIobuf<'static>;
 
+  /// Copies the data byte-by-byte in the Iobuf into a writeable Iobuf taken
+  /// from `pool`, instead of a freshly allocated one. The new Iobuf will not
+  /// share storage with the old Iobuf.
+  ///
+  /// When you're done with the new Iobuf, `put` it back into the pool, so the
+  /// next clone can reuse it.
+  ///
+  /// ```rust
+  /// use iobuf::{IobufPool,ROIobuf,Iobuf};
+  ///
+  /// let mut pool = IobufPool::new();
+  /// let b = ROIobuf::from_str("hello");
+  ///
+  /// let c = b.deep_clone_from_pool(&mut pool);
+  /// let p = c.ptr();
+  /// pool.put(c);
+  ///
+  /// let d = b.deep_clone_from_pool(&mut pool);
+  /// assert_eq!(d.ptr(), p);
+  /// unsafe { assert_eq!(d.as_window_slice(), b"hello"); }
+  /// ```
+  fn deep_clone_from_pool(&self, pool: &mut IobufPool) -> RWIobuf<'static>;
+
+  /// Copies the window into a new, read-only Iobuf which owns its storage.
+  /// Only the window is copied, so the new Iobuf's limits are its window.
+  ///
+  /// This is how to keep hold of an Iobuf made with `from_slice` after the
+  /// slice goes away.
+  ///
+  /// ```rust
+  /// use iobuf::{ROIobuf,Iobuf};
+  ///
+  /// let owned: ROIobuf<'static> = {
+  ///   let data = vec!(1u8, 2, 3, 4);
+  ///   let mut b = ROIobuf::from_slice(&data[]);
+  ///   assert_eq!(b.advance(1), Ok(()));
+  ///   b.window_to_owned()
+  /// };
+  ///
+  /// assert_eq!(owned.cap(), 3);
+  /// unsafe { assert_eq!(owned.as_window_slice(), [ 2, 3, 4 ]); }
+  /// ```
+  fn window_to_owned(&self) -> ROIobuf<'static>;
+
+  /// Copies the first `n` bytes of the window into a new Iobuf which owns its
+  /// storage, and advances the window past them.
+  ///
+  /// Unlike `split_start_at`, this copies, so that the prefix can be kept
+  /// after the buffer it came from goes away. The rest of the window is still
+  /// parsed in place.
+  ///
+  /// An error is returned, and nothing is copied or consumed, if the window
+  /// is shorter than `n`. Splitting off nothing gives an `RWIobuf::empty()`,
+  /// which doesn't allocate, and so doesn't own a buffer.
+  ///
+  /// ```rust
+  /// use iobuf::{ROIobuf,RWIobuf,Iobuf};
+  ///
+  /// let header: RWIobuf<'static> = {
+  ///   let data = vec!(0x01, 0x02, 0xAA, 0xBB, 0xCC);
+  ///   let mut b = ROIobuf::from_slice(&data[]);
+  ///   assert_eq!(b.split_to_owned(6).is_err(), true);
+  ///   let header = b.split_to_owned(2).unwrap();
+  ///   assert_eq!(b.consume_be::<u8>(), Ok(0xAA));
+  ///   header
+  /// };
+  ///
+  /// assert_eq!(header.cap(), 2);
+  /// assert_eq!(header.peek_be::<u16>(0), Ok(0x0102));
+  /// ```
+  fn split_to_owned(&mut self, n: u32) -> Result<RWIobuf<'static>, ()>;
+
+  /// Copies the window into a `CString`, to hand to C functions which take a
+  /// NUL-terminated `const char*`.
+  ///
+  /// Returns `Err(())` if the window has a NUL byte in it, since C would see
+  /// the string as ending there.
+  ///
+  /// ```rust
+  /// use iobuf::{ROIobuf,Iobuf};
+  ///
+  /// let s = ROIobuf::from_str("/tmp/sock").to_cstring().unwrap();
+  /// assert_eq!(s.as_bytes(), b"/tmp/sock");
+  ///
+  /// assert!(ROIobuf::from_str("/tmp\0/sock").to_cstring().is_err());
+  /// ```
+  fn to_cstring(&self) -> Result<CString, ()>;
+
+  /// A pointer to the start of the window, as a NUL-terminated C string,
+  /// without copying. This only works if the byte right after the window is a
+  /// NUL, and is still within the limits. Cast the pointer to `*const c_char`
+  /// to pass it to C.
+  ///
+  /// Returns `Err(())` if the byte after the window isn't a NUL, or if there's
+  /// a NUL in the window.
+  ///
+  /// This is unsafe because the pointer is only valid while the buffer is
+  /// alive, and the string is only NUL-terminated while nothing overwrites
+  /// the byte after the window.
+  ///
+  /// ```rust
+  /// use iobuf::{ROIobuf,Iobuf};
+  ///
+  /// let mut b = ROIobuf::from_str("eth0\0");
+  /// unsafe { assert!(b.as_cstr_ptr().is_err()); }
+  ///
+  /// assert_eq!(b.resize(4), Ok(()));
+  /// unsafe {
+  ///   let p = b.as_cstr_ptr().unwrap();
+  ///   assert_eq!(*p.offset(4), 0);
+  /// }
+  /// ```
+  unsafe fn as_cstr_ptr(&self) -> Result<*const u8, ()>;
+
   /// Returns `Ok` if the Iobuf is the last to reference the underlying data,
   /// and converts it to a `UniqueIobuf` for sending to another task. This can
   /// also be used to safely convert from a `ROIobuf` to a `RWIobuf`, and to
@@ -139,6 +267,25 @@ pub trait Iobuf: Clone + Debug {
   /// ```
   fn is_empty(&self) -> bool;
 
+  /// The number of whole primitives of type `T` which fit in the window,
+  /// starting `from` bytes into it. That's 0 if `from` is past the end of the
+  /// window.
+  ///
+  /// This is for sizing a loop, or a `Vec`, before reading an array.
+  ///
+  /// ```rust
+  /// use iobuf::{ROIobuf,Iobuf};
+  ///
+  /// let data = [ 0u8; 10 ];
+  /// let b = ROIobuf::from_slice(&data);
+  ///
+  /// assert_eq!(b.count_prims::<u32>(1), 2);
+  /// assert_eq!(b.count_prims::<u16>(0), 5);
+  /// assert_eq!(b.count_prims::<u64>(3), 0);
+  /// assert_eq!(b.count_prims::<u8>(11), 0);
+  /// ```
+  fn count_prims<T: Prim>(&self, from: u32) -> u32;
+
   /// Reads the data in the window as an immutable slice. Note that `Peek`s
   /// and `Poke`s into the iobuf will change the contents of the slice, even
   /// though it advertises itself as immutable. Therefore, this function is
@@ -185,6 +332,32 @@ pub trait Iobuf: Clone + Debug {
   /// ```
   unsafe fn as_limit_slice<'b>(&'b self) -> &'b [u8];
 
+  /// Reads the data in the window as an immutable slice of primitives,
+  /// without copying. This has all the same caveats as `as_window_slice`.
+  ///
+  /// No byte swapping is done, so the primitives are in the host's byte
+  /// order. Only use this on data that's known to be native-endian.
+  ///
+  /// An error is returned if the start of the window isn't aligned for `T`,
+  /// if the window's length isn't a multiple of `T`'s size, or if `T` takes
+  /// up a different amount of space in memory than in an Iobuf.
+  ///
+  /// ```rust
+  /// use iobuf::{ROIobuf,Iobuf};
+  ///
+  /// let data = [ 1u32, 2, 3 ];
+  /// let p = data.as_ptr() as *const u8;
+  /// let bytes = unsafe { std::slice::from_raw_buf(&p, 12) };
+  ///
+  /// let mut b = ROIobuf::from_slice(bytes);
+  /// unsafe { assert_eq!(b.window_as_slice::<u32>(), Ok(&data[])); }
+  ///
+  /// assert_eq!(b.advance(2), Ok(()));
+  /// unsafe { assert_eq!(b.window_as_slice::<u32>(), Err(())); }
+  /// unsafe { assert_eq!(b.window_as_slice::<u16>().map(|s| s.len()), Ok(5)); }
+  /// ```
+  unsafe fn window_as_slice<'b, T: Prim>(&'b self) -> Result<&'b [T], ()>;
+
   /// Changes the Iobuf's bounds to the subrange specified by `pos` and `len`,
   /// which must lie within the current window.
   ///
@@ -364,6 +537,57 @@ pub trait Iobuf: Clone + Debug {
   /// ```
   fn advance(&mut self, len: u32) -> Result<(), ()>;
 
+  /// Like `advance`, but takes and returns the Iobuf, so that setting up a
+  /// view can be done in one expression. On failure, the Iobuf is dropped.
+  ///
+  /// ```rust
+  /// use iobuf::{ROIobuf,Iobuf};
+  ///
+  /// let b = ROIobuf::from_str("header:payload:trailer")
+  ///           .advanced(7)
+  ///           .and_then(|b| b.subbed(0, 7))
+  ///           .map(|b| b.narrowed())
+  ///           .unwrap();
+  ///
+  /// unsafe { assert_eq!(b.as_window_slice(), b"payload"); }
+  /// assert_eq!(b.cap(), 7);
+  ///
+  /// assert!(b.advanced(8).is_err());
+  /// ```
+  fn advanced(self, len: u32) -> Result<Self, ()>;
+
+  /// Like `resize`, but takes and returns the Iobuf. On failure, the Iobuf is
+  /// dropped.
+  ///
+  /// ```rust
+  /// use iobuf::{ROIobuf,Iobuf};
+  ///
+  /// let b = ROIobuf::from_str("hello").resized(4).unwrap();
+  /// unsafe { assert_eq!(b.as_window_slice(), b"hell"); }
+  /// ```
+  fn resized(self, len: u32) -> Result<Self, ()>;
+
+  /// Like `sub`, but takes and returns the Iobuf. On failure, the Iobuf is
+  /// dropped.
+  ///
+  /// ```rust
+  /// use iobuf::{ROIobuf,Iobuf};
+  ///
+  /// let b = ROIobuf::from_str("hello").subbed(1, 3).unwrap();
+  /// unsafe { assert_eq!(b.as_window_slice(), b"ell"); }
+  /// ```
+  fn subbed(self, pos: u32, len: u32) -> Result<Self, ()>;
+
+  /// Like `narrow`, but takes and returns the Iobuf.
+  ///
+  /// ```rust
+  /// use iobuf::{ROIobuf,Iobuf};
+  ///
+  /// let b = ROIobuf::from_str("hello").advanced(2).unwrap().narrowed();
+  /// assert_eq!(b.cap(), 3);
+  /// ```
+  fn narrowed(self) -> Self;
+
   /// Advances the lower bound of the window by `len`. No bounds checking will
   /// be performed.
   ///
@@ -436,6 +660,20 @@ pub trait Iobuf: Clone + Debug {
   /// ```
   unsafe fn unsafe_advance(&mut self, len: u32);
 
+  /// Advances the lower bound of the window by up to `len`, stopping at the
+  /// upper bound. Returns how far the window was actually advanced.
+  ///
+  /// ```rust
+  /// use iobuf::{ROIobuf,Iobuf};
+  ///
+  /// let mut b = ROIobuf::from_str("hello");
+  /// assert_eq!(b.advance_upto(3), 3);
+  /// assert_eq!(b.advance_upto(3), 2);
+  /// assert!(b.is_empty());
+  /// assert_eq!(b.advance_upto(3), 0);
+  /// ```
+  fn advance_upto(&mut self, len: u32) -> u32;
+
   /// Advances the upper bound of the window by `len`. `Err(())` will be
   /// returned if you advance past the upper limit.
   ///
@@ -455,6 +693,20 @@ pub trait Iobuf: Clone + Debug {
   /// be performed.
   unsafe fn unsafe_extend(&mut self, len: u32);
 
+  /// Advances the upper bound of the window by up to `len`, stopping at the
+  /// upper limit. Returns how far the window was actually extended.
+  ///
+  /// ```rust
+  /// use iobuf::{ROIobuf,Iobuf};
+  ///
+  /// let mut b = ROIobuf::from_str("hello");
+  /// assert_eq!(b.resize(2), Ok(()));
+  /// assert_eq!(b.extend_upto(1), 1);
+  /// assert_eq!(b.extend_upto(5), 2);
+  /// unsafe { assert_eq!(b.as_window_slice(), b"hello"); }
+  /// ```
+  fn extend_upto(&mut self, len: u32) -> u32;
+
   /// Returns `true` if the `other` Iobuf's window is the region directly after
   /// our window. This does not inspect the buffer -- it only compares raw
   /// pointers.
@@ -483,6 +735,24 @@ pub trait Iobuf: Clone + Debug {
   /// ```
   fn is_extended_by<Buf: Iobuf>(&self, other: &Buf) -> bool;
 
+  /// Compares the window with another Iobuf's window, as byte strings, in
+  /// lexicographic order. A window which is a prefix of the other one comes
+  /// first. The other Iobuf can be any kind of Iobuf.
+  ///
+  /// ```rust
+  /// use std::cmp::Ordering;
+  /// use iobuf::{ROIobuf,RWIobuf,Iobuf};
+  ///
+  /// let a = ROIobuf::from_str("apple");
+  /// let b = RWIobuf::from_str_copy("apricot");
+  ///
+  /// assert_eq!(a.cmp_window(&b), Ordering::Less);
+  /// assert_eq!(b.cmp_window(&a), Ordering::Greater);
+  /// assert_eq!(a.cmp_window(&RWIobuf::from_str_copy("apple")), Ordering::Equal);
+  /// assert_eq!(a.cmp_window(&ROIobuf::from_str("app")), Ordering::Greater);
+  /// ```
+  fn cmp_window<Buf: Iobuf>(&self, other: &Buf) -> Ordering;
+
   /// Attempts to extend an Iobuf with the contents of another Iobuf. If this
   /// Iobuf's window is not the region directly before the other Iobuf's window,
   /// no extension will be performed and `Err(())` will be returned. If the
@@ -519,6 +789,12 @@ pub trait Iobuf: Clone + Debug {
 
   /// Sets the length of the window, provided it does not exceed the limits.
   ///
+  /// Only the end of the window moves. The data is never touched: shrinking
+  /// the window hides the bytes at its end, and growing it exposes whatever
+  /// bytes were already in the buffer there. Those may be stale, or, in a
+  /// freshly allocated buffer, uninitialized. Use `RWIobuf::resize_zeroed` if
+  /// they should be zeroed.
+  ///
   /// ```rust
   /// use iobuf::{ROIobuf,Iobuf};
   ///
@@ -529,9 +805,42 @@ pub trait Iobuf: Clone + Debug {
   /// assert_eq!(b.peek_be::<u8>(3), Err(()));
   /// assert_eq!(b.advance(1), Ok(()));
   /// assert_eq!(b.resize(5), Err(()));
+  ///
+  /// // Growing it back exposes the same bytes as before.
+  /// assert_eq!(b.resize(4), Ok(()));
+  /// assert_eq!(unsafe { b.as_window_slice() }, b"ello");
   /// ```
   fn resize(&mut self, len: u32) -> Result<(), ()>;
 
+  /// Sets the length of the window, keeping its contents. This is `resize`,
+  /// spelled out for callers who want to be explicit about the data.
+  ///
+  /// Shrinking keeps the first `len` bytes of the window. Growing, within the
+  /// limits, exposes the bytes just past the end of the window as they are in
+  /// the buffer, which may be stale (or, in a fresh buffer, uninitialized).
+  /// Nothing is ever written. If `len` would go past the limits, `Err(())` is
+  /// returned and the window is left alone.
+  ///
+  /// ```rust
+  /// use iobuf::{RWIobuf,Iobuf};
+  ///
+  /// let mut b = RWIobuf::from_str_copy("hello world");
+  /// assert_eq!(b.advance(6), Ok(()));
+  ///
+  /// // Shrinking keeps the front.
+  /// assert_eq!(b.resize_preserving(3), Ok(()));
+  /// unsafe { assert_eq!(b.as_window_slice(), b"wor"); }
+  ///
+  /// // Stale bytes come back into view.
+  /// assert_eq!(b.poke_be(0, b'W'), Ok(()));
+  /// assert_eq!(b.resize_preserving(5), Ok(()));
+  /// unsafe { assert_eq!(b.as_window_slice(), b"World"); }
+  ///
+  /// assert_eq!(b.resize_preserving(6), Err(()));
+  /// assert_eq!(b.len(), 5);
+  /// ```
+  fn resize_preserving(&mut self, len: u32) -> Result<(), ()>;
+
   /// Sets the length of the window. No bounds checking will be performed.
   ///
   /// ```rust
@@ -549,6 +858,20 @@ pub trait Iobuf: Clone + Debug {
   /// ```
   unsafe fn unsafe_resize(&mut self, len: u32);
 
+  /// Sets the length of the window to `len`, or as close to it as the limits
+  /// allow. Returns the new length of the window.
+  ///
+  /// ```rust
+  /// use iobuf::{ROIobuf,Iobuf};
+  ///
+  /// let mut b = ROIobuf::from_str("hello");
+  /// assert_eq!(b.advance(1), Ok(()));
+  /// assert_eq!(b.resize_upto(2), 2);
+  /// assert_eq!(b.resize_upto(10), 4);
+  /// unsafe { assert_eq!(b.as_window_slice(), b"ello"); }
+  /// ```
+  fn resize_upto(&mut self, len: u32) -> u32;
+
   /// Splits an Iobuf around an index.
   ///
   /// ```rust
@@ -761,6 +1084,101 @@ pub trait Iobuf: Clone + Debug {
   /// ```
   fn peek(&self, pos: u32, dst: &mut [u8]) -> Result<(), ()>;
 
+  /// Like `peek`, but copies into `len` bytes of possibly uninitialized
+  /// memory at `dst`, instead of a slice. Nothing is read from `dst`, so it
+  /// never has to be zeroed first.
+  ///
+  /// Only the window is bounds checked. `dst` must be valid for `len` bytes of
+  /// writes, and mustn't overlap the window.
+  ///
+  /// ```rust
+  /// use std::mem;
+  /// use iobuf::{ROIobuf,Iobuf};
+  ///
+  /// let b = ROIobuf::from_str("hello");
+  ///
+  /// unsafe {
+  ///   let mut dst: [u8; 4] = mem::uninitialized();
+  ///   assert_eq!(b.peek_into_uninit(1, dst.as_mut_ptr(), 4), Ok(()));
+  ///   assert_eq!(&dst[], b"ello");
+  ///   assert_eq!(b.peek_into_uninit(2, dst.as_mut_ptr(), 4), Err(()));
+  /// }
+  /// ```
+  unsafe fn peek_into_uninit(&self, pos: u32, dst: *mut u8, len: u32) -> Result<(), ()>;
+
+  /// Copies the bytes starting `pos` bytes into the window into several
+  /// slices, one after another, filling each in turn. There's only one
+  /// bounds check, for all of them together. If the window is too short,
+  /// nothing is copied, and `Err(())` is returned.
+  ///
+  /// ```rust
+  /// use iobuf::{ROIobuf,Iobuf};
+  ///
+  /// let b = ROIobuf::from_str("..abcde");
+  /// let mut x = [0u8; 2];
+  /// let mut y = [0u8; 3];
+  ///
+  /// assert_eq!(b.peek_vectored(2, &mut [ &mut x, &mut y ]), Ok(()));
+  /// assert_eq!(&x[], b"ab");
+  /// assert_eq!(&y[], b"cde");
+  ///
+  /// assert_eq!(b.peek_vectored(3, &mut [ &mut x, &mut y ]), Err(()));
+  /// ```
+  fn peek_vectored(&self, pos: u32, dsts: &mut [&mut [u8]]) -> Result<(), ()>;
+
+  /// Copies bytes from the front of the window into several slices, one
+  /// after another, like the fields of a record, and advances the window past
+  /// all of them. There's only one bounds check, for all of them together. If
+  /// the window is too short, nothing is copied, the window isn't moved, and
+  /// `Err(())` is returned.
+  ///
+  /// ```rust
+  /// use iobuf::{ROIobuf,Iobuf};
+  ///
+  /// let mut b = ROIobuf::from_slice_copy(&[ 1, 2, 3, 4, 5, 6, 7, 8, 9 ]);
+  /// let mut mac  = [0u8; 6];
+  /// let mut kind = [0u8; 2];
+  ///
+  /// assert_eq!(b.consume_vectored(&mut [ &mut mac, &mut kind ]), Ok(()));
+  /// assert_eq!(mac, [ 1, 2, 3, 4, 5, 6 ]);
+  /// assert_eq!(kind, [ 7, 8 ]);
+  /// assert_eq!(b.len(), 1);
+  /// ```
+  fn consume_vectored(&mut self, dsts: &mut [&mut [u8]]) -> Result<(), ()>;
+
+  /// Replaces the contents of `dst` with a copy of the window. `dst`'s
+  /// existing capacity is reused, so calling this over and over with the same
+  /// `Vec` stops allocating once it's big enough.
+  ///
+  /// ```rust
+  /// use iobuf::{ROIobuf,Iobuf};
+  ///
+  /// let mut v = vec!(9u8, 9, 9, 9, 9, 9);
+  ///
+  /// ROIobuf::from_str("abc").peek_all_into(&mut v);
+  /// assert_eq!(&v[], b"abc");
+  ///
+  /// ROIobuf::empty().peek_all_into(&mut v);
+  /// assert!(v.is_empty());
+  /// ```
+  fn peek_all_into(&self, dst: &mut Vec<u8>);
+
+  /// Like `peek_all_into`, but also advances the window to the end, leaving
+  /// it empty.
+  ///
+  /// ```rust
+  /// use iobuf::{ROIobuf,Iobuf};
+  ///
+  /// let mut v = Vec::new();
+  /// let mut b = ROIobuf::from_str("hello");
+  /// assert_eq!(b.advance(1), Ok(()));
+  ///
+  /// b.consume_all_into(&mut v);
+  /// assert_eq!(&v[], b"ello");
+  /// assert!(b.is_empty());
+  /// ```
+  fn consume_all_into(&mut self, dst: &mut Vec<u8>);
+
   /// Reads a big-endian primitive at a given offset from the beginning of the
   /// window.
   ///
@@ -778,7 +1196,7 @@ pub trait Iobuf: Clone + Debug {
   /// assert_eq!(b.peek_be(1), Ok(0x0304u16));
   /// assert_eq!(b.peek_be::<u16>(2), Err(()));
   /// ```
-  fn peek_be<T: Int>(&self, pos: u32) -> Result<T, ()>;
+  fn peek_be<T: Prim>(&self, pos: u32) -> Result<T, ()>;
 
   /// Reads a little-endian primitive at a given offset from the beginning of
   /// the window.
@@ -797,7 +1215,7 @@ pub trait Iobuf: Clone + Debug {
   /// assert_eq!(b.peek_le(1), Ok(0x0403u16));
   /// assert_eq!(b.peek_le::<u16>(2), Err(()));
   /// ```
-  fn peek_le<T: Int>(&self, pos: u32) -> Result<T, ()>;
+  fn peek_le<T: Prim>(&self, pos: u32) -> Result<T, ()>;
 
   /// Reads bytes, starting from the front of the window, into the supplied
   /// buffer. Either the entire buffer is filled, or an error is returned
@@ -842,7 +1260,7 @@ pub trait Iobuf: Clone + Debug {
   /// assert_eq!(b.consume_be::<u16>(), Err(()));
   /// assert_eq!(b.consume_be(), Ok(0x04u8));
   /// ```
-  fn consume_be<T: Int>(&mut self) -> Result<T, ()>;
+  fn consume_be<T: Prim>(&mut self) -> Result<T, ()>;
 
   /// Reads a little-endian primitive from the beginning of the window.
   ///
@@ -863,105 +1281,1397 @@ pub trait Iobuf: Clone + Debug {
   /// assert_eq!(b.consume_le::<u16>(), Err(()));
   /// assert_eq!(b.consume_le(), Ok(0x04u8));
   /// ```
-  fn consume_le<T: Int>(&mut self) -> Result<T, ()>;
+  fn consume_le<T: Prim>(&mut self) -> Result<T, ()>;
 
-  /// Returns an `Err(())` if the `len` bytes, starting at `pos`, are not all
-  /// in the window. To be used with the `try!` macro.
-  ///
-  /// Make sure you use this in conjunction with the `unsafe` combinators. It
-  /// is recommended you minimize your bounds checks by doing it once with
-  /// `check_range`, followed by unsafe accesses. If you do unsafe accesses
-  /// without a `check_range`, there will likely be reliability and security
-  /// issues with your application.
-  ///
-  /// Below is a correct usage of `check_range` to minimize bounds checks:
+  /// Reads a `len`-byte big-endian unsigned integer at `pos` bytes into the
+  /// window. Leading zero bytes are fine, and are ignored. Only available
+  /// with the `num-bigint` feature.
   ///
   /// ```rust
-  /// use std::result::Result::{self,Ok};
-  /// use iobuf::{ROIobuf,Iobuf};
+  /// extern crate iobuf;
+  /// extern crate "num-bigint" as num_bigint;
   ///
-  /// // [ number of byte buffers, size of first byte buffer, ...bytes, etc. ]
-  /// let data = [ 0x02, 0x02, 0x55, 0x66, 0x03, 0x11, 0x22, 0x33 ];
-  /// let mut b = ROIobuf::from_slice(&data);
+  /// use std::num::FromPrimitive;
+  /// use iobuf::{ROIobuf,Iobuf};
   ///
-  /// fn parse<B: Iobuf>(b: &mut B) -> Result<usize, ()> {
-  ///   let mut sum = 0us;
+  /// fn main() {
+  ///   let data = [ 0x00, 0x01, 0x00, 0x00 ];
+  ///   let b = ROIobuf::from_slice(&data);
   ///
-  ///   let num_buffers: u8 = try!(b.consume_be());
+  ///   assert_eq!(b.peek_biguint_be(0, 4), Ok(FromPrimitive::from_u64(0x10000).unwrap()));
+  ///   assert!(b.peek_biguint_be(1, 4).is_err());
+  /// }
+  /// ```
+  #[cfg(feature = "num-bigint")]
+  fn peek_biguint_be(&self, pos: u32, len: u32) -> Result<BigUint, ()>;
+
+  /// Reads a `len`-byte big-endian unsigned integer from the beginning of the
+  /// window, and advances past it. Only available with the `num-bigint`
+  /// feature.
   ///
-  ///   for _ in (0 .. num_buffers) {
-  ///     let len: u8 = try!(b.consume_be());
+  /// ```rust
+  /// extern crate iobuf;
+  /// extern crate "num-bigint" as num_bigint;
   ///
-  ///     unsafe {
-  ///       try!(b.check_range(0, len as u32));
+  /// use std::num::FromPrimitive;
+  /// use num_bigint::BigUint;
+  /// use iobuf::{ROIobuf,Iobuf};
   ///
-  ///       for _ in (0 .. len) {
-  ///         sum += b.unsafe_consume_be::<u8>() as usize;
-  ///       }
-  ///     }
-  ///   }
+  /// fn main() {
+  ///   // A 2-byte length, then an integer of that length.
+  ///   let data = [ 0x00, 0x03, 0x01, 0x00, 0x01, 0xFF ];
+  ///   let mut b = ROIobuf::from_slice(&data);
   ///
-  ///   Ok(sum)
+  ///   let len = b.consume_be::<u16>().unwrap() as u32;
+  ///   let n: BigUint = b.consume_biguint_be(len).unwrap();
+  ///   assert_eq!(n, FromPrimitive::from_u64(0x010001).unwrap());
+  ///   assert_eq!(b.len(), 1);
   /// }
-  ///
-  /// assert_eq!(parse(&mut b), Ok(0x55 + 0x66 + 0x11 + 0x22 + 0x33));
   /// ```
-  fn check_range(&self, pos: u32, len: u32) -> Result<(), ()>;
+  #[cfg(feature = "num-bigint")]
+  fn consume_biguint_be(&mut self, len: u32) -> Result<BigUint, ()>;
 
-  /// The same as `check_range`, but with a `usize` length. If you're checking
-  /// the range of something which might overflow an `i32`, use this version
-  /// instead of `check_range`.
+  /// Reads an optional big-endian primitive from the beginning of the window,
+  /// for trailing fields which may be left off.
+  ///
+  /// If the whole primitive is in the window, it's returned, and the window
+  /// is advanced past it. Otherwise, `None` is returned, and the window isn't
+  /// moved. That's the case both when the window is empty, and when there are
+  /// a few bytes left, but not enough for the whole primitive. A truncated
+  /// field counts as missing.
   ///
   /// ```rust
   /// use iobuf::{ROIobuf,Iobuf};
   ///
-  /// let mut b = ROIobuf::from_str("hello");
+  /// let data = [ 0x01, 0x02, 0x03 ];
+  /// let mut b = ROIobuf::from_slice(&data);
   ///
-  /// assert_eq!(b.check_range_usize(1u32, 5u), Err(()));
+  /// assert_eq!(b.consume_be_opt(), Some(0x0102u16));
+  /// assert_eq!(b.consume_be_opt::<u16>(), None);
+  /// assert_eq!(b.len(), 1);
+  /// assert_eq!(b.consume_be_opt(), Some(0x03u8));
+  /// assert_eq!(b.consume_be_opt::<u8>(), None);
   /// ```
-  fn check_range_usize(&self, pos: u32, len: usize) -> Result<(), ()>;
+  fn consume_be_opt<T: Prim>(&mut self) -> Option<T>;
 
-  /// The same as `check_range`, but fails if the bounds check returns `Err(())`.
+  /// Reads an optional little-endian primitive from the beginning of the
+  /// window. See `consume_be_opt`.
   ///
-  /// ```should_fail
+  /// ```rust
   /// use iobuf::{ROIobuf,Iobuf};
   ///
-  /// let mut b = ROIobuf::from_str("hello");
+  /// let data = [ 0x01, 0x02, 0x03 ];
+  /// let mut b = ROIobuf::from_slice(&data);
   ///
-  /// b.check_range_fail(1, 5); // boom.
+  /// assert_eq!(b.consume_le_opt(), Some(0x0201u16));
+  /// assert_eq!(b.consume_le_opt::<u16>(), None);
+  /// assert_eq!(b.len(), 1);
   /// ```
-  fn check_range_fail(&self, pos: u32, len: u32);
+  fn consume_le_opt<T: Prim>(&mut self) -> Option<T>;
 
-  /// The same as `check_range_usize`, but fails if the bounds check returns
-  /// `Err(())`.
+  /// Reads the run of ASCII hexadecimal digits at the front of the window, in
+  /// either case, as a number, and advances past them. The run ends at the
+  /// first byte which isn't a digit, or at the end of the window.
   ///
-  /// ```should_fail
+  /// Returns `Err(())`, without moving the window, if the window doesn't start
+  /// with a digit, or if the number doesn't fit in a `u64`.
+  ///
+  /// If the rest of the number might still be on its way, make sure whatever
+  /// follows it is in the window first. Otherwise, a number which was cut off
+  /// will look complete.
+  ///
+  /// ```rust
   /// use iobuf::{ROIobuf,Iobuf};
   ///
-  /// let mut b = ROIobuf::from_str("hello");
+  /// // An HTTP chunk size line.
+  /// let mut b = ROIobuf::from_str("1a3f\r\n");
   ///
-  /// b.check_range_usize_fail(1u32, 5u); // boom.
+  /// assert_eq!(b.consume_hex_u64(), Ok(0x1a3f));
+  /// unsafe { assert_eq!(b.as_window_slice(), b"\r\n"); }
+  ///
+  /// assert_eq!(b.consume_hex_u64(), Err(()));
+  /// assert_eq!(ROIobuf::from_str("10000000000000000").consume_hex_u64(), Err(()));
   /// ```
-  fn check_range_usize_fail(&self, pos: u32, len: usize);
+  fn consume_hex_u64(&mut self) -> Result<u64, ()>;
 
-  /// Reads the bytes at a given offset from the beginning of the window, into
-  /// the supplied buffer. It is undefined behavior to read outside the iobuf
-  /// window.
+  /// Reads the run of ASCII decimal digits at the front of the window as a
+  /// number, and advances past them. See `consume_hex_u64`.
   ///
   /// ```rust
   /// use iobuf::{ROIobuf,Iobuf};
   ///
-  /// let data = [1,2,3,4,5,6];
-  /// let mut b = ROIobuf::from_slice(&data);
+  /// let mut b = ROIobuf::from_str("Content-Length: 1024\r\n");
   ///
-  /// let mut dst = [0; 4];
+  /// assert_eq!(b.advance(16), Ok(()));
+  /// assert_eq!(b.consume_decimal_u64(), Ok(1024));
+  /// assert_eq!(b.len(), 2);
   ///
-  /// unsafe {
-  ///   // one range check, instead of two!
-  ///   assert_eq!(b.check_range(0, 5), Ok(()));
+  /// assert_eq!(ROIobuf::from_str("18446744073709551615").consume_decimal_u64(), Ok(18446744073709551615));
+  /// assert_eq!(ROIobuf::from_str("18446744073709551616").consume_decimal_u64(), Err(()));
+  /// ```
+  fn consume_decimal_u64(&mut self) -> Result<u64, ()>;
+
+  /// Reads a `width`-byte big-endian signed integer from the front of the
+  /// window, sign-extends it to an `i64`, and advances past it.
   ///
-  ///   assert_eq!(b.unsafe_peek_be::<u8>(0), 1u8);
-  ///   b.unsafe_peek(1, &mut dst);
+  /// The integer is taken to be in two's complement, so the top bit of its
+  /// most significant byte (the first byte read) is the sign bit. If it's
+  /// set, the result is negative: every bit of the `i64` above the ones read
+  /// is set. Otherwise, they're all clear, and the result is the same as
+  /// reading the bytes as an unsigned number.
+  ///
+  /// An error is returned, and nothing is consumed, if the window is shorter
+  /// than `width`.
+  ///
+  /// Panics if `width` isn't between 1 and 8.
+  ///
+  /// ```rust
+  /// use iobuf::{ROIobuf,Iobuf};
+  ///
+  /// // Two 24-bit audio samples: -3, then 3.
+  /// let data = [ 0xFF, 0xFF, 0xFD, 0x00, 0x00, 0x03 ];
+  /// let mut b = ROIobuf::from_slice(&data);
+  ///
+  /// assert_eq!(b.consume_int_be(3), Ok(-3));
+  /// assert_eq!(b.consume_int_be(3), Ok(3));
+  /// assert_eq!(b.consume_int_be(3), Err(()));
+  /// ```
+  fn consume_int_be(&mut self, width: u8) -> Result<i64, ()>;
+
+  /// Reads a `width`-byte little-endian signed integer from the front of the
+  /// window, sign-extends it to an `i64`, and advances past it. The sign bit
+  /// is the top bit of the last byte read. See `consume_int_be`.
+  ///
+  /// ```rust
+  /// use iobuf::{ROIobuf,Iobuf};
+  ///
+  /// let data = [ 0xFD, 0xFF, 0xFF, 0x03, 0x00, 0x00 ];
+  /// let mut b = ROIobuf::from_slice(&data);
+  ///
+  /// assert_eq!(b.consume_int_le(3), Ok(-3));
+  /// assert_eq!(b.consume_int_le(3), Ok(3));
+  /// ```
+  fn consume_int_le(&mut self, width: u8) -> Result<i64, ()>;
+
+  /// Reads a big-endian signed fixed-point number `pos` bytes into the window,
+  /// and returns its value.
+  ///
+  /// The number has `int_bits` integer bits, which include the sign bit, and
+  /// `frac_bits` fractional bits, so Q16.16 is 32 bits wide. It's stored as a
+  /// two's complement integer which is `2^frac_bits` times the value.
+  ///
+  /// Panics if the number isn't a whole number of bytes, up to 8.
+  ///
+  /// ```rust
+  /// use iobuf::{ROIobuf,Iobuf};
+  ///
+  /// // Q8.8
+  /// let data = [ 0xFE, 0x80 ];
+  /// let b = ROIobuf::from_slice(&data);
+  ///
+  /// assert_eq!(b.peek_fixed_be(0, 8, 8), Ok(-1.5));
+  /// assert_eq!(b.peek_fixed_be(1, 8, 8), Err(()));
+  /// ```
+  fn peek_fixed_be(&self, pos: u32, int_bits: u8, frac_bits: u8) -> Result<f64, ()>;
+
+  /// Reads a big-endian signed fixed-point number from the front of the
+  /// window, and advances past it. See `peek_fixed_be`.
+  ///
+  /// ```rust
+  /// use iobuf::{ROIobuf,Iobuf};
+  ///
+  /// // Q4.12
+  /// let data = [ 0x18, 0x00, 0xFF ];
+  /// let mut b = ROIobuf::from_slice(&data);
+  ///
+  /// assert_eq!(b.consume_fixed_be(4, 12), Ok(1.5));
+  /// assert_eq!(b.consume_fixed_be(4, 12), Err(()));
+  /// assert_eq!(b.len(), 1);
+  /// ```
+  fn consume_fixed_be(&mut self, int_bits: u8, frac_bits: u8) -> Result<f64, ()>;
+
+  /// Reads a big-endian Q16.16 fixed-point number from the front of the
+  /// window, and advances past it. This is `consume_fixed_be(16, 16)`.
+  ///
+  /// ```rust
+  /// use iobuf::{ROIobuf,Iobuf};
+  ///
+  /// let data = [ 0x00, 0x03, 0x24, 0x3F ];
+  /// let mut b = ROIobuf::from_slice(&data);
+  ///
+  /// let pi = b.consume_q16_16_be().unwrap();
+  /// assert!((pi - 3.14159).abs() < 1.0 / 65536.0);
+  /// ```
+  fn consume_q16_16_be(&mut self) -> Result<f64, ()>;
+
+  /// Reads a 4-byte IPv4 address `pos` bytes into the window.
+  ///
+  /// ```rust
+  /// use std::io::net::ip::Ipv4Addr;
+  /// use iobuf::{ROIobuf,Iobuf};
+  ///
+  /// let data = [ 0x05, 127, 0, 0, 1 ];
+  /// let b = ROIobuf::from_slice(&data);
+  ///
+  /// assert_eq!(b.peek_ipv4(1), Ok(Ipv4Addr(127, 0, 0, 1)));
+  /// assert_eq!(b.peek_ipv4(2), Err(()));
+  /// ```
+  fn peek_ipv4(&self, pos: u32) -> Result<IpAddr, ()>;
+
+  /// Reads a 16-byte IPv6 address `pos` bytes into the window, in network
+  /// byte order.
+  ///
+  /// ```rust
+  /// use iobuf::{ROIobuf,Iobuf};
+  ///
+  /// let data = [ 0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x01 ];
+  /// let b = ROIobuf::from_slice(&data);
+  ///
+  /// assert_eq!(b.peek_ipv6(0), Ok("2001:db8::1".parse().unwrap()));
+  /// assert_eq!(b.peek_ipv6(1), Err(()));
+  /// ```
+  fn peek_ipv6(&self, pos: u32) -> Result<IpAddr, ()>;
+
+  /// Reads a 4-byte IPv4 address followed by a big-endian u16 port, `pos`
+  /// bytes into the window.
+  ///
+  /// ```rust
+  /// use iobuf::{ROIobuf,Iobuf};
+  ///
+  /// let data = [ 10, 0, 0, 1, 0x04, 0x38 ];
+  /// let b = ROIobuf::from_slice(&data);
+  ///
+  /// assert_eq!(b.peek_socket_addr_v4(0), Ok("10.0.0.1:1080".parse().unwrap()));
+  /// ```
+  fn peek_socket_addr_v4(&self, pos: u32) -> Result<SocketAddr, ()>;
+
+  /// Reads a 4-byte IPv4 address from the front of the window, and advances
+  /// past it. If there isn't a whole one, nothing is consumed.
+  ///
+  /// ```rust
+  /// use std::io::net::ip::Ipv4Addr;
+  /// use iobuf::{ROIobuf,Iobuf};
+  ///
+  /// let data = [ 192, 168, 0, 1, 8, 8, 8 ];
+  /// let mut b = ROIobuf::from_slice(&data);
+  ///
+  /// assert_eq!(b.consume_ipv4(), Ok(Ipv4Addr(192, 168, 0, 1)));
+  /// assert_eq!(b.consume_ipv4(), Err(()));
+  /// assert_eq!(b.len(), 3);
+  /// ```
+  fn consume_ipv4(&mut self) -> Result<IpAddr, ()>;
+
+  /// Reads a 16-byte IPv6 address from the front of the window, in network
+  /// byte order, and advances past it. If there isn't a whole one, nothing is
+  /// consumed.
+  ///
+  /// ```rust
+  /// use iobuf::{ROIobuf,Iobuf};
+  ///
+  /// let mut data = [ 0u8; 17 ];
+  /// data[15] = 1;
+  /// let mut b = ROIobuf::from_slice(&data);
+  ///
+  /// assert_eq!(b.consume_ipv6(), Ok("::1".parse().unwrap()));
+  /// assert_eq!(b.consume_ipv6(), Err(()));
+  /// assert_eq!(b.len(), 1);
+  /// ```
+  fn consume_ipv6(&mut self) -> Result<IpAddr, ()>;
+
+  /// Reads a 4-byte IPv4 address followed by a big-endian u16 port from the
+  /// front of the window, and advances past them. If there isn't a whole one,
+  /// nothing is consumed.
+  ///
+  /// ```rust
+  /// use iobuf::{ROIobuf,Iobuf};
+  ///
+  /// let data = [ 10, 0, 0, 1, 0x04, 0x38, 10, 0, 0, 2, 0x04 ];
+  /// let mut b = ROIobuf::from_slice(&data);
+  ///
+  /// assert_eq!(b.consume_socket_addr_v4(), Ok("10.0.0.1:1080".parse().unwrap()));
+  /// assert_eq!(b.consume_socket_addr_v4(), Err(()));
+  /// assert_eq!(b.len(), 5);
+  /// ```
+  fn consume_socket_addr_v4(&mut self) -> Result<SocketAddr, ()>;
+
+  /// Reads a big-endian primitive from the beginning of the window, and maps
+  /// it through `validate`, for fields which may only take certain values.
+  /// If `validate` accepts it, the window is advanced past the primitive.
+  ///
+  /// If `validate` returns `None`, the window isn't moved, and the raw value
+  /// is returned in `EnumError::Invalid`. If the window is too short,
+  /// `EnumError::Incomplete` is returned. See `iobuf_enum!` for a way to
+  /// generate `validate` from an enum.
+  ///
+  /// ```rust
+  /// use iobuf::{EnumError, Iobuf, ROIobuf};
+  ///
+  /// let data = [ 0x00, 0x06, 0x00, 0x01 ];
+  /// let mut b = ROIobuf::from_slice(&data);
+  ///
+  /// fn proto(x: u16) -> Option<&'static str> {
+  ///   match x { 6 => Some("tcp"), 17 => Some("udp"), _ => None }
+  /// }
+  ///
+  /// assert_eq!(b.consume_be_enum(proto), Ok("tcp"));
+  /// assert_eq!(b.consume_be_enum(proto), Err(EnumError::Invalid(1)));
+  /// assert_eq!(b.len(), 2);
+  /// ```
+  fn consume_be_enum<T: Prim, E, F: FnOnce(T) -> Option<E>>(&mut self, validate: F) -> Result<E, EnumError<T>>;
+
+  /// Reads a little-endian primitive from the beginning of the window, and
+  /// maps it through `validate`. See `consume_be_enum`.
+  fn consume_le_enum<T: Prim, E, F: FnOnce(T) -> Option<E>>(&mut self, validate: F) -> Result<E, EnumError<T>>;
+
+  /// Reads a primitive `pos` bytes into the window, big-endian if
+  /// `big_endian` is set, and little-endian otherwise. This is handy when the
+  /// byte order is only known at runtime, from a file header, say.
+  ///
+  /// An error is returned if bytes outside of the window were requested.
+  ///
+  /// ```rust
+  /// use iobuf::{ROIobuf,Iobuf};
+  ///
+  /// let data = [ 0x01, 0x02 ];
+  /// let b = ROIobuf::from_slice(&data);
+  ///
+  /// assert_eq!(b.peek_prim(0, true),  Ok(0x0102u16));
+  /// assert_eq!(b.peek_prim(0, false), Ok(0x0201u16));
+  /// assert_eq!(b.peek_prim::<u16>(1, true), Err(()));
+  /// ```
+  fn peek_prim<T: Prim>(&self, pos: u32, big_endian: bool) -> Result<T, ()>;
+
+  /// Reads a primitive from the beginning of the window, big-endian if
+  /// `big_endian` is set, and little-endian otherwise.
+  ///
+  /// After the primitive has been read, the window will be moved such that it
+  /// is no longer included.
+  ///
+  /// An error is returned if bytes outside of the window were requested.
+  ///
+  /// ```rust
+  /// use iobuf::{ROIobuf,Iobuf};
+  ///
+  /// let data = [ 0x01, 0x02, 0x01, 0x02 ];
+  /// let mut b = ROIobuf::from_slice(&data);
+  ///
+  /// let mut big_endian = true;
+  /// assert_eq!(b.consume_prim(big_endian), Ok(0x0102u16));
+  /// big_endian = false;
+  /// assert_eq!(b.consume_prim(big_endian), Ok(0x0201u16));
+  /// assert_eq!(b.consume_prim::<u8>(big_endian), Err(()));
+  /// ```
+  fn consume_prim<T: Prim>(&mut self, big_endian: bool) -> Result<T, ()>;
+
+  /// Reads a fixed-size array of big-endian primitives from the beginning of
+  /// the window, with a single bounds check.
+  ///
+  /// After the array has been read, the window will be moved such that it is
+  /// no longer included.
+  ///
+  /// An error is returned if bytes outside of the window were requested.
+  ///
+  /// There are no 128-bit primitives, so this is also the way to read 128-bit
+  /// fields (like UUIDs): as a `[u64; 2]` holding the high half, then the low
+  /// half.
+  ///
+  /// ```rust
+  /// use iobuf::{ROIobuf,Iobuf};
+  ///
+  /// let data = [ 0xFF, 0x80, 0x00, 0x01, 0x00, 0x02, 0x00, 0x03 ];
+  /// let mut b = ROIobuf::from_slice(&data);
+  ///
+  /// let rgba: [u8; 4] = b.consume_be_array().unwrap();
+  /// assert_eq!(rgba, [ 0xFF, 0x80, 0x00, 0x01 ]);
+  ///
+  /// assert_eq!(b.consume_be_array::<[u16; 3]>(), Err(()));
+  /// assert_eq!(b.consume_be_array(), Ok([ 0x0002u16, 0x0003 ]));
+  /// ```
+  fn consume_be_array<A: IntArray>(&mut self) -> Result<A, ()>;
+
+  /// Reads a fixed-size array of little-endian primitives from the beginning
+  /// of the window, with a single bounds check.
+  ///
+  /// After the array has been read, the window will be moved such that it is
+  /// no longer included.
+  ///
+  /// An error is returned if bytes outside of the window were requested.
+  ///
+  /// ```rust
+  /// use iobuf::{ROIobuf,Iobuf};
+  ///
+  /// let data = [ 0x01, 0x00, 0x02, 0x00 ];
+  /// let mut b = ROIobuf::from_slice(&data);
+  ///
+  /// assert_eq!(b.consume_le_array(), Ok([ 1u16, 2 ]));
+  /// assert!(b.is_empty());
+  /// ```
+  fn consume_le_array<A: IntArray>(&mut self) -> Result<A, ()>;
+
+  /// Returns `true` if the window holds the same bytes as `other`, ignoring
+  /// ASCII case. Bytes outside of the ASCII range must match exactly.
+  ///
+  /// ```rust
+  /// use iobuf::{ROIobuf,Iobuf};
+  ///
+  /// let b = ROIobuf::from_str("Content-Length");
+  /// assert!(b.eq_ignore_ascii_case(b"content-length"));
+  /// assert!(!b.eq_ignore_ascii_case(b"content-type"));
+  /// ```
+  fn eq_ignore_ascii_case(&self, other: &[u8]) -> bool;
+
+  /// Returns `true` if the window starts with `prefix`, ignoring ASCII case.
+  /// Bytes outside of the ASCII range must match exactly.
+  ///
+  /// ```rust
+  /// use iobuf::{ROIobuf,Iobuf};
+  ///
+  /// let b = ROIobuf::from_str("GET / HTTP/1.1");
+  /// assert!(b.starts_with_ignore_ascii_case(b"get "));
+  /// assert!(!b.starts_with_ignore_ascii_case(b"post "));
+  /// ```
+  fn starts_with_ignore_ascii_case(&self, prefix: &[u8]) -> bool;
+
+  /// Advances the window past `tag`, if the window starts with it.
+  ///
+  /// Otherwise, the window is left untouched. If some byte in the window
+  /// differs from the tag, `Mismatch` reports where. If the window is just a
+  /// matching prefix of the tag, the error is `Incomplete`, since the rest of
+  /// the tag may still be on its way.
+  ///
+  /// ```rust
+  /// use iobuf::{ROIobuf,Iobuf,TagError};
+  ///
+  /// let mut b = ROIobuf::from_str("\r\nabc\r");
+  ///
+  /// assert_eq!(b.consume_tag(b"\r\n"), Ok(()));
+  /// assert_eq!(b.consume_tag(b"\r\n"), Err(TagError::Mismatch(0)));
+  /// assert_eq!(b.consume_tag(b"abd"),  Err(TagError::Mismatch(2)));
+  /// assert_eq!(b.consume_tag(b"abc"),  Ok(()));
+  /// assert_eq!(b.consume_tag(b"\r\n"), Err(TagError::Incomplete));
+  /// assert_eq!(b.len(), 1);
+  /// ```
+  fn consume_tag(&mut self, tag: &[u8]) -> Result<(), TagError>;
+
+  /// Like `consume_tag`, but ignores ASCII case. Bytes outside of the ASCII
+  /// range must match exactly.
+  ///
+  /// ```rust
+  /// use iobuf::{ROIobuf,Iobuf,TagError};
+  ///
+  /// let mut b = ROIobuf::from_str("Host: example.com");
+  ///
+  /// assert_eq!(b.consume_tag_ignore_ascii_case(b"host:"), Ok(()));
+  /// assert_eq!(b.consume_tag_ignore_ascii_case(b"\t"), Err(TagError::Mismatch(0)));
+  /// assert_eq!(b.len(), 12);
+  /// ```
+  fn consume_tag_ignore_ascii_case(&mut self, tag: &[u8]) -> Result<(), TagError>;
+
+  /// Returns `true` if all of `s` lies inside this Iobuf's buffer. This is
+  /// useful for deciding whether a slice handed back by some other API aliases
+  /// the buffer, or needs to be copied into it.
+  ///
+  /// Only the addresses are compared. It doesn't matter whether the slice is
+  /// inside the window, or came from this Iobuf at all. For buffers allocated
+  /// by Iobuf, the whole buffer is checked, regardless of the limits. For
+  /// buffers which wrap someone else's memory, only the limits are known, so
+  /// only the limits are checked.
+  ///
+  /// ```rust
+  /// use iobuf::{RWIobuf,Iobuf};
+  ///
+  /// let b = RWIobuf::from_str_copy("hello, world");
+  /// let s = unsafe { b.as_window_slice() };
+  ///
+  /// assert!(b.owns_slice(&s[7..]));
+  /// assert!(!b.owns_slice(b"world"));
+  /// ```
+  fn owns_slice(&self, s: &[u8]) -> bool;
+
+  /// Iterates over the window in `size`-byte chunks, starting from the back.
+  /// The first chunk is the tail of the window, and the last chunk (the one
+  /// at the front of the window) may be shorter than `size`. The chunks are
+  /// Iobufs that share this Iobuf's buffer and limits.
+  ///
+  /// Panics if `size` is 0.
+  ///
+  /// ```rust
+  /// use iobuf::{ROIobuf,Iobuf};
+  ///
+  /// let b = ROIobuf::from_str("hello, world");
+  /// let mut it = b.rchunks(5);
+  ///
+  /// unsafe {
+  ///   assert_eq!(it.next().unwrap().as_window_slice(), b"world");
+  ///   assert_eq!(it.next().unwrap().as_window_slice(), b"llo, ");
+  ///   assert_eq!(it.next().unwrap().as_window_slice(), b"he");
+  /// }
+  /// assert!(it.next().is_none());
+  /// ```
+  fn rchunks<'b>(&'b self, size: u32) -> RChunks<'b, Self>;
+
+  /// Splits the window into `parts` Iobufs which tile it, in order. The
+  /// pieces' lengths differ by at most one byte, with the longer ones first.
+  /// Each piece's limits are narrowed to its window, so handing the pieces to
+  /// different workers can't make them step on each other.
+  ///
+  /// If `parts` is more than `len()`, the pieces at the end are empty.
+  ///
+  /// Panics if `parts` is 0.
+  ///
+  /// ```rust
+  /// use iobuf::{ROIobuf,Iobuf};
+  ///
+  /// let b = ROIobuf::from_str("abcdefgh");
+  /// let parts = b.split_into(3);
+  ///
+  /// assert_eq!(parts.len(), 3);
+  /// unsafe {
+  ///   assert_eq!(parts[0].as_window_slice(), b"abc");
+  ///   assert_eq!(parts[1].as_window_slice(), b"def");
+  ///   assert_eq!(parts[2].as_window_slice(), b"gh");
+  /// }
+  /// ```
+  fn split_into(&self, parts: u32) -> Vec<Self>;
+
+  /// Iterates over the bytes in the window, advancing the window past each
+  /// one as it's returned. Stopping early leaves the rest of the bytes in the
+  /// window.
+  ///
+  /// The bytes are read without a bounds check apiece, so this is about as
+  /// fast as iterating over a slice.
+  ///
+  /// ```rust
+  /// use iobuf::{ROIobuf,Iobuf};
+  ///
+  /// let mut b = ROIobuf::from_str("GET /index.html");
+  ///
+  /// let method: Vec<u8> = b.drain().take_while(|&c| c != b' ').collect();
+  /// assert_eq!(&method[], b"GET");
+  /// unsafe { assert_eq!(b.as_window_slice(), b"/index.html"); }
+  /// ```
+  fn drain<'b>(&'b mut self) -> Drain<'b, Self>;
+
+  /// Turns this Iobuf into an iterator over the bytes in its window. The
+  /// iterator owns the Iobuf, so it can be passed around on its own. Use
+  /// `IntoBytes::into_inner` to get the Iobuf back, with whatever's left in
+  /// the window.
+  ///
+  /// ```rust
+  /// use iobuf::{ROIobuf,Iobuf};
+  ///
+  /// let sum = ROIobuf::from_slice_copy(&[ 1, 2, 3 ]).into_bytes()
+  ///   .fold(0u32, |a, c| a + c as u32);
+  /// assert_eq!(sum, 6);
+  /// ```
+  fn into_bytes(self) -> IntoBytes<Self>;
+
+  /// Turns this Iobuf into a `Reader` which consumes its window. The reader
+  /// owns the Iobuf, so it isn't tied to a borrow, and can be moved into
+  /// anything that wants a `Reader` of its own. Use `IobufReader::into_inner`
+  /// to get the Iobuf back, with whatever hasn't been read in the window.
+  ///
+  /// This is also how to use `read_line`, `read_until`, and `lines` on an
+  /// Iobuf, since the reader is a `Buffer` too.
+  ///
+  /// ```rust
+  /// use iobuf::{ROIobuf,Iobuf};
+  ///
+  /// fn consume_all<R: Reader>(mut r: R) -> Vec<u8> { r.read_to_end().unwrap() }
+  ///
+  /// let b = ROIobuf::from_str_copy("hello");
+  /// assert_eq!(consume_all(b.into_reader()), b"hello".to_vec());
+  /// ```
+  fn into_reader(self) -> IobufReader<Self>;
+
+  /// Splits the window around the first occurrence of `delim`, returning
+  /// Iobufs over the bytes before it and the bytes after it. The delimiter
+  /// itself is in neither. Returns `None` if `delim` isn't in the window.
+  ///
+  /// Both Iobufs share this one's buffer and limits. Only their windows
+  /// differ.
+  ///
+  /// ```rust
+  /// use iobuf::{ROIobuf,Iobuf};
+  ///
+  /// let b = ROIobuf::from_str("key=value=1");
+  ///
+  /// let (k, v) = b.split_once(b'=').unwrap();
+  /// unsafe {
+  ///   assert_eq!(k.as_window_slice(), b"key");
+  ///   assert_eq!(v.as_window_slice(), b"value=1");
+  /// }
+  ///
+  /// assert!(b.split_once(b':').is_none());
+  /// ```
+  fn split_once(&self, delim: u8) -> Option<(Self, Self)>;
+
+  /// The offset into the window of the last occurrence of `byte`, or `None` if
+  /// it isn't in the window. The window is searched from the back, so this is
+  /// quick when the byte is near the end.
+  ///
+  /// ```rust
+  /// use iobuf::{ROIobuf,Iobuf};
+  ///
+  /// let mut b = ROIobuf::from_str("usr/local/lib/libz.so");
+  ///
+  /// let slash = b.rfind(b'/').unwrap();
+  /// assert_eq!(slash, 13);
+  /// assert_eq!(b.advance(slash + 1), Ok(()));
+  /// unsafe { assert_eq!(b.as_window_slice(), b"libz.so"); }
+  ///
+  /// assert_eq!(b.rfind(b'/'), None);
+  /// ```
+  fn rfind(&self, byte: u8) -> Option<u32>;
+
+  /// The offset into the window of the start of the last occurrence of
+  /// `needle`, or `None` if it isn't in the window. An empty `needle` is
+  /// found at the end of the window.
+  ///
+  /// ```rust
+  /// use iobuf::{ROIobuf,Iobuf};
+  ///
+  /// let b = ROIobuf::from_str("--xx\r\n--xx\r\nbody");
+  ///
+  /// assert_eq!(b.rfind_slice(b"\r\n"), Some(10));
+  /// assert_eq!(b.rfind_slice(b"--xx"), Some(6));
+  /// assert_eq!(b.rfind_slice(b"--yy"), None);
+  /// assert_eq!(b.rfind_slice(b""), Some(16));
+  /// ```
+  fn rfind_slice(&self, needle: &[u8]) -> Option<u32>;
+
+  /// Iterates over the window as a sequence of big-endian primitives.
+  ///
+  /// If the window's length isn't a multiple of the primitive's size, the
+  /// bytes at the end that don't make up a whole primitive are skipped. They
+  /// can be retrieved with `remainder`.
+  ///
+  /// ```rust
+  /// use iobuf::{ROIobuf,Iobuf};
+  ///
+  /// let data = [ 0x00, 0x01, 0x00, 0x02, 0x00, 0x03, 0xFF ];
+  /// let b = ROIobuf::from_slice(&data);
+  ///
+  /// assert_eq!(b.iter_be::<u16>().fold(0, |a, x| a + x), 6);
+  /// assert_eq!(b.iter_be::<u16>().remainder(), [ 0xFF ]);
+  /// ```
+  fn iter_be<'b, T: Prim>(&'b self) -> WordIter<'b, T>;
+
+  /// Iterates over the window as a sequence of little-endian primitives.
+  ///
+  /// If the window's length isn't a multiple of the primitive's size, the
+  /// bytes at the end that don't make up a whole primitive are skipped. They
+  /// can be retrieved with `remainder`.
+  ///
+  /// ```rust
+  /// use iobuf::{ROIobuf,Iobuf};
+  ///
+  /// let data = [ 0x01, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00 ];
+  /// let b = ROIobuf::from_slice(&data);
+  ///
+  /// assert_eq!(b.iter_le::<u32>().collect::<Vec<u32>>(), vec!(1, 2));
+  /// ```
+  fn iter_le<'b, T: Prim>(&'b self) -> WordIter<'b, T>;
+
+  /// Folds `f` over every byte in the window, from front to back, starting
+  /// with `init`. This is a safe way to compute checksums, histograms, and
+  /// other reductions in a single pass.
+  ///
+  /// ```rust
+  /// use iobuf::{ROIobuf,Iobuf};
+  ///
+  /// let data = [ 3, 1, 4, 1, 5, 9, 2, 6 ];
+  /// let b = ROIobuf::from_slice(&data);
+  ///
+  /// assert_eq!(b.fold(0u32, |sum, x| sum + x as u32), 31);
+  /// assert_eq!(b.fold(0u8, |max, x| if x > max { x } else { max }), 9);
+  /// ```
+  fn fold<B, F: FnMut(B, u8) -> B>(&self, init: B, f: F) -> B;
+
+  /// Returns `true` if `pred` holds for every byte in the window. This is
+  /// `true` for an empty window.
+  ///
+  /// ```rust
+  /// use iobuf::{ROIobuf,Iobuf};
+  ///
+  /// let b = ROIobuf::from_str("12345");
+  /// assert!(b.all_bytes(|c| c >= b'0' && c <= b'9'));
+  /// assert!(!b.all_bytes(|c| c == b'1'));
+  /// ```
+  fn all_bytes<F: FnMut(u8) -> bool>(&self, pred: F) -> bool;
+
+  /// Returns `true` if every byte in the window is ASCII.
+  ///
+  /// ```rust
+  /// use iobuf::{ROIobuf,Iobuf};
+  ///
+  /// assert!(ROIobuf::from_str("Accept: */*\r\n").is_ascii());
+  /// assert!(!ROIobuf::from_str("\u{2603}").is_ascii());
+  /// ```
+  fn is_ascii(&self) -> bool;
+
+  /// Returns `true` if every byte in the window is printable ASCII: a space,
+  /// or a visible character. Control characters (including `\r` and `\n`)
+  /// aren't printable.
+  ///
+  /// ```rust
+  /// use iobuf::{ROIobuf,Iobuf};
+  ///
+  /// assert!(ROIobuf::from_str("Accept: */*").is_ascii_printable());
+  /// assert!(!ROIobuf::from_str("Accept: */*\r\n").is_ascii_printable());
+  /// ```
+  fn is_ascii_printable(&self) -> bool;
+
+  /// Returns the offset, from the start of the window, of the first byte that
+  /// isn't ASCII. This is `None` if the whole window is ASCII.
+  ///
+  /// ```rust
+  /// use iobuf::{ROIobuf,Iobuf};
+  ///
+  /// assert_eq!(ROIobuf::from_str("abc").first_non_ascii(), None);
+  /// assert_eq!(ROIobuf::from_str("ab\u{e9}").first_non_ascii(), Some(2));
+  /// ```
+  fn first_non_ascii(&self) -> Option<u32>;
+
+  /// Shrinks the window from both ends past any bytes matching `pred`, like
+  /// `str::trim_matches`. Nothing is copied; only the window moves. If every
+  /// byte matches, the window ends up empty.
+  ///
+  /// ```rust
+  /// use iobuf::{ROIobuf,Iobuf};
+  ///
+  /// let mut b = ROIobuf::from_str("0042.5000");
+  /// b.trim_matches(|c| c == b'0');
+  /// unsafe { assert_eq!(b.as_window_slice(), b"42.5"); }
+  /// ```
+  fn trim_matches<F: FnMut(u8) -> bool>(&mut self, pred: F);
+
+  /// Shrinks the window from both ends past any ASCII whitespace: spaces,
+  /// tabs, carriage returns, line feeds, and form feeds.
+  ///
+  /// ```rust
+  /// use iobuf::{ROIobuf,Iobuf};
+  ///
+  /// let mut b = ROIobuf::from_str("  hi  ");
+  /// b.trim_ascii_whitespace();
+  /// unsafe { assert_eq!(b.as_window_slice(), b"hi"); }
+  /// assert_eq!(b.len(), 2);
+  /// ```
+  fn trim_ascii_whitespace(&mut self);
+
+  /// Binary searches the window for the first byte which doesn't satisfy
+  /// `pred`, and returns its offset into the window. This is `len()` if every
+  /// byte satisfies it.
+  ///
+  /// `pred` must be true for a (possibly empty) run of bytes at the front of
+  /// the window, and false for the rest. For a sorted window, that's a
+  /// predicate like `|b| b < threshold`. If it isn't, the result is some
+  /// offset in the window, but not necessarily a meaningful one.
+  ///
+  /// ```rust
+  /// use iobuf::{ROIobuf,Iobuf};
+  ///
+  /// let sorted = [ 1, 3, 3, 7, 10, 20, 40, 41 ];
+  /// let b = ROIobuf::from_slice(&sorted);
+  ///
+  /// assert_eq!(b.partition_point(|x| x < 10), 4);
+  /// assert_eq!(b.partition_point(|x| x <= 3), 3);
+  /// assert_eq!(b.partition_point(|x| x < 100), 8);
+  /// assert_eq!(b.partition_point(|_| false), 0);
+  /// ```
+  fn partition_point<F: FnMut(u8) -> bool>(&self, pred: F) -> u32;
+
+  /// Like `partition_point`, but over `size`-byte records packed back to back
+  /// in the window, like a sorted index. `pred` is given each record it looks
+  /// at, and the result is the index of the first record which doesn't
+  /// satisfy it, counted in records.
+  ///
+  /// Returns `Err(())` if `size` is 0, or the window's length isn't a multiple
+  /// of `size`.
+  ///
+  /// ```rust
+  /// use iobuf::{ROIobuf,Iobuf};
+  ///
+  /// // Sorted (u16 key, u16 value) pairs, big-endian.
+  /// let index = [ 0x00, 0x05, 0x00, 0x01,
+  ///               0x00, 0x09, 0x00, 0x02,
+  ///               0x01, 0x00, 0x00, 0x03 ];
+  /// let b = ROIobuf::from_slice(&index);
+  ///
+  /// fn key(r: &[u8]) -> u16 { (r[0] as u16) << 8 | r[1] as u16 }
+  ///
+  /// let i = b.partition_point_records(4, |r| key(r) < 9).unwrap();
+  /// assert_eq!(i, 1);
+  /// assert_eq!(b.peek_be::<u16>(i * 4 + 2), Ok(2));
+  ///
+  /// assert_eq!(b.partition_point_records(4, |r| key(r) < 0x1000), Ok(3));
+  /// assert_eq!(b.partition_point_records(5, |_| true), Err(()));
+  /// ```
+  fn partition_point_records<F: FnMut(&[u8]) -> bool>(&self, size: u32, pred: F) -> Result<u32, ()>;
+
+  /// Counts how many times each byte value occurs in the window. Entry `i` of
+  /// the result is the number of bytes equal to `i`.
+  ///
+  /// ```rust
+  /// use iobuf::{ROIobuf,Iobuf};
+  ///
+  /// let h = ROIobuf::from_str("hello").byte_histogram();
+  /// assert_eq!(h[b'l' as usize], 2);
+  /// assert_eq!(h[b'h' as usize], 1);
+  /// assert_eq!(h[b'z' as usize], 0);
+  /// ```
+  fn byte_histogram(&self) -> [u32; 256];
+
+  /// The Shannon entropy of the window's bytes, in bits per byte. This ranges
+  /// from 0 (every byte is the same) to 8 (every byte value is equally
+  /// common). Compressed or encrypted data is close to 8, text is much lower.
+  /// The entropy of an empty window is 0.
+  ///
+  /// ```rust
+  /// use iobuf::{ROIobuf,Iobuf};
+  ///
+  /// assert_eq!(ROIobuf::from_str("aaaaaaaa").shannon_entropy(), 0.0);
+  ///
+  /// let data: Vec<u8> = range(0u32, 1024).map(|i| i as u8).collect();
+  /// let e = ROIobuf::from_slice(&data[]).shannon_entropy();
+  /// assert!(e > 7.99 && e <= 8.0);
+  /// ```
+  fn shannon_entropy(&self) -> f64;
+
+  /// The smallest byte in the window, or `None` if the window is empty.
+  ///
+  /// ```rust
+  /// use iobuf::{ROIobuf,Iobuf};
+  ///
+  /// let samples = [ 0x80, 0x7F, 0x03, 0xF0 ];
+  /// assert_eq!(ROIobuf::from_slice(&samples).byte_min(), Some(0x03));
+  /// assert_eq!(ROIobuf::empty().byte_min(), None);
+  /// ```
+  fn byte_min(&self) -> Option<u8>;
+
+  /// The largest byte in the window, or `None` if the window is empty.
+  ///
+  /// ```rust
+  /// use iobuf::{ROIobuf,Iobuf};
+  ///
+  /// let samples = [ 0x80, 0x7F, 0x03, 0xF0 ];
+  /// assert_eq!(ROIobuf::from_slice(&samples).byte_max(), Some(0xF0));
+  /// assert_eq!(ROIobuf::empty().byte_max(), None);
+  /// ```
+  fn byte_max(&self) -> Option<u8>;
+
+  /// The sum of the bytes in the window, each taken as an unsigned number. The
+  /// sum of an empty window is 0. This can't overflow, since a window holds
+  /// fewer than 2^32 bytes.
+  ///
+  /// ```rust
+  /// use iobuf::{ROIobuf,Iobuf};
+  ///
+  /// let samples = [ 0x80, 0x7F, 0x03, 0xF0 ];
+  /// assert_eq!(ROIobuf::from_slice(&samples).byte_sum(), 0x80 + 0x7F + 0x03 + 0xF0);
+  /// assert_eq!(ROIobuf::empty().byte_sum(), 0);
+  /// ```
+  fn byte_sum(&self) -> u64;
+
+  /// The CRC32C (Castagnoli) checksum of the window, as used by iSCSI, SCTP
+  /// and many storage formats. This isn't the same as the zlib CRC32, which
+  /// uses a different polynomial.
+  ///
+  /// This uses the SSE4.2 `crc32` instruction when the CPU has it.
+  ///
+  /// ```rust
+  /// use iobuf::{ROIobuf,Iobuf};
+  ///
+  /// assert_eq!(ROIobuf::from_str("123456789").crc32c(), 0xE3069283);
+  /// assert_eq!(ROIobuf::empty().crc32c(), 0);
+  /// ```
+  fn crc32c(&self) -> u32;
+
+  /// The CRC32C of the `len` bytes starting `pos` bytes into the window. See
+  /// `crc32c`.
+  ///
+  /// ```rust
+  /// use iobuf::{ROIobuf,Iobuf};
+  ///
+  /// let b = ROIobuf::from_str("xx123456789x");
+  /// assert_eq!(b.crc32c_from(2, 9), Ok(0xE3069283));
+  /// assert_eq!(b.crc32c_from(4, 9), Err(()));
+  /// ```
+  fn crc32c_from(&self, pos: u32, len: u32) -> Result<u32, ()>;
+
+  /// Feeds the window into `hasher`, exactly as if the window's bytes had
+  /// been written to it as a slice. This is for content-addressing or
+  /// sharding on a buffer's contents with a hash function of your choosing,
+  /// without copying the window out or reaching for `as_window_slice`.
+  ///
+  /// ```rust
+  /// use std::hash::Writer;
+  /// use iobuf::{ROIobuf,Iobuf};
+  ///
+  /// // 64-bit FNV-1a.
+  /// struct Fnv(u64);
+  ///
+  /// impl Writer for Fnv {
+  ///   fn write(&mut self, bytes: &[u8]) {
+  ///     for &c in bytes.iter() {
+  ///       self.0 = (self.0 ^ c as u64) * 0x100000001b3;
+  ///     }
+  ///   }
+  /// }
+  ///
+  /// let mut b = ROIobuf::from_str("key:user42");
+  /// assert_eq!(b.advance(4), Ok(()));
+  ///
+  /// let mut from_buf = Fnv(0xcbf29ce484222325);
+  /// b.hash_window(&mut from_buf);
+  ///
+  /// let mut from_slice = Fnv(0xcbf29ce484222325);
+  /// from_slice.write(b"user42");
+  ///
+  /// assert_eq!(from_buf.0, from_slice.0);
+  /// ```
+  fn hash_window<H: hash::Writer>(&self, hasher: &mut H);
+
+  /// Runs `f`, and if it fails, puts the window and limits back to how they
+  /// were before it ran. On success, they're left wherever `f` moved them.
+  ///
+  /// This makes it easy to try to parse something that might not have fully
+  /// arrived yet, without remembering to restore the window on every error
+  /// path. Calls can be nested, and each level only rolls back its own
+  /// changes.
+  ///
+  /// Only the window and limits are rolled back, not the data. Don't
+  /// `compact` inside `f`: that moves the data, so the restored window would
+  /// point at the wrong bytes.
+  ///
+  /// ```rust
+  /// use iobuf::{ROIobuf,Iobuf};
+  ///
+  /// let data = [ 0x00, 0x03, 0xAA, 0xBB ];
+  /// let mut b = ROIobuf::from_slice(&data);
+  ///
+  /// // A u16 length, followed by that many bytes.
+  /// fn frame(b: &mut ROIobuf) -> Result<u32, ()> {
+  ///   b.with_rollback(|b| {
+  ///     let len = try!(b.consume_be::<u16>()) as u32;
+  ///     try!(b.advance(len));
+  ///     Ok(len)
+  ///   })
+  /// }
+  ///
+  /// assert_eq!(frame(&mut b), Err(()));
+  /// assert_eq!(b.len(), 4);
+  ///
+  /// assert_eq!(b.with_rollback(|b| b.advance(2)), Ok(()));
+  /// assert_eq!(b.len(), 2);
+  /// ```
+  fn with_rollback<R, E, F: FnOnce(&mut Self) -> Result<R, E>>(&mut self, f: F) -> Result<R, E>;
+
+  /// Snapshots the window and limits, and returns a guard to consume through.
+  /// Calling `commit` on the guard keeps the advancement. Calling `rollback`,
+  /// or just dropping the guard, restores the snapshot.
+  ///
+  /// This is `with_rollback` for when a closure is awkward, like when the
+  /// validation that decides the outcome happens well after the reads. The
+  /// guard borrows this Iobuf mutably until it's gone.
+  ///
+  /// As with `with_rollback`, only the window and limits are restored, not
+  /// the data.
+  ///
+  /// ```rust
+  /// use iobuf::{ROIobuf,Iobuf};
+  ///
+  /// // A version byte, a u16 length, then a u8 checksum of the version and
+  /// // length bytes.
+  /// fn record(b: &mut ROIobuf) -> Result<(u8, u16), ()> {
+  ///   let mut t = b.transaction();
+  ///   let version = try!(t.consume_be::<u8>());
+  ///   let len     = try!(t.consume_be::<u16>());
+  ///   let sum     = try!(t.consume_be::<u8>());
+  ///   if sum != version ^ (len >> 8) as u8 ^ len as u8 {
+  ///     return Err(()); // `t` is dropped here, which rolls back.
+  ///   }
+  ///   t.commit();
+  ///   Ok((version, len))
+  /// }
+  ///
+  /// let data = [ 0x01, 0x00, 0x10, 0xFF, 0x01, 0x00, 0x10, 0x11 ];
+  /// let mut b = ROIobuf::from_slice(&data);
+  ///
+  /// assert_eq!(record(&mut b), Err(()));
+  /// assert_eq!(b.len(), 8);
+  ///
+  /// assert_eq!(b.advance(4), Ok(()));
+  /// assert_eq!(record(&mut b), Ok((1, 0x10)));
+  /// assert!(b.is_empty());
+  /// ```
+  fn transaction<'b>(&'b mut self) -> BufTransaction<'b, Self>;
+
+  /// Borrows the window as a slice which also knows where the window starts in
+  /// the underlying buffer, so that offsets into the slice can be turned back
+  /// into offsets into the buffer.
+  ///
+  /// This is unsafe for the same reason `as_window_slice` is. The Iobuf is
+  /// borrowed for as long as the slice is, but other Iobufs sharing the same
+  /// buffer (clones, or the `RWIobuf` behind a `read_only` view) can still
+  /// poke into it. Make sure nothing does while the slice is alive.
+  ///
+  /// ```rust
+  /// use iobuf::{ROIobuf,Iobuf};
+  ///
+  /// let mut b = ROIobuf::from_str("GET /index.html HTTP/1.1");
+  /// assert_eq!(b.advance(4), Ok(()));
+  ///
+  /// let w = unsafe { b.window_guard_positioned() };
+  /// let space = w.iter().position(|&c| c == b' ').unwrap();
+  /// assert_eq!(&w[..space], b"/index.html");
+  /// assert_eq!(w.start(), 4);
+  /// assert_eq!(w.absolute(space), 15);
+  /// ```
+  unsafe fn window_guard_positioned<'b>(&'b mut self) -> PositionedWindow<'b, Self>;
+
+  /// Decodes a `ReadFrom` value, like a header, from the front of the window,
+  /// without advancing it. Decode the header once, and pass the result
+  /// around, instead of peeking at its fields over and over again.
+  ///
+  /// ```rust
+  /// use iobuf::{ROIobuf,Iobuf,ReadFrom};
+  ///
+  /// #[derive(Copy, PartialEq, Debug)]
+  /// struct Header { kind: u8, flags: u8, len: u16 }
+  ///
+  /// impl ReadFrom for Header {
+  ///   fn read_from<Buf: Iobuf>(b: &mut Buf) -> Result<Header, ()> {
+  ///     Ok(Header {
+  ///       kind:  try!(b.consume_be()),
+  ///       flags: try!(b.consume_be()),
+  ///       len:   try!(b.consume_be()),
+  ///     })
+  ///   }
+  /// }
+  ///
+  /// let data = [ 0x01, 0x80, 0x00, 0x10, 0xFF ];
+  /// let b = ROIobuf::from_slice(&data);
+  ///
+  /// assert_eq!(b.peek_header(), Ok(Header { kind: 1, flags: 0x80, len: 16 }));
+  /// assert_eq!(b.len(), 5);
+  ///
+  /// let mut c = b.clone();
+  /// assert_eq!(c.advance(2), Ok(()));
+  /// assert_eq!(c.peek_header::<Header>(), Err(()));
+  /// ```
+  fn peek_header<H: ReadFrom>(&self) -> Result<H, ()>;
+
+  /// Iterates over `ReadFrom` records packed back to back in the window,
+  /// advancing past each one as it's decoded. This stops once the window is
+  /// empty.
+  ///
+  /// A record that fails to decode, like a partial one at the end of the
+  /// window, comes out as a single `Err(())`, after which iteration stops.
+  /// The window is then left at the start of that record.
+  ///
+  /// ```rust
+  /// use iobuf::{ROIobuf,Iobuf,ReadFrom};
+  ///
+  /// #[derive(Copy, PartialEq, Debug)]
+  /// struct Sample { time: u32, channel: u16, value: u16 }
+  ///
+  /// impl ReadFrom for Sample {
+  ///   fn read_from<Buf: Iobuf>(b: &mut Buf) -> Result<Sample, ()> {
+  ///     Ok(Sample {
+  ///       time:    try!(b.consume_be()),
+  ///       channel: try!(b.consume_be()),
+  ///       value:   try!(b.consume_be()),
+  ///     })
+  ///   }
+  /// }
+  ///
+  /// let data = [ 0, 0, 0, 1,  0, 3,  0, 10,
+  ///              0, 0, 0, 2,  0, 4,  1,  0,
+  ///              0, 0, 0, 3 ];
+  /// let mut b = ROIobuf::from_slice(&data);
+  ///
+  /// let mut total = 0u32;
+  /// for s in b.read_records::<Sample>() {
+  ///   match s {
+  ///     Ok(s)   => total += s.value as u32,
+  ///     Err(()) => break,
+  ///   }
+  /// }
+  ///
+  /// assert_eq!(total, 10 + 256);
+  /// assert_eq!(b.len(), 4); // the partial record is still there.
+  /// ```
+  fn read_records<'b, H: ReadFrom>(&'b mut self) -> RecordIter<'b, Self, H>;
+
+  /// Iterates over `size`-byte records packed back to back in the window,
+  /// advancing past each one as it's returned. The records are Iobufs that
+  /// share this Iobuf's buffer and limits.
+  ///
+  /// Iteration stops once fewer than `size` bytes are left, without consuming
+  /// them. `Records::leftover` says how many bytes of a partial record there
+  /// are, so a streaming reader knows to wait for more input. Unlike
+  /// `rchunks`, no short record is ever returned.
+  ///
+  /// Panics if `size` is 0.
+  ///
+  /// ```rust
+  /// use iobuf::{ROIobuf,Iobuf};
+  ///
+  /// let data = [ 0x00, 0x01, 0x00, 0x02, 0x00 ];
+  /// let mut b = ROIobuf::from_slice(&data);
+  ///
+  /// let leftover = {
+  ///   let mut it = b.consume_records(2);
+  ///   let vals: Vec<u16> = it.by_ref().map(|r| r.peek_be(0).unwrap()).collect();
+  ///   assert_eq!(vals, vec!(1, 2));
+  ///   it.leftover()
+  /// };
+  ///
+  /// assert_eq!(leftover, 1);
+  /// assert_eq!(b.len(), 1);
+  /// ```
+  fn consume_records<'b>(&'b mut self, size: u32) -> Records<'b, Self>;
+
+  /// Iterates over type-length-value records at the front of the window,
+  /// advancing past each one as it's returned. Each record is a
+  /// `type_width`-byte type, then a `len_width`-byte length, then that many
+  /// bytes of value, with the integers read big-endian if `big_endian` is
+  /// true, and little-endian otherwise.
+  ///
+  /// Each record comes out as its type, and an Iobuf over its value which
+  /// shares this Iobuf's buffer and limits.
+  ///
+  /// A truncated record, or one whose length doesn't fit in a `u32`, comes out
+  /// as a single `Err(())`, after which iteration stops. The window is then
+  /// left at the start of that record.
+  ///
+  /// Panics if `type_width` is more than 8, or `len_width` isn't between 1
+  /// and 8. A `type_width` of 0 means there's no type, and it's always 0.
+  ///
+  /// ```rust
+  /// use iobuf::{ROIobuf,Iobuf};
+  ///
+  /// // 1-byte types, 2-byte big-endian lengths.
+  /// let data = [ 0x01, 0x00, 0x03, b'f', b'o', b'o',
+  ///              0x07, 0x00, 0x01, 0x2A ];
+  /// let mut b = ROIobuf::from_slice(&data);
+  ///
+  /// let mut name = None;
+  /// let mut ttl  = None;
+  ///
+  /// for r in b.tlv_iter(1, 2, true) {
+  ///   match r {
+  ///     Ok((1, v)) => name = Some(unsafe { v.as_window_slice().to_vec() }),
+  ///     Ok((7, v)) => ttl  = v.peek_be::<u8>(0).ok(),
+  ///     Ok(_)      => {},
+  ///     Err(())    => panic!("bad record"),
+  ///   }
+  /// }
+  ///
+  /// assert_eq!(name, Some(b"foo".to_vec()));
+  /// assert_eq!(ttl, Some(42));
+  /// assert!(b.is_empty());
+  /// ```
+  fn tlv_iter<'b>(&'b mut self, type_width: u8, len_width: u8, big_endian: bool) -> TlvIter<'b, Self>;
+
+  /// Runs `f` on a read-only copy of this Iobuf, and throws the copy away
+  /// afterwards. Whatever `f` does to the copy's window can't leak back into
+  /// this one, which makes "would a whole frame parse from here?" checks
+  /// explicit.
+  ///
+  /// The copy only borrows the buffer, so making it doesn't touch the
+  /// refcount.
+  ///
+  /// ```rust
+  /// use iobuf::{RWIobuf,Iobuf};
+  ///
+  /// let b = RWIobuf::from_str_copy("\x00\x04abc");
+  ///
+  /// let complete = b.lookahead(|b| {
+  ///   match b.consume_be::<u16>() {
+  ///     Ok(len) => b.advance(len as u32).is_ok(),
+  ///     Err(()) => false,
+  ///   }
+  /// });
+  ///
+  /// assert!(!complete);
+  /// assert_eq!(b.len(), 5);
+  /// ```
+  fn lookahead<'b, R, F: FnOnce(&mut ROIobuf<'b>) -> R>(&'b self, f: F) -> R;
+
+  /// Runs `f` with both the limits and the window narrowed to the `len` bytes
+  /// starting at `pos`, so that `f` can't see anything outside of them, even
+  /// with `reset`.
+  ///
+  /// Afterwards, the limits are put back, and the window is put back and then
+  /// advanced past the sub-window, no matter what `f` did to it. This makes it
+  /// easy to hand a length-delimited field to a sub-parser, and carry on right
+  /// after it.
+  ///
+  /// An error is returned, and `f` isn't run, if the sub-window isn't in the
+  /// window.
+  ///
+  /// ```rust
+  /// use iobuf::{ROIobuf,Iobuf};
+  ///
+  /// let mut b = ROIobuf::from_str("\x03abcdef");
+  ///
+  /// let len = b.consume_be::<u8>().unwrap() as u32;
+  /// assert_eq!(b.with_sub_window(0, len, |b| {
+  ///   b.reset();
+  ///   unsafe { b.as_window_slice().to_vec() }
+  /// }), Ok(b"abc".to_vec()));
+  ///
+  /// unsafe { assert_eq!(b.as_window_slice(), b"def"); }
+  /// assert_eq!(b.with_sub_window(1, 3, |_| ()), Err(()));
+  /// ```
+  fn with_sub_window<R, F: FnOnce(&mut Self) -> R>(&mut self, pos: u32, len: u32, f: F) -> Result<R, ()>;
+
+  /// Runs `f` `count` times, collecting the results. This is for the common
+  /// "a count, followed by that many records" layout.
+  ///
+  /// If any call fails, the window and limits are rolled back to where they
+  /// were before the first call, as with `with_rollback`, and the error is
+  /// returned.
+  ///
+  /// ```rust
+  /// use iobuf::{ROIobuf,Iobuf};
+  ///
+  /// let data = [ 0x03, 0x00, 0x01, 0x00, 0x02, 0x00, 0x03, 0xFF ];
+  /// let mut b = ROIobuf::from_slice(&data);
+  ///
+  /// let count = b.consume_be::<u8>().unwrap() as u32;
+  /// assert_eq!(b.consume_repeat(count, |b| b.consume_be::<u16>()), Ok(vec!(1, 2, 3)));
+  /// assert_eq!(b.len(), 1);
+  ///
+  /// assert_eq!(b.consume_repeat(2, |b| b.consume_be::<u8>()), Err(()));
+  /// assert_eq!(b.len(), 1);
+  /// ```
+  fn consume_repeat<R, E, F: FnMut(&mut Self) -> Result<R, E>>(&mut self, count: u32, f: F) -> Result<Vec<R>, E>;
+
+  /// Runs `f` until the window is empty, collecting the results. This is for
+  /// records that run all the way to the end of the window.
+  ///
+  /// If any call fails, the window and limits are rolled back to where they
+  /// were before the first call, as with `with_rollback`, and the error is
+  /// returned.
+  ///
+  /// `f` must consume something every time it succeeds, or this will never
+  /// finish.
+  ///
+  /// ```rust
+  /// use iobuf::{ROIobuf,Iobuf};
+  ///
+  /// let data = [ 0x00, 0x01, 0x00, 0x02, 0x00 ];
+  /// let mut b = ROIobuf::from_slice(&data);
+  ///
+  /// assert_eq!(b.consume_repeat_until_empty(|b| b.consume_be::<u16>()), Err(()));
+  /// assert_eq!(b.len(), 5);
+  ///
+  /// assert_eq!(b.resize(4), Ok(()));
+  /// assert_eq!(b.consume_repeat_until_empty(|b| b.consume_be::<u16>()), Ok(vec!(1, 2)));
+  /// assert!(b.is_empty());
+  /// ```
+  fn consume_repeat_until_empty<R, E, F: FnMut(&mut Self) -> Result<R, E>>(&mut self, f: F) -> Result<Vec<R>, E>;
+
+  /// Returns an `Err(())` if the `len` bytes, starting at `pos`, are not all
+  /// in the window. To be used with the `try!` macro.
+  ///
+  /// Make sure you use this in conjunction with the `unsafe` combinators. It
+  /// is recommended you minimize your bounds checks by doing it once with
+  /// `check_range`, followed by unsafe accesses. If you do unsafe accesses
+  /// without a `check_range`, there will likely be reliability and security
+  /// issues with your application.
+  ///
+  /// Below is a correct usage of `check_range` to minimize bounds checks:
+  ///
+  /// ```rust
+  /// use std::result::Result::{self,Ok};
+  /// use iobuf::{ROIobuf,Iobuf};
+  ///
+  /// // [ number of byte buffers, size of first byte buffer, ...bytes, etc. ]
+  /// let data = [ 0x02, 0x02, 0x55, 0x66, 0x03, 0x11, 0x22, 0x33 ];
+  /// let mut b = ROIobuf::from_slice(&data);
+  ///
+  /// fn parse<B: Iobuf>(b: &mut B) -> Result<usize, ()> {
+  ///   let mut sum = 0us;
+  ///
+  ///   let num_buffers: u8 = try!(b.consume_be());
+  ///
+  ///   for _ in (0 .. num_buffers) {
+  ///     let len: u8 = try!(b.consume_be());
+  ///
+  ///     unsafe {
+  ///       try!(b.check_range(0, len as u32));
+  ///
+  ///       for _ in (0 .. len) {
+  ///         sum += b.unsafe_consume_be::<u8>() as usize;
+  ///       }
+  ///     }
+  ///   }
+  ///
+  ///   Ok(sum)
+  /// }
+  ///
+  /// assert_eq!(parse(&mut b), Ok(0x55 + 0x66 + 0x11 + 0x22 + 0x33));
+  /// ```
+  fn check_range(&self, pos: u32, len: u32) -> Result<(), ()>;
+
+  /// The same as `check_range`, but with a `usize` length. If you're checking
+  /// the range of something which might overflow an `i32`, use this version
+  /// instead of `check_range`.
+  ///
+  /// ```rust
+  /// use iobuf::{ROIobuf,Iobuf};
+  ///
+  /// let mut b = ROIobuf::from_str("hello");
+  ///
+  /// assert_eq!(b.check_range_usize(1u32, 5u), Err(()));
+  /// ```
+  fn check_range_usize(&self, pos: u32, len: usize) -> Result<(), ()>;
+
+  /// The same as `check_range`, but fails if the bounds check returns `Err(())`.
+  ///
+  /// ```should_fail
+  /// use iobuf::{ROIobuf,Iobuf};
+  ///
+  /// let mut b = ROIobuf::from_str("hello");
+  ///
+  /// b.check_range_fail(1, 5); // boom.
+  /// ```
+  fn check_range_fail(&self, pos: u32, len: u32);
+
+  /// The same as `check_range_usize`, but fails if the bounds check returns
+  /// `Err(())`.
+  ///
+  /// ```should_fail
+  /// use iobuf::{ROIobuf,Iobuf};
+  ///
+  /// let mut b = ROIobuf::from_str("hello");
+  ///
+  /// b.check_range_usize_fail(1u32, 5u); // boom.
+  /// ```
+  fn check_range_usize_fail(&self, pos: u32, len: usize);
+
+  /// Reads the bytes at a given offset from the beginning of the window, into
+  /// the supplied buffer. It is undefined behavior to read outside the iobuf
+  /// window.
+  ///
+  /// ```rust
+  /// use iobuf::{ROIobuf,Iobuf};
+  ///
+  /// let data = [1,2,3,4,5,6];
+  /// let mut b = ROIobuf::from_slice(&data);
+  ///
+  /// let mut dst = [0; 4];
+  ///
+  /// unsafe {
+  ///   // one range check, instead of two!
+  ///   assert_eq!(b.check_range(0, 5), Ok(()));
+  ///
+  ///   assert_eq!(b.unsafe_peek_be::<u8>(0), 1u8);
+  ///   b.unsafe_peek(1, &mut dst);
   /// }
   ///
   /// let expected = [ 2u8, 3, 4, 5 ];
@@ -988,7 +2698,7 @@ pub trait Iobuf: Clone + Debug {
   ///   assert_eq!(z, 0x0102 + 0x03040506);
   /// }
   /// ```
-  unsafe fn unsafe_peek_be<T: Int>(&self, pos: u32) -> T;
+  unsafe fn unsafe_peek_be<T: Prim>(&self, pos: u32) -> T;
 
   /// Reads a little-endian primitive at a given offset from the beginning of
   /// the window. It is undefined behavior to read outside the iobuf window.
@@ -1009,7 +2719,7 @@ pub trait Iobuf: Clone + Debug {
   ///   assert_eq!(z, 0x0201 + 0x06050403);
   /// }
   /// ```
-  unsafe fn unsafe_peek_le<T: Int>(&self, pos: u32) -> T;
+  unsafe fn unsafe_peek_le<T: Prim>(&self, pos: u32) -> T;
 
   /// Reads bytes, starting from the front of the window, into the supplied
   /// buffer. After the bytes have been read, the window will be moved to no
@@ -1039,7 +2749,7 @@ pub trait Iobuf: Clone + Debug {
   ///   assert_eq!(b.unsafe_consume_be::<u8>(), 0x04u8);
   /// }
   /// ```
-  unsafe fn unsafe_consume_be<T: Int>(&mut self) -> T;
+  unsafe fn unsafe_consume_be<T: Prim>(&mut self) -> T;
 
   /// Reads a little-endian primitive at the beginning of the window.
   ///
@@ -1062,7 +2772,7 @@ pub trait Iobuf: Clone + Debug {
   ///   assert_eq!(b.unsafe_consume_le::<u8>(), 0x04u8);
   /// }
   /// ```
-  unsafe fn unsafe_consume_le<T: Int>(&mut self) -> T;
+  unsafe fn unsafe_consume_le<T: Prim>(&mut self) -> T;
 
   /// For internal use only.
   unsafe fn as_raw<'b>(&'b self) -> &RawIobuf<'b>;
@@ -1075,6 +2785,26 @@ pub trait Iobuf: Clone + Debug {
   /// refcount modification on `clone` or `drop`) or borrowed memory.
   fn is_owned(&self) -> bool;
 
+  /// Takes a snapshot of this Iobuf's limits, window, buffer, and refcount,
+  /// for logging, bug reports, and tests that check internal invariants.
+  ///
+  /// ```rust
+  /// use iobuf::{Iobuf, IobufState, Ownership, RWIobuf};
+  ///
+  /// let mut b = RWIobuf::new(10);
+  /// assert_eq!(b.sub(2, 6), Ok(()));
+  /// assert_eq!(b.advance(1), Ok(()));
+  /// let c = b.clone();
+  ///
+  /// assert_eq!(c.debug_state(), IobufState {
+  ///   lo_min: 2, lo: 3, hi: 8, hi_max: 8,
+  ///   buffer_len: 10,
+  ///   ownership:  Ownership::Heap,
+  ///   refcount:   2,
+  /// });
+  /// ```
+  fn debug_state(&self) -> IobufState;
+
   /// Returns an index into the buffer returned by `ptr` that represents the
   /// inclusive lower bound of the limits.
   fn lo_min(&self) -> u32;
@@ -1092,3 +2822,164 @@ pub trait Iobuf: Clone + Debug {
   fn hi_max(&self) -> u32;
 
 }
+
+
+/// Writing through a position in the window, for code which is generic over
+/// what it writes into. Unlike `MutIobuf`, this doesn't need an Iobuf behind
+/// it, so stand-ins like `CountingIobuf` and `ElasticWriter` implement it too.
+///
+/// `RWIobuf` also has all of these as inherent functions, so this only needs
+/// to be imported when writing generic code. See the functions of the same
+/// name on `RWIobuf` for details.
+///
+/// ```rust
+/// use iobuf::{ElasticWriter, Iobuf, IobufWrite, RWIobuf};
+///
+/// fn encode<B: IobufWrite>(id: u32, name: &str, out: &mut B) -> Result<(), ()> {
+///   try!(out.fill_be(id));
+///   try!(out.fill_be(name.len() as u8));
+///   out.fill(name.as_bytes())
+/// }
+///
+/// let mut b = RWIobuf::new(8);
+/// assert_eq!(encode(1, "abc", &mut b), Ok(()));
+/// assert_eq!(encode(1, "abc", &mut b), Err(()));
+///
+/// b.flip_lo();
+/// unsafe { assert_eq!(b.as_window_slice(), b"\x00\x00\x00\x01\x03abc"); }
+///
+/// let mut w = ElasticWriter::new(RWIobuf::new(4));
+/// assert_eq!(encode(1, "abc", &mut w), Ok(()));
+/// assert_eq!(w.written(), 8);
+/// ```
+pub trait IobufWrite {
+  /// Writes `src` at `pos` bytes into the window. See `RWIobuf::poke`.
+  fn poke(&self, pos: u32, src: &[u8]) -> Result<(), ()>;
+
+  /// Writes a big-endian primitive at `pos` bytes into the window. See
+  /// `RWIobuf::poke_be`.
+  fn poke_be<T: Prim>(&self, pos: u32, t: T) -> Result<(), ()>;
+
+  /// Writes a little-endian primitive at `pos` bytes into the window. See
+  /// `RWIobuf::poke_le`.
+  fn poke_le<T: Prim>(&self, pos: u32, t: T) -> Result<(), ()>;
+
+  /// Writes `src` into the front of the window, and advances past it. See
+  /// `RWIobuf::fill`.
+  fn fill(&mut self, src: &[u8]) -> Result<(), ()>;
+
+  /// Writes a big-endian primitive into the front of the window, and advances
+  /// past it. See `RWIobuf::fill_be`.
+  fn fill_be<T: Prim>(&mut self, t: T) -> Result<(), ()>;
+
+  /// Writes a little-endian primitive into the front of the window, and
+  /// advances past it. See `RWIobuf::fill_le`.
+  fn fill_le<T: Prim>(&mut self, t: T) -> Result<(), ()>;
+}
+
+/// The writing half of the Iobuf interface, for code which is generic over
+/// the kind of writable Iobuf it writes into. The checked writes are in
+/// `IobufWrite`. This adds what only makes sense with a real buffer behind
+/// it: slices of the buffer, and writes without bounds checks.
+///
+/// `RWIobuf` also has all of these as inherent functions, so this only needs
+/// to be imported when writing generic code. See the functions of the same
+/// name on `RWIobuf` for details.
+///
+/// ```rust
+/// use iobuf::{Iobuf, MutIobuf, RWIobuf};
+///
+/// fn encode<B: MutIobuf>(id: u32, name: &str, out: &mut B) -> Result<(), ()> {
+///   try!(out.check_range(0, 5 + name.len() as u32));
+///   unsafe {
+///     out.unsafe_fill_be(id);
+///     out.unsafe_fill_be(name.len() as u8);
+///   }
+///   out.fill(name.as_bytes())
+/// }
+///
+/// let mut b = RWIobuf::new(8);
+/// assert_eq!(encode(1, "abc", &mut b), Ok(()));
+/// assert_eq!(encode(1, "abc", &mut b), Err(()));
+///
+/// b.flip_lo();
+/// unsafe { assert_eq!(b.as_window_slice(), b"\x00\x00\x00\x01\x03abc"); }
+/// ```
+pub trait MutIobuf: Iobuf + IobufWrite {
+  /// Reads the data in the window as a mutable slice. See
+  /// `RWIobuf::as_mut_window_slice`.
+  unsafe fn as_mut_window_slice<'b>(&'b self) -> &'b mut [u8];
+
+  /// Reads the data in the limits as a mutable slice. See
+  /// `RWIobuf::as_mut_limit_slice`.
+  unsafe fn as_mut_limit_slice<'b>(&'b self) -> &'b mut [u8];
+
+  /// `poke`, without bounds checks. See `RWIobuf::unsafe_poke`.
+  unsafe fn unsafe_poke(&self, pos: u32, src: &[u8]);
+
+  /// `poke_be`, without bounds checks. See `RWIobuf::unsafe_poke_be`.
+  unsafe fn unsafe_poke_be<T: Prim>(&self, pos: u32, t: T);
+
+  /// `poke_le`, without bounds checks. See `RWIobuf::unsafe_poke_le`.
+  unsafe fn unsafe_poke_le<T: Prim>(&self, pos: u32, t: T);
+
+  /// `fill`, without bounds checks. See `RWIobuf::unsafe_fill`.
+  unsafe fn unsafe_fill(&mut self, src: &[u8]);
+
+  /// `fill_be`, without bounds checks. See `RWIobuf::unsafe_fill_be`.
+  unsafe fn unsafe_fill_be<T: Prim>(&mut self, t: T);
+
+  /// `fill_le`, without bounds checks. See `RWIobuf::unsafe_fill_le`.
+  unsafe fn unsafe_fill_le<T: Prim>(&mut self, t: T);
+}
+
+/// A value which can be decoded from the front of an Iobuf, like a fixed-size
+/// protocol header.
+///
+/// `read_from` should consume exactly the bytes it decodes. If it fails, the
+/// window may be left anywhere. Use `Iobuf::with_rollback` if that matters.
+pub trait ReadFrom {
+  /// Decodes a value from the front of the window, advancing past it.
+  fn read_from<Buf: Iobuf>(buf: &mut Buf) -> Result<Self, ()>;
+}
+
+/// Constructors for Iobufs which own their storage, for generic code which
+/// needs to make new buffers of the same flavor as the ones it was given.
+///
+/// The new buffers are always the `'static` flavor of `Self`, since they
+/// don't borrow anything.
+///
+/// ```rust
+/// use iobuf::{Iobuf, IobufAlloc, ROIobuf, RWIobuf};
+///
+/// // Copies every other byte of the window into a new buffer of the same kind.
+/// fn evens<B: IobufAlloc>(b: &B) -> B::Owned {
+///   let mut v = Vec::new();
+///   for i in range(0, b.len()) {
+///     if i % 2 == 0 { v.push(b.peek_be::<u8>(i).unwrap()) }
+///   }
+///   B::owned_from_vec(v)
+/// }
+///
+/// let r: ROIobuf<'static> = evens(&ROIobuf::from_str("a1b2c3"));
+/// unsafe { assert_eq!(r.as_window_slice(), b"abc"); }
+///
+/// let w: RWIobuf<'static> = evens(&RWIobuf::from_str_copy("x-y-"));
+/// assert_eq!(w.poke(0, b"z"), Ok(()));
+/// unsafe { assert_eq!(w.as_window_slice(), b"zy"); }
+/// ```
+pub trait IobufAlloc: Iobuf {
+  /// The flavor of Iobuf these constructors make.
+  type Owned: Iobuf;
+
+  /// A new buffer of `len` bytes, with the limits and window covering all of
+  /// it. Writable flavors have undefined contents, like `RWIobuf::new`.
+  /// Read-only flavors (`ROIobuf` and `AROIobuf`) are zeroed, since nothing
+  /// could write to them.
+  fn alloc(len: usize) -> Self::Owned;
+
+  /// A new buffer holding the contents of `v`, with the limits and window
+  /// covering all of it. The bytes are copied, since an Iobuf keeps its
+  /// refcount in front of its data.
+  fn owned_from_vec(v: Vec<u8>) -> Self::Owned;
+}
@@ -0,0 +1,135 @@
+//! Raw IP addresses, as carried by SOCKS5 and friends. Addresses are always in
+//! network byte order.
+
+use std::io::net::ip::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use raw::RawIobuf;
+
+impl<'a> RawIobuf<'a> {
+  #[inline]
+  pub fn peek_ipv4(&self, pos: u32) -> Result<IpAddr, ()> {
+    let mut a = [0u8; 4];
+    try!(self.peek(pos, &mut a));
+    Ok(Ipv4Addr(a[0], a[1], a[2], a[3]))
+  }
+
+  #[inline]
+  pub fn peek_ipv6(&self, pos: u32) -> Result<IpAddr, ()> {
+    try!(self.check_range_u32(pos, 16));
+    unsafe {
+      let g = |i: u32| -> u16 { self.unsafe_peek_be(pos + 2 * i) };
+      Ok(Ipv6Addr(g(0), g(1), g(2), g(3), g(4), g(5), g(6), g(7)))
+    }
+  }
+
+  #[inline]
+  pub fn peek_socket_addr_v4(&self, pos: u32) -> Result<SocketAddr, ()> {
+    try!(self.check_range_u32(pos, 6));
+    let ip = try!(self.peek_ipv4(pos));
+    let port = unsafe { self.unsafe_peek_be(pos + 4) };
+    Ok(SocketAddr { ip: ip, port: port })
+  }
+
+  #[inline]
+  pub fn consume_ipv4(&mut self) -> Result<IpAddr, ()> {
+    let ip = try!(self.peek_ipv4(0));
+    unsafe { self.unsafe_advance(4); }
+    Ok(ip)
+  }
+
+  #[inline]
+  pub fn consume_ipv6(&mut self) -> Result<IpAddr, ()> {
+    let ip = try!(self.peek_ipv6(0));
+    unsafe { self.unsafe_advance(16); }
+    Ok(ip)
+  }
+
+  #[inline]
+  pub fn consume_socket_addr_v4(&mut self) -> Result<SocketAddr, ()> {
+    let addr = try!(self.peek_socket_addr_v4(0));
+    unsafe { self.unsafe_advance(6); }
+    Ok(addr)
+  }
+
+  /// Writes `ip` at `pos`, returning the number of bytes written: 4 for IPv4,
+  /// and 16 for IPv6.
+  pub fn poke_ip(&self, pos: u32, ip: IpAddr) -> Result<u32, ()> {
+    match ip {
+      Ipv4Addr(a, b, c, d) => {
+        try!(self.poke(pos, &[ a, b, c, d ]));
+        Ok(4)
+      }
+      Ipv6Addr(a, b, c, d, e, f, g, h) => {
+        try!(self.check_range_u32(pos, 16));
+        for (i, &x) in [ a, b, c, d, e, f, g, h ].iter().enumerate() {
+          unsafe { self.unsafe_poke_be(pos + 2 * i as u32, x) };
+        }
+        Ok(16)
+      }
+    }
+  }
+
+  #[inline]
+  pub fn fill_ip(&mut self, ip: IpAddr) -> Result<(), ()> {
+    let len = try!(self.poke_ip(0, ip));
+    unsafe { self.unsafe_advance(len); }
+    Ok(())
+  }
+
+  pub fn poke_socket_addr(&self, pos: u32, addr: SocketAddr) -> Result<u32, ()> {
+    let len = match addr.ip { Ipv4Addr(..) => 4, Ipv6Addr(..) => 16 };
+    try!(self.check_range_u32(pos, len + 2));
+    try!(self.poke_ip(pos, addr.ip));
+    unsafe { self.unsafe_poke_be(pos + len, addr.port) };
+    Ok(len + 2)
+  }
+
+  #[inline]
+  pub fn fill_socket_addr(&mut self, addr: SocketAddr) -> Result<(), ()> {
+    let len = try!(self.poke_socket_addr(0, addr));
+    unsafe { self.unsafe_advance(len); }
+    Ok(())
+  }
+}
+
+#[test]
+fn ip_round_trips() {
+  use iobuf::Iobuf;
+  use impls::{ROIobuf, RWIobuf};
+
+  let v4: IpAddr = "192.168.1.20".parse().unwrap();
+  let v6: IpAddr = "2001:db8::ff00:42:8329".parse().unwrap();
+  let sa: SocketAddr = "10.0.0.1:1080".parse().unwrap();
+
+  let mut b = RWIobuf::new(32);
+  assert_eq!(b.fill_ip(v4), Ok(()));
+  assert_eq!(b.fill_ip(v6), Ok(()));
+  assert_eq!(b.fill_socket_addr(sa), Ok(()));
+  assert_eq!(b.fill_ip(v6), Err(()));
+  assert_eq!(b.len(), 6);
+  b.flip_lo();
+
+  unsafe {
+    assert_eq!(&b.as_window_slice()[..4], [ 192, 168, 1, 20 ]);
+    assert_eq!(&b.as_window_slice()[4..20],
+               [ 0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0xff, 0x00, 0x00, 0x42, 0x83, 0x29 ]);
+    assert_eq!(&b.as_window_slice()[20..], [ 10, 0, 0, 1, 0x04, 0x38 ]);
+  }
+
+  assert_eq!(b.peek_ipv6(4), Ok(v6));
+  assert_eq!(b.consume_ipv4(), Ok(v4));
+  // Failed reads don't move the window.
+  assert_eq!(b.resize(15), Ok(()));
+  assert_eq!(b.consume_ipv6(), Err(()));
+  assert_eq!(b.len(), 15);
+  assert_eq!(b.resize(22), Ok(()));
+  assert_eq!(b.consume_ipv6(), Ok(v6));
+  assert_eq!(b.consume_socket_addr_v4(), Ok(sa));
+  assert!(b.is_empty());
+  assert_eq!(b.consume_ipv4(), Err(()));
+
+  let data = [ 10, 0, 0, 1, 0x04 ];
+  let mut short = ROIobuf::from_slice(&data);
+  assert_eq!(short.consume_socket_addr_v4(), Err(()));
+  assert_eq!(short.len(), 5);
+}
@@ -25,6 +25,31 @@
 //! register/cache usage and faster moves, both of which are critical for
 //! performance.
 //!
+//! There is deliberately no 64-bit variant. Making the offsets generic would
+//! drag the wider type through every bounds check in the hot paths, and a
+//! parallel set of types would double the API. To work with something bigger,
+//! like a 10 GB capture file mapped into memory, keep the mapping as a plain
+//! `&[u8]` with 64-bit offsets, and wrap the part you're currently parsing
+//! with `ROIobuf::from_slice`. Keep each piece under 2 GB, or `from_slice`
+//! will panic. Positions within the piece are still `u32`s; add them to the
+//! piece's 64-bit offset to get a position in the whole mapping:
+//!
+//! ```rust
+//! use std::cmp;
+//! use iobuf::{Iobuf, ROIobuf};
+//!
+//! // Stands in for a huge mapping.
+//! let mapping: Vec<u8> = range(0u32, 1000).map(|i| i as u8).collect();
+//!
+//! let offset: u64 = 600;
+//! let piece_len: u64 = 1 << 20;
+//! let end = cmp::min(offset + piece_len, mapping.len() as u64);
+//! let piece = &mapping[offset as usize..end as usize];
+//! let mut b = ROIobuf::from_slice(piece);
+//! assert_eq!(b.consume_be::<u8>(), Ok(600u32 as u8));
+//! assert_eq!(offset + b.lo() as u64, 601);
+//! ```
+//!
 //! Although this library is designed for efficiency, and hence gives you lots
 //! of ways to omit bounds checks, that does not mean it's recommended you do.
 //! They merely provide a way for you to manually bounds check a whole bunch
@@ -41,20 +66,47 @@
 #![feature(alloc)]
 #![feature(core)]
 #![feature(test)]
+#![feature(asm)]
 
 #![deny(missing_docs)]
 #![allow(unused_features)]
 
 extern crate alloc;
 #[cfg(test)] extern crate test;
-//#[cfg(test)] extern crate quickcheck;
+#[cfg(feature = "quickcheck")] extern crate quickcheck;
+#[cfg(feature = "zlib")] extern crate "libz-sys" as libz;
+#[cfg(feature = "num-bigint")] extern crate "num-bigint" as num_bigint;
 
-pub use raw::Allocator;
-pub use iobuf::Iobuf;
+pub use raw::{Allocator, EnumError, IntArray, IobufState, Ownership, Prim, TagError};
+pub use iobuf::{Iobuf, IobufAlloc, IobufWrite, MutIobuf, ReadFrom};
 pub use impls::{RWIobuf, ROIobuf, AROIobuf, UniqueIobuf};
-pub use ringbuf::IORingbuf;
+pub use ringbuf::{IORingbuf, RingbufState, RingStats};
 pub use bufspan::{BufSpan, ByteIter, SpanIter, SpanMoveIter};
 pub use appendbuf::AppendBuf;
+pub use growable::GrowableRWIobuf;
+pub use words::WordIter;
+pub use chunks::RChunks;
+pub use bytes::{Drain, IntoBytes};
+pub use records::{RecordIter, Records};
+pub use tlv::TlvIter;
+pub use pool::IobufPool;
+pub use ordered::{Endian, OrderedIobuf};
+pub use counting::CountingIobuf;
+pub use dirty::DirtyIobuf;
+pub use elastic::ElasticWriter;
+pub use reader::IobufReader;
+pub use transaction::BufTransaction;
+pub use window::PositionedWindow;
+pub use checksum::{Adler32, Checksummed, Summer};
+pub use crc32c::Crc32c;
+pub use counted::Counted;
+pub use http::{ChunkEvent, ChunkError, consume_http_chunk};
+pub use udp::{Datagram, recv_from_into, send_to_from};
+pub use stream::copy_stream;
+#[cfg(feature = "zlib")] pub use zlib::{Inflater, Deflater, FlateStatus};
+#[cfg(feature = "trace")] pub use traced::Traced;
+
+#[macro_use] mod macros;
 
 mod raw;
 mod iobuf;
@@ -62,3 +114,31 @@ mod impls;
 mod ringbuf;
 mod bufspan;
 mod appendbuf;
+mod growable;
+mod words;
+mod chunks;
+mod pool;
+mod ordered;
+mod counting;
+mod dirty;
+mod elastic;
+mod reader;
+mod transaction;
+mod window;
+mod records;
+mod bytes;
+mod tlv;
+mod checksum;
+mod crc32c;
+mod counted;
+pub mod ops;
+#[cfg(unix)] mod locked;
+#[cfg(all(unix, feature = "guard-pages"))] mod guarded;
+mod http;
+mod udp;
+mod stream;
+mod ip;
+#[cfg(feature = "quickcheck")] mod arbitrary;
+#[cfg(feature = "zlib")] mod zlib;
+#[cfg(feature = "trace")] mod traced;
+#[cfg(feature = "num-bigint")] mod bigint;
@@ -0,0 +1,110 @@
+//! Memory that's locked into RAM with `mlock`, so that secrets (like key
+//! material) held in it are never written out to swap.
+
+use alloc::heap;
+
+use std::intrinsics;
+use std::io::{IoError, IoResult};
+use std::os;
+
+use raw::Allocator;
+
+#[cfg(test)]
+use std::sync::atomic::{AtomicUint, Ordering, ATOMIC_UINT_INIT};
+
+extern {
+  fn mlock(addr: *const u8, len: usize) -> i32;
+  fn munlock(addr: *const u8, len: usize) -> i32;
+}
+
+/// The number of locked allocations which have been released. Used to make
+/// sure that happens exactly once per buffer.
+#[cfg(test)]
+pub static RELEASED: AtomicUint = ATOMIC_UINT_INIT;
+
+/// Rounds `len` up to a whole number of pages.
+///
+/// `mlock` works on whole pages, and locks don't nest. If two locked buffers
+/// shared a page, freeing one would unlock the other. Therefore, every locked
+/// allocation gets pages all to itself.
+#[inline]
+fn round_to_pages(len: usize) -> usize {
+  let page = os::page_size();
+  (len + page - 1) / page * page
+}
+
+/// Allocates page-aligned memory, and zeroes and unlocks it when it's freed.
+/// The locking itself is done by `lock` once the allocation has been made, so
+/// that an error can be reported.
+pub struct LockedAllocator;
+
+impl Allocator for LockedAllocator {
+  fn allocate(&self, len: usize, _align: usize) -> *mut u8 {
+    unsafe {
+      heap::allocate(round_to_pages(len), os::page_size())
+    }
+  }
+
+  fn deallocate(&self, ptr: *mut u8, len: usize, _align: usize) {
+    unsafe {
+      // A volatile write, so the zeroing can't be optimized away.
+      intrinsics::volatile_set_memory(ptr, 0, len);
+      munlock(ptr as *const u8, round_to_pages(len));
+      heap::deallocate(ptr, round_to_pages(len), os::page_size());
+    }
+
+    release_hook();
+  }
+}
+
+#[cfg(test)]
+#[inline]
+fn release_hook() {
+  RELEASED.fetch_add(1, Ordering::SeqCst);
+}
+
+#[cfg(not(test))]
+#[inline(always)]
+fn release_hook() {}
+
+/// Locks an allocation made by `LockedAllocator` into RAM. This fails if the
+/// `RLIMIT_MEMLOCK` budget has been exceeded.
+pub unsafe fn lock(ptr: *const u8, len: usize) -> IoResult<()> {
+  if mlock(ptr, round_to_pages(len)) == 0 {
+    Ok(())
+  } else {
+    Err(IoError::last_error())
+  }
+}
+
+#[test]
+fn locked_buffers_are_released_exactly_once() {
+  use iobuf::Iobuf;
+  use impls::RWIobuf;
+
+  let before = RELEASED.load(Ordering::SeqCst);
+
+  {
+    let a = match RWIobuf::new_locked(100) {
+      Ok(a)  => a,
+      // Not allowed to lock any memory here, so there's nothing to test.
+      Err(_) => return,
+    };
+    assert_eq!(RELEASED.load(Ordering::SeqCst), before);
+
+    assert_eq!(a.poke_be(0, 0xDEADBEEFu32), Ok(()));
+
+    let b = a.clone();
+    let mut c = b.clone();
+    assert_eq!(c.advance(4), Ok(()));
+    drop(a);
+    drop(b);
+    assert_eq!(RELEASED.load(Ordering::SeqCst), before);
+
+    let d = c.read_only();
+    assert_eq!(d.len(), 96);
+    assert_eq!(RELEASED.load(Ordering::SeqCst), before);
+  }
+
+  assert_eq!(RELEASED.load(Ordering::SeqCst), before + 1);
+}
@@ -0,0 +1,285 @@
+/// Declares a C-like enum with explicit discriminants, along with a
+/// `from_prim` function mapping a raw value to the matching variant, or
+/// `None` if there isn't one. `from_prim` plugs straight into
+/// `Iobuf::consume_be_enum` and `Iobuf::consume_le_enum`.
+///
+/// The enum derives `Copy`, `Clone`, `PartialEq`, `Eq`, and `Debug`.
+///
+/// ```rust
+/// #[macro_use] extern crate iobuf;
+///
+/// use iobuf::{EnumError, Iobuf, ROIobuf};
+///
+/// iobuf_enum! {
+///   enum MsgType: u8 {
+///     Hello   = 1,
+///     Data    = 2,
+///     Goodbye = 0xFF,
+///   }
+/// }
+///
+/// fn main() {
+///   let data = [ 0x02, 0x07 ];
+///   let mut b = ROIobuf::from_slice(&data);
+///
+///   assert_eq!(b.consume_be_enum(MsgType::from_prim), Ok(MsgType::Data));
+///   assert_eq!(b.consume_be_enum(MsgType::from_prim), Err(EnumError::Invalid(7)));
+///   assert_eq!(MsgType::from_prim(0xFF), Some(MsgType::Goodbye));
+/// }
+/// ```
+#[macro_export]
+macro_rules! iobuf_enum {
+  (pub enum $name:ident: $repr:ty { $($variant:ident = $val:expr),+ $(,)* }) => {
+    #[derive(Copy, Clone, PartialEq, Eq, Debug)]
+    pub enum $name { $($variant = $val),+ }
+
+    impl $name {
+      /// The variant whose discriminant is `x`, if there is one.
+      #[allow(dead_code)]
+      pub fn from_prim(x: $repr) -> Option<$name> {
+        $(if x == $val { return Some($name::$variant) })+
+        None
+      }
+    }
+  };
+  (enum $name:ident: $repr:ty { $($variant:ident = $val:expr),+ $(,)* }) => {
+    #[derive(Copy, Clone, PartialEq, Eq, Debug)]
+    enum $name { $($variant = $val),+ }
+
+    impl $name {
+      #[allow(dead_code)]
+      fn from_prim(x: $repr) -> Option<$name> {
+        $(if x == $val { return Some($name::$variant) })+
+        None
+      }
+    }
+  };
+}
+
+/// Declares a struct mirroring a fixed-layout wire header, along with
+/// functions to read it from and write it to Iobufs, so that the two can't
+/// drift out of sync.
+///
+/// Each field is an integer type, optionally followed by `be` or `le` for its
+/// byte order (big-endian if neither is given), or a byte array, written
+/// `[u8; N]`. The fields are laid out in order, with no padding.
+///
+/// The struct gets:
+///
+///   * `wire_len() -> u32`, the number of bytes it takes up.
+///   * `consume_from(&mut B) -> Result<Self, ()>`, which reads it from the
+///     front of an Iobuf's window and advances past it.
+///   * `fill_into(&self, &mut RWIobuf) -> Result<(), ()>`, which writes it to
+///     the front of a `RWIobuf`'s window and advances past it.
+///
+/// Both do one bounds check up front, for the whole struct, and then unchecked
+/// reads or writes. If the window is too short, nothing is read or written,
+/// and the window isn't moved.
+///
+/// All the fields are public. The struct derives `Copy`, `Clone`, `PartialEq`,
+/// `Eq`, and `Debug`.
+///
+/// ```rust
+/// #[macro_use] extern crate iobuf;
+///
+/// use iobuf::{Iobuf, ROIobuf, RWIobuf};
+///
+/// define_packet! {
+///   struct Udp {
+///     src_port: u16 be,
+///     dst_port: u16 be,
+///     len:      u16,
+///     checksum: u16 le,
+///   }
+/// }
+///
+/// fn main() {
+///   let data = [ 0x00, 0x35, 0xC0, 0x01, 0x00, 0x1C, 0xCD, 0xAB, 0xFF ];
+///   let mut b = ROIobuf::from_slice(&data);
+///
+///   let h = Udp::consume_from(&mut b).unwrap();
+///   assert_eq!(h, Udp { src_port: 53, dst_port: 0xC001, len: 28, checksum: 0xABCD });
+///   assert_eq!(b.len(), 1);
+///   assert_eq!(Udp::consume_from(&mut b), Err(()));
+///
+///   let mut out = RWIobuf::new(Udp::wire_len() as usize);
+///   assert_eq!(h.fill_into(&mut out), Ok(()));
+///   out.flip_lo();
+///   unsafe { assert_eq!(out.as_window_slice(), &data[..8]); }
+/// }
+/// ```
+#[macro_export]
+macro_rules! define_packet {
+  (pub struct $name:ident { $($body:tt)* }) => {
+    define_packet!(@parse [pub] $name [] $($body)*);
+  };
+  (struct $name:ident { $($body:tt)* }) => {
+    define_packet!(@parse [] $name [] $($body)*);
+  };
+
+  // Normalizes each field into `{ name: type => (kind) }`.
+  (@parse $vis:tt $name:ident [$($acc:tt)*] $f:ident : $t:ident be, $($rest:tt)*) => {
+    define_packet!(@parse $vis $name [$($acc)* { $f: $t => (be) }] $($rest)*);
+  };
+  (@parse $vis:tt $name:ident [$($acc:tt)*] $f:ident : $t:ident be) => {
+    define_packet!(@parse $vis $name [$($acc)* { $f: $t => (be) }]);
+  };
+  (@parse $vis:tt $name:ident [$($acc:tt)*] $f:ident : $t:ident le, $($rest:tt)*) => {
+    define_packet!(@parse $vis $name [$($acc)* { $f: $t => (le) }] $($rest)*);
+  };
+  (@parse $vis:tt $name:ident [$($acc:tt)*] $f:ident : $t:ident le) => {
+    define_packet!(@parse $vis $name [$($acc)* { $f: $t => (le) }]);
+  };
+  (@parse $vis:tt $name:ident [$($acc:tt)*] $f:ident : $t:ident, $($rest:tt)*) => {
+    define_packet!(@parse $vis $name [$($acc)* { $f: $t => (be) }] $($rest)*);
+  };
+  (@parse $vis:tt $name:ident [$($acc:tt)*] $f:ident : $t:ident) => {
+    define_packet!(@parse $vis $name [$($acc)* { $f: $t => (be) }]);
+  };
+  (@parse $vis:tt $name:ident [$($acc:tt)*] $f:ident : [u8; $n:expr], $($rest:tt)*) => {
+    define_packet!(@parse $vis $name [$($acc)* { $f: [u8; $n] => (bytes $n) }] $($rest)*);
+  };
+  (@parse $vis:tt $name:ident [$($acc:tt)*] $f:ident : [u8; $n:expr]) => {
+    define_packet!(@parse $vis $name [$($acc)* { $f: [u8; $n] => (bytes $n) }]);
+  };
+
+  (@parse [pub] $name:ident [$({ $f:ident : $t:ty => $k:tt })*]) => {
+    #[derive(Copy, Clone, PartialEq, Eq, Debug)]
+    pub struct $name { $(pub $f: $t),* }
+    define_packet!(@impl $name [$({ $f: $t => $k })*]);
+  };
+  (@parse [] $name:ident [$({ $f:ident : $t:ty => $k:tt })*]) => {
+    #[derive(Copy, Clone, PartialEq, Eq, Debug)]
+    struct $name { $(pub $f: $t),* }
+    define_packet!(@impl $name [$({ $f: $t => $k })*]);
+  };
+
+  (@impl $name:ident [$({ $f:ident : $t:ty => $k:tt })*]) => {
+    impl $name {
+      /// The number of bytes this takes up in an Iobuf.
+      #[allow(dead_code)]
+      #[inline]
+      pub fn wire_len() -> u32 {
+        0 $(+ ::std::mem::size_of::<$t>() as u32)*
+      }
+
+      /// Reads this from the front of the window, and advances past it.
+      #[allow(dead_code)]
+      pub fn consume_from<B: $crate::Iobuf>(b: &mut B) -> Result<$name, ()> {
+        try!(b.check_range(0, $name::wire_len()));
+        unsafe {
+          Ok($name { $($f: define_packet!(@consume b, $k)),* })
+        }
+      }
+
+      /// Writes this to the front of the window, and advances past it.
+      #[allow(dead_code)]
+      pub fn fill_into(&self, b: &mut $crate::RWIobuf) -> Result<(), ()> {
+        try!($crate::Iobuf::check_range(&*b, 0, $name::wire_len()));
+        unsafe {
+          $(define_packet!(@fill b, self.$f, $k);)*
+        }
+        Ok(())
+      }
+    }
+  };
+
+  (@consume $b:ident, (be)) => { $b.unsafe_consume_be() };
+  (@consume $b:ident, (le)) => { $b.unsafe_consume_le() };
+  (@consume $b:ident, (bytes $n:expr)) => {{
+    let mut a = [0u8; $n];
+    $b.unsafe_consume(&mut a);
+    a
+  }};
+
+  (@fill $b:ident, $x:expr, (be)) => { $b.unsafe_fill_be($x) };
+  (@fill $b:ident, $x:expr, (le)) => { $b.unsafe_fill_le($x) };
+  (@fill $b:ident, $x:expr, (bytes $n:expr)) => { $b.unsafe_fill(&$x) };
+}
+
+#[cfg(test)]
+mod test {
+  use iobuf::Iobuf;
+  use impls::{ROIobuf, RWIobuf};
+
+  define_packet! {
+    struct Tcp {
+      src_port: u16 be,
+      dst_port: u16 be,
+      seq:      u32 be,
+      ack:      u32 be,
+      offset:   u8,
+      flags:    u8,
+      window:   u16 be,
+      checksum: u16 be,
+      urgent:   u16 be,
+    }
+  }
+
+  define_packet! {
+    pub struct Ethernet {
+      dst:       [u8; 6],
+      src:       [u8; 6],
+      ethertype: u16 be
+    }
+  }
+
+  define_packet! {
+    struct LeRecord {
+      magic: u32 le,
+      count: u64 le,
+      tag:   [u8; 2],
+      kind:  i16 le,
+    }
+  }
+
+  #[test]
+  fn tcp_round_trip() {
+    let data = [
+      0x01, 0xBB, 0xD4, 0x31, 0x00, 0x00, 0x10, 0x00, 0x00, 0x00, 0x20, 0x00,
+      0x50, 0x18, 0x01, 0xF5, 0xAB, 0xCD, 0x00, 0x00,
+    ];
+    assert_eq!(Tcp::wire_len(), 20);
+
+    let mut b = ROIobuf::from_slice(&data);
+    let h = Tcp::consume_from(&mut b).unwrap();
+    assert_eq!(h, Tcp {
+      src_port: 443, dst_port: 0xD431, seq: 0x1000, ack: 0x2000,
+      offset: 0x50, flags: 0x18, window: 0x01F5, checksum: 0xABCD, urgent: 0,
+    });
+    assert!(b.is_empty());
+
+    let mut out = RWIobuf::new(20);
+    assert_eq!(h.fill_into(&mut out), Ok(()));
+    assert_eq!(h.fill_into(&mut out), Err(()));
+    out.flip_lo();
+    unsafe { assert_eq!(out.as_window_slice(), &data[]); }
+  }
+
+  #[test]
+  fn arrays_and_little_endian() {
+    let e = Ethernet { dst: [0xFF; 6], src: [1, 2, 3, 4, 5, 6], ethertype: 0x0800 };
+    let r = LeRecord { magic: 0xFEEDFACE, count: 3, tag: [b'o', b'k'], kind: -2 };
+    assert_eq!(Ethernet::wire_len(), 14);
+    assert_eq!(LeRecord::wire_len(), 16);
+
+    let mut out = RWIobuf::new(30);
+    assert_eq!(e.fill_into(&mut out), Ok(()));
+    assert_eq!(r.fill_into(&mut out), Ok(()));
+    out.flip_lo();
+
+    assert_eq!(out.peek_be::<u16>(12), Ok(0x0800));
+    assert_eq!(out.peek_be::<u32>(14), Ok(0xCEFAEDFE));
+
+    // A short window reads nothing.
+    let mut short = out.clone();
+    assert_eq!(short.resize(29), Ok(()));
+    assert_eq!(Ethernet::consume_from(&mut short), Ok(e));
+    assert_eq!(LeRecord::consume_from(&mut short), Err(()));
+    assert_eq!(short.len(), 15);
+
+    assert_eq!(Ethernet::consume_from(&mut out), Ok(e));
+    assert_eq!(LeRecord::consume_from(&mut out), Ok(r));
+    assert!(out.is_empty());
+  }
+}
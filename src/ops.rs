@@ -0,0 +1,121 @@
+//! Operations between two Iobufs.
+//!
+//! These don't belong to either buffer more than the other, so they're free
+//! functions instead of methods.
+
+use std::cmp::Ordering;
+use std::ptr;
+
+use iobuf::{Iobuf, MutIobuf};
+
+/// Copies the first `len` bytes of `src`'s window to the start of `dst`'s
+/// window. Neither window is moved.
+///
+/// The two Iobufs may share a buffer, and their windows may overlap. The copy
+/// is done as if through an intermediate buffer.
+///
+/// An error is returned, and nothing is copied, if either window is shorter
+/// than `len`.
+///
+/// ```rust
+/// use iobuf::{ops, Iobuf, ROIobuf, RWIobuf};
+///
+/// let src = ROIobuf::from_str("hello");
+/// let dst = RWIobuf::from_str_copy("world");
+///
+/// assert_eq!(ops::copy(&src, &dst, 3), Ok(()));
+/// unsafe { assert_eq!(dst.as_window_slice(), b"helld"); }
+///
+/// assert_eq!(ops::copy(&src, &dst, 6), Err(()));
+/// ```
+pub fn copy<S: Iobuf, D: MutIobuf>(src: &S, dst: &D, len: u32) -> Result<(), ()> {
+  try!(src.check_range(0, len));
+  try!(dst.check_range(0, len));
+  // Slices would claim the two windows don't alias, so this sticks to raw
+  // pointers.
+  unsafe {
+    ptr::copy_memory(
+      dst.ptr().offset(dst.lo() as isize),
+      src.ptr().offset(src.lo() as isize) as *const u8,
+      len as usize);
+  }
+  Ok(())
+}
+
+/// Like `copy`, but also advances both windows past the copied bytes.
+///
+/// ```rust
+/// use iobuf::{ops, Iobuf, ROIobuf, RWIobuf};
+///
+/// let mut src = ROIobuf::from_str("hello");
+/// let mut dst = RWIobuf::new(4);
+///
+/// assert_eq!(ops::transfer(&mut src, &mut dst, 3), Ok(()));
+/// assert_eq!(ops::transfer(&mut src, &mut dst, 2), Err(()));
+/// assert_eq!(ops::transfer(&mut src, &mut dst, 1), Ok(()));
+///
+/// unsafe { assert_eq!(src.as_window_slice(), b"o"); }
+/// dst.flip_lo();
+/// unsafe { assert_eq!(dst.as_window_slice(), b"hell"); }
+/// ```
+pub fn transfer<S: Iobuf, D: MutIobuf>(src: &mut S, dst: &mut D, len: u32) -> Result<(), ()> {
+  try!(copy(src, dst, len));
+  unsafe {
+    src.unsafe_advance(len);
+    dst.unsafe_advance(len);
+  }
+  Ok(())
+}
+
+/// Compares the windows of two Iobufs byte-by-byte, lexicographically. A
+/// window which is a prefix of the other is less.
+///
+/// ```rust
+/// use std::cmp::Ordering;
+/// use iobuf::{ops, ROIobuf, RWIobuf};
+///
+/// let a = ROIobuf::from_str("abc");
+/// let b = RWIobuf::from_str_copy("abd");
+///
+/// assert_eq!(ops::compare(&a, &b), Ordering::Less);
+/// assert_eq!(ops::compare(&b, &a), Ordering::Greater);
+/// assert_eq!(ops::compare(&a, &a), Ordering::Equal);
+/// assert_eq!(ops::compare(&ROIobuf::from_str("ab"), &a), Ordering::Less);
+/// ```
+pub fn compare<A: Iobuf, B: Iobuf>(a: &A, b: &B) -> Ordering {
+  a.cmp_window(b)
+}
+
+#[test]
+fn copy_overlapping() {
+  use impls::RWIobuf;
+
+  let b = RWIobuf::from_str_copy("0123456789");
+
+  // Forwards, onto itself.
+  let mut dst = b.clone();
+  assert_eq!(dst.advance(2), Ok(()));
+  assert_eq!(copy(&b, &dst, 6), Ok(()));
+  unsafe { assert_eq!(b.as_window_slice(), b"0101234589"); }
+
+  // Backwards, onto itself.
+  let mut src = b.clone();
+  assert_eq!(src.advance(4), Ok(()));
+  assert_eq!(copy(&src, &b, 6), Ok(()));
+  unsafe { assert_eq!(b.as_window_slice(), b"2345894589"); }
+
+  // The same window.
+  assert_eq!(copy(&b, &b, 10), Ok(()));
+  unsafe { assert_eq!(b.as_window_slice(), b"2345894589"); }
+
+  // Transferring within one buffer, like compacting it by hand.
+  let mut src = b.clone();
+  let mut dst = b.clone();
+  assert_eq!(src.advance(5), Ok(()));
+  assert_eq!(transfer(&mut src, &mut dst, 5), Ok(()));
+  assert!(src.is_empty());
+  assert_eq!(dst.lo(), 5);
+  unsafe { assert_eq!(b.as_window_slice(), b"9458994589"); }
+
+  assert_eq!(compare(&b, &dst), Ordering::Greater);
+}
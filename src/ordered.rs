@@ -0,0 +1,156 @@
+use raw::Prim;
+use iobuf::Iobuf;
+use impls::RWIobuf;
+
+/// A byte order.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Endian {
+  /// Most significant byte first. Also known as network byte order.
+  Big,
+  /// Least significant byte first.
+  Little,
+}
+
+/// An Iobuf which remembers a byte order, so that primitives can be read and
+/// written without picking `_be` or `_le` at every call site.
+///
+/// This is handy for formats whose byte order is given in a file header: read
+/// the header, `set_default_order`, then read all the fields with plain
+/// `peek`/`poke`/`fill`/`consume`.
+///
+/// The order is kept in this wrapper, rather than in the Iobuf itself, so that
+/// Iobufs stay 24 bytes. It starts out as `Endian::Big`.
+///
+/// Mixing the unsuffixed functions with the suffixed ones on the underlying
+/// Iobuf (through `get_ref` and `get_mut`) is fine. They all share the same
+/// window, and the suffixed functions ignore the stored order.
+///
+/// ```rust
+/// use iobuf::{Endian, Iobuf, OrderedIobuf, ROIobuf};
+///
+/// let data = [ 0x01, 0x02, 0x03, 0x04, 0x05, 0x06 ];
+/// let mut b = OrderedIobuf::new(ROIobuf::from_slice(&data));
+///
+/// assert_eq!(b.consume::<u16>(), Ok(0x0102));
+///
+/// b.set_default_order(Endian::Little);
+/// assert_eq!(b.consume::<u16>(), Ok(0x0403));
+///
+/// assert_eq!(b.get_mut().consume_be::<u16>(), Ok(0x0506));
+/// assert!(b.get_ref().is_empty());
+/// ```
+pub struct OrderedIobuf<Buf> {
+  buf:   Buf,
+  order: Endian,
+}
+
+impl<Buf: Iobuf> OrderedIobuf<Buf> {
+  /// Wraps an Iobuf, with a big-endian default order.
+  #[inline]
+  pub fn new(buf: Buf) -> OrderedIobuf<Buf> {
+    OrderedIobuf::with_order(buf, Endian::Big)
+  }
+
+  /// Wraps an Iobuf, with the given default order.
+  #[inline]
+  pub fn with_order(buf: Buf, order: Endian) -> OrderedIobuf<Buf> {
+    OrderedIobuf { buf: buf, order: order }
+  }
+
+  /// Sets the byte order used by the unsuffixed functions from now on.
+  #[inline(always)]
+  pub fn set_default_order(&mut self, order: Endian) { self.order = order }
+
+  /// The byte order used by the unsuffixed functions.
+  #[inline(always)]
+  pub fn default_order(&self) -> Endian { self.order }
+
+  /// The underlying Iobuf.
+  #[inline(always)]
+  pub fn get_ref(&self) -> &Buf { &self.buf }
+
+  /// The underlying Iobuf. Moving its window moves ours.
+  #[inline(always)]
+  pub fn get_mut(&mut self) -> &mut Buf { &mut self.buf }
+
+  /// Unwraps the underlying Iobuf, forgetting the byte order.
+  #[inline(always)]
+  pub fn into_inner(self) -> Buf { self.buf }
+
+  /// Reads a primitive at `pos` bytes into the window, in the default order.
+  /// See `Iobuf::peek_be`.
+  #[inline]
+  pub fn peek<T: Prim>(&self, pos: u32) -> Result<T, ()> {
+    match self.order {
+      Endian::Big    => self.buf.peek_be(pos),
+      Endian::Little => self.buf.peek_le(pos),
+    }
+  }
+
+  /// Reads a primitive from the front of the window in the default order, and
+  /// advances the window past it. See `Iobuf::consume_be`.
+  #[inline]
+  pub fn consume<T: Prim>(&mut self) -> Result<T, ()> {
+    match self.order {
+      Endian::Big    => self.buf.consume_be(),
+      Endian::Little => self.buf.consume_le(),
+    }
+  }
+}
+
+impl<'a> OrderedIobuf<RWIobuf<'a>> {
+  /// Writes a primitive at `pos` bytes into the window, in the default order.
+  /// See `RWIobuf::poke_be`.
+  #[inline]
+  pub fn poke<T: Prim>(&self, pos: u32, t: T) -> Result<(), ()> {
+    match self.order {
+      Endian::Big    => self.buf.poke_be(pos, t),
+      Endian::Little => self.buf.poke_le(pos, t),
+    }
+  }
+
+  /// Writes a primitive to the front of the window in the default order, and
+  /// advances the window past it. See `RWIobuf::fill_be`.
+  #[inline]
+  pub fn fill<T: Prim>(&mut self, t: T) -> Result<(), ()> {
+    match self.order {
+      Endian::Big    => self.buf.fill_be(t),
+      Endian::Little => self.buf.fill_le(t),
+    }
+  }
+}
+
+#[test]
+fn flipping_the_order() {
+  use impls::ROIobuf;
+
+  let data = [ 0x12, 0x34, 0x56, 0x78 ];
+
+  let mut b = OrderedIobuf::new(ROIobuf::from_slice(&data));
+  assert_eq!(b.default_order(), Endian::Big);
+  assert_eq!(b.peek::<u32>(0), Ok(0x12345678));
+  b.set_default_order(Endian::Little);
+  assert_eq!(b.peek::<u32>(0), Ok(0x78563412));
+  assert_eq!(b.peek::<u32>(1), Err(()));
+
+  b.set_default_order(Endian::Big);
+  assert_eq!(b.consume::<u16>(), Ok(0x1234));
+  b.set_default_order(Endian::Little);
+  assert_eq!(b.consume::<u16>(), Ok(0x7856));
+  assert_eq!(b.consume::<u8>(), Err(()));
+
+  let mut w = OrderedIobuf::with_order(RWIobuf::new(6), Endian::Little);
+  assert_eq!(w.fill(0x0102u16), Ok(()));
+  w.set_default_order(Endian::Big);
+  assert_eq!(w.fill(0x0304u16), Ok(()));
+  assert_eq!(w.get_mut().fill_le(0x0506u16), Ok(()));
+  assert_eq!(w.fill(0u8), Err(()));
+
+  let mut w = w.into_inner();
+  w.flip_lo();
+  unsafe { assert_eq!(w.as_window_slice(), [ 0x02, 0x01, 0x03, 0x04, 0x06, 0x05 ]); }
+
+  let w = OrderedIobuf::with_order(w, Endian::Little);
+  assert_eq!(w.poke(0, 0xAABBu16), Ok(()));
+  assert_eq!(w.get_ref().peek_be::<u16>(0), Ok(0xBBAA));
+}
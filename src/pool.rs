@@ -0,0 +1,135 @@
+use iobuf::Iobuf;
+use impls::{self, RWIobuf};
+
+/// A pool of recycled buffers.
+///
+/// Allocating a fresh buffer for every message (or every `deep_clone`) can be
+/// expensive. Instead, `put` buffers back into a pool once you're done with
+/// them, and `take` them back out when you need a new one. In steady state,
+/// no allocations are made at all.
+///
+/// Only buffers which are uniquely owned are recycled. If a buffer is still
+/// referenced by any other Iobuf, or wasn't allocated by us, `put` just drops
+/// it.
+///
+/// ```rust
+/// use iobuf::{IobufPool, Iobuf};
+///
+/// let mut pool = IobufPool::new();
+///
+/// let b = pool.take(100);
+/// let p = b.ptr();
+/// pool.put(b);
+/// assert_eq!(pool.len(), 1);
+///
+/// let b = pool.take(50);
+/// assert_eq!(b.ptr(), p);
+/// assert_eq!(b.len(), 50);
+/// assert_eq!(pool.len(), 0);
+/// ```
+pub struct IobufPool {
+  free: Vec<RWIobuf<'static>>,
+}
+
+impl IobufPool {
+  /// Creates a new, empty pool.
+  #[inline]
+  pub fn new() -> IobufPool {
+    IobufPool { free: Vec::new() }
+  }
+
+  /// The number of buffers waiting in the pool.
+  #[inline]
+  pub fn len(&self) -> usize {
+    self.free.len()
+  }
+
+  /// Returns a buffer to the pool. The buffer is only kept if no other Iobuf
+  /// references it. Otherwise, it's dropped.
+  #[inline]
+  pub fn put<Buf: Iobuf>(&mut self, buf: Buf) {
+    match buf.unique() {
+      Ok(b)  => self.free.push(impls::reclaim_unique(b)),
+      Err(_) => {},
+    }
+  }
+
+  /// Takes a buffer with a window (and limits) of `len` bytes out of the pool,
+  /// with undefined contents. This is just like `RWIobuf::new`, except that a
+  /// recycled buffer is used if one is big enough.
+  ///
+  /// If no buffer in the pool is big enough, a new one is allocated, and one of
+  /// the buffers which is too small is thrown away, so that the pool will
+  /// eventually fill up with buffers of the right size.
+  pub fn take(&mut self, len: u32) -> RWIobuf<'static> {
+    match self.free.iter().position(|b| b.cap() >= len) {
+      Some(i) => {
+        let mut b = self.free.swap_remove(i);
+        b.set_limits_and_window((0, len), (0, len)).unwrap();
+        b
+      }
+      None => {
+        self.free.pop();
+        RWIobuf::new(len as usize)
+      }
+    }
+  }
+
+  /// Copies the limits of `src` into a buffer from the pool. The window is
+  /// in the same place, relative to the limits, as `src`'s.
+  pub fn deep_clone<Buf: Iobuf>(&mut self, src: &Buf) -> RWIobuf<'static> {
+    let mut b = self.take(src.cap());
+
+    unsafe {
+      b.unsafe_poke(0, src.as_limit_slice());
+    }
+
+    let lo_min = src.lo_min();
+    b.set_limits_and_window((0, src.cap()), (src.lo() - lo_min, src.hi() - lo_min)).unwrap();
+    b
+  }
+}
+
+#[test]
+fn pool_reuse_across_clones() {
+  use impls::ROIobuf;
+
+  let mut pool = IobufPool::new();
+
+  let mut src = ROIobuf::from_str("hello, world");
+  assert_eq!(src.advance(7), Ok(()));
+
+  let a = src.deep_clone_from_pool(&mut pool);
+  unsafe {
+    assert_eq!(a.as_window_slice(), b"world");
+    assert_eq!(a.as_limit_slice(), b"hello, world");
+  }
+
+  let p = a.ptr();
+
+  // Still shared, so it can't be recycled.
+  let a2 = a.clone();
+  pool.put(a);
+  assert_eq!(pool.len(), 0);
+
+  pool.put(a2);
+  assert_eq!(pool.len(), 1);
+
+  for _ in range(0u32, 10) {
+    let b = src.deep_clone_from_pool(&mut pool);
+    assert_eq!(b.ptr(), p);
+    assert_eq!(pool.len(), 0);
+    unsafe { assert_eq!(b.as_window_slice(), b"world"); }
+    pool.put(b);
+  }
+
+  // Not heap allocated, so it can't be recycled.
+  pool.put(src);
+  assert_eq!(pool.len(), 1);
+
+  // Too big for anything in the pool.
+  let c = pool.take(100);
+  assert!(c.ptr() != p);
+  assert_eq!(c.len(), 100);
+  assert_eq!(pool.len(), 0);
+}
@@ -1,15 +1,22 @@
 use alloc::heap;
 
+use std::cmp::Ordering as CmpOrdering;
+use std::ffi::CString;
 use std::fmt::{self, Formatter};
+#[cfg(unix)] use std::io::IoResult;
 use std::marker::{NoCopy, ContravariantLifetime};
 use std::mem;
-use std::num::Int;
+use std::num::{Float, Int};
 use std::ptr;
 use std::raw::{self, Repr};
 use std::u32;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::sync::atomic::{self, AtomicUint, Ordering};
 
+use crc32c;
+use words::WordIter;
+#[cfg(unix)] use locked;
+
 #[cfg(target_pointer_width = "64")]
 const TARGET_WORD_SIZE: usize = 64;
 
@@ -34,6 +41,273 @@ pub trait Allocator: Sync + Send {
   fn deallocate(&self, ptr: *mut u8, len: usize, align: usize);
 }
 
+/// A fixed-size value, which can be read out of and written into an Iobuf with
+/// the `_be` and `_le` functions (`peek_be`, `fill_le`, `consume_be`, etc).
+///
+/// This is implemented for all the primitive integer types, and for `f32` and
+/// `f64`, which are sent as their IEEE 754 bits. Implement it for
+/// your own types, like a `SequenceNumber(u32)` newtype, by converting to and
+/// from the integer they're sent as. Then they can be read and written
+/// directly, keeping your fields strongly typed all the way through.
+///
+/// ```rust
+/// use iobuf::{Iobuf, Prim, ROIobuf};
+///
+/// #[derive(Copy, PartialEq, Debug)]
+/// struct SequenceNumber(u32);
+///
+/// impl Prim for SequenceNumber {
+///   type Repr = u32;
+///   fn from_repr(r: u32) -> SequenceNumber { SequenceNumber(r) }
+///   fn to_repr(self) -> u32 { self.0 }
+/// }
+///
+/// let data = [ 0x00, 0x00, 0x01, 0x00 ];
+/// let mut b = ROIobuf::from_slice(&data);
+/// assert_eq!(b.consume_be(), Ok(SequenceNumber(256)));
+/// ```
+pub trait Prim: Copy {
+  /// The integer this type is sent as. Its size is the number of bytes the
+  /// type takes up in an Iobuf.
+  type Repr: Int;
+
+  /// Converts from the integer this type is sent as.
+  fn from_repr(r: Self::Repr) -> Self;
+
+  /// Converts to the integer this type is sent as.
+  fn to_repr(self) -> Self::Repr;
+}
+
+macro_rules! int_prim_impls {
+  ($($t:ty)+) => {
+    $(
+      impl Prim for $t {
+        type Repr = $t;
+
+        #[inline(always)]
+        fn from_repr(r: $t) -> $t { r }
+
+        #[inline(always)]
+        fn to_repr(self) -> $t { self }
+      }
+    )+
+  }
+}
+
+int_prim_impls! { u8 u16 u32 u64 usize i8 i16 i32 i64 isize }
+
+macro_rules! float_prim_impls {
+  ($($t:ty => $r:ty),+) => {
+    $(
+      impl Prim for $t {
+        type Repr = $r;
+
+        #[inline(always)]
+        fn from_repr(r: $r) -> $t { unsafe { mem::transmute(r) } }
+
+        #[inline(always)]
+        fn to_repr(self) -> $r { unsafe { mem::transmute(self) } }
+      }
+    )+
+  }
+}
+
+float_prim_impls! { f32 => u32, f64 => u64 }
+
+/// The number of bytes a `Prim` takes up in an Iobuf.
+#[inline(always)]
+pub fn prim_len<T: Prim>() -> usize {
+  mem::size_of::<T::Repr>()
+}
+
+/// The number of elements in an `IntArray`.
+#[inline(always)]
+fn array_len<A: IntArray>() -> usize {
+  mem::size_of::<A>() / mem::size_of::<A::Elem>()
+}
+
+/// A fixed-size array of primitives, which can be read all at once with
+/// `consume_be_array` or `consume_le_array`. This is implemented for arrays of
+/// up to 32 elements.
+pub trait IntArray: Copy {
+  /// The type of each element.
+  type Elem: Prim;
+
+  /// Views the array as a slice of its elements.
+  fn as_mut_elems<'a>(&'a mut self) -> &'a mut [Self::Elem];
+}
+
+macro_rules! int_array_impls {
+  ($($n:expr)+) => {
+    $(
+      impl<T: Prim> IntArray for [T; $n] {
+        type Elem = T;
+
+        #[inline(always)]
+        fn as_mut_elems<'a>(&'a mut self) -> &'a mut [T] { self }
+      }
+    )+
+  }
+}
+
+int_array_impls! {
+   1  2  3  4  5  6  7  8  9 10 11 12 13 14 15 16
+  17 18 19 20 21 22 23 24 25 26 27 28 29 30 31 32
+}
+
+/// Maps every byte to its ASCII lowercase equivalent. Bytes outside of `A-Z`
+/// map to themselves.
+static ASCII_LOWER_MAP: [u8; 256] = [
+  0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d, 0x0e, 0x0f,
+  0x10, 0x11, 0x12, 0x13, 0x14, 0x15, 0x16, 0x17, 0x18, 0x19, 0x1a, 0x1b, 0x1c, 0x1d, 0x1e, 0x1f,
+  0x20, 0x21, 0x22, 0x23, 0x24, 0x25, 0x26, 0x27, 0x28, 0x29, 0x2a, 0x2b, 0x2c, 0x2d, 0x2e, 0x2f,
+  0x30, 0x31, 0x32, 0x33, 0x34, 0x35, 0x36, 0x37, 0x38, 0x39, 0x3a, 0x3b, 0x3c, 0x3d, 0x3e, 0x3f,
+  0x40, 0x61, 0x62, 0x63, 0x64, 0x65, 0x66, 0x67, 0x68, 0x69, 0x6a, 0x6b, 0x6c, 0x6d, 0x6e, 0x6f,
+  0x70, 0x71, 0x72, 0x73, 0x74, 0x75, 0x76, 0x77, 0x78, 0x79, 0x7a, 0x5b, 0x5c, 0x5d, 0x5e, 0x5f,
+  0x60, 0x61, 0x62, 0x63, 0x64, 0x65, 0x66, 0x67, 0x68, 0x69, 0x6a, 0x6b, 0x6c, 0x6d, 0x6e, 0x6f,
+  0x70, 0x71, 0x72, 0x73, 0x74, 0x75, 0x76, 0x77, 0x78, 0x79, 0x7a, 0x7b, 0x7c, 0x7d, 0x7e, 0x7f,
+  0x80, 0x81, 0x82, 0x83, 0x84, 0x85, 0x86, 0x87, 0x88, 0x89, 0x8a, 0x8b, 0x8c, 0x8d, 0x8e, 0x8f,
+  0x90, 0x91, 0x92, 0x93, 0x94, 0x95, 0x96, 0x97, 0x98, 0x99, 0x9a, 0x9b, 0x9c, 0x9d, 0x9e, 0x9f,
+  0xa0, 0xa1, 0xa2, 0xa3, 0xa4, 0xa5, 0xa6, 0xa7, 0xa8, 0xa9, 0xaa, 0xab, 0xac, 0xad, 0xae, 0xaf,
+  0xb0, 0xb1, 0xb2, 0xb3, 0xb4, 0xb5, 0xb6, 0xb7, 0xb8, 0xb9, 0xba, 0xbb, 0xbc, 0xbd, 0xbe, 0xbf,
+  0xc0, 0xc1, 0xc2, 0xc3, 0xc4, 0xc5, 0xc6, 0xc7, 0xc8, 0xc9, 0xca, 0xcb, 0xcc, 0xcd, 0xce, 0xcf,
+  0xd0, 0xd1, 0xd2, 0xd3, 0xd4, 0xd5, 0xd6, 0xd7, 0xd8, 0xd9, 0xda, 0xdb, 0xdc, 0xdd, 0xde, 0xdf,
+  0xe0, 0xe1, 0xe2, 0xe3, 0xe4, 0xe5, 0xe6, 0xe7, 0xe8, 0xe9, 0xea, 0xeb, 0xec, 0xed, 0xee, 0xef,
+  0xf0, 0xf1, 0xf2, 0xf3, 0xf4, 0xf5, 0xf6, 0xf7, 0xf8, 0xf9, 0xfa, 0xfb, 0xfc, 0xfd, 0xfe, 0xff,
+];
+
+/// Compares two equal-length slices, ignoring ASCII case.
+#[inline]
+fn bytes_eq_ignore_ascii_case(a: &[u8], b: &[u8]) -> bool {
+  a.iter().zip(b.iter()).all(|(&x, &y)|
+    ASCII_LOWER_MAP[x as usize] == ASCII_LOWER_MAP[y as usize])
+}
+
+/// The number of bytes `c` takes up in a JSON string, once escaped.
+#[inline]
+fn json_escaped_len(c: char) -> u64 {
+  match c {
+    '"' | '\\' | '\n' | '\r' | '\t' | '\x08' | '\x0c' => 2,
+    '\x00'...'\x1f' => 6,
+    '\x20'...'\x7f' => 1,
+    _ if (c as u32) < 0x10000 => 6,
+    _ => 12,
+  }
+}
+
+/// Writes `\uXXXX` to `p`, returning the byte after it.
+#[inline]
+unsafe fn write_json_u_escape(p: *mut u8, u: u32) -> *mut u8 {
+  static HEX: &'static [u8] = b"0123456789abcdef";
+  *p = b'\\';
+  *p.offset(1) = b'u';
+  for i in range(0u32, 4) {
+    *p.offset(2 + i as isize) = HEX[((u >> (12 - 4 * i)) & 0xF) as usize];
+  }
+  p.offset(6)
+}
+
+/// Writes `c`, escaped for a JSON string, to `p`, returning the byte after it.
+/// `p` must have room for `json_escaped_len(c)` bytes.
+#[inline]
+unsafe fn write_json_escaped(p: *mut u8, c: char) -> *mut u8 {
+  let short = match c {
+    '"'     => b'"',
+    '\\'    => b'\\',
+    '\n'    => b'n',
+    '\r'    => b'r',
+    '\t'    => b't',
+    '\x08'  => b'b',
+    '\x0c'  => b'f',
+    '\x00'...'\x1f' => return write_json_u_escape(p, c as u32),
+    '\x20'...'\x7f' => { *p = c as u8; return p.offset(1) }
+    _ if (c as u32) < 0x10000 => return write_json_u_escape(p, c as u32),
+    _ => {
+      // Above the BMP, so it has to be written as a UTF-16 surrogate pair.
+      let v = c as u32 - 0x10000;
+      let p = write_json_u_escape(p, 0xD800 + (v >> 10));
+      return write_json_u_escape(p, 0xDC00 + (v & 0x3FF));
+    }
+  };
+  *p = b'\\';
+  *p.offset(1) = short;
+  p.offset(2)
+}
+
+/// The number of bytes in a fixed-point number with `int_bits` integer bits
+/// (including the sign bit) and `frac_bits` fractional bits.
+#[inline]
+fn fixed_width(int_bits: u8, frac_bits: u8) -> u8 {
+  let bits = int_bits as u32 + frac_bits as u32;
+  if bits == 0 || bits > 64 || bits % 8 != 0 {
+    panic!("Iobuf: a Q{}.{} fixed-point number isn't a whole number of bytes, up to 8", int_bits, frac_bits);
+  }
+  (bits / 8) as u8
+}
+
+/// Why `consume_tag` didn't consume anything.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum TagError {
+  /// The window doesn't start with the tag. This is the offset of the first
+  /// byte that's different.
+  Mismatch(u32),
+  /// Everything in the window matches the start of the tag, but the window
+  /// is shorter than the tag. More data might complete it.
+  Incomplete,
+}
+
+/// Why `consume_be_enum` or `consume_le_enum` didn't consume anything.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum EnumError<T> {
+  /// The primitive was read, but isn't one of the allowed values. This is
+  /// the raw value, for diagnostics.
+  Invalid(T),
+  /// The window is too short to hold the primitive.
+  Incomplete,
+}
+
+#[inline]
+fn match_tag<F: FnMut(u8, u8) -> bool>(s: &[u8], tag: &[u8], mut eq: F) -> Result<(), TagError> {
+  match s.iter().zip(tag.iter()).position(|(&x, &y)| !eq(x, y)) {
+    Some(i) => Err(TagError::Mismatch(i as u32)),
+    None if s.len() < tag.len() => Err(TagError::Incomplete),
+    None => Ok(()),
+  }
+}
+
+/// Where an Iobuf's memory came from. See `IobufState`.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Ownership {
+  /// Borrowed from something else, like a slice or a string. There's no
+  /// refcount.
+  Borrowed,
+  /// Allocated from the global heap, and refcounted.
+  Heap,
+  /// Allocated from a custom `Allocator`, and refcounted.
+  Allocator,
+}
+
+/// A snapshot of an Iobuf's internals, for logging and bug reports. See
+/// `Iobuf::debug_state`.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct IobufState {
+  /// The lower limit, as an offset into the buffer.
+  pub lo_min:     u32,
+  /// The start of the window, as an offset into the buffer.
+  pub lo:         u32,
+  /// The end of the window, as an offset into the buffer.
+  pub hi:         u32,
+  /// The upper limit, as an offset into the buffer.
+  pub hi_max:     u32,
+  /// The length of the whole buffer. For borrowed memory, only the part
+  /// that's known about, up to `hi_max`.
+  pub buffer_len: u32,
+  /// Where the memory came from.
+  pub ownership:  Ownership,
+  /// The number of Iobufs sharing the buffer. This is 0 for borrowed memory.
+  pub refcount:   u32,
+}
+
 struct AllocationHeader {
   allocator: *mut (),
   allocation_length: usize,
@@ -133,6 +407,62 @@ impl Deallocator {
   }
 }
 
+/// Wraps whatever allocator a buffer already had. When the last reference to
+/// the buffer goes away, instead of releasing the memory, the allocation is
+/// revived with a refcount of 1 and handed to the hook. From then on, it's
+/// released by the wrapped allocator.
+struct DropHook<F> {
+  inner: Option<Arc<Box<Allocator>>>,
+  hook:  Mutex<Option<F>>,
+}
+
+impl<F: FnOnce(RawIobuf<'static>) + Send + 'static> Allocator for DropHook<F> {
+  fn allocate(&self, len: usize, align: usize) -> *mut u8 {
+    match self.inner {
+      None        => unsafe { heap::allocate(len, align) },
+      Some(ref a) => a.allocate(len, align),
+    }
+  }
+
+  fn deallocate(&self, ptr: *mut u8, len: usize, align: usize) {
+    let header_len = mem::size_of::<AllocationHeader>();
+
+    match self.hook.lock().unwrap().take() {
+      Some(f) => unsafe {
+        let allocator =
+          match self.inner {
+            None        => ptr::null_mut(),
+            Some(ref a) => mem::transmute(a.clone()),
+          };
+
+        ptr::write(ptr as *mut AllocationHeader,
+          AllocationHeader {
+            allocator: allocator,
+            allocation_length: len,
+            refcount: 1,
+          });
+
+        let buf_len = (len - header_len) as u32;
+
+        f(RawIobuf {
+          buf:    ptr.offset(header_len as isize),
+          lo_min_and_owned_bit: OWNED_MASK,
+          lo:     0,
+          hi:     buf_len,
+          hi_max: buf_len,
+          lifetm: ContravariantLifetime,
+          nocopy: NoCopy,
+        })
+      },
+      None =>
+        match self.inner {
+          None        => unsafe { heap::deallocate(ptr, len, align) },
+          Some(ref a) => a.deallocate(ptr, len, align),
+        },
+    }
+  }
+}
+
 // By factoring out the calls to `panic!`, we prevent rustc from emitting a ton
 // of formatting code in our tight, little functions, and also help guide
 // inlining.
@@ -209,6 +539,68 @@ impl<'a> RawIobuf<'a> {
     }
   }
 
+  /// The number of bytes at the front of foreign memory which are used for
+  /// the refcount and the allocator.
+  #[inline(always)]
+  pub fn foreign_header_len() -> usize {
+    mem::size_of::<AllocationHeader>()
+  }
+
+  /// Takes ownership of `len` bytes of word-aligned memory at `mem`, which
+  /// weren't allocated by us. The first `foreign_header_len()` bytes are
+  /// overwritten with the allocation header, and the rest becomes the buffer.
+  /// Once the last reference is dropped, the memory is handed to `allocator`'s
+  /// `deallocate`.
+  pub unsafe fn from_foreign(
+      mem:       *mut u8,
+      len:       usize,
+      allocator: Arc<Box<Allocator>>) -> RawIobuf<'static> {
+    let header_len = RawIobuf::foreign_header_len();
+
+    if len < header_len {
+      panic!("Foreign memory is too small for an Iobuf header: {} bytes", len);
+    }
+
+    let buf_len = len - header_len;
+
+    if buf_len > MAX_BUFFER_LEN {
+      buffer_too_big(buf_len);
+    }
+
+    ptr::write(mem as *mut AllocationHeader,
+      AllocationHeader {
+        allocator: mem::transmute(allocator),
+        allocation_length: len,
+        refcount: 1,
+      });
+
+    RawIobuf {
+      buf:    mem.offset(header_len as isize),
+      lo_min_and_owned_bit: OWNED_MASK,
+      lo:     0,
+      hi:     buf_len as u32,
+      hi_max: buf_len as u32,
+      lifetm: ContravariantLifetime,
+      nocopy: NoCopy,
+    }
+  }
+
+  /// Hands the whole buffer to `f` once the last reference to it is dropped,
+  /// instead of releasing the memory. Fails if the buffer isn't owned.
+  pub fn on_last_drop<F: FnOnce(RawIobuf<'static>) + Send + 'static>(&mut self, f: F) -> Result<(), ()> {
+    match self.header() {
+      None => Err(()),
+      Some(h) => unsafe {
+        let inner =
+          if h.allocator.is_null() { None } else { Some(mem::transmute(h.allocator)) };
+        let hook: Arc<Box<Allocator>> =
+          Arc::new(Box::new(DropHook { inner: inner, hook: Mutex::new(Some(f)) }) as Box<Allocator>);
+        h.allocator = mem::transmute(hook);
+        Ok(())
+      },
+    }
+  }
+
   #[inline]
   pub fn new(len: usize) -> RawIobuf<'static> {
     RawIobuf::new_impl(len, ptr::null_mut())
@@ -321,6 +713,21 @@ impl<'a> RawIobuf<'a> {
     self.hi_max = source.hi_max;
   }
 
+  /// A copy of this Iobuf which doesn't own the buffer, and therefore never
+  /// touches the refcount. It can't outlive the borrow of `self`.
+  #[inline]
+  pub fn borrowed_view<'b>(&'b self) -> RawIobuf<'b> {
+    RawIobuf {
+      buf:    self.buf,
+      lo_min_and_owned_bit: self.lo_min_and_owned_bit & !OWNED_MASK,
+      lo:     self.lo,
+      hi:     self.hi,
+      hi_max: self.hi_max,
+      lifetm: ContravariantLifetime,
+      nocopy: NoCopy,
+    }
+  }
+
   #[inline]
   pub unsafe fn clone_nonatomic(&self) -> RawIobuf<'a> {
     self.nonatomic_inc_ref_count();
@@ -399,6 +806,19 @@ impl<'a> RawIobuf<'a> {
     }
   }
 
+  /// Locks the whole allocation (header included) into RAM. The allocation
+  /// must have been made by a `LockedAllocator`.
+  #[cfg(unix)]
+  pub unsafe fn lock_allocation(&self) -> IoResult<()> {
+    match self.header() {
+      None => Ok(()),
+      Some(h) =>
+        locked::lock(
+          self.buf.offset(-(mem::size_of::<AllocationHeader>() as isize)) as *const u8,
+          h.allocation_length),
+    }
+  }
+
   #[inline]
   pub fn from_str(s: &'a str) -> RawIobuf<'a> {
     RawIobuf::from_slice(s.as_bytes())
@@ -439,6 +859,7 @@ impl<'a> RawIobuf<'a> {
 
   #[inline]
   pub fn from_slice_copy(s: &[u8]) -> RawIobuf<'static> {
+    if s.is_empty() { return RawIobuf::empty() }
     unsafe {
       let b = RawIobuf::new(s.len());
       let s = s.repr();
@@ -457,6 +878,57 @@ impl<'a> RawIobuf<'a> {
     }
   }
 
+  /// Owned buffers are shared with a refcount bump. Borrowed ones are copied,
+  /// limits and all, keeping the window in the same place relative to them.
+  #[inline]
+  pub unsafe fn to_owned_nonatomic(&self) -> RawIobuf<'static> {
+    if self.is_owned() {
+      // Owned memory never borrows from anything, so the lifetime may grow.
+      return mem::transmute(self.clone_nonatomic())
+    }
+    let lo_min = self.lo_min();
+    let mut b = RawIobuf::from_slice_copy(self.as_limit_slice());
+    b.lo = self.lo - lo_min;
+    b.hi = self.hi - lo_min;
+    b
+  }
+
+  #[inline]
+  pub fn window_to_owned(&self) -> RawIobuf<'static> {
+    unsafe { RawIobuf::from_slice_copy(self.as_window_slice()) }
+  }
+
+  #[inline]
+  pub fn split_to_owned(&mut self, n: u32) -> Result<RawIobuf<'static>, ()> {
+    try!(self.check_range_u32(0, n));
+    unsafe {
+      let b = RawIobuf::from_slice_copy(&self.as_window_slice()[..n as usize]);
+      self.unsafe_advance(n);
+      Ok(b)
+    }
+  }
+
+  #[inline]
+  pub fn cmp_window(&self, other: &RawIobuf) -> CmpOrdering {
+    unsafe { Ord::cmp(self.as_window_slice(), other.as_window_slice()) }
+  }
+
+  #[inline]
+  pub fn to_cstring(&self) -> Result<CString, ()> {
+    if self.position(0).is_some() { return Err(()) }
+    unsafe { Ok(CString::from_slice(self.as_window_slice())) }
+  }
+
+  #[inline]
+  pub unsafe fn as_cstr_ptr(&self) -> Result<*const u8, ()> {
+    if self.hi == self.hi_max
+    || *self.buf.offset(self.hi as isize) != 0
+    || self.position(0).is_some() {
+      return Err(())
+    }
+    Ok(self.buf.offset(self.lo as isize) as *const u8)
+  }
+
   #[inline]
   pub fn deep_clone(&self) -> RawIobuf<'static> {
     unsafe {
@@ -469,6 +941,21 @@ impl<'a> RawIobuf<'a> {
     }
   }
 
+  /// Widens the limits and window to cover the whole buffer that was
+  /// allocated. This does nothing to buffers we don't own.
+  #[inline]
+  pub unsafe fn reset_to_allocation(&mut self) {
+    let len =
+      match self.header() {
+        None => return,
+        Some(h) => (h.allocation_length - mem::size_of::<AllocationHeader>()) as u32,
+      };
+    self.set_lo_min(0);
+    self.lo     = 0;
+    self.hi     = len;
+    self.hi_max = len;
+  }
+
   #[inline]
   pub fn deep_clone_with_allocator(&self, allocator: Arc<Box<Allocator>>) -> RawIobuf<'static> {
     unsafe {
@@ -528,6 +1015,21 @@ impl<'a> RawIobuf<'a> {
     mem::transmute(self.as_raw_window_slice())
   }
 
+  #[inline]
+  pub unsafe fn window_as_slice<'b, T: Prim>(&'b self) -> Result<&'b [T], ()> {
+    let size = prim_len::<T>();
+    let p    = self.buf.offset(self.lo as isize) as usize;
+    let len  = self.len() as usize;
+
+    if mem::size_of::<T>() != size
+    || p % mem::min_align_of::<T>() != 0
+    || len % size != 0 {
+      return Err(());
+    }
+
+    Ok(mem::transmute(raw::Slice { data: p as *const T, len: len / size }))
+  }
+
   #[inline]
   pub unsafe fn as_mut_window_slice<'b>(&'b self) -> &'b mut [u8] {
     mem::transmute(self.as_raw_window_slice())
@@ -673,6 +1175,24 @@ impl<'a> RawIobuf<'a> {
   }
 
   /// Both the limits and the window are [lo, hi).
+  /// The limits and window, to be put back with `restore_bounds`.
+  #[inline(always)]
+  pub fn bounds(&self) -> (u32, u32, u32, u32) {
+    (self.lo_min(), self.lo, self.hi, self.hi_max)
+  }
+
+  /// Puts back limits and window saved with `bounds`. The limits may be
+  /// widened again, so the bounds must have come from an Iobuf over the same
+  /// buffer.
+  #[inline(always)]
+  pub unsafe fn restore_bounds(&mut self, bounds: (u32, u32, u32, u32)) {
+    let (lo_min, lo, hi, hi_max) = bounds;
+    self.set_lo_min(lo_min);
+    self.lo     = lo;
+    self.hi     = hi;
+    self.hi_max = hi_max;
+  }
+
   #[inline]
   pub fn set_limits_and_window(&mut self, limits: (u32, u32), window: (u32, u32)) -> Result<(), ()> {
     let (new_lo_min, new_hi_max) = limits;
@@ -724,6 +1244,13 @@ impl<'a> RawIobuf<'a> {
     self.hi == self.lo
   }
 
+  #[inline]
+  pub fn count_prims<T: Prim>(&self, from: u32) -> u32 {
+    let len = self.len();
+    if from >= len { return 0 }
+    (len - from) / prim_len::<T>() as u32
+  }
+
   #[inline]
   pub fn narrow(&mut self) {
     let lo = self.lo;
@@ -746,6 +1273,13 @@ impl<'a> RawIobuf<'a> {
     self.lo += len;
   }
 
+  #[inline]
+  pub fn advance_upto(&mut self, len: u32) -> u32 {
+    let len = if len > self.len() { self.len() } else { len };
+    self.lo += len;
+    len
+  }
+
   #[inline]
   pub fn extend(&mut self, len: u32) -> Result<(), ()> {
     unsafe {
@@ -775,6 +1309,14 @@ impl<'a> RawIobuf<'a> {
     self.hi += len;
   }
 
+  #[inline]
+  pub fn extend_upto(&mut self, len: u32) -> u32 {
+    let room = self.hi_max - self.hi;
+    let len  = if len > room { room } else { len };
+    self.hi += len;
+    len
+  }
+
   #[inline]
   pub fn is_extended_by<'b>(&self, other: &RawIobuf<'b>) -> bool {
     unsafe {
@@ -804,12 +1346,52 @@ impl<'a> RawIobuf<'a> {
     Ok(())
   }
 
+  #[inline]
+  pub fn resize_zeroed(&mut self, len: u32) -> Result<(), ()> {
+    let old_len = self.len();
+    try!(self.resize(len));
+    if len > old_len {
+      unsafe {
+        ptr::set_memory(self.buf.offset((self.lo + old_len) as isize), 0, (len - old_len) as usize);
+      }
+    }
+    Ok(())
+  }
+
+  pub fn dedup_by<F: FnMut(u8, u8) -> bool>(&mut self, mut same: F) -> u32 {
+    unsafe {
+      let len = {
+        let s = self.as_mut_window_slice();
+        if s.is_empty() { return 0 }
+        // `s[..w]` is what's been kept so far.
+        let mut w = 1;
+        for r in range(1, s.len()) {
+          if !same(s[w - 1], s[r]) {
+            s[w] = s[r];
+            w += 1;
+          }
+        }
+        w as u32
+      };
+      self.unsafe_resize(len);
+      len
+    }
+  }
+
   #[inline]
   pub unsafe fn unsafe_resize(&mut self, len: u32) {
     self.debug_check_range_u32(0, len);
     self.hi = self.lo + len;
   }
 
+  #[inline]
+  pub fn resize_upto(&mut self, len: u32) -> u32 {
+    let room = self.hi_max - self.lo;
+    let len  = if len > room { room } else { len };
+    self.hi = self.lo + len;
+    len
+  }
+
   #[inline]
   pub fn split_at_nonatomic(&self, pos: u32) -> Result<(RawIobuf<'a>, RawIobuf<'a>), ()> {
     unsafe {
@@ -915,16 +1497,133 @@ impl<'a> RawIobuf<'a> {
 
   #[inline]
   pub fn compact(&mut self) {
+    let len = self.len();
+    let lo_min = self.lo_min();
+    // Nothing to move if the data's already at the front, or there isn't any.
+    // Otherwise, the source and destination can overlap, so this has to be a
+    // memmove.
+    if self.lo != lo_min && len != 0 {
+      unsafe {
+        ptr::copy_memory(
+          self.buf.offset(lo_min as isize),
+          self.buf.offset(self.lo as isize) as *const u8,
+          len as usize);
+      }
+    }
+    self.lo = lo_min + len;
+    self.hi = self.hi_max;
+  }
+
+  #[inline]
+  pub unsafe fn unsafe_compact(&mut self) {
+    let len = self.len();
+    let lo_min = self.lo_min();
+    ptr::copy_nonoverlapping_memory(
+      self.buf.offset(lo_min as isize),
+      self.buf.offset(self.lo as isize) as *const u8,
+      len as usize);
+    self.lo = lo_min + len;
+    self.hi = self.hi_max;
+  }
+
+  pub fn reset_to_front(&mut self) {
     unsafe {
       let len = self.len();
-      let lo_min = self.lo_min();
-      ptr::copy_memory(
-        self.buf.offset(lo_min as isize),
-        self.buf.offset(self.lo as isize) as *const u8,
-        len as usize);
-      self.lo = lo_min + len;
-      self.hi = self.hi_max;
+      let src = self.buf.offset(self.lo as isize) as *const u8;
+      if self.is_unique_nonatomic() {
+        self.reset_to_allocation();
+      }
+      let front = self.lo_min();
+      ptr::copy_memory(self.buf.offset(front as isize), src, len as usize);
+      self.lo = front;
+      self.hi = front + len;
+    }
+  }
+
+  pub fn replace_byte(&self, from: u8, to: u8) -> u32 {
+    let mut n = 0;
+    unsafe {
+      for c in self.as_mut_window_slice().iter_mut() {
+        if *c == from {
+          *c = to;
+          n += 1;
+        }
+      }
+    }
+    n
+  }
+
+  pub fn mask(&self, key: [u8; 4], offset: u32) {
+    unsafe {
+      let mut k = [0u8; 8];
+      for (i, x) in k.iter_mut().enumerate() {
+        *x = key[(i + offset as usize) % 4];
+      }
+      let k: u64 = mem::transmute(k);
+
+      let mut p = self.buf.offset(self.lo as isize);
+      let mut len = self.len() as usize;
+
+      // Eight bytes at a time. The window isn't necessarily aligned, so go
+      // through a local.
+      while len >= 8 {
+        let mut w: u64 = mem::uninitialized();
+        ptr::copy_nonoverlapping_memory(&mut w as *mut u64 as *mut u8, p as *const u8, 8);
+        w ^= k;
+        ptr::copy_nonoverlapping_memory(p, &w as *const u64 as *const u8, 8);
+        p = p.offset(8);
+        len -= 8;
+      }
+
+      // Each chunk was a multiple of the key's length, so the rest still
+      // starts at `offset` into the key.
+      for i in range(0, len) {
+        *p.offset(i as isize) ^= key[(i + offset as usize) % 4];
+      }
+    }
+  }
+
+  #[inline]
+  pub unsafe fn peek_into_uninit(&self, pos: u32, dst: *mut u8, len: u32) -> Result<(), ()> {
+    try!(self.check_range_u32(pos, len));
+    ptr::copy_nonoverlapping_memory(
+      dst,
+      self.buf.offset((self.lo + pos) as isize) as *const u8,
+      len as usize);
+    Ok(())
+  }
+
+  pub fn peek_vectored(&self, pos: u32, dsts: &mut [&mut [u8]]) -> Result<u32, ()> {
+    // Summed as a u64, so a total of more than 4 GB is an error, not a wrap.
+    let total = dsts.iter().fold(0u64, |n, d| n + d.len() as u64);
+    unsafe {
+      try!(self.check_range(pos as u64, total));
+      let mut src = self.buf.offset((self.lo + pos) as isize) as *const u8;
+      for d in dsts.iter_mut() {
+        ptr::copy_nonoverlapping_memory(d.as_mut_ptr(), src, d.len());
+        src = src.offset(d.len() as isize);
+      }
     }
+    Ok(total as u32)
+  }
+
+  #[inline]
+  pub fn consume_vectored(&mut self, dsts: &mut [&mut [u8]]) -> Result<(), ()> {
+    let total = try!(self.peek_vectored(0, dsts));
+    self.lo += total;
+    Ok(())
+  }
+
+  #[inline]
+  pub fn peek_all_into(&self, dst: &mut Vec<u8>) {
+    dst.clear();
+    unsafe { dst.push_all(self.as_window_slice()) }
+  }
+
+  #[inline]
+  pub fn consume_all_into(&mut self, dst: &mut Vec<u8>) {
+    self.peek_all_into(dst);
+    self.lo = self.hi;
   }
 
   #[inline]
@@ -936,17 +1635,17 @@ impl<'a> RawIobuf<'a> {
   }
 
   #[inline]
-  pub fn peek_be<T: Int>(&self, pos: u32) -> Result<T, ()> {
+  pub fn peek_be<T: Prim>(&self, pos: u32) -> Result<T, ()> {
     unsafe {
-      try!(self.check_range_u32(pos, mem::size_of::<T>() as u32));
+      try!(self.check_range_u32(pos, prim_len::<T>() as u32));
       Ok(self.unsafe_peek_be::<T>(pos))
     }
   }
 
   #[inline]
-  pub fn peek_le<T: Int>(&self, pos: u32) -> Result<T, ()> {
+  pub fn peek_le<T: Prim>(&self, pos: u32) -> Result<T, ()> {
     unsafe {
-      try!(self.check_range_u32(pos, mem::size_of::<T>() as u32));
+      try!(self.check_range_u32(pos, prim_len::<T>() as u32));
       Ok(self.unsafe_peek_le::<T>(pos))
     }
   }
@@ -960,17 +1659,17 @@ impl<'a> RawIobuf<'a> {
   }
 
   #[inline]
-  pub fn poke_be<T: Int>(&self, pos: u32, t: T) -> Result<(), ()> {
+  pub fn poke_be<T: Prim>(&self, pos: u32, t: T) -> Result<(), ()> {
     unsafe {
-      try!(self.check_range_u32(pos, mem::size_of::<T>() as u32));
+      try!(self.check_range_u32(pos, prim_len::<T>() as u32));
       Ok(self.unsafe_poke_be(pos, t))
     }
   }
 
   #[inline]
-  pub fn poke_le<T: Int>(&self, pos: u32, t: T) -> Result<(), ()> {
+  pub fn poke_le<T: Prim>(&self, pos: u32, t: T) -> Result<(), ()> {
     unsafe {
-      try!(self.check_range_u32(pos, mem::size_of::<T>() as u32));
+      try!(self.check_range_u32(pos, prim_len::<T>() as u32));
       Ok(self.unsafe_poke_le(pos, t))
     }
   }
@@ -983,87 +1682,559 @@ impl<'a> RawIobuf<'a> {
     }
   }
 
-  #[inline]
-  pub fn fill_be<T: Int>(&mut self, t: T) -> Result<(), ()> {
+  pub fn poke_vectored(&self, pos: u32, srcs: &[&[u8]]) -> Result<u32, ()> {
+    // Summed as a u64, so a total of more than 4 GB is an error, not a wrap.
+    let total = srcs.iter().fold(0u64, |n, s| n + s.len() as u64);
     unsafe {
-      try!(self.check_range_u32(0, mem::size_of::<T>() as u32));
-      Ok(self.unsafe_fill_be(t))
+      try!(self.check_range(pos as u64, total));
+      let mut dst = self.buf.offset((self.lo + pos) as isize);
+      for s in srcs.iter() {
+        ptr::copy_nonoverlapping_memory(dst, s.as_ptr(), s.len());
+        dst = dst.offset(s.len() as isize);
+      }
     }
+    Ok(total as u32)
   }
 
   #[inline]
-  pub fn fill_le<T: Int>(&mut self, t: T) -> Result<(), ()> {
-    unsafe {
-      try!(self.check_range_u32(0, mem::size_of::<T>() as u32));
-      Ok(self.unsafe_fill_le(t)) // Ok, unsafe fillet? om nom.
-    }
+  pub fn fill_vectored(&mut self, srcs: &[&[u8]]) -> Result<(), ()> {
+    let total = try!(self.poke_vectored(0, srcs));
+    self.lo += total;
+    Ok(())
   }
 
   #[inline]
-  pub fn consume(&mut self, dst: &mut [u8]) -> Result<(), ()> {
+  pub fn fill_native_slice<T: Prim>(&mut self, src: &[T]) -> Result<(), ()> {
+    let size = prim_len::<T>();
+    if mem::size_of::<T>() != size { return Err(()) }
+    let len = src.len() as u64 * size as u64;
     unsafe {
-      try!(self.check_range_usize(0, dst.len()));
-      Ok(self.unsafe_consume(dst))
+      try!(self.check_range(0, len));
+      ptr::copy_nonoverlapping_memory(
+        self.buf.offset(self.lo as isize),
+        src.as_ptr() as *const u8,
+        len as usize);
+      self.lo += len as u32;
     }
+    Ok(())
   }
 
-  #[inline]
-  pub fn consume_le<T: Int>(&mut self) -> Result<T, ()> {
+  pub fn fill_json_escaped(&mut self, s: &str) -> Result<(), ()> {
+    let len = s.chars().fold(0, |n, c| n + json_escaped_len(c));
+    try!(self.check_range(0, len));
     unsafe {
-      try!(self.check_range_u32(0, mem::size_of::<T>() as u32));
-      Ok(self.unsafe_consume_le())
+      let mut p = self.buf.offset(self.lo as isize);
+      for c in s.chars() {
+        p = write_json_escaped(p, c);
+      }
     }
+    self.lo += len as u32;
+    Ok(())
   }
 
+  /// The number of bytes `[pos, pos + len)` reaches past the end of the
+  /// window, saturating at `u32::MAX`.
   #[inline]
-  pub fn consume_be<T: Int>(&mut self) -> Result<T, ()> {
-    unsafe {
-      try!(self.check_range_u32(0, mem::size_of::<T>() as u32));
-      Ok(self.unsafe_consume_be())
-    }
+  fn overflow_usize(&self, pos: u32, len: usize) -> u32 {
+    let end    = pos as u64 + len as u64;
+    let window = self.len() as u64;
+    if end <= window { 0 }
+    else if end - window > u32::MAX as u64 { u32::MAX }
+    else { (end - window) as u32 }
   }
 
   #[inline]
-  pub unsafe fn unsafe_peek(&self, pos: u32, dst: &mut [u8]) {
-    let len = dst.len();
-    self.debug_check_range_usize(pos, len);
-
-    let dst: raw::Slice<u8> = mem::transmute(dst);
-
-    ptr::copy_nonoverlapping_memory(
-      dst.data as *mut u8,
-      self.buf.offset((self.lo + pos) as isize) as *const u8,
-      len);
+  pub fn poke_reporting(&self, pos: u32, src: &[u8]) -> Result<(), u32> {
+    match self.overflow_usize(pos, src.len()) {
+      0 => unsafe { Ok(self.unsafe_poke(pos, src)) },
+      n => Err(n),
+    }
   }
 
   #[inline]
-  pub unsafe fn unsafe_peek_be<T: Int>(&self, pos: u32) -> T {
-    let len = mem::size_of::<T>();
-    self.debug_check_range_usize(pos, len);
-
-    let mut dst: T = mem::uninitialized();
-
-    let dst_ptr = &mut dst as *mut T;
-    ptr::copy_nonoverlapping_memory(
-      dst_ptr as *mut u8,
-      self.buf.offset((self.lo + pos) as isize) as *const u8,
-      len);
-    Int::from_be(dst)
+  pub fn fill_reporting(&mut self, src: &[u8]) -> Result<(), u32> {
+    match self.overflow_usize(0, src.len()) {
+      0 => unsafe { Ok(self.unsafe_fill(src)) },
+      n => Err(n),
+    }
   }
 
   #[inline]
-  pub unsafe fn unsafe_peek_le<T: Int>(&self, pos: u32) -> T {
-    let len = mem::size_of::<T>();
-    self.debug_check_range_usize(pos, len);
-
-    let mut dst: T = mem::uninitialized();
-
-    let dst_ptr = &mut dst as *mut T;
+  pub fn fill_be<T: Prim>(&mut self, t: T) -> Result<(), ()> {
+    unsafe {
+      try!(self.check_range_u32(0, prim_len::<T>() as u32));
+      Ok(self.unsafe_fill_be(t))
+    }
+  }
+
+  #[inline]
+  pub fn fill_le<T: Prim>(&mut self, t: T) -> Result<(), ()> {
+    unsafe {
+      try!(self.check_range_u32(0, prim_len::<T>() as u32));
+      Ok(self.unsafe_fill_le(t)) // Ok, unsafe fillet? om nom.
+    }
+  }
+
+  #[inline]
+  pub fn consume(&mut self, dst: &mut [u8]) -> Result<(), ()> {
+    unsafe {
+      try!(self.check_range_usize(0, dst.len()));
+      Ok(self.unsafe_consume(dst))
+    }
+  }
+
+  #[inline]
+  pub fn consume_le<T: Prim>(&mut self) -> Result<T, ()> {
+    unsafe {
+      try!(self.check_range_u32(0, prim_len::<T>() as u32));
+      Ok(self.unsafe_consume_le())
+    }
+  }
+
+  pub fn consume_digits_u64(&mut self, radix: u32) -> Result<u64, ()> {
+    let (n, len) = unsafe {
+      let mut n = 0u64;
+      let mut len = 0u32;
+      for &c in self.as_window_slice().iter() {
+        let d = match (c as char).to_digit(radix) {
+          None    => break,
+          Some(d) => d as u64,
+        };
+        n = match n.checked_mul(radix as u64).and_then(|n| n.checked_add(d)) {
+          None    => return Err(()),
+          Some(n) => n,
+        };
+        len += 1;
+      }
+      (n, len)
+    };
+    if len == 0 { return Err(()) }
+    unsafe { self.unsafe_advance(len); }
+    Ok(n)
+  }
+
+  pub fn peek_int(&self, pos: u32, width: u8, big_endian: bool) -> Result<i64, ()> {
+    if width == 0 || width > 8 {
+      panic!("Iobuf::consume_int: width must be between 1 and 8, not {}", width);
+    }
+    try!(self.check_range_u32(pos, width as u32));
+    let x = unsafe {
+      let s = &self.as_window_slice()[pos as usize..pos as usize + width as usize];
+      if big_endian {
+        s.iter().fold(0u64, |n, &c| n << 8 | c as u64)
+      } else {
+        s.iter().rev().fold(0u64, |n, &c| n << 8 | c as u64)
+      }
+    };
+    // Move the sign bit up to bit 63, then let an arithmetic shift copy it
+    // back down.
+    let unused = 64 - 8 * width as usize;
+    Ok(((x << unused) as i64) >> unused)
+  }
+
+  pub fn consume_int(&mut self, width: u8, big_endian: bool) -> Result<i64, ()> {
+    let x = try!(self.peek_int(0, width, big_endian));
+    unsafe { self.unsafe_advance(width as u32); }
+    Ok(x)
+  }
+
+  pub fn peek_fixed_be(&self, pos: u32, int_bits: u8, frac_bits: u8) -> Result<f64, ()> {
+    let width = fixed_width(int_bits, frac_bits);
+    let x = try!(self.peek_int(pos, width, true));
+    Ok(x as f64 / 2f64.powi(frac_bits as i32))
+  }
+
+  pub fn consume_fixed_be(&mut self, int_bits: u8, frac_bits: u8) -> Result<f64, ()> {
+    let x = try!(self.peek_fixed_be(0, int_bits, frac_bits));
+    unsafe { self.unsafe_advance(fixed_width(int_bits, frac_bits) as u32); }
+    Ok(x)
+  }
+
+  pub fn poke_fixed_be(&self, pos: u32, x: f64, int_bits: u8, frac_bits: u8) -> Result<(), ()> {
+    let width = fixed_width(int_bits, frac_bits);
+    let scaled = (x * 2f64.powi(frac_bits as i32)).round();
+    // Both bounds are powers of two, so they're exact. NaN fails both.
+    let limit = 2f64.powi(8 * width as i32 - 1);
+    if !(scaled >= -limit && scaled < limit) { return Err(()) }
+    try!(self.check_range_u32(pos, width as u32));
+    let n = scaled as i64 as u64;
+    for i in range(0, width as u32) {
+      unsafe { self.unsafe_poke_be(pos + i, (n >> (8 * (width as u32 - 1 - i) as usize)) as u8) };
+    }
+    Ok(())
+  }
+
+  pub fn fill_fixed_be(&mut self, x: f64, int_bits: u8, frac_bits: u8) -> Result<(), ()> {
+    try!(self.poke_fixed_be(0, x, int_bits, frac_bits));
+    unsafe { self.unsafe_advance(fixed_width(int_bits, frac_bits) as u32); }
+    Ok(())
+  }
+
+  #[inline]
+  pub fn consume_enum<T: Prim, E, F: FnOnce(T) -> Option<E>>(&mut self, validate: F, big_endian: bool) -> Result<E, EnumError<T>> {
+    let t = match self.peek_prim(0, big_endian) {
+      Ok(t)   => t,
+      Err(()) => return Err(EnumError::Incomplete),
+    };
+    match validate(t) {
+      None    => Err(EnumError::Invalid(t)),
+      Some(e) => {
+        unsafe { self.unsafe_advance(prim_len::<T>() as u32); }
+        Ok(e)
+      }
+    }
+  }
+
+  #[inline]
+  pub fn consume_be_opt<T: Prim>(&mut self) -> Option<T> {
+    self.consume_be().ok()
+  }
+
+  #[inline]
+  pub fn consume_le_opt<T: Prim>(&mut self) -> Option<T> {
+    self.consume_le().ok()
+  }
+
+  #[inline]
+  pub fn peek_prim<T: Prim>(&self, pos: u32, big_endian: bool) -> Result<T, ()> {
+    if big_endian { self.peek_be(pos) } else { self.peek_le(pos) }
+  }
+
+  #[inline]
+  pub fn poke_prim<T: Prim>(&self, pos: u32, t: T, big_endian: bool) -> Result<(), ()> {
+    if big_endian { self.poke_be(pos, t) } else { self.poke_le(pos, t) }
+  }
+
+  #[inline]
+  pub fn fill_prim<T: Prim>(&mut self, t: T, big_endian: bool) -> Result<(), ()> {
+    if big_endian { self.fill_be(t) } else { self.fill_le(t) }
+  }
+
+  #[inline]
+  pub fn consume_prim<T: Prim>(&mut self, big_endian: bool) -> Result<T, ()> {
+    if big_endian { self.consume_be() } else { self.consume_le() }
+  }
+
+  #[inline]
+  pub fn consume_be<T: Prim>(&mut self) -> Result<T, ()> {
+    unsafe {
+      try!(self.check_range_u32(0, prim_len::<T>() as u32));
+      Ok(self.unsafe_consume_be())
+    }
+  }
+
+  #[inline]
+  pub fn consume_be_array<A: IntArray>(&mut self) -> Result<A, ()> {
+    unsafe {
+      let elem_len = prim_len::<A::Elem>() as u32;
+      let len      = array_len::<A>() as u32 * elem_len;
+      try!(self.check_range_u32(0, len));
+      let mut ret: A = mem::uninitialized();
+      for (i, x) in ret.as_mut_elems().iter_mut().enumerate() {
+        *x = self.unsafe_peek_be(i as u32 * elem_len);
+      }
+      self.lo += len;
+      Ok(ret)
+    }
+  }
+
+  #[inline]
+  pub fn consume_le_array<A: IntArray>(&mut self) -> Result<A, ()> {
+    unsafe {
+      let elem_len = prim_len::<A::Elem>() as u32;
+      let len      = array_len::<A>() as u32 * elem_len;
+      try!(self.check_range_u32(0, len));
+      let mut ret: A = mem::uninitialized();
+      for (i, x) in ret.as_mut_elems().iter_mut().enumerate() {
+        *x = self.unsafe_peek_le(i as u32 * elem_len);
+      }
+      self.lo += len;
+      Ok(ret)
+    }
+  }
+
+  #[inline]
+  pub fn eq_ignore_ascii_case(&self, other: &[u8]) -> bool {
+    let s = unsafe { self.as_window_slice() };
+    s.len() == other.len() && bytes_eq_ignore_ascii_case(s, other)
+  }
+
+  #[inline]
+  pub fn starts_with_ignore_ascii_case(&self, prefix: &[u8]) -> bool {
+    let s = unsafe { self.as_window_slice() };
+    s.len() >= prefix.len() && bytes_eq_ignore_ascii_case(&s[..prefix.len()], prefix)
+  }
+
+  #[inline]
+  pub fn consume_tag(&mut self, tag: &[u8]) -> Result<(), TagError> {
+    try!(match_tag(unsafe { self.as_window_slice() }, tag, |x, y| x == y));
+    unsafe { self.unsafe_advance(tag.len() as u32) };
+    Ok(())
+  }
+
+  #[inline]
+  pub fn consume_tag_ignore_ascii_case(&mut self, tag: &[u8]) -> Result<(), TagError> {
+    try!(match_tag(unsafe { self.as_window_slice() }, tag, |x, y|
+      ASCII_LOWER_MAP[x as usize] == ASCII_LOWER_MAP[y as usize]));
+    unsafe { self.unsafe_advance(tag.len() as u32) };
+    Ok(())
+  }
+
+  #[inline]
+  pub fn owns_slice(&self, s: &[u8]) -> bool {
+    // We know the whole buffer if we allocated it. Otherwise, the limits are
+    // all we've got.
+    let (start, end) =
+      match self.header() {
+        Some(h) => (0, h.allocation_length - mem::size_of::<AllocationHeader>()),
+        None    => (self.lo_min() as usize, self.hi_max as usize),
+      };
+
+    let buf   = self.buf as usize;
+    let s_ptr = s.as_ptr() as usize;
+
+    s_ptr >= buf + start && s_ptr + s.len() <= buf + end
+  }
+
+  #[inline]
+  pub fn iter_be<'b, T: Prim>(&'b self) -> WordIter<'b, T> {
+    WordIter::new(unsafe { self.as_window_slice() }, true)
+  }
+
+  #[inline]
+  pub fn iter_le<'b, T: Prim>(&'b self) -> WordIter<'b, T> {
+    WordIter::new(unsafe { self.as_window_slice() }, false)
+  }
+
+  #[inline]
+  pub fn fold<B, F: FnMut(B, u8) -> B>(&self, init: B, mut f: F) -> B {
+    unsafe { self.as_window_slice().iter().fold(init, |acc, &c| f(acc, c)) }
+  }
+
+  /// The offset of the first `c` in the window.
+  #[inline]
+  pub fn position(&self, c: u8) -> Option<u32> {
+    unsafe {
+      self.as_window_slice().iter().position(|&x| x == c).map(|i| i as u32)
+    }
+  }
+
+  #[inline]
+  pub fn rposition(&self, c: u8) -> Option<u32> {
+    unsafe {
+      self.as_window_slice().iter().rposition(|&x| x == c).map(|i| i as u32)
+    }
+  }
+
+  pub fn rfind_slice(&self, needle: &[u8]) -> Option<u32> {
+    unsafe {
+      let s = self.as_window_slice();
+      let n = needle.len();
+      if n > s.len() { return None }
+      if n == 0 { return Some(s.len() as u32) }
+
+      // Look for the last byte of the needle, and only then compare the rest.
+      let last = needle[n - 1];
+      let mut end = s.len();
+      while end >= n {
+        match s[..end].iter().rposition(|&x| x == last) {
+          None => return None,
+          Some(i) if i + 1 < n => return None,
+          Some(i) => {
+            if &s[i + 1 - n..i + 1] == needle { return Some((i + 1 - n) as u32) }
+            end = i;
+          }
+        }
+      }
+      None
+    }
+  }
+
+  #[inline]
+  pub fn all_bytes<F: FnMut(u8) -> bool>(&self, mut pred: F) -> bool {
+    unsafe { self.as_window_slice().iter().all(|&c| pred(c)) }
+  }
+
+  pub fn trim_matches<F: FnMut(u8) -> bool>(&mut self, mut pred: F) {
+    unsafe {
+      let (front, back) = {
+        let s = self.as_window_slice();
+        match s.iter().position(|&c| !pred(c)) {
+          None => (s.len(), 0),
+          Some(front) => {
+            let last = s.iter().rposition(|&c| !pred(c)).unwrap();
+            (front, s.len() - last - 1)
+          }
+        }
+      };
+      self.lo += front as u32;
+      self.hi -= back as u32;
+    }
+  }
+
+  #[inline]
+  pub fn trim_ascii_whitespace(&mut self) {
+    self.trim_matches(|c| match c {
+      b' ' | b'\t' | b'\r' | b'\n' | b'\x0c' => true,
+      _ => false,
+    })
+  }
+
+  pub fn partition_point<F: FnMut(u8) -> bool>(&self, mut pred: F) -> u32 {
+    unsafe {
+      let s = self.as_window_slice();
+      let (mut lo, mut hi) = (0, s.len());
+      while lo < hi {
+        let mid = lo + (hi - lo) / 2;
+        if pred(s[mid]) { lo = mid + 1 } else { hi = mid }
+      }
+      lo as u32
+    }
+  }
+
+  pub fn partition_point_records<F: FnMut(&[u8]) -> bool>(&self, size: u32, mut pred: F) -> Result<u32, ()> {
+    if size == 0 || self.len() % size != 0 { return Err(()) }
+    unsafe {
+      let s = self.as_window_slice();
+      let size = size as usize;
+      let (mut lo, mut hi) = (0, s.len() / size);
+      while lo < hi {
+        let mid = lo + (hi - lo) / 2;
+        if pred(&s[mid * size..(mid + 1) * size]) { lo = mid + 1 } else { hi = mid }
+      }
+      Ok(lo as u32)
+    }
+  }
+
+  #[inline]
+  pub fn is_ascii(&self) -> bool {
+    self.all_bytes(|c| c < 0x80)
+  }
+
+  #[inline]
+  pub fn is_ascii_printable(&self) -> bool {
+    self.all_bytes(|c| c >= 0x20 && c < 0x7F)
+  }
+
+  #[inline]
+  pub fn first_non_ascii(&self) -> Option<u32> {
+    unsafe {
+      self.as_window_slice().iter().position(|&c| c >= 0x80).map(|i| i as u32)
+    }
+  }
+
+  #[inline]
+  pub fn byte_histogram(&self) -> [u32; 256] {
+    let mut h = [0u32; 256];
+    unsafe {
+      for &c in self.as_window_slice().iter() {
+        h[c as usize] += 1;
+      }
+    }
+    h
+  }
+
+  pub fn shannon_entropy(&self) -> f64 {
+    let len = self.len() as f64;
+    let h = self.byte_histogram();
+    let mut e = 0.0f64;
+    for &n in h.iter() {
+      if n == 0 { continue; }
+      let p = n as f64 / len;
+      e -= p * p.log2();
+    }
+    e
+  }
+
+  // These are plain loops over the slice, rather than `fold`s, so that they
+  // vectorize.
+  pub fn byte_min(&self) -> Option<u8> {
+    if self.is_empty() { return None }
+    let mut m = 0xFFu8;
+    unsafe {
+      for &c in self.as_window_slice().iter() {
+        if c < m { m = c }
+      }
+    }
+    Some(m)
+  }
+
+  pub fn byte_max(&self) -> Option<u8> {
+    if self.is_empty() { return None }
+    let mut m = 0u8;
+    unsafe {
+      for &c in self.as_window_slice().iter() {
+        if c > m { m = c }
+      }
+    }
+    Some(m)
+  }
+
+  pub fn byte_sum(&self) -> u64 {
+    let mut sum = 0u64;
+    unsafe {
+      for &c in self.as_window_slice().iter() {
+        sum += c as u64;
+      }
+    }
+    sum
+  }
+
+  #[inline]
+  pub fn crc32c(&self) -> u32 {
+    unsafe { crc32c::update(0, self.as_window_slice()) }
+  }
+
+  #[inline]
+  pub fn crc32c_from(&self, pos: u32, len: u32) -> Result<u32, ()> {
+    try!(self.check_range_u32(pos, len));
+    unsafe {
+      let s = self.as_window_slice();
+      Ok(crc32c::update(0, &s[pos as usize..(pos + len) as usize]))
+    }
+  }
+
+  #[inline]
+  pub unsafe fn unsafe_peek(&self, pos: u32, dst: &mut [u8]) {
+    let len = dst.len();
+    self.debug_check_range_usize(pos, len);
+
+    let dst: raw::Slice<u8> = mem::transmute(dst);
+
+    ptr::copy_nonoverlapping_memory(
+      dst.data as *mut u8,
+      self.buf.offset((self.lo + pos) as isize) as *const u8,
+      len);
+  }
+
+  #[inline]
+  pub unsafe fn unsafe_peek_be<T: Prim>(&self, pos: u32) -> T {
+    let len = prim_len::<T>();
+    self.debug_check_range_usize(pos, len);
+
+    let mut dst: T::Repr = mem::uninitialized();
+
+    let dst_ptr = &mut dst as *mut T::Repr;
     ptr::copy_nonoverlapping_memory(
       dst_ptr as *mut u8,
       self.buf.offset((self.lo + pos) as isize) as *const u8,
       len);
-    Int::from_le(dst)
+    Prim::from_repr(Int::from_be(dst))
+  }
+
+  #[inline]
+  pub unsafe fn unsafe_peek_le<T: Prim>(&self, pos: u32) -> T {
+    let len = prim_len::<T>();
+    self.debug_check_range_usize(pos, len);
+
+    let mut dst: T::Repr = mem::uninitialized();
+
+    let dst_ptr = &mut dst as *mut T::Repr;
+    ptr::copy_nonoverlapping_memory(
+      dst_ptr as *mut u8,
+      self.buf.offset((self.lo + pos) as isize) as *const u8,
+      len);
+    Prim::from_repr(Int::from_le(dst))
   }
 
   #[inline]
@@ -1080,13 +2251,13 @@ impl<'a> RawIobuf<'a> {
   }
 
   #[inline]
-  pub unsafe fn unsafe_poke_be<T: Int>(&self, pos: u32, mut t: T) {
-    let len = mem::size_of::<T>();
+  pub unsafe fn unsafe_poke_be<T: Prim>(&self, pos: u32, t: T) {
+    let len = prim_len::<T>();
     self.debug_check_range_usize(pos, len);
 
-    t = t.to_be();
+    let t = t.to_repr().to_be();
 
-    let tp = &t as *const T;
+    let tp = &t as *const T::Repr;
 
     ptr::copy_nonoverlapping_memory(
       self.buf.offset((self.lo + pos) as isize),
@@ -1095,15 +2266,15 @@ impl<'a> RawIobuf<'a> {
   }
 
   #[inline]
-  pub unsafe fn unsafe_poke_le<T: Int>(&self, pos: u32, mut t: T) {
-    let len = mem::size_of::<T>();
+  pub unsafe fn unsafe_poke_le<T: Prim>(&self, pos: u32, t: T) {
+    let len = prim_len::<T>();
     self.debug_check_range_usize(pos, len);
 
-    t = t.to_le();
+    let t = t.to_repr().to_le();
 
     ptr::copy_nonoverlapping_memory(
       self.buf.offset((self.lo + pos) as isize),
-      &t as *const T as *const u8,
+      &t as *const T::Repr as *const u8,
       len);
   }
 
@@ -1115,16 +2286,16 @@ impl<'a> RawIobuf<'a> {
   }
 
   #[inline]
-  pub unsafe fn unsafe_fill_be<T: Int>(&mut self, t: T) {
-    let bytes = mem::size_of::<T>() as u32;
+  pub unsafe fn unsafe_fill_be<T: Prim>(&mut self, t: T) {
+    let bytes = prim_len::<T>() as u32;
     self.debug_check_range_u32(0, bytes);
     self.unsafe_poke_be(0, t);
     self.lo += bytes;
   }
 
   #[inline]
-  pub unsafe fn unsafe_fill_le<T: Int>(&mut self, t: T) {
-    let bytes = mem::size_of::<T>() as u32;
+  pub unsafe fn unsafe_fill_le<T: Prim>(&mut self, t: T) {
+    let bytes = prim_len::<T>() as u32;
     self.debug_check_range_u32(0, bytes);
     self.unsafe_poke_le(0, t);
     self.lo += bytes;
@@ -1138,8 +2309,8 @@ impl<'a> RawIobuf<'a> {
   }
 
   #[inline]
-  pub unsafe fn unsafe_consume_le<T: Int>(&mut self) -> T {
-    let bytes = mem::size_of::<T>() as u32;
+  pub unsafe fn unsafe_consume_le<T: Prim>(&mut self) -> T {
+    let bytes = prim_len::<T>() as u32;
     self.debug_check_range_u32(0, bytes);
     let ret = self.unsafe_peek_le::<T>(0);
     self.lo += bytes;
@@ -1147,8 +2318,8 @@ impl<'a> RawIobuf<'a> {
   }
 
   #[inline]
-  pub unsafe fn unsafe_consume_be<T: Int>(&mut self) -> T {
-    let bytes = mem::size_of::<T>() as u32;
+  pub unsafe fn unsafe_consume_be<T: Prim>(&mut self) -> T {
+    let bytes = prim_len::<T>() as u32;
     self.debug_check_range_u32(0, bytes);
     let ret = self.unsafe_peek_be::<T>(0);
     self.lo += bytes;
@@ -1227,6 +2398,28 @@ impl<'a> RawIobuf<'a> {
     write!(f, "\n")
   }
 
+  pub fn debug_state(&self) -> IobufState {
+    let (buffer_len, ownership, refcount) =
+      match self.header() {
+        None => (self.hi_max, Ownership::Borrowed, 0),
+        Some(h) => unsafe {
+          let len = (h.allocation_length - mem::size_of::<AllocationHeader>()) as u32;
+          let ownership = if h.allocator.is_null() { Ownership::Heap } else { Ownership::Allocator };
+          (len, ownership, h.atomic_refcount().load(Ordering::Relaxed) as u32)
+        },
+      };
+
+    IobufState {
+      lo_min:     self.lo_min(),
+      lo:         self.lo,
+      hi:         self.hi,
+      hi_max:     self.hi_max,
+      buffer_len: buffer_len,
+      ownership:  ownership,
+      refcount:   refcount,
+    }
+  }
+
   pub fn show(&self, f: &mut Formatter, ty: &str) -> fmt::Result {
     try!(write!(f, "{} IObuf, limits=[{},{}), bounds=[{},{})\n",
                 ty, self.lo_min(), self.hi_max, self.lo, self.hi));
@@ -1348,3 +2541,790 @@ fn test_allocator() {
 
   RWIobuf::new_with_allocator(1000, Arc::new(Box::new(MyAllocator) as Box<Allocator>));
 }
+
+#[test]
+fn consume_arrays() {
+  use impls::ROIobuf;
+  use iobuf::Iobuf;
+
+  let data = [ 0x00, 0x01, 0x00, 0x02, 0x00, 0x03, 0x00, 0x04, 0x3f, 0x80, 0x00, 0x00 ];
+
+  let mut b = ROIobuf::from_slice(&data);
+  assert_eq!(b.consume_be_array(), Ok([ 1u16, 2, 3, 4 ]));
+  assert_eq!(b.len(), 4);
+  assert_eq!(b.consume_be_array::<[u16; 3]>(), Err(()));
+  assert_eq!(b.len(), 4);
+  assert_eq!(b.consume_be_array(), Ok([ 0x3f80_0000u32 ]));
+  assert!(b.is_empty());
+
+  let mut b = ROIobuf::from_slice(&data);
+  assert_eq!(b.consume_le_array(), Ok([ 0x0100u16, 0x0200, 0x0300, 0x0400 ]));
+  assert_eq!(b.consume_le_array(), Ok([ 0x803fu16, 0x0000 ]));
+  assert!(b.is_empty());
+}
+
+#[test]
+fn ignore_ascii_case() {
+  use iobuf::Iobuf;
+  use impls::ROIobuf;
+
+  let b = ROIobuf::from_str("Content-Type");
+  assert!(b.eq_ignore_ascii_case(b"content-type"));
+  assert!(b.eq_ignore_ascii_case(b"CONTENT-TYPE"));
+  assert!(!b.eq_ignore_ascii_case(b"content-typ"));
+  assert!(!b.eq_ignore_ascii_case(b"content_type"));
+  assert!(b.starts_with_ignore_ascii_case(b"CONTENT-"));
+  assert!(b.starts_with_ignore_ascii_case(b""));
+  assert!(!b.starts_with_ignore_ascii_case(b"content-type-x"));
+
+  // Only ASCII is folded. "É" is 0xC3 0x89 and "é" is 0xC3 0xA9 in UTF-8.
+  let b = ROIobuf::from_str("X-Caf\u{e9}");
+  assert!(b.eq_ignore_ascii_case("x-CAF\u{e9}".as_bytes()));
+  assert!(!b.eq_ignore_ascii_case("x-caf\u{c9}".as_bytes()));
+  assert!(!b.eq_ignore_ascii_case(b"x-caf\xc3\x89"));
+  assert!(b.starts_with_ignore_ascii_case(b"x-caf\xc3"));
+}
+
+#[test]
+fn ascii_checks() {
+  use iobuf::Iobuf;
+  use impls::ROIobuf;
+
+  let b = ROIobuf::from_str("Host: example.com");
+  assert!(b.is_ascii());
+  assert!(b.is_ascii_printable());
+  assert_eq!(b.first_non_ascii(), None);
+
+  let b = ROIobuf::from_str("Host: example.com\r\n");
+  assert!(b.is_ascii());
+  assert!(!b.is_ascii_printable());
+
+  let b = ROIobuf::from_str("X-Name: caf\u{e9}");
+  assert!(!b.is_ascii());
+  assert!(!b.is_ascii_printable());
+  assert_eq!(b.first_non_ascii(), Some(11));
+
+  let b = ROIobuf::from_str("");
+  assert!(b.is_ascii());
+  assert!(b.is_ascii_printable());
+  assert_eq!(b.first_non_ascii(), None);
+}
+
+#[test]
+fn owns_slice() {
+  use iobuf::Iobuf;
+  use impls::{ROIobuf, RWIobuf};
+
+  let mut b = RWIobuf::from_str_copy("hello, world");
+  let c = b.clone();
+  assert_eq!(b.advance(7), Ok(()));
+  b.narrow();
+
+  unsafe {
+    // Limits don't matter for buffers we allocated.
+    assert!(b.owns_slice(c.as_window_slice()));
+    assert!(b.owns_slice(&c.as_window_slice()[3..5]));
+    assert!(b.owns_slice(&c.as_window_slice()[12..]));
+  }
+
+  let s = b"hello, world";
+  assert!(!b.owns_slice(s));
+
+  let d = RWIobuf::from_str_copy("hello, world");
+  assert!(!b.owns_slice(unsafe { d.as_window_slice() }));
+
+  // For everything else, only the limits are known.
+  let mut e = ROIobuf::from_slice(s);
+  assert_eq!(e.sub(2, 5), Ok(()));
+  assert!(e.owns_slice(&s[2..7]));
+  assert!(e.owns_slice(&s[3..4]));
+  assert!(!e.owns_slice(&s[1..4]));
+  assert!(!e.owns_slice(&s[5..8]));
+}
+
+#[test]
+fn u128_as_u64_pairs() {
+  use std::u64;
+  use iobuf::Iobuf;
+  use impls::RWIobuf;
+
+  let vals = [ [ u64::MAX, u64::MAX ],
+               [ 0, 0 ],
+               [ 0x0001_0203_0405_0607, 0x0809_0A0B_0C0D_0E0F ] ];
+
+  for v in vals.iter() {
+    let mut b = RWIobuf::new(16);
+    assert_eq!(b.fill_be(v[0]), Ok(()));
+    assert_eq!(b.fill_be(v[1]), Ok(()));
+    b.flip_lo();
+    assert_eq!(b.peek_be(15), Ok(v[1] as u8));
+    assert_eq!(b.consume_be_array(), Ok(*v));
+
+    let mut b = RWIobuf::new(16);
+    assert_eq!(b.fill_le(v[0]), Ok(()));
+    assert_eq!(b.fill_le(v[1]), Ok(()));
+    b.flip_lo();
+    assert_eq!(b.peek_be(0), Ok(v[0] as u8));
+    assert_eq!(b.consume_le_array(), Ok(*v));
+  }
+
+  let mut b = RWIobuf::new(16);
+  assert_eq!(b.fill(&[ 0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15 ]), Ok(()));
+  b.flip_lo();
+  assert_eq!(b.consume_be_array(), Ok([ 0x0001_0203_0405_0607u64, 0x0809_0A0B_0C0D_0E0F ]));
+}
+
+#[test]
+fn float_arrays() {
+  use std::f32;
+  use iobuf::Iobuf;
+  use impls::RWIobuf;
+
+  let v = [ 1.5f32, -0.0, f32::INFINITY ];
+
+  let mut b = RWIobuf::new(12);
+  for &x in v.iter() { assert_eq!(b.fill_be(x), Ok(())); }
+  b.flip_lo();
+  assert_eq!(b.peek_be(0), Ok(0x3FC00000u32));
+  assert_eq!(b.peek_be(4), Ok(0x80000000u32));
+  let got: [f32; 3] = b.consume_be_array().unwrap();
+  assert_eq!(got, v);
+  assert!(got[1].is_sign_negative());
+
+  let mut b = RWIobuf::new(12);
+  for &x in v.iter() { assert_eq!(b.fill_le(x), Ok(())); }
+  b.flip_lo();
+  assert_eq!(b.peek_le(0), Ok(0x3FC00000u32));
+  assert_eq!(b.consume_le_array(), Ok(v));
+
+  let b = RWIobuf::new(8);
+  assert_eq!(b.poke_be(0, -2.25f64), Ok(()));
+  assert_eq!(b.peek_be(0), Ok(0xC002000000000000u64));
+  assert_eq!(b.peek_be(0), Ok(-2.25f64));
+}
+
+#[test]
+fn user_prims() {
+  use iobuf::Iobuf;
+  use impls::RWIobuf;
+
+  #[derive(Copy, PartialEq, Debug)]
+  struct PortId(u16);
+
+  impl Prim for PortId {
+    type Repr = u16;
+    fn from_repr(r: u16) -> PortId { PortId(r) }
+    fn to_repr(self) -> u16 { self.0 }
+  }
+
+  let mut b = RWIobuf::new(10);
+
+  assert_eq!(b.poke_be(0, PortId(0x0102)), Ok(()));
+  assert_eq!(b.poke_le(2, PortId(0x0102)), Ok(()));
+  assert_eq!(b.poke_be(9, PortId(0)), Err(()));
+  unsafe {
+    b.unsafe_poke_be(4, PortId(0x0304));
+    b.unsafe_poke_le(6, PortId(0x0304));
+  }
+
+  assert_eq!(b.peek_be(0), Ok(0x0102u16));
+  assert_eq!(b.peek_be(2), Ok(0x0201u16));
+  assert_eq!(b.peek_be(4), Ok(PortId(0x0304)));
+  assert_eq!(b.peek_le(6), Ok(PortId(0x0304)));
+  assert_eq!(b.peek_be::<PortId>(9), Err(()));
+  unsafe {
+    assert_eq!(b.unsafe_peek_be(0), PortId(0x0102));
+    assert_eq!(b.unsafe_peek_le(2), PortId(0x0102));
+  }
+
+  assert_eq!(b.fill_be(PortId(0xAABB)), Ok(()));
+  assert_eq!(b.fill_le(PortId(0xAABB)), Ok(()));
+  unsafe {
+    b.unsafe_fill_be(PortId(0xCCDD));
+    b.unsafe_fill_le(PortId(0xCCDD));
+  }
+  assert_eq!(b.len(), 2);
+  assert_eq!(b.fill_be(PortId(0)), Ok(()));
+  assert_eq!(b.fill_be(PortId(0)), Err(()));
+
+  b.flip_lo();
+
+  assert_eq!(b.consume_be(), Ok(PortId(0xAABB)));
+  assert_eq!(b.consume_le(), Ok(PortId(0xAABB)));
+  unsafe {
+    assert_eq!(b.unsafe_consume_be(), PortId(0xCCDD));
+    assert_eq!(b.unsafe_consume_le(), PortId(0xCCDD));
+  }
+  assert_eq!(b.consume_be_array(), Ok([ PortId(0) ]));
+  assert_eq!(b.consume_be::<PortId>(), Err(()));
+}
+
+#[test]
+fn upto_boundaries() {
+  use iobuf::Iobuf;
+  use impls::ROIobuf;
+
+  let mut b = ROIobuf::from_str("0123456789");
+  assert_eq!(b.sub_window(2, 5), Ok(()));
+
+  let mut c = b.clone();
+  assert_eq!(c.advance_upto(4), 4);
+  assert_eq!(c.len(), 1);
+  let mut c = b.clone();
+  assert_eq!(c.advance_upto(5), 5);
+  assert!(c.is_empty());
+  let mut c = b.clone();
+  assert_eq!(c.advance_upto(6), 5);
+  assert!(c.is_empty());
+  assert_eq!(c.advance_upto(1), 0);
+  assert_eq!(c.lo(), 7);
+
+  let mut c = b.clone();
+  assert_eq!(c.extend_upto(2), 2);
+  assert_eq!(c.hi(), 9);
+  let mut c = b.clone();
+  assert_eq!(c.extend_upto(3), 3);
+  assert_eq!(c.hi(), 10);
+  let mut c = b.clone();
+  assert_eq!(c.extend_upto(4), 3);
+  assert_eq!(c.hi(), 10);
+  assert_eq!(c.extend_upto(1), 0);
+
+  let mut c = b.clone();
+  assert_eq!(c.resize_upto(0), 0);
+  assert!(c.is_empty());
+  assert_eq!(c.resize_upto(7), 7);
+  assert_eq!(c.hi(), 9);
+  assert_eq!(c.resize_upto(8), 8);
+  assert_eq!(c.hi(), 10);
+  assert_eq!(c.resize_upto(9), 8);
+  assert_eq!(c.hi(), 10);
+  assert_eq!(c.lo(), 2);
+}
+
+#[test]
+fn entropy_bounds() {
+  use iobuf::Iobuf;
+  use impls::ROIobuf;
+
+  assert_eq!(ROIobuf::empty().shannon_entropy(), 0.0);
+  assert_eq!(ROIobuf::from_str("ab").shannon_entropy(), 1.0);
+  assert_eq!(ROIobuf::from_str("abcd").shannon_entropy(), 2.0);
+
+  let mut b = ROIobuf::from_str("aaaabcd");
+  assert_eq!(b.advance(3), Ok(()));
+  assert_eq!(b.shannon_entropy(), 2.0);
+  assert_eq!(b.byte_histogram()[b'a' as usize], 1);
+}
+
+#[test]
+fn consume_tag_at_boundaries() {
+  use iobuf::Iobuf;
+  use impls::ROIobuf;
+
+  let mut b = ROIobuf::from_str("abcdef");
+  assert_eq!(b.resize(3), Ok(()));
+
+  // The tag runs past the end of the window, but not the limits.
+  assert_eq!(b.consume_tag(b"abcd"), Err(TagError::Incomplete));
+  assert_eq!(b.consume_tag(b"abxd"), Err(TagError::Mismatch(2)));
+  assert_eq!(b.consume_tag_ignore_ascii_case(b"ABCD"), Err(TagError::Incomplete));
+  assert_eq!(b.lo(), 0);
+
+  // Exactly the window.
+  assert_eq!(b.consume_tag_ignore_ascii_case(b"ABC"), Ok(()));
+  assert!(b.is_empty());
+
+  // An empty tag always matches, and an empty window is always incomplete.
+  assert_eq!(b.consume_tag(b""), Ok(()));
+  assert_eq!(b.consume_tag(b"d"), Err(TagError::Incomplete));
+  assert_eq!(b.lo(), 3);
+}
+
+#[test]
+fn window_as_slice_alignment() {
+  use iobuf::Iobuf;
+  use impls::ROIobuf;
+
+  let data = [ 0u64, 0x0102030405060708, !0 ];
+  let bytes: &[u8] = unsafe { mem::transmute(raw::Slice { data: data.as_ptr() as *const u8, len: 24 }) };
+  let mut b = ROIobuf::from_slice(bytes);
+
+  unsafe {
+    assert_eq!(b.window_as_slice::<u64>(), Ok(&data[]));
+    assert_eq!(b.window_as_slice::<u8>(), Ok(bytes));
+
+    for i in range(1u32, 8) {
+      let mut c = b.clone();
+      assert_eq!(c.advance(i), Ok(()));
+      assert_eq!(c.resize(16), Ok(()));
+      assert_eq!(c.window_as_slice::<u64>(), Err(()));
+    }
+
+    // Aligned, but not a whole number of u64s.
+    assert_eq!(b.resize(20), Ok(()));
+    assert_eq!(b.window_as_slice::<u64>(), Err(()));
+    assert_eq!(b.window_as_slice::<u32>().map(|s| s.len()), Ok(5));
+
+    assert_eq!(b.advance(8), Ok(()));
+    assert_eq!(b.resize(8), Ok(()));
+    assert_eq!(b.window_as_slice::<u64>(), Ok(&data[1..2]));
+  }
+}
+
+#[test]
+fn consume_opt_trailing_fields() {
+  use iobuf::Iobuf;
+  use impls::ROIobuf;
+
+  let data = [ 0x00, 0x01, 0x00, 0x00, 0x00, 0x02, 0xFF, 0xFF ];
+
+  // Full: both optional fields are there.
+  let mut b = ROIobuf::from_slice(&data[..6]);
+  assert_eq!(b.consume_be::<u16>(), Ok(1));
+  assert_eq!(b.consume_be_opt::<u32>(), Some(2));
+  assert!(b.is_empty());
+
+  // Empty: the optional field was left off.
+  assert_eq!(b.consume_be_opt::<u32>(), None);
+  assert_eq!(b.consume_le_opt::<u8>(), None);
+  assert_eq!(b.lo(), 6);
+
+  // Partial: a truncated field is missing, not half-read.
+  let mut b = ROIobuf::from_slice(&data[..5]);
+  assert_eq!(b.consume_be::<u16>(), Ok(1));
+  assert_eq!(b.consume_le_opt::<u32>(), None);
+  assert_eq!(b.consume_be_opt::<u32>(), None);
+  assert_eq!(b.len(), 3);
+  assert_eq!(b.consume_le_opt::<u16>(), Some(0));
+}
+
+#[test]
+fn peek_all_into_reuses_capacity() {
+  use iobuf::Iobuf;
+  use impls::ROIobuf;
+
+  let mut v = Vec::new();
+
+  let b = ROIobuf::from_str("0123456789");
+  b.peek_all_into(&mut v);
+  assert_eq!(&v[], b"0123456789");
+
+  let (cap, ptr) = (v.capacity(), v.as_ptr());
+
+  let mut c = ROIobuf::from_str("abcdefghij");
+  c.consume_all_into(&mut v);
+  assert_eq!(&v[], b"abcdefghij");
+  assert!(c.is_empty());
+  assert_eq!(v.capacity(), cap);
+  assert_eq!(v.as_ptr(), ptr);
+
+  // A smaller window still doesn't reallocate.
+  let b = ROIobuf::from_str("xyz");
+  b.peek_all_into(&mut v);
+  assert_eq!(&v[], b"xyz");
+  assert_eq!(v.as_ptr(), ptr);
+}
+
+#[test]
+fn rfind_slice_edges() {
+  use iobuf::Iobuf;
+  use impls::ROIobuf;
+
+  let b = ROIobuf::from_str("aaaa");
+  assert_eq!(b.rfind_slice(b"aa"), Some(2));
+  assert_eq!(b.rfind_slice(b"aaaa"), Some(0));
+  assert_eq!(b.rfind_slice(b"aaaaa"), None);
+
+  let b = ROIobuf::from_str("abcab");
+  assert_eq!(b.rfind_slice(b"ab"), Some(3));
+  assert_eq!(b.rfind_slice(b"abc"), Some(0));
+  assert_eq!(b.rfind_slice(b"bca"), Some(1));
+  assert_eq!(b.rfind_slice(b"cb"), None);
+  assert_eq!(b.rfind(b'a'), Some(3));
+  assert_eq!(b.rfind(b'c'), Some(2));
+
+  let e = ROIobuf::empty();
+  assert_eq!(e.rfind(b'a'), None);
+  assert_eq!(e.rfind_slice(b""), Some(0));
+  assert_eq!(e.rfind_slice(b"a"), None);
+}
+
+#[test]
+fn trim_matches_edges() {
+  use iobuf::Iobuf;
+  use impls::ROIobuf;
+
+  let mut b = ROIobuf::from_str("    ");
+  b.trim_ascii_whitespace();
+  assert!(b.is_empty());
+  assert_eq!(b.cap(), 4);
+
+  let mut b = ROIobuf::from_str("x");
+  b.trim_ascii_whitespace();
+  assert_eq!(b.len(), 1);
+
+  let mut b = ROIobuf::from_str("\t\r\n a b \n");
+  b.trim_ascii_whitespace();
+  unsafe { assert_eq!(b.as_window_slice(), b"a b"); }
+
+  // Only the window shrinks, so the trimmed bytes are still in the limits.
+  b.reset();
+  assert_eq!(b.len(), 9);
+
+  let mut e = ROIobuf::empty();
+  e.trim_matches(|_| true);
+  assert!(e.is_empty());
+}
+
+#[test]
+fn consume_enum_rolls_back() {
+  use iobuf::Iobuf;
+  use impls::ROIobuf;
+
+  iobuf_enum! {
+    enum Op: u16 {
+      Read  = 0x0100,
+      Write = 0x0200,
+    }
+  }
+
+  let data = [ 0x01, 0x00, 0x03, 0x00, 0x02 ];
+  let mut b = ROIobuf::from_slice(&data);
+
+  assert_eq!(b.consume_be_enum(Op::from_prim), Ok(Op::Read));
+  assert_eq!(b.consume_be_enum(Op::from_prim), Err(EnumError::Invalid(0x0300)));
+  assert_eq!(b.len(), 3);
+  assert_eq!(b.consume_le_enum(Op::from_prim), Err(EnumError::Invalid(0x0003)));
+  assert_eq!(b.advance(1), Ok(()));
+  assert_eq!(b.consume_le_enum(Op::from_prim), Ok(Op::Write));
+  assert!(b.is_empty());
+
+  let mut b = ROIobuf::from_slice(&data[..1]);
+  assert_eq!(b.consume_be_enum(Op::from_prim), Err(EnumError::Incomplete));
+  assert_eq!(b.len(), 1);
+}
+
+#[test]
+fn dedup_edges() {
+  use iobuf::Iobuf;
+  use impls::RWIobuf;
+
+  let mut b = RWIobuf::from_str_copy("");
+  assert_eq!(b.dedup(), 0);
+
+  let mut b = RWIobuf::from_str_copy("aaaa");
+  assert_eq!(b.dedup(), 1);
+  unsafe { assert_eq!(b.as_window_slice(), b"a"); }
+
+  let mut b = RWIobuf::from_str_copy("xabbay");
+  assert_eq!(b.advance(1), Ok(()));
+  assert_eq!(b.resize(4), Ok(()));
+  assert_eq!(b.dedup(), 3);
+  b.reset();
+  // Only the window is touched.
+  unsafe { assert_eq!(b.as_window_slice(), b"xabaay"); }
+}
+
+#[test]
+fn fill_native_slice_is_a_memcpy() {
+  use iobuf::Iobuf;
+  use impls::RWIobuf;
+
+  let src = [ 0x01020304u32, 0x05060708 ];
+
+  let mut b = RWIobuf::new(9);
+  assert_eq!(b.fill_native_slice(&src), Ok(()));
+  assert_eq!(b.len(), 1);
+  assert_eq!(b.fill_native_slice(&src[..1]), Err(()));
+  assert_eq!(b.fill_native_slice::<u32>(&[]), Ok(()));
+  b.flip_lo();
+
+  unsafe {
+    let expected: &[u8] =
+      if cfg!(target_endian = "little") { &[ 4, 3, 2, 1, 8, 7, 6, 5 ] }
+      else                              { &[ 1, 2, 3, 4, 5, 6, 7, 8 ] };
+    assert_eq!(b.as_window_slice(), expected);
+    assert_eq!(b.window_as_slice::<u32>(), Ok(&src[]));
+  }
+}
+
+#[test]
+fn fill_vectored_all_or_nothing() {
+  use iobuf::Iobuf;
+  use impls::RWIobuf;
+
+  let mut b = RWIobuf::from_slice_copy(&[0; 8]);
+  assert_eq!(b.fill_vectored(&[ b"hdr", b"", b"body", b"!!" ]), Err(()));
+  assert_eq!(b.len(), 8);
+  unsafe { assert_eq!(b.as_window_slice(), [0; 8]); }
+
+  assert_eq!(b.fill_vectored(&[ b"hdr", b"", b"body", b"!" ]), Ok(()));
+  assert!(b.is_empty());
+  assert_eq!(b.fill_vectored(&[]), Ok(()));
+  b.flip_lo();
+  unsafe { assert_eq!(b.as_window_slice(), b"hdrbody!"); }
+
+  assert_eq!(b.poke_vectored(6, &[ b"x", b"y" ]), Ok(()));
+  assert_eq!(b.poke_vectored(7, &[ b"x", b"y" ]), Err(()));
+  unsafe { assert_eq!(b.as_window_slice(), b"hdrbodxy"); }
+}
+
+#[test]
+fn reset_to_front_widens_unique_buffers() {
+  use iobuf::Iobuf;
+  use impls::RWIobuf;
+
+  let mut b = RWIobuf::from_str_copy("..hello..");
+  assert_eq!(b.sub(1, 7), Ok(()));
+  assert_eq!(b.advance(1), Ok(()));
+  assert_eq!(b.resize(5), Ok(()));
+
+  b.reset_to_front();
+  assert_eq!((b.lo_min(), b.hi_max()), (0, 9));
+  assert_eq!(b.len(), 5);
+  assert_eq!(b.lo_space(), 0);
+  assert_eq!(b.hi_space(), 4);
+  unsafe { assert_eq!(&b.as_limit_slice()[..5], b"hello"); }
+
+  // A shared buffer keeps its limits.
+  let mut b = RWIobuf::from_str_copy("..hello..");
+  assert_eq!(b.sub(1, 7), Ok(()));
+  assert_eq!(b.advance(3), Ok(()));
+  let c = b.clone();
+  b.reset_to_front();
+  assert_eq!((b.lo_min(), b.hi_max()), (1, 8));
+  unsafe { assert_eq!(b.as_window_slice(), b"llo."); }
+  assert_eq!(b.lo_space(), 0);
+  drop(c);
+}
+
+#[test]
+fn consume_vectored_edges() {
+  use iobuf::Iobuf;
+  use impls::ROIobuf;
+
+  let mut b = ROIobuf::from_str("abcdefg");
+
+  let mut x = [0u8; 2];
+  let mut y = [0u8; 0];
+  let mut z = [0u8; 3];
+
+  assert_eq!(b.consume_vectored(&mut [ &mut x, &mut y, &mut z ]), Ok(()));
+  assert_eq!((&x[], &z[]), (&b"ab"[], &b"cde"[]));
+  assert_eq!(b.len(), 2);
+
+  // Short: nothing is copied, and the window doesn't move.
+  let mut w = [0u8; 2];
+  assert_eq!(b.consume_vectored(&mut [ &mut y, &mut w, &mut x ]), Err(()));
+  assert_eq!(w, [ 0, 0 ]);
+  assert_eq!(b.len(), 2);
+
+  let mut v = [0u8; 0];
+  assert_eq!(b.consume_vectored(&mut [ &mut y, &mut v, &mut w ]), Ok(()));
+  assert_eq!(&w[], b"fg");
+  assert!(b.is_empty());
+  assert_eq!(b.consume_vectored(&mut []), Ok(()));
+}
+
+#[test]
+fn fill_json_escaped_edges() {
+  use iobuf::Iobuf;
+  use impls::RWIobuf;
+
+  fn escape(s: &str) -> Vec<u8> {
+    let mut b = RWIobuf::new(64);
+    assert_eq!(b.fill_json_escaped(s), Ok(()));
+    b.flip_lo();
+    unsafe { b.as_window_slice().to_vec() }
+  }
+
+  assert_eq!(escape(""), b"");
+  assert_eq!(escape("\\/\t\r\x08\x0c"), b"\\\\/\\t\\r\\b\\f");
+  assert_eq!(escape("\x00\x1f\x7f"), b"\\u0000\\u001f\x7f");
+  assert_eq!(escape("\u{e9}\u{20ac}"), b"\\u00e9\\u20ac");
+  // U+1F600 is a surrogate pair in UTF-16.
+  assert_eq!(escape("\u{1f600}"), b"\\ud83d\\ude00");
+
+  // Not enough room for the whole thing means nothing's written.
+  let mut b = RWIobuf::new(7);
+  assert_eq!(b.fill_json_escaped("a\u{e9}"), Ok(()));
+  assert_eq!(b.fill_json_escaped("\""), Err(()));
+  assert_eq!(b.len(), 0);
+  assert_eq!(b.fill_json_escaped(""), Ok(()));
+
+  let mut b = RWIobuf::new(3);
+  assert_eq!(b.fill_json_escaped("ab\n"), Err(()));
+  assert_eq!(b.len(), 3);
+}
+
+#[test]
+fn consume_int_sign_extends() {
+  use std::i64;
+  use iobuf::Iobuf;
+  use impls::ROIobuf;
+
+  // -2 as a 24-bit sample, then 0x7FFFFF, the largest positive one.
+  let data = [ 0xFF, 0xFF, 0xFE, 0x7F, 0xFF, 0xFF ];
+  let mut b = ROIobuf::from_slice(&data);
+  assert_eq!(b.consume_int_be(3), Ok(-2));
+  assert_eq!(b.consume_int_be(3), Ok(0x7FFFFF));
+  assert_eq!(b.consume_int_be(1), Err(()));
+
+  let mut b = ROIobuf::from_slice(&data);
+  assert_eq!(b.consume_int_le(3), Ok(-0x010001));
+  assert_eq!(b.consume_int_le(2), Ok(-129));
+  assert_eq!(b.consume_int_le(2), Err(()));
+  assert_eq!(b.len(), 1);
+
+  let data = [ 0x80, 0, 0, 0, 0, 0, 0, 0 ];
+  assert_eq!(ROIobuf::from_slice(&data).consume_int_be(8), Ok(i64::MIN));
+  assert_eq!(ROIobuf::from_slice(&data).consume_int_be(1), Ok(-128));
+  assert_eq!(ROIobuf::from_slice(&data).consume_int_le(8), Ok(0x80));
+}
+
+#[test]
+#[should_fail]
+fn consume_int_too_wide() {
+  use iobuf::Iobuf;
+  use impls::ROIobuf;
+
+  let _ = ROIobuf::from_slice(&[0; 9]).consume_int_be(9);
+}
+
+#[test]
+fn compact_overlapping() {
+  use iobuf::Iobuf;
+  use impls::RWIobuf;
+
+  let data: Vec<u8> = range(0u8, 10).collect();
+
+  // Source [2, 8) and destination [0, 6) overlap.
+  let mut b = RWIobuf::from_slice_copy(&data[]);
+  assert_eq!(b.advance(2), Ok(()));
+  assert_eq!(b.resize(6), Ok(()));
+  b.compact();
+  assert_eq!((b.lo(), b.hi()), (6, 10));
+  b.flip_lo();
+  unsafe { assert_eq!(b.as_window_slice(), [ 2, 3, 4, 5, 6, 7 ]); }
+
+  // Off by one byte.
+  let mut b = RWIobuf::from_slice_copy(&data[]);
+  assert_eq!(b.advance(1), Ok(()));
+  b.compact();
+  b.flip_lo();
+  unsafe { assert_eq!(b.as_window_slice(), &data[1..]); }
+
+  // Already at the front: nothing moves, but the window still does.
+  let mut b = RWIobuf::from_slice_copy(&data[]);
+  assert_eq!(b.resize(4), Ok(()));
+  b.compact();
+  assert_eq!((b.lo(), b.hi()), (4, 10));
+  unsafe { assert_eq!(b.as_limit_slice(), &data[]); }
+
+  // Nothing left at all.
+  let mut b = RWIobuf::from_slice_copy(&data[]);
+  assert_eq!(b.advance(10), Ok(()));
+  b.compact();
+  assert_eq!((b.lo(), b.hi()), (0, 10));
+
+  // Respects the lower limit.
+  let mut b = RWIobuf::from_slice_copy(&data[]);
+  assert_eq!(b.advance(3), Ok(()));
+  b.narrow();
+  b.reset();
+  assert_eq!(b.advance(5), Ok(()));
+  b.compact();
+  assert_eq!((b.lo(), b.hi()), (5, 10));
+  unsafe { assert_eq!(b.as_limit_slice(), [ 8, 9, 5, 6, 7, 8, 9 ]); }
+
+  // Doesn't overlap, so the unchecked version is fine.
+  let mut b = RWIobuf::from_slice_copy(&data[]);
+  assert_eq!(b.advance(6), Ok(()));
+  unsafe { b.unsafe_compact(); }
+  b.flip_lo();
+  unsafe { assert_eq!(b.as_window_slice(), [ 6, 7, 8, 9 ]); }
+}
+
+#[bench]
+fn compact_nothing_to_move(bench: &mut ::test::Bencher) {
+  use iobuf::Iobuf;
+  use impls::RWIobuf;
+
+  // The common case in a receive loop: everything was consumed.
+  let mut b = RWIobuf::new(4096);
+  bench.iter(|| {
+    unsafe { b.unsafe_advance(4096) };
+    b.compact();
+    ::test::black_box(b.len())
+  })
+}
+
+#[test]
+fn replace_byte_edges() {
+  use iobuf::Iobuf;
+  use impls::RWIobuf;
+
+  let mut b = RWIobuf::from_str_copy("a\r\nb\r\n\r");
+  assert_eq!(b.advance(1), Ok(()));
+  assert_eq!(b.resize(5), Ok(()));
+  // Only the window is touched.
+  assert_eq!(b.replace_byte(b'\r', b'\n'), 2);
+  b.reset();
+  unsafe { assert_eq!(b.as_window_slice(), b"a\n\nb\n\n\r"); }
+
+  // Replacing a byte with itself still counts it.
+  assert_eq!(b.replace_byte(b'\n', b'\n'), 4);
+  assert_eq!(RWIobuf::empty().replace_byte(0, 1), 0);
+}
+
+#[test]
+fn q16_16_round_trips() {
+  use iobuf::Iobuf;
+  use impls::RWIobuf;
+
+  let resolution = 1.0 / 65536.0;
+  let values = [ 0.0, 1.0, -1.0, 3.14159265358979, -2.71828182845904,
+                 32767.99998, -32768.0, resolution, -resolution, 1234.5678 ];
+
+  let mut b = RWIobuf::new(4 * values.len());
+  for &x in values.iter() {
+    assert_eq!(b.fill_q16_16_be(x), Ok(()));
+  }
+  assert_eq!(b.fill_q16_16_be(0.0), Err(()));
+  b.flip_lo();
+
+  for &x in values.iter() {
+    let y = b.consume_q16_16_be().unwrap();
+    assert!((x - y).abs() <= resolution / 2.0, "{} came back as {}", x, y);
+  }
+  assert_eq!(b.consume_q16_16_be(), Err(()));
+
+  // Out of range, and not a number at all.
+  let b = RWIobuf::new(4);
+  assert_eq!(b.poke_fixed_be(0, 32768.0, 16, 16), Err(()));
+  assert_eq!(b.poke_fixed_be(0, -32768.00001, 16, 16), Err(()));
+  assert_eq!(b.poke_fixed_be(0, 0.0 / 0.0, 16, 16), Err(()));
+
+  // Halfway between two steps rounds away from zero.
+  assert_eq!(b.poke_fixed_be(0, 0.5, 8, 0), Ok(()));
+  assert_eq!(b.peek_be::<u8>(0), Ok(1));
+  assert_eq!(b.poke_fixed_be(0, -0.5, 8, 0), Ok(()));
+  assert_eq!(b.peek_be::<u8>(0), Ok(0xFF));
+
+  // Other formats.
+  assert_eq!(b.poke_fixed_be(0, -1.5, 4, 12), Ok(()));
+  assert_eq!(b.peek_be::<u16>(0), Ok(0xE800));
+  assert_eq!(b.peek_fixed_be(0, 4, 12), Ok(-1.5));
+  assert_eq!(b.poke_fixed_be(0, 0.25, 1, 31), Ok(()));
+  assert_eq!(b.peek_be::<u32>(0), Ok(0x20000000));
+}
+
+#[test]
+#[should_fail]
+fn fixed_point_needs_whole_bytes() {
+  use iobuf::Iobuf;
+  use impls::ROIobuf;
+
+  let _ = ROIobuf::from_slice(&[0; 4]).consume_fixed_be(12, 8);
+}
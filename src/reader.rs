@@ -0,0 +1,166 @@
+//! Lets Iobufs be used wherever a `Reader` or `Buffer` is expected, so that
+//! `read_line`, `read_until`, `lines`, and friends all work on them.
+//!
+//! An Iobuf is a fixed buffer with no underlying stream behind it. Reading
+//! just consumes the window, and `fill_buf` never makes more bytes available
+//! than were already in it. Once the window is empty, reads fail with
+//! `EndOfFile`.
+//!
+//! This is all done through `IobufReader`. See its docs for why the Iobufs
+//! aren't `Reader`s themselves.
+
+use std::cmp;
+use std::io::{self, Buffer, IoResult, Reader};
+
+use iobuf::Iobuf;
+
+#[inline]
+fn read_into<Buf: Iobuf>(b: &mut Buf, dst: &mut [u8]) -> IoResult<usize> {
+  if dst.is_empty() { return Ok(0) }
+  let n = cmp::min(b.len() as usize, dst.len());
+  if n == 0 { return Err(io::standard_error(io::EndOfFile)) }
+  unsafe { b.unsafe_consume(&mut dst[..n]) };
+  Ok(n)
+}
+
+#[inline]
+fn fill_buf<'b, Buf: Iobuf>(b: &'b mut Buf) -> IoResult<&'b [u8]> {
+  if b.is_empty() {
+    Err(io::standard_error(io::EndOfFile))
+  } else {
+    Ok(unsafe { b.as_window_slice() })
+  }
+}
+
+#[inline]
+fn consume<Buf: Iobuf>(b: &mut Buf, amt: usize) {
+  let amt = cmp::min(b.len() as usize, amt);
+  unsafe { b.unsafe_advance(amt as u32) }
+}
+
+/// A `Reader` and `Buffer` which owns an Iobuf, and reads by consuming its
+/// window. Made by `Iobuf::into_reader`.
+///
+/// It doesn't borrow anything, so it can be moved into something that keeps
+/// the reader around, like a decompressor.
+///
+/// The Iobufs don't implement `Reader` and `Buffer` themselves. If they did,
+/// `Buffer::consume` would clash with `Iobuf::consume` wherever both traits
+/// are in scope (which, with the prelude, is everywhere), and every such call
+/// would have to be spelled out in full. To keep using the Iobuf after
+/// reading some of it, get it back with `into_inner`, or read from a clone,
+/// which is cheap.
+///
+/// ```rust
+/// use iobuf::{Iobuf, ROIobuf};
+///
+/// let b = ROIobuf::from_str("GET / HTTP/1.1\nHost: x\n");
+///
+/// let mut r = b.clone().into_reader();
+/// assert_eq!(r.read_line(), Ok("GET / HTTP/1.1\n".to_string()));
+///
+/// let rest = r.into_inner();
+/// assert_eq!(rest.len(), 8);
+/// assert_eq!(b.len(), 23);
+/// ```
+pub struct IobufReader<Buf> {
+  buf: Buf,
+}
+
+impl<Buf: Iobuf> IobufReader<Buf> {
+  /// Reads from `buf`'s window.
+  #[inline(always)]
+  pub fn new(buf: Buf) -> IobufReader<Buf> { IobufReader { buf: buf } }
+
+  /// The underlying Iobuf. Its window holds the bytes that haven't been read
+  /// yet.
+  #[inline(always)]
+  pub fn get_ref(&self) -> &Buf { &self.buf }
+
+  /// The underlying Iobuf. Moving its window changes what's read next.
+  #[inline(always)]
+  pub fn get_mut(&mut self) -> &mut Buf { &mut self.buf }
+
+  /// Gives back the Iobuf, with whatever hasn't been read still in the window.
+  #[inline(always)]
+  pub fn into_inner(self) -> Buf { self.buf }
+}
+
+impl<Buf: Iobuf> Reader for IobufReader<Buf> {
+  #[inline]
+  fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> { read_into(&mut self.buf, buf) }
+}
+
+impl<Buf: Iobuf> Buffer for IobufReader<Buf> {
+  #[inline]
+  fn fill_buf<'b>(&'b mut self) -> IoResult<&'b [u8]> { fill_buf(&mut self.buf) }
+
+  #[inline]
+  fn consume(&mut self, amt: usize) { consume(&mut self.buf, amt) }
+}
+
+#[test]
+fn read_until_newline() {
+  use impls::{ROIobuf, RWIobuf};
+
+  let mut r = ROIobuf::from_str("GET / HTTP/1.1\nHost: x\n\ntrailing").into_reader();
+
+  assert_eq!(r.read_until(b'\n'), Ok(b"GET / HTTP/1.1\n".to_vec()));
+  assert_eq!(r.read_line(), Ok("Host: x\n".to_string()));
+  assert_eq!(r.read_until(b'\n'), Ok(b"\n".to_vec()));
+  assert_eq!(r.get_ref().len(), 8);
+
+  // No newline left: the rest comes back, then end of file.
+  assert_eq!(r.read_until(b'\n'), Ok(b"trailing".to_vec()));
+  assert!(r.get_ref().is_empty());
+  assert_eq!(r.read_until(b'\n').map_err(|e| e.kind), Err(io::EndOfFile));
+
+  let mut r = RWIobuf::from_str_copy("a\nb\nc").into_reader();
+  let lines: Vec<String> = r.lines().map(|l| l.unwrap()).collect();
+  assert_eq!(lines, vec!("a\n".to_string(), "b\n".to_string(), "c".to_string()));
+
+  let mut r = ROIobuf::from_str("abc").into_reader();
+  let mut dst = [0u8; 2];
+  assert_eq!(r.read(&mut dst), Ok(2));
+  assert_eq!(dst, [ b'a', b'b' ]);
+  assert_eq!(r.read(&mut dst), Ok(1));
+  assert_eq!(r.read(&mut dst).map_err(|e| e.kind), Err(io::EndOfFile));
+
+  // Both `consume`s are in scope here, and neither needs spelling out.
+  let mut b = ROIobuf::from_str("xy");
+  let mut dst = [0u8; 1];
+  assert_eq!(b.consume(&mut dst), Ok(()));
+  let mut r = b.into_reader();
+  r.consume(1);
+  assert!(r.get_ref().is_empty());
+}
+
+#[test]
+fn owning_reader_reads_to_the_end() {
+  use impls::{ROIobuf, RWIobuf};
+
+  // Takes the reader by value, and keeps it past the caller's borrows.
+  fn slurp<R: Reader + 'static>(mut r: R) -> Vec<u8> {
+    r.read_to_end().unwrap()
+  }
+
+  let b = ROIobuf::from_str_copy("compressed, probably");
+  assert_eq!(slurp(b.into_reader()), b"compressed, probably".to_vec());
+
+  let mut b = RWIobuf::from_str_copy("0123456789");
+  assert_eq!(b.advance(4), Ok(()));
+  let a = b.atomic_read_only().unwrap();
+  assert_eq!(slurp(a.into_reader()), b"456789".to_vec());
+
+  let mut r = ROIobuf::from_str("ab\ncd").into_reader();
+  assert_eq!(r.read_line(), Ok("ab\n".to_string()));
+  let mut dst = [0u8; 1];
+  assert_eq!(r.read(&mut dst), Ok(1));
+  assert_eq!(dst, [ b'c' ]);
+  assert_eq!(r.get_ref().len(), 1);
+  let rest = r.into_inner();
+  unsafe { assert_eq!(rest.as_window_slice(), b"d"); }
+
+  let mut r = ROIobuf::empty().into_reader();
+  assert_eq!(r.read(&mut dst).map_err(|e| e.kind), Err(io::EndOfFile));
+}
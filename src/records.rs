@@ -0,0 +1,145 @@
+use std::marker::CovariantType;
+
+use iobuf::{Iobuf, ReadFrom};
+
+/// An iterator which decodes `ReadFrom` records from the front of an Iobuf's
+/// window, advancing past each one. See `Iobuf::read_records`.
+///
+/// Iteration stops once the window is empty. If a record fails to decode,
+/// like a partial record at the end, `Some(Err(()))` is returned once, the
+/// window is left at the start of that record, and iteration stops.
+pub struct RecordIter<'b, Buf: 'b, H> {
+  buf:    &'b mut Buf,
+  failed: bool,
+  marker: CovariantType<H>,
+}
+
+impl<'b, Buf: Iobuf, H: ReadFrom> RecordIter<'b, Buf, H> {
+  /// Decodes records from the front of `buf`'s window.
+  #[inline]
+  pub fn new(buf: &'b mut Buf) -> RecordIter<'b, Buf, H> {
+    RecordIter {
+      buf:    buf,
+      failed: false,
+      marker: CovariantType,
+    }
+  }
+}
+
+impl<'b, Buf: Iobuf, H: ReadFrom> Iterator for RecordIter<'b, Buf, H> {
+  type Item = Result<H, ()>;
+
+  #[inline]
+  fn next(&mut self) -> Option<Result<H, ()>> {
+    if self.failed || self.buf.is_empty() { return None; }
+
+    let ret = self.buf.with_rollback(|b| ReadFrom::read_from(b));
+    if ret.is_err() { self.failed = true; }
+    Some(ret)
+  }
+}
+
+/// An iterator which cuts `size`-byte records off the front of an Iobuf's
+/// window, advancing past each one. See `Iobuf::consume_records`.
+///
+/// Iteration stops once fewer than `size` bytes are left. Those bytes stay in
+/// the window, and `leftover` says how many there are.
+pub struct Records<'b, Buf: 'b> {
+  buf:  &'b mut Buf,
+  size: u32,
+}
+
+impl<'b, Buf: Iobuf> Records<'b, Buf> {
+  /// Cuts `size`-byte records off the front of `buf`'s window.
+  #[inline]
+  pub fn new(buf: &'b mut Buf, size: u32) -> Records<'b, Buf> {
+    if size == 0 { panic!("Iobuf::consume_records: the record size must not be 0") }
+    Records { buf: buf, size: size }
+  }
+
+  /// The number of bytes at the end of the window which don't make up a
+  /// whole record. Once iteration stops, these are a partial record, waiting
+  /// on more input.
+  #[inline]
+  pub fn leftover(&self) -> u32 {
+    self.buf.len() % self.size
+  }
+}
+
+impl<'b, Buf: Iobuf> Iterator for Records<'b, Buf> {
+  type Item = Buf;
+
+  #[inline]
+  fn next(&mut self) -> Option<Buf> {
+    if self.buf.len() < self.size { return None; }
+
+    let mut ret = self.buf.clone();
+    unsafe {
+      ret.unsafe_resize(self.size);
+      self.buf.unsafe_advance(self.size);
+    }
+    Some(ret)
+  }
+
+  #[inline]
+  fn size_hint(&self) -> (usize, Option<usize>) {
+    let n = (self.buf.len() / self.size) as usize;
+    (n, Some(n))
+  }
+}
+
+#[test]
+fn stops_after_a_partial_record() {
+  use impls::ROIobuf;
+
+  #[derive(Copy, PartialEq, Debug)]
+  struct Pair(u8, u16);
+
+  impl ReadFrom for Pair {
+    fn read_from<Buf: Iobuf>(b: &mut Buf) -> Result<Pair, ()> {
+      let x = try!(b.consume_be());
+      let y = try!(b.consume_be());
+      Ok(Pair(x, y))
+    }
+  }
+
+  let data = [ 1, 0, 2, 3, 0, 4, 5, 0 ];
+  let mut b = ROIobuf::from_slice(&data);
+
+  {
+    let mut it = b.read_records::<Pair>();
+    assert_eq!(it.next(), Some(Ok(Pair(1, 2))));
+    assert_eq!(it.next(), Some(Ok(Pair(3, 4))));
+    assert_eq!(it.next(), Some(Err(())));
+    assert_eq!(it.next(), None);
+  }
+  assert_eq!(b.len(), 2);
+  assert_eq!(b.peek_be::<u16>(0), Ok(0x0500));
+
+  let mut e = ROIobuf::empty();
+  assert_eq!(e.read_records::<Pair>().next(), None);
+}
+
+#[test]
+fn records_leave_the_partial_one() {
+  use impls::ROIobuf;
+
+  let mut b = ROIobuf::from_str("aaabbbcc");
+
+  {
+    let mut it = b.consume_records(3);
+    assert_eq!(it.size_hint(), (2, Some(2)));
+    assert_eq!(it.leftover(), 2);
+    unsafe {
+      assert_eq!(it.next().unwrap().as_window_slice(), b"aaa");
+      assert_eq!(it.next().unwrap().as_window_slice(), b"bbb");
+    }
+    assert!(it.next().is_none());
+    assert_eq!(it.leftover(), 2);
+  }
+  unsafe { assert_eq!(b.as_window_slice(), b"cc"); }
+
+  assert_eq!(b.consume_records(2).count(), 1);
+  assert!(b.is_empty());
+  assert_eq!(b.consume_records(1).leftover(), 0);
+}
@@ -1,8 +1,39 @@
+use std::cmp;
 use std::mem;
 
+use raw::IobufState;
 use iobuf::Iobuf;
 use impls::{RWIobuf, ROIobuf};
 
+// The capacity of `IORingbuf::default()`, in bytes.
+const DEFAULT_CAPACITY: usize = 4096;
+
+/// A snapshot of an `IORingbuf`'s internals, for logging and bug reports. See
+/// `IORingbuf::debug_state`.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct RingbufState {
+  /// The buffer new data is pushed into. Its window is the free space.
+  pub push:   IobufState,
+  /// The buffer data is popped from. Its window is the data to pop.
+  pub pop:    IobufState,
+  /// The number of bytes waiting to be popped. See `IORingbuf::len`.
+  pub queued: u32,
+  /// The number of bytes which could still be pushed, in total.
+  pub free:   u32,
+}
+
+/// Counters covering an `IORingbuf`'s whole life, or since the last
+/// `reset_stats`, for tuning buffer sizes. See `IORingbuf::stats`.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct RingStats {
+  /// The number of bytes pushed in, in total.
+  pub total_pushed: u64,
+  /// The number of bytes popped out, in total.
+  pub total_popped: u64,
+  /// The most bytes that were ever queued up at once.
+  pub max_queued:   u32,
+}
+
 /// A ring buffer implemented with `Iobuf`s.
 pub struct IORingbuf {
   /// The contents of the window is space for input to be put into. Therefore,
@@ -11,6 +42,16 @@ pub struct IORingbuf {
   /// The contents of the window are things needing to be output. Therefore,
   /// initally empty.
   o_buf: RWIobuf<'static>,
+  /// See `set_high_watermark`.
+  high: u32,
+  /// See `set_low_watermark`.
+  low:  u32,
+  /// See `stats`. These only include traffic up to the last `sync_stats`.
+  stats: RingStats,
+  /// `i_buf.lo_space()` as of the last `sync_stats`.
+  push_mark: u32,
+  /// `o_buf.len()` as of the last `sync_stats`.
+  pop_mark:  u32,
 }
 
 impl IORingbuf {
@@ -21,6 +62,11 @@ impl IORingbuf {
       IORingbuf {
         i_buf: RWIobuf::new(left_size),
         o_buf: RWIobuf::new(cap - left_size),
+        high:  cap as u32,
+        low:   0,
+        stats: RingStats { total_pushed: 0, total_popped: 0, max_queued: 0 },
+        push_mark: 0,
+        pop_mark:  0,
       };
     ret.o_buf.flip_lo(); // start with an empty o_buf.
     ret
@@ -31,8 +77,9 @@ impl IORingbuf {
   ///
   /// It is easy to get garbage data if using a clone of the returned Iobuf.
   /// This is not memory-unsafe, but should be avoided.
-  #[inline(always)]
+  #[inline]
   pub fn push_buf(&mut self) -> &mut RWIobuf<'static> {
+    self.sync_stats();
     &mut self.i_buf
   }
 
@@ -47,16 +94,217 @@ impl IORingbuf {
   /// This is not memory-unsafe, but should be avoided.
   #[inline]
   pub fn pop_buf(&mut self) -> &mut ROIobuf<'static> {
+    self.sync_stats();
     if self.o_buf.is_empty() {
       self.i_buf.flip_lo();
       self.o_buf.reset();
       mem::swap(&mut self.i_buf, &mut self.o_buf);
+      self.mark_stats();
     }
     // Clients should only be doing read-only operations into the iobuf, so
     // return a ROIobuf.
     unsafe { mem::transmute(&mut self.o_buf) }
   }
 
+  /// Pops exactly `n` bytes off the front of the ring buffer, as one Iobuf.
+  /// Returns `None`, without changing anything, if fewer than `n` bytes are
+  /// waiting to be popped.
+  ///
+  /// The ring buffer is made of two internal buffers. If the `n` bytes are all
+  /// in the one being popped from, the returned Iobuf shares its memory, and
+  /// nothing is copied. If they straddle the seam between the two, a new
+  /// buffer is allocated, and both pieces are copied into it. Frames which
+  /// are small compared to half the capacity rarely straddle.
+  ///
+  /// Like with `pop_buf`, the memory of a zero-copy result gets reused once
+  /// the ring buffer cycles around to it, or `reserve_contiguous` moves data
+  /// around. Use `window_to_owned` on the result to keep it around longer
+  /// than that.
+  ///
+  /// ```rust
+  /// use iobuf::{IORingbuf, Iobuf};
+  ///
+  /// let mut rb = IORingbuf::new(8);
+  /// assert_eq!(rb.push_buf().fill(b"abcd"), Ok(()));
+  ///
+  /// assert!(rb.pop_exact(5).is_none());
+  /// assert_eq!(rb.len(), 4);
+  ///
+  /// let frame = rb.pop_exact(3).unwrap();
+  /// unsafe { assert_eq!(frame.as_window_slice(), b"abc"); }
+  /// assert_eq!(rb.len(), 1);
+  /// ```
+  pub fn pop_exact(&mut self, n: u32) -> Option<ROIobuf<'static>> {
+    if self.len() < n { return None }
+
+    {
+      let o = self.pop_buf();
+      if o.len() >= n {
+        let mut ret = o.clone();
+        unsafe {
+          ret.unsafe_resize(n);
+          o.unsafe_advance(n);
+        }
+        return Some(ret)
+      }
+    }
+
+    // Straddling the seam. Copy out what's left of the popping side, then
+    // switch sides and copy out the rest.
+    let mut ret = RWIobuf::new(n as usize);
+    unsafe {
+      let first = self.o_buf.len();
+      ret.unsafe_fill(self.o_buf.as_window_slice());
+      self.o_buf.unsafe_advance(first);
+
+      let o = self.pop_buf();
+      let rest = n - first;
+      ret.unsafe_fill(&o.as_window_slice()[..rest as usize]);
+      o.unsafe_advance(rest);
+    }
+    ret.flip_lo();
+    Some(ret.read_only())
+  }
+
+  /// Returns the push buffer, only if its window has room for at least `n`
+  /// bytes in a row, so that a whole frame can be written with one `fill`.
+  ///
+  /// If there isn't enough room, but there would be after moving the data
+  /// waiting to be popped around, that's done first. The data keeps its order.
+  /// Otherwise, `None` is returned and nothing is changed. That's when fewer
+  /// than `n` bytes are free in total.
+  ///
+  /// The ring buffer is made of two internal buffers of about half the
+  /// capacity each, and the push buffer is one of them. So `None` is also
+  /// returned if `n` is bigger than the push buffer's capacity, even if that
+  /// much room is free in total.
+  ///
+  /// ```rust
+  /// use iobuf::{IORingbuf, Iobuf};
+  ///
+  /// let mut rb = IORingbuf::new(8);
+  /// assert_eq!(rb.push_buf().fill(b"abc"), Ok(()));
+  ///
+  /// // Only one byte is left in the push buffer, but the data can be moved
+  /// // out of the way.
+  /// assert_eq!(rb.reserve_contiguous(4).unwrap().fill(b"defg"), Ok(()));
+  /// assert!(rb.reserve_contiguous(5).is_none());
+  ///
+  /// let b = rb.pop_exact(7).unwrap();
+  /// unsafe { assert_eq!(b.as_window_slice(), b"abcdefg"); }
+  /// ```
+  pub fn reserve_contiguous(&mut self, n: u32) -> Option<&mut RWIobuf<'static>> {
+    self.sync_stats();
+    if self.i_buf.len() >= n { return Some(&mut self.i_buf) }
+
+    // Move as much of the pushed data as fits behind the data waiting in the
+    // pop buffer. The rest is moved to the front of the push buffer.
+    let pushed = self.i_buf.lo_space();
+    let total  = self.o_buf.len() + pushed;
+    let rest   = if total > self.o_buf.cap() { total - self.o_buf.cap() } else { 0 };
+    if pushed == 0 || self.i_buf.cap() - rest < n { return None }
+
+    self.o_buf.compact();
+    self.i_buf.flip_lo();
+    let moved = pushed - rest;
+    unsafe {
+      self.o_buf.unsafe_fill(&self.i_buf.as_window_slice()[..moved as usize]);
+      self.i_buf.unsafe_advance(moved);
+    }
+    self.o_buf.flip_lo();
+    self.i_buf.compact();
+    self.mark_stats();
+
+    Some(&mut self.i_buf)
+  }
+
+  /// How much traffic has gone through the ring buffer since it was made, or
+  /// since the last `reset_stats`.
+  ///
+  /// The ring buffer can't see pushes and pops as they happen, since they're
+  /// done straight on the Iobufs returned by `push_buf` and `pop_buf`. It
+  /// catches up on them whenever one of its own functions is called, instead.
+  /// Data pushed but not yet handed over to the popping side counts as
+  /// pushed and queued. The queue can only grow while the push buffer is
+  /// borrowed, so `max_queued` is still exact.
+  ///
+  /// ```rust
+  /// use iobuf::{IORingbuf, Iobuf, RingStats};
+  ///
+  /// let mut rb = IORingbuf::new(8);
+  ///
+  /// assert_eq!(rb.push_buf().fill(b"abc"), Ok(()));
+  /// assert_eq!(rb.pop_buf().advance(2), Ok(()));
+  /// assert_eq!(rb.push_buf().fill(b"defg"), Ok(()));
+  ///
+  /// assert_eq!(rb.stats(), RingStats { total_pushed: 7, total_popped: 2, max_queued: 5 });
+  ///
+  /// rb.reset_stats();
+  /// assert_eq!(rb.stats(), RingStats { total_pushed: 0, total_popped: 0, max_queued: 5 });
+  /// ```
+  pub fn stats(&self) -> RingStats {
+    let (pushed, popped) = self.unsynced_traffic();
+    RingStats {
+      total_pushed: self.stats.total_pushed + pushed as u64,
+      total_popped: self.stats.total_popped + popped as u64,
+      max_queued:   cmp::max(self.stats.max_queued, self.len()),
+    }
+  }
+
+  /// Zeroes the totals, and starts tracking the deepest queue over from the
+  /// current depth.
+  pub fn reset_stats(&mut self) {
+    self.mark_stats();
+    self.stats = RingStats { total_pushed: 0, total_popped: 0, max_queued: self.len() };
+  }
+
+  /// The bytes pushed and popped since the last `sync_stats`.
+  #[inline]
+  fn unsynced_traffic(&self) -> (u32, u32) {
+    let pushed = self.i_buf.lo_space();
+    let left   = self.o_buf.len();
+    (if pushed > self.push_mark { pushed - self.push_mark } else { 0 },
+     if left < self.pop_mark { self.pop_mark - left } else { 0 })
+  }
+
+  /// Catches the stats up with any pushes and pops since the last call.
+  #[inline]
+  fn sync_stats(&mut self) {
+    self.stats = self.stats();
+    self.mark_stats();
+  }
+
+  /// Records where the buffers are, so that only traffic after this point is
+  /// counted by the next `sync_stats`. Call this after moving data between
+  /// the buffers.
+  #[inline]
+  fn mark_stats(&mut self) {
+    self.push_mark = self.i_buf.lo_space();
+    self.pop_mark  = self.o_buf.len();
+  }
+
+  /// Takes a snapshot of both internal buffers, and how much data is queued
+  /// up. See `Iobuf::debug_state`.
+  ///
+  /// ```rust
+  /// use iobuf::{IORingbuf, Iobuf};
+  ///
+  /// let mut rb = IORingbuf::new(8);
+  /// assert_eq!(rb.push_buf().fill(b"abc"), Ok(()));
+  ///
+  /// let s = rb.debug_state();
+  /// assert_eq!((s.queued, s.free), (3, 5));
+  /// assert_eq!((s.push.lo, s.push.hi), (3, 4));
+  /// ```
+  pub fn debug_state(&self) -> RingbufState {
+    RingbufState {
+      push:   self.i_buf.debug_state(),
+      pop:    self.o_buf.debug_state(),
+      queued: self.len(),
+      free:   self.cap() - self.len(),
+    }
+  }
+
   /// `true` if there is no data to pop in the Iobuf.
   #[inline]
   pub fn is_empty(&self) -> bool {
@@ -68,4 +316,225 @@ impl IORingbuf {
   pub fn is_full(&self) -> bool {
     self.i_buf.is_empty()
   }
+
+  /// The number of bytes of data waiting to be popped. This includes data
+  /// which has been pushed, but isn't visible through `pop_buf` yet.
+  #[inline]
+  pub fn len(&self) -> u32 {
+    self.i_buf.lo_space() + self.o_buf.len()
+  }
+
+  /// The most data the ring buffer can hold at once.
+  #[inline]
+  pub fn cap(&self) -> u32 {
+    self.i_buf.cap() + self.o_buf.cap()
+  }
+
+  /// Sets the watermark `above_high_watermark` checks against. This defaults
+  /// to the ring buffer's capacity.
+  #[inline(always)]
+  pub fn set_high_watermark(&mut self, bytes: u32) { self.high = bytes }
+
+  /// Sets the watermark `below_low_watermark` checks against. This defaults
+  /// to 0.
+  #[inline(always)]
+  pub fn set_low_watermark(&mut self, bytes: u32) { self.low = bytes }
+
+  /// `true` if at least the high watermark's worth of data is waiting to be
+  /// popped. Use this to stop reading more data in, or to start flushing it
+  /// out. With the default watermark, this means the ring buffer is full.
+  ///
+  /// ```rust
+  /// use iobuf::{IORingbuf, Iobuf};
+  ///
+  /// let mut rb = IORingbuf::new(8);
+  /// rb.set_high_watermark(3);
+  /// rb.set_low_watermark(1);
+  ///
+  /// assert!(rb.below_low_watermark());
+  /// assert_eq!(rb.push_buf().fill(b"abc"), Ok(()));
+  /// assert!(rb.above_high_watermark());
+  ///
+  /// assert_eq!(rb.pop_buf().advance(2), Ok(()));
+  /// assert!(!rb.above_high_watermark());
+  /// assert!(rb.below_low_watermark());
+  /// ```
+  #[inline]
+  pub fn above_high_watermark(&self) -> bool {
+    self.len() >= self.high
+  }
+
+  /// `true` if no more than the low watermark's worth of data is waiting to
+  /// be popped. Use this to start reading data in again. With the default
+  /// watermark, this means the ring buffer is empty.
+  #[inline]
+  pub fn below_low_watermark(&self) -> bool {
+    self.len() <= self.low
+  }
+}
+
+/// The default ring buffer has room for 4 KiB.
+impl Default for IORingbuf {
+  #[inline]
+  fn default() -> IORingbuf { IORingbuf::new(DEFAULT_CAPACITY) }
+}
+
+#[test]
+fn watermarks_through_cycles() {
+  let mut rb = IORingbuf::new(10);
+  assert_eq!(rb.cap(), 10);
+
+  // The defaults are "full" and "empty".
+  assert!(rb.below_low_watermark());
+  assert!(!rb.above_high_watermark());
+  assert_eq!(rb.push_buf().fill(b"abcde"), Ok(()));
+  assert!(rb.is_full());
+  assert!(!rb.above_high_watermark());
+  assert_eq!(rb.pop_buf().len(), 5);
+  assert_eq!(rb.push_buf().fill(b"fghij"), Ok(()));
+  assert_eq!(rb.len(), 10);
+  assert!(rb.above_high_watermark());
+
+  assert_eq!(rb.pop_buf().advance(5), Ok(()));
+  assert_eq!(rb.pop_buf().advance(5), Ok(()));
+  assert!(rb.is_empty());
+  assert!(rb.below_low_watermark());
+
+  rb.set_high_watermark(6);
+  rb.set_low_watermark(2);
+
+  for _ in range(0u32, 3) {
+    let mut pushed = 0;
+    while !rb.above_high_watermark() {
+      // Unflipped data in the push buffer counts.
+      assert_eq!(rb.push_buf().fill(b"x"), Ok(()));
+      pushed += 1;
+      if rb.is_full() { rb.pop_buf(); }
+    }
+    assert_eq!(pushed, 6);
+    assert_eq!(rb.len(), 6);
+
+    let mut popped = 0;
+    while !rb.below_low_watermark() {
+      assert!(rb.pop_buf().advance(1).is_ok());
+      popped += 1;
+    }
+    assert_eq!(popped, 4);
+    assert_eq!(rb.len(), 2);
+
+    while !rb.is_empty() {
+      let b = rb.pop_buf();
+      let n = b.len();
+      assert_eq!(b.advance(n), Ok(()));
+    }
+  }
+}
+
+#[test]
+fn default_capacity() {
+  let rb: IORingbuf = Default::default();
+  assert_eq!(rb.cap(), 4096);
+  assert!(rb.is_empty());
+}
+
+#[test]
+fn pop_exact_across_the_seam() {
+  let mut rb = IORingbuf::new(8);
+
+  assert_eq!(rb.push_buf().fill(b"abcd"), Ok(()));
+  assert_eq!(rb.pop_exact(3).map(|b| unsafe { b.as_window_slice().to_vec() }), Some(b"abc".to_vec()));
+
+  assert_eq!(rb.push_buf().fill(b"efgh"), Ok(()));
+  assert!(rb.is_full());
+  assert_eq!(rb.len(), 5);
+
+  // "d" is left on the popping side, and "efgh" is on the pushing side.
+  assert!(rb.pop_exact(6).is_none());
+  assert_eq!(rb.len(), 5);
+  let b = rb.pop_exact(3).unwrap();
+  unsafe { assert_eq!(b.as_window_slice(), b"def"); }
+  assert_eq!(b.cap(), 3);
+  assert_eq!(rb.len(), 2);
+
+  let b = rb.pop_exact(2).unwrap();
+  unsafe { assert_eq!(b.as_window_slice(), b"gh"); }
+  assert!(rb.is_empty());
+  assert_eq!(rb.pop_exact(0).map(|b| b.len()), Some(0));
+}
+
+#[test]
+fn reserve_contiguous_with_pops() {
+  let mut rb = IORingbuf::new(10);
+  let mut next = 0u8;
+  let mut expect = 0u8;
+
+  for round in range(0u32, 20) {
+    // Push frames of up to half the capacity.
+    let n = round % 5 + 1;
+    match rb.reserve_contiguous(n) {
+      None => assert!(rb.cap() - rb.len() < n),
+      Some(b) => {
+        assert!(b.len() >= n);
+        for _ in range(0, n) {
+          assert_eq!(b.fill_be(next), Ok(()));
+          next += 1;
+        }
+      }
+    }
+
+    // Pop some, and check nothing got reordered.
+    let k = if rb.len() < 3 { rb.len() } else { 3 };
+    let b = rb.pop_exact(k).unwrap();
+    unsafe {
+      for &c in b.as_window_slice().iter() {
+        assert_eq!(c, expect);
+        expect += 1;
+      }
+    }
+  }
+
+  while !rb.is_empty() {
+    let b = rb.pop_buf();
+    while let Ok(c) = b.consume_be::<u8>() {
+      assert_eq!(c, expect);
+      expect += 1;
+    }
+  }
+  assert_eq!(expect, next);
+}
+
+#[test]
+fn stats_for_known_traffic() {
+  let mut rb = IORingbuf::new(10);
+  assert_eq!(rb.stats(), RingStats { total_pushed: 0, total_popped: 0, max_queued: 0 });
+
+  // Fill the push side, without flipping it over.
+  assert_eq!(rb.push_buf().fill(b"abcd"), Ok(()));
+  assert_eq!(rb.stats(), RingStats { total_pushed: 4, total_popped: 0, max_queued: 4 });
+
+  // Flip it over, then top it up.
+  assert_eq!(rb.pop_buf().advance(1), Ok(()));
+  assert_eq!(rb.push_buf().fill(b"efghi"), Ok(()));
+  assert_eq!(rb.stats(), RingStats { total_pushed: 9, total_popped: 1, max_queued: 8 });
+
+  // Drain it through `pop_exact`, across the seam.
+  assert!(rb.pop_exact(6).is_some());
+  assert_eq!(rb.stats(), RingStats { total_pushed: 9, total_popped: 7, max_queued: 8 });
+
+  // Moving data between the buffers doesn't count as traffic.
+  assert_eq!(rb.push_buf().fill(b"jkl"), Ok(()));
+  assert!(rb.reserve_contiguous(3).is_some());
+  assert_eq!(rb.push_buf().len(), 5);
+  assert_eq!(rb.stats(), RingStats { total_pushed: 12, total_popped: 7, max_queued: 8 });
+
+  rb.reset_stats();
+  assert_eq!(rb.stats(), RingStats { total_pushed: 0, total_popped: 0, max_queued: 5 });
+
+  while !rb.is_empty() {
+    let b = rb.pop_buf();
+    let n = b.len();
+    assert_eq!(b.advance(n), Ok(()));
+  }
+  assert_eq!(rb.push_buf().fill(b"mn"), Ok(()));
+  assert_eq!(rb.stats(), RingStats { total_pushed: 2, total_popped: 5, max_queued: 5 });
 }
@@ -0,0 +1,156 @@
+use std::io::{self, IoResult, Reader, Writer};
+
+use iobuf::Iobuf;
+use impls::RWIobuf;
+
+/// Copies everything from `r` to `w`, through `scratch`, until `r` hits EOF.
+/// Returns the number of bytes written to `w`.
+///
+/// `scratch`'s window holds output that's still pending, and is written out
+/// first. A new `RWIobuf`'s window covers the whole (uninitialized) buffer, so
+/// `flip_lo` it before the first call to start with nothing pending. On a
+/// write error, the bytes which couldn't be written are left in
+/// `scratch`'s window, so nothing is lost, and calling this again with the
+/// same `scratch` picks up where it left off. A writer which fails partway
+/// through a write may see some of those bytes twice. On a read error, or on
+/// success, `scratch`'s window is left empty.
+///
+/// It's an error (`InvalidInput`) for `scratch` to have no room to read into.
+///
+/// ```rust
+/// use std::io::MemReader;
+/// use iobuf::{Iobuf, RWIobuf, copy_stream};
+///
+/// let mut r = MemReader::new(range(0u8, 100).collect());
+/// let mut w = Vec::new();
+/// let mut scratch = RWIobuf::new(16);
+/// scratch.flip_lo(); // nothing pending yet
+///
+/// assert_eq!(copy_stream(&mut r, &mut w, &mut scratch), Ok(100));
+/// assert_eq!(w, range(0u8, 100).collect::<Vec<u8>>());
+/// assert!(scratch.is_empty());
+/// ```
+pub fn copy_stream<R: Reader, W: Writer>(r: &mut R, w: &mut W, scratch: &mut RWIobuf) -> IoResult<u64> {
+  let mut total = 0u64;
+  loop {
+    if !scratch.is_empty() {
+      let len = scratch.len();
+      try!(w.write(unsafe { scratch.as_window_slice() }));
+      unsafe { scratch.unsafe_advance(len) };
+      total += len as u64;
+    }
+
+    scratch.reset();
+    if scratch.is_empty() {
+      return Err(io::standard_error(io::InvalidInput));
+    }
+
+    let got = r.read(unsafe { scratch.as_mut_window_slice() });
+    match got {
+      Ok(n) => unsafe { scratch.unsafe_advance(n as u32) },
+      Err(_) => {}
+    }
+    scratch.flip_lo();
+
+    match got {
+      Ok(_) => {}
+      Err(ref e) if e.kind == io::EndOfFile => return Ok(total),
+      Err(e) => return Err(e),
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::cmp;
+  use std::io::{self, IoResult, Reader, Writer};
+
+  use iobuf::Iobuf;
+  use impls::RWIobuf;
+  use super::copy_stream;
+
+  // Hands out at most `chunk` bytes per read, and fails once, at `fail_at`.
+  struct ShortReader { data: Vec<u8>, pos: usize, chunk: usize, fail_at: Option<usize> }
+
+  impl Reader for ShortReader {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+      if Some(self.pos) == self.fail_at {
+        self.fail_at = None;
+        return Err(io::standard_error(io::ConnectionReset));
+      }
+      if self.pos == self.data.len() { return Err(io::standard_error(io::EndOfFile)) }
+      let n = cmp::min(self.chunk, cmp::min(buf.len(), self.data.len() - self.pos));
+      buf[..n].clone_from_slice(&self.data[self.pos..self.pos + n]);
+      self.pos += n;
+      Ok(n)
+    }
+  }
+
+  // Fails the `fail_on`th write, without writing anything.
+  struct FlakyWriter { out: Vec<u8>, writes: usize, fail_on: Option<usize> }
+
+  impl Writer for FlakyWriter {
+    fn write(&mut self, buf: &[u8]) -> IoResult<()> {
+      self.writes += 1;
+      if Some(self.writes) == self.fail_on {
+        return Err(io::standard_error(io::BrokenPipe));
+      }
+      self.out.push_all(buf);
+      Ok(())
+    }
+  }
+
+  fn data() -> Vec<u8> { range(0u32, 1000).map(|i| (i * 31) as u8).collect() }
+
+  #[test]
+  fn short_reads() {
+    let mut r = ShortReader { data: data(), pos: 0, chunk: 7, fail_at: None };
+    let mut w = FlakyWriter { out: Vec::new(), writes: 0, fail_on: None };
+    let mut scratch = RWIobuf::new(64);
+    scratch.flip_lo();
+    assert_eq!(copy_stream(&mut r, &mut w, &mut scratch), Ok(1000));
+    assert_eq!(w.out, data());
+    assert!(scratch.is_empty());
+  }
+
+  #[test]
+  fn retry_after_write_error() {
+    let mut r = ShortReader { data: data(), pos: 0, chunk: 100, fail_at: None };
+    let mut w = FlakyWriter { out: Vec::new(), writes: 0, fail_on: Some(3) };
+    let mut scratch = RWIobuf::new(64);
+    scratch.flip_lo();
+
+    let e = copy_stream(&mut r, &mut w, &mut scratch).unwrap_err();
+    assert_eq!(e.kind, io::BrokenPipe);
+    assert_eq!(w.out.len(), 128);
+    assert_eq!(scratch.len(), 64);
+
+    // The failed chunk is written first on the retry.
+    assert_eq!(copy_stream(&mut r, &mut w, &mut scratch), Ok(1000 - 128));
+    assert_eq!(w.out, data());
+  }
+
+  #[test]
+  fn retry_after_read_error() {
+    let mut r = ShortReader { data: data(), pos: 0, chunk: 10, fail_at: Some(500) };
+    let mut w = FlakyWriter { out: Vec::new(), writes: 0, fail_on: None };
+    let mut scratch = RWIobuf::new(64);
+    scratch.flip_lo();
+
+    let e = copy_stream(&mut r, &mut w, &mut scratch).unwrap_err();
+    assert_eq!(e.kind, io::ConnectionReset);
+    assert_eq!(w.out.len(), 500);
+    assert!(scratch.is_empty());
+
+    assert_eq!(copy_stream(&mut r, &mut w, &mut scratch), Ok(500));
+    assert_eq!(w.out, data());
+  }
+
+  #[test]
+  fn no_room() {
+    let mut r = ShortReader { data: data(), pos: 0, chunk: 10, fail_at: None };
+    let mut w = FlakyWriter { out: Vec::new(), writes: 0, fail_on: None };
+    let mut scratch = RWIobuf::new(0);
+    assert_eq!(copy_stream(&mut r, &mut w, &mut scratch).unwrap_err().kind, io::InvalidInput);
+  }
+}
@@ -0,0 +1,135 @@
+use iobuf::Iobuf;
+
+/// An iterator over type-length-value records at the front of an Iobuf's
+/// window, advancing past each one. See `Iobuf::tlv_iter`.
+///
+/// Iteration stops once the window is empty. If a record is truncated, or
+/// its length doesn't fit in a `u32`, `Some(Err(()))` is returned once, the
+/// window is left at the start of that record, and iteration stops.
+pub struct TlvIter<'b, Buf: 'b> {
+  buf:        &'b mut Buf,
+  type_width: u32,
+  len_width:  u32,
+  big_endian: bool,
+  failed:     bool,
+}
+
+/// Reads an unsigned integer as wide as `s`.
+#[inline]
+fn read_uint(s: &[u8], big_endian: bool) -> u64 {
+  if big_endian {
+    s.iter().fold(0u64, |n, &c| n << 8 | c as u64)
+  } else {
+    s.iter().rev().fold(0u64, |n, &c| n << 8 | c as u64)
+  }
+}
+
+impl<'b, Buf: Iobuf> TlvIter<'b, Buf> {
+  /// Iterates over the records at the front of `buf`'s window, with types
+  /// `type_width` bytes long, and lengths `len_width` bytes long.
+  #[inline]
+  pub fn new(buf: &'b mut Buf, type_width: u8, len_width: u8, big_endian: bool) -> TlvIter<'b, Buf> {
+    if type_width > 8 || len_width == 0 || len_width > 8 {
+      panic!("Iobuf::tlv_iter: the type must be 0 to 8 bytes wide, and the length 1 to 8 bytes wide");
+    }
+    TlvIter {
+      buf:        buf,
+      type_width: type_width as u32,
+      len_width:  len_width as u32,
+      big_endian: big_endian,
+      failed:     false,
+    }
+  }
+
+  /// Reads the header of the next record, returning its type, and where its
+  /// value starts and ends.
+  #[inline]
+  fn header(&self) -> Result<(u64, u32, u32), ()> {
+    let hdr = self.type_width + self.len_width;
+    if self.buf.len() < hdr { return Err(()) }
+
+    let s = unsafe { self.buf.as_window_slice() };
+    let ty  = read_uint(&s[..self.type_width as usize], self.big_endian);
+    let len = read_uint(&s[self.type_width as usize..hdr as usize], self.big_endian);
+
+    // Compare against what's left, so a huge length can't wrap the sum.
+    if len > (self.buf.len() - hdr) as u64 { return Err(()) }
+    Ok((ty, hdr, hdr + len as u32))
+  }
+}
+
+impl<'b, Buf: Iobuf> Iterator for TlvIter<'b, Buf> {
+  type Item = Result<(u64, Buf), ()>;
+
+  #[inline]
+  fn next(&mut self) -> Option<Result<(u64, Buf), ()>> {
+    if self.failed || self.buf.is_empty() { return None; }
+
+    match self.header() {
+      Err(()) => {
+        self.failed = true;
+        Some(Err(()))
+      },
+      Ok((ty, start, end)) => {
+        let mut value = self.buf.clone();
+        unsafe {
+          value.unsafe_sub_window(start, end - start);
+          self.buf.unsafe_advance(end);
+        }
+        Some(Ok((ty, value)))
+      },
+    }
+  }
+}
+
+#[test]
+fn truncated_records() {
+  use impls::ROIobuf;
+
+  // 2-byte little-endian types, 1-byte lengths.
+  let data = [ 0x01, 0x00, 0x02, 0xAA, 0xBB,
+               0x02, 0x01, 0x00,
+               0x03, 0x00, 0x05, 0xCC ];
+  let mut b = ROIobuf::from_slice(&data);
+
+  {
+    let mut it = b.tlv_iter(2, 1, false);
+    let (ty, v) = it.next().unwrap().unwrap();
+    assert_eq!(ty, 1);
+    unsafe { assert_eq!(v.as_window_slice(), [ 0xAA, 0xBB ]); }
+    let (ty, v) = it.next().unwrap().unwrap();
+    assert_eq!(ty, 0x0102);
+    assert!(v.is_empty());
+    assert!(it.next().unwrap().is_err());
+    assert!(it.next().is_none());
+  }
+  assert_eq!(b.len(), 4);
+
+  // A header that's cut off.
+  let mut b = ROIobuf::from_slice(&data[..6]);
+  assert_eq!(b.tlv_iter(2, 1, false).count(), 2);
+  assert_eq!(b.len(), 1);
+
+  // No type field.
+  let data = [ 0x00, 0x00, 0x00, 0x01, 0xFF ];
+  let mut b = ROIobuf::from_slice(&data);
+  let vals: Vec<u32> = b.tlv_iter(0, 2, true).map(|r| r.unwrap().1.len()).collect();
+  assert_eq!(vals, vec!(0, 1));
+
+  // A length so big that adding the header to it wraps.
+  let data = [ 0x07, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xAA ];
+  let mut b = ROIobuf::from_slice(&data);
+  {
+    let mut it = b.tlv_iter(1, 8, true);
+    assert!(it.next().unwrap().is_err());
+    assert!(it.next().is_none());
+  }
+  assert_eq!(b.len(), 10);
+}
+
+#[test]
+#[should_fail]
+fn tlv_too_wide() {
+  use impls::ROIobuf;
+  ROIobuf::from_str("").tlv_iter(1, 9, true);
+}
@@ -0,0 +1,803 @@
+//! An Iobuf which logs every operation done through it, for debugging parsers.
+//! Only built with the `trace` feature.
+
+use std::cmp;
+use std::io::{Reader, Writer};
+use std::io::net::ip::{IpAddr, SocketAddr};
+use std::mem;
+use std::num::ToPrimitive;
+
+#[cfg(feature = "num-bigint")] use num_bigint::BigUint;
+
+use raw::{EnumError, IntArray, Prim, TagError, prim_len};
+use iobuf::{Iobuf, IobufWrite, MutIobuf};
+use impls::RWIobuf;
+
+/// An Iobuf which writes a line to `out` for every operation done through it:
+/// the operation and its arguments, the window before and after, and the
+/// result. Failed operations are marked `FAILED`.
+///
+/// Only values returned by the operations are printed, never the contents of
+/// the buffer, so tracing can't read anything outside the window. Errors
+/// writing the trace are ignored.
+///
+/// Windows are printed as `[lo, hi)`, as offsets into the buffer returned by
+/// `ptr()`.
+///
+/// Every method of `Iobuf`, `IobufWrite` and `MutIobuf` which can move the
+/// window or the limits is here, including the `unsafe_` ones, along with
+/// `peek`, `peek_be`, `peek_le`, and the pokes. So is every method of
+/// `RWIobuf`'s own which can move the window, except `fill_uninit`. The
+/// closure-taking ones (`with_rollback`, `with_sub_window`, and the
+/// `consume_repeat`s) hand `f` the `Traced`, so what `f` does is traced, too,
+/// before the line for the method itself.
+///
+/// Methods which hand out a guard or an iterator borrowing the Iobuf
+/// (`transaction`, `drain`, `read_records`, and so on) aren't here. Writing
+/// the trace needs `&mut self`, so `Traced` isn't an `Iobuf` itself. Those
+/// methods, the rest of the `&self` API, and generic code which wants a
+/// `B: Iobuf`, can use the wrapped Iobuf through `get_ref` and `get_mut`, but
+/// nothing done that way is traced.
+///
+/// ```rust
+/// use iobuf::{Iobuf, ROIobuf, Traced};
+///
+/// let mut out = Vec::new();
+/// {
+///   let data = [ 1, 2, 3, 4 ];
+///   let mut b = Traced::new(ROIobuf::from_slice(&data), &mut out);
+///   assert_eq!(b.consume_be::<u16>(), Ok(0x0102));
+///   assert_eq!(b.advance(7), Err(()));
+/// }
+///
+/// assert_eq!(String::from_utf8(out).unwrap(),
+///            "consume_be 16-bit @ [0, 4) -> [2, 4) = 0x102\n\
+///             advance 7 @ [2, 4) FAILED\n");
+/// ```
+pub struct Traced<Buf, W> {
+  buf: Buf,
+  out: W,
+}
+
+// The bits of a primitive, as an unsigned number.
+fn bits<T: Prim>(t: T) -> u64 {
+  let r = t.to_repr();
+  let x = match r.to_u64() {
+    Some(x) => x,
+    None    => r.to_i64().unwrap() as u64,
+  };
+  let len = prim_len::<T>();
+  if len >= 8 { x } else { x & ((1 << (len * 8)) - 1) }
+}
+
+impl<Buf: Iobuf, W: Writer> Traced<Buf, W> {
+  /// Starts tracing the operations on `buf` to `out`.
+  #[inline]
+  pub fn new(buf: Buf, out: W) -> Traced<Buf, W> {
+    Traced { buf: buf, out: out }
+  }
+
+  /// The underlying Iobuf.
+  #[inline(always)]
+  pub fn get_ref(&self) -> &Buf { &self.buf }
+
+  /// The underlying Iobuf. Nothing done through this is traced.
+  #[inline(always)]
+  pub fn get_mut(&mut self) -> &mut Buf { &mut self.buf }
+
+  /// Unwraps the underlying Iobuf and the trace's writer.
+  #[inline(always)]
+  pub fn into_inner(self) -> (Buf, W) { (self.buf, self.out) }
+
+  // Writes the trace line for an operation which started with the window at
+  // `[lo, hi)`. `shown` is the printed result, or `None` if it failed.
+  fn log(&mut self, op: String, lo: u32, hi: u32, shown: Option<String>) {
+    let _ = match shown {
+      Some(s) =>
+        writeln!(&mut self.out, "{} @ [{}, {}) -> [{}, {}){}",
+                 op, lo, hi, self.buf.lo(), self.buf.hi(), s),
+      None =>
+        writeln!(&mut self.out, "{} @ [{}, {}) FAILED", op, lo, hi),
+    };
+  }
+
+  fn trace<R, E, F, S>(&mut self, op: String, f: F, show: S) -> Result<R, E>
+      where F: FnOnce(&mut Buf) -> Result<R, E>,
+            S: FnOnce(&R) -> String {
+    let (lo, hi) = (self.buf.lo(), self.buf.hi());
+    let r = f(&mut self.buf);
+    let shown = r.as_ref().ok().map(show);
+    self.log(op, lo, hi, shown);
+    r
+  }
+
+  // Like `trace`, but `f` gets the `Traced` itself, so everything it does is
+  // traced too, before the line for `op`. The window and limits are put back
+  // if `f` fails.
+  fn trace_rollback<R, E, F>(&mut self, op: String, f: F) -> Result<R, E>
+      where F: FnOnce(&mut Traced<Buf, W>) -> Result<R, E> {
+    let saved = self.buf.clone();
+    let (lo, hi) = (saved.lo(), saved.hi());
+    let r = f(self);
+    let shown = match r {
+      Ok(_)  => Some(String::new()),
+      Err(_) => { self.buf = saved; None },
+    };
+    self.log(op, lo, hi, shown);
+    r
+  }
+
+  /// Traces `Iobuf::peek`.
+  pub fn peek(&mut self, pos: u32, dst: &mut [u8]) -> Result<(), ()> {
+    self.trace(format!("peek {} {}", pos, dst.len()), |b| b.peek(pos, dst), |_| String::new())
+  }
+
+  /// Traces `Iobuf::peek_be`.
+  pub fn peek_be<T: Prim>(&mut self, pos: u32) -> Result<T, ()> {
+    self.trace(format!("peek_be {}-bit {}", prim_len::<T>() * 8, pos),
+               |b| b.peek_be(pos), |&t| format!(" = 0x{:x}", bits(t)))
+  }
+
+  /// Traces `Iobuf::peek_le`.
+  pub fn peek_le<T: Prim>(&mut self, pos: u32) -> Result<T, ()> {
+    self.trace(format!("peek_le {}-bit {}", prim_len::<T>() * 8, pos),
+               |b| b.peek_le(pos), |&t| format!(" = 0x{:x}", bits(t)))
+  }
+
+  /// Traces `Iobuf::advance`.
+  pub fn advance(&mut self, len: u32) -> Result<(), ()> {
+    self.trace(format!("advance {}", len), |b| b.advance(len), |_| String::new())
+  }
+
+  /// Traces `Iobuf::resize`.
+  pub fn resize(&mut self, len: u32) -> Result<(), ()> {
+    self.trace(format!("resize {}", len), |b| b.resize(len), |_| String::new())
+  }
+
+  /// Traces `Iobuf::consume`.
+  pub fn consume(&mut self, dst: &mut [u8]) -> Result<(), ()> {
+    self.trace(format!("consume {}", dst.len()), |b| b.consume(dst), |_| String::new())
+  }
+
+  /// Traces `Iobuf::consume_be`.
+  pub fn consume_be<T: Prim>(&mut self) -> Result<T, ()> {
+    self.trace(format!("consume_be {}-bit", prim_len::<T>() * 8),
+               |b| b.consume_be(), |&t| format!(" = 0x{:x}", bits(t)))
+  }
+
+  /// Traces `Iobuf::consume_le`.
+  pub fn consume_le<T: Prim>(&mut self) -> Result<T, ()> {
+    self.trace(format!("consume_le {}-bit", prim_len::<T>() * 8),
+               |b| b.consume_le(), |&t| format!(" = 0x{:x}", bits(t)))
+  }
+
+  /// Traces `Iobuf::advance_upto`.
+  pub fn advance_upto(&mut self, len: u32) -> u32 {
+    self.trace(format!("advance_upto {}", len),
+               |b| Ok::<u32, ()>(b.advance_upto(len)), |&n| format!(" = {}", n)).unwrap()
+  }
+
+  /// Traces `Iobuf::split_start_at`.
+  pub fn split_start_at(&mut self, pos: u32) -> Result<Buf, ()> {
+    self.trace(format!("split_start_at {}", pos), |b| b.split_start_at(pos), |_| String::new())
+  }
+
+  /// Traces `Iobuf::consume_vectored`.
+  pub fn consume_vectored(&mut self, dsts: &mut [&mut [u8]]) -> Result<(), ()> {
+    let lens: Vec<String> = dsts.iter().map(|d| d.len().to_string()).collect();
+    self.trace(format!("consume_vectored [{}]", lens.connect(", ")),
+               |b| b.consume_vectored(dsts), |_| String::new())
+  }
+
+  /// Traces `Iobuf::consume_all_into`.
+  pub fn consume_all_into(&mut self, dst: &mut Vec<u8>) {
+    let _ = self.trace("consume_all_into".to_string(),
+                       |b| Ok::<(), ()>(b.consume_all_into(dst)), |_| String::new());
+  }
+
+  /// Traces `Iobuf::consume_prim`.
+  pub fn consume_prim<T: Prim>(&mut self, big_endian: bool) -> Result<T, ()> {
+    self.trace(format!("consume_prim {}-bit {}", prim_len::<T>() * 8, if big_endian { "be" } else { "le" }),
+               |b| b.consume_prim(big_endian), |&t| format!(" = 0x{:x}", bits(t)))
+  }
+
+  /// Traces `Iobuf::consume_int_be`.
+  pub fn consume_int_be(&mut self, width: u8) -> Result<i64, ()> {
+    self.trace(format!("consume_int_be {}", width), |b| b.consume_int_be(width), |&x| format!(" = {}", x))
+  }
+
+  /// Traces `Iobuf::consume_int_le`.
+  pub fn consume_int_le(&mut self, width: u8) -> Result<i64, ()> {
+    self.trace(format!("consume_int_le {}", width), |b| b.consume_int_le(width), |&x| format!(" = {}", x))
+  }
+
+  /// Traces `Iobuf::consume_hex_u64`.
+  pub fn consume_hex_u64(&mut self) -> Result<u64, ()> {
+    self.trace("consume_hex_u64".to_string(), |b| b.consume_hex_u64(), |&x| format!(" = 0x{:x}", x))
+  }
+
+  /// Traces `Iobuf::consume_decimal_u64`.
+  pub fn consume_decimal_u64(&mut self) -> Result<u64, ()> {
+    self.trace("consume_decimal_u64".to_string(), |b| b.consume_decimal_u64(), |&x| format!(" = {}", x))
+  }
+
+  /// Traces `Iobuf::consume_fixed_be`.
+  pub fn consume_fixed_be(&mut self, int_bits: u8, frac_bits: u8) -> Result<f64, ()> {
+    self.trace(format!("consume_fixed_be Q{}.{}", int_bits, frac_bits),
+               |b| b.consume_fixed_be(int_bits, frac_bits), |&x| format!(" = {}", x))
+  }
+
+  /// Traces `Iobuf::consume_q16_16_be`.
+  pub fn consume_q16_16_be(&mut self) -> Result<f64, ()> {
+    self.trace("consume_q16_16_be".to_string(), |b| b.consume_q16_16_be(), |&x| format!(" = {}", x))
+  }
+
+  /// Traces `Iobuf::consume_ipv4`.
+  pub fn consume_ipv4(&mut self) -> Result<IpAddr, ()> {
+    self.trace("consume_ipv4".to_string(), |b| b.consume_ipv4(), |x| format!(" = {}", x))
+  }
+
+  /// Traces `Iobuf::consume_ipv6`.
+  pub fn consume_ipv6(&mut self) -> Result<IpAddr, ()> {
+    self.trace("consume_ipv6".to_string(), |b| b.consume_ipv6(), |x| format!(" = {}", x))
+  }
+
+  /// Traces `Iobuf::consume_socket_addr_v4`.
+  pub fn consume_socket_addr_v4(&mut self) -> Result<SocketAddr, ()> {
+    self.trace("consume_socket_addr_v4".to_string(), |b| b.consume_socket_addr_v4(), |x| format!(" = {}", x))
+  }
+
+  /// Traces `Iobuf::consume_tag`. Only the tag's length is printed.
+  pub fn consume_tag(&mut self, tag: &[u8]) -> Result<(), TagError> {
+    self.trace(format!("consume_tag {}", tag.len()), |b| b.consume_tag(tag), |_| String::new())
+  }
+
+  /// Traces `Iobuf::consume_tag_ignore_ascii_case`. Only the tag's length is
+  /// printed.
+  pub fn consume_tag_ignore_ascii_case(&mut self, tag: &[u8]) -> Result<(), TagError> {
+    self.trace(format!("consume_tag_ignore_ascii_case {}", tag.len()),
+               |b| b.consume_tag_ignore_ascii_case(tag), |_| String::new())
+  }
+
+  /// Traces `Iobuf::flip_lo`.
+  pub fn flip_lo(&mut self) {
+    let _ = self.trace("flip_lo".to_string(), |b| Ok::<(), ()>(b.flip_lo()), |_| String::new());
+  }
+
+  /// Traces `Iobuf::flip_hi`.
+  pub fn flip_hi(&mut self) {
+    let _ = self.trace("flip_hi".to_string(), |b| Ok::<(), ()>(b.flip_hi()), |_| String::new());
+  }
+
+  /// Traces `Iobuf::rewind`.
+  pub fn rewind(&mut self) {
+    let _ = self.trace("rewind".to_string(), |b| Ok::<(), ()>(b.rewind()), |_| String::new());
+  }
+
+  /// Traces `Iobuf::reset`.
+  pub fn reset(&mut self) {
+    let _ = self.trace("reset".to_string(), |b| Ok::<(), ()>(b.reset()), |_| String::new());
+  }
+
+  /// Traces `Iobuf::split_to_owned`.
+  pub fn split_to_owned(&mut self, n: u32) -> Result<RWIobuf<'static>, ()> {
+    self.trace(format!("split_to_owned {}", n), |b| b.split_to_owned(n), |_| String::new())
+  }
+
+  /// Traces `Iobuf::sub_window`.
+  pub fn sub_window(&mut self, pos: u32, len: u32) -> Result<(), ()> {
+    self.trace(format!("sub_window {} {}", pos, len), |b| b.sub_window(pos, len), |_| String::new())
+  }
+
+  /// Traces `Iobuf::sub_window_from`.
+  pub fn sub_window_from(&mut self, pos: u32) -> Result<(), ()> {
+    self.trace(format!("sub_window_from {}", pos), |b| b.sub_window_from(pos), |_| String::new())
+  }
+
+  /// Traces `Iobuf::sub_window_to`.
+  pub fn sub_window_to(&mut self, len: u32) -> Result<(), ()> {
+    self.trace(format!("sub_window_to {}", len), |b| b.sub_window_to(len), |_| String::new())
+  }
+
+  /// Traces `Iobuf::sub`.
+  pub fn sub(&mut self, pos: u32, len: u32) -> Result<(), ()> {
+    self.trace(format!("sub {} {}", pos, len), |b| b.sub(pos, len), |_| String::new())
+  }
+
+  /// Traces `Iobuf::sub_from`.
+  pub fn sub_from(&mut self, pos: u32) -> Result<(), ()> {
+    self.trace(format!("sub_from {}", pos), |b| b.sub_from(pos), |_| String::new())
+  }
+
+  /// Traces `Iobuf::sub_to`.
+  pub fn sub_to(&mut self, len: u32) -> Result<(), ()> {
+    self.trace(format!("sub_to {}", len), |b| b.sub_to(len), |_| String::new())
+  }
+
+  /// Traces `Iobuf::set_limits_and_window`.
+  pub fn set_limits_and_window(&mut self, limits: (u32, u32), window: (u32, u32)) -> Result<(), ()> {
+    self.trace(format!("set_limits_and_window [{}, {}) [{}, {})", limits.0, limits.1, window.0, window.1),
+               |b| b.set_limits_and_window(limits, window), |_| String::new())
+  }
+
+  /// Traces `Iobuf::narrow`.
+  pub fn narrow(&mut self) {
+    let _ = self.trace("narrow".to_string(), |b| Ok::<(), ()>(b.narrow()), |_| String::new());
+  }
+
+  /// Traces `Iobuf::extend`.
+  pub fn extend(&mut self, len: u32) -> Result<(), ()> {
+    self.trace(format!("extend {}", len), |b| b.extend(len), |_| String::new())
+  }
+
+  /// Traces `Iobuf::extend_upto`.
+  pub fn extend_upto(&mut self, len: u32) -> u32 {
+    self.trace(format!("extend_upto {}", len),
+               |b| Ok::<u32, ()>(b.extend_upto(len)), |&n| format!(" = {}", n)).unwrap()
+  }
+
+  /// Traces `Iobuf::extend_with`. Only the length of `other`'s window is
+  /// printed.
+  pub fn extend_with<B: Iobuf>(&mut self, other: &B) -> Result<(), ()> {
+    self.trace(format!("extend_with {}", other.len()), |b| b.extend_with(other), |_| String::new())
+  }
+
+  /// Traces `Iobuf::resize_preserving`.
+  pub fn resize_preserving(&mut self, len: u32) -> Result<(), ()> {
+    self.trace(format!("resize_preserving {}", len), |b| b.resize_preserving(len), |_| String::new())
+  }
+
+  /// Traces `Iobuf::resize_upto`.
+  pub fn resize_upto(&mut self, len: u32) -> u32 {
+    self.trace(format!("resize_upto {}", len),
+               |b| Ok::<u32, ()>(b.resize_upto(len)), |&n| format!(" = {}", n)).unwrap()
+  }
+
+  /// Traces `Iobuf::consume_be_opt`.
+  pub fn consume_be_opt<T: Prim>(&mut self) -> Option<T> {
+    self.trace(format!("consume_be_opt {}-bit", prim_len::<T>() * 8),
+               |b| b.consume_be_opt().ok_or(()), |&t| format!(" = 0x{:x}", bits(t))).ok()
+  }
+
+  /// Traces `Iobuf::consume_le_opt`.
+  pub fn consume_le_opt<T: Prim>(&mut self) -> Option<T> {
+    self.trace(format!("consume_le_opt {}-bit", prim_len::<T>() * 8),
+               |b| b.consume_le_opt().ok_or(()), |&t| format!(" = 0x{:x}", bits(t))).ok()
+  }
+
+  /// Traces `Iobuf::consume_biguint_be`.
+  #[cfg(feature = "num-bigint")]
+  pub fn consume_biguint_be(&mut self, len: u32) -> Result<BigUint, ()> {
+    self.trace(format!("consume_biguint_be {}", len), |b| b.consume_biguint_be(len), |x| format!(" = {}", x))
+  }
+
+  /// Traces `Iobuf::consume_be_enum`.
+  pub fn consume_be_enum<T: Prim, E, F: FnOnce(T) -> Option<E>>(&mut self, validate: F) -> Result<E, EnumError<T>> {
+    self.trace(format!("consume_be_enum {}-bit", prim_len::<T>() * 8),
+               |b| b.consume_be_enum(validate), |_| String::new())
+  }
+
+  /// Traces `Iobuf::consume_le_enum`.
+  pub fn consume_le_enum<T: Prim, E, F: FnOnce(T) -> Option<E>>(&mut self, validate: F) -> Result<E, EnumError<T>> {
+    self.trace(format!("consume_le_enum {}-bit", prim_len::<T>() * 8),
+               |b| b.consume_le_enum(validate), |_| String::new())
+  }
+
+  /// Traces `Iobuf::consume_be_array`.
+  pub fn consume_be_array<A: IntArray>(&mut self) -> Result<A, ()> {
+    self.trace(format!("consume_be_array {}", mem::size_of::<A>()), |b| b.consume_be_array(), |_| String::new())
+  }
+
+  /// Traces `Iobuf::consume_le_array`.
+  pub fn consume_le_array<A: IntArray>(&mut self) -> Result<A, ()> {
+    self.trace(format!("consume_le_array {}", mem::size_of::<A>()), |b| b.consume_le_array(), |_| String::new())
+  }
+
+  /// Traces `Iobuf::trim_matches`.
+  pub fn trim_matches<F: FnMut(u8) -> bool>(&mut self, pred: F) {
+    let _ = self.trace("trim_matches".to_string(), |b| Ok::<(), ()>(b.trim_matches(pred)), |_| String::new());
+  }
+
+  /// Traces `Iobuf::trim_ascii_whitespace`.
+  pub fn trim_ascii_whitespace(&mut self) {
+    let _ = self.trace("trim_ascii_whitespace".to_string(),
+                       |b| Ok::<(), ()>(b.trim_ascii_whitespace()), |_| String::new());
+  }
+
+  /// Traces `Iobuf::with_rollback`. Everything `f` does through the `Traced`
+  /// is traced before the line for `with_rollback` itself.
+  pub fn with_rollback<R, E, F: FnOnce(&mut Traced<Buf, W>) -> Result<R, E>>(&mut self, f: F) -> Result<R, E> {
+    self.trace_rollback("with_rollback".to_string(), f)
+  }
+
+  /// Traces `Iobuf::with_sub_window`. Everything `f` does through the
+  /// `Traced` is traced before the line for `with_sub_window` itself.
+  pub fn with_sub_window<R, F: FnOnce(&mut Traced<Buf, W>) -> R>(&mut self, pos: u32, len: u32, f: F) -> Result<R, ()> {
+    let op = format!("with_sub_window {} {}", pos, len);
+    let saved = self.buf.clone();
+    let (lo, hi) = (saved.lo(), saved.hi());
+    if self.buf.sub(pos, len).is_err() {
+      self.log(op, lo, hi, None);
+      return Err(());
+    }
+    self.buf.narrow();
+    let ret = f(self);
+    self.buf = saved;
+    unsafe { self.buf.unsafe_advance(pos + len) }
+    self.log(op, lo, hi, Some(String::new()));
+    Ok(ret)
+  }
+
+  /// Traces `Iobuf::consume_repeat`. Everything `f` does through the
+  /// `Traced` is traced before the line for `consume_repeat` itself.
+  pub fn consume_repeat<R, E, F: FnMut(&mut Traced<Buf, W>) -> Result<R, E>>(&mut self, count: u32, mut f: F) -> Result<Vec<R>, E> {
+    self.trace_rollback(format!("consume_repeat {}", count), |t| {
+      // `count` often comes off the wire, so don't let it size the allocation.
+      let mut ret = Vec::with_capacity(cmp::min(count, t.buf.len()) as usize);
+      for _ in range(0, count) {
+        ret.push(try!(f(t)));
+      }
+      Ok(ret)
+    })
+  }
+
+  /// Traces `Iobuf::consume_repeat_until_empty`. Everything `f` does through
+  /// the `Traced` is traced before the line for `consume_repeat_until_empty`
+  /// itself.
+  pub fn consume_repeat_until_empty<R, E, F: FnMut(&mut Traced<Buf, W>) -> Result<R, E>>(&mut self, mut f: F) -> Result<Vec<R>, E> {
+    self.trace_rollback("consume_repeat_until_empty".to_string(), |t| {
+      let mut ret = Vec::new();
+      while !t.buf.is_empty() {
+        ret.push(try!(f(t)));
+      }
+      Ok(ret)
+    })
+  }
+
+  /// Traces `Iobuf::unsafe_advance`.
+  pub unsafe fn unsafe_advance(&mut self, len: u32) {
+    let _ = self.trace(format!("unsafe_advance {}", len), |b| Ok::<(), ()>(b.unsafe_advance(len)), |_| String::new());
+  }
+
+  /// Traces `Iobuf::unsafe_extend`.
+  pub unsafe fn unsafe_extend(&mut self, len: u32) {
+    let _ = self.trace(format!("unsafe_extend {}", len), |b| Ok::<(), ()>(b.unsafe_extend(len)), |_| String::new());
+  }
+
+  /// Traces `Iobuf::unsafe_resize`.
+  pub unsafe fn unsafe_resize(&mut self, len: u32) {
+    let _ = self.trace(format!("unsafe_resize {}", len), |b| Ok::<(), ()>(b.unsafe_resize(len)), |_| String::new());
+  }
+
+  /// Traces `Iobuf::unsafe_sub_window`.
+  pub unsafe fn unsafe_sub_window(&mut self, pos: u32, len: u32) {
+    let _ = self.trace(format!("unsafe_sub_window {} {}", pos, len),
+                       |b| Ok::<(), ()>(b.unsafe_sub_window(pos, len)), |_| String::new());
+  }
+
+  /// Traces `Iobuf::unsafe_sub_window_from`.
+  pub unsafe fn unsafe_sub_window_from(&mut self, pos: u32) {
+    let _ = self.trace(format!("unsafe_sub_window_from {}", pos),
+                       |b| Ok::<(), ()>(b.unsafe_sub_window_from(pos)), |_| String::new());
+  }
+
+  /// Traces `Iobuf::unsafe_sub_window_to`.
+  pub unsafe fn unsafe_sub_window_to(&mut self, len: u32) {
+    let _ = self.trace(format!("unsafe_sub_window_to {}", len),
+                       |b| Ok::<(), ()>(b.unsafe_sub_window_to(len)), |_| String::new());
+  }
+
+  /// Traces `Iobuf::unsafe_sub`.
+  pub unsafe fn unsafe_sub(&mut self, pos: u32, len: u32) {
+    let _ = self.trace(format!("unsafe_sub {} {}", pos, len),
+                       |b| Ok::<(), ()>(b.unsafe_sub(pos, len)), |_| String::new());
+  }
+
+  /// Traces `Iobuf::unsafe_sub_from`.
+  pub unsafe fn unsafe_sub_from(&mut self, pos: u32) {
+    let _ = self.trace(format!("unsafe_sub_from {}", pos),
+                       |b| Ok::<(), ()>(b.unsafe_sub_from(pos)), |_| String::new());
+  }
+
+  /// Traces `Iobuf::unsafe_sub_to`.
+  pub unsafe fn unsafe_sub_to(&mut self, len: u32) {
+    let _ = self.trace(format!("unsafe_sub_to {}", len),
+                       |b| Ok::<(), ()>(b.unsafe_sub_to(len)), |_| String::new());
+  }
+
+  /// Traces `Iobuf::unsafe_split_start_at`.
+  pub unsafe fn unsafe_split_start_at(&mut self, pos: u32) -> Buf {
+    self.trace(format!("unsafe_split_start_at {}", pos),
+               |b| Ok::<Buf, ()>(b.unsafe_split_start_at(pos)), |_| String::new()).unwrap()
+  }
+
+  /// Traces `Iobuf::unsafe_consume`.
+  pub unsafe fn unsafe_consume(&mut self, dst: &mut [u8]) {
+    let _ = self.trace(format!("unsafe_consume {}", dst.len()),
+                       |b| Ok::<(), ()>(b.unsafe_consume(dst)), |_| String::new());
+  }
+
+  /// Traces `Iobuf::unsafe_consume_be`.
+  pub unsafe fn unsafe_consume_be<T: Prim>(&mut self) -> T {
+    self.trace(format!("unsafe_consume_be {}-bit", prim_len::<T>() * 8),
+               |b| Ok::<T, ()>(b.unsafe_consume_be()), |&t| format!(" = 0x{:x}", bits(t))).unwrap()
+  }
+
+  /// Traces `Iobuf::unsafe_consume_le`.
+  pub unsafe fn unsafe_consume_le<T: Prim>(&mut self) -> T {
+    self.trace(format!("unsafe_consume_le {}-bit", prim_len::<T>() * 8),
+               |b| Ok::<T, ()>(b.unsafe_consume_le()), |&t| format!(" = 0x{:x}", bits(t))).unwrap()
+  }
+}
+
+impl<Buf: MutIobuf, W: Writer> Traced<Buf, W> {
+  /// Traces `IobufWrite::poke`.
+  pub fn poke(&mut self, pos: u32, src: &[u8]) -> Result<(), ()> {
+    self.trace(format!("poke {} {}", pos, src.len()), |b| b.poke(pos, src), |_| String::new())
+  }
+
+  /// Traces `IobufWrite::poke_be`.
+  pub fn poke_be<T: Prim>(&mut self, pos: u32, t: T) -> Result<(), ()> {
+    self.trace(format!("poke_be {}-bit {} 0x{:x}", prim_len::<T>() * 8, pos, bits(t)),
+               |b| b.poke_be(pos, t), |_| String::new())
+  }
+
+  /// Traces `IobufWrite::poke_le`.
+  pub fn poke_le<T: Prim>(&mut self, pos: u32, t: T) -> Result<(), ()> {
+    self.trace(format!("poke_le {}-bit {} 0x{:x}", prim_len::<T>() * 8, pos, bits(t)),
+               |b| b.poke_le(pos, t), |_| String::new())
+  }
+
+  /// Traces `IobufWrite::fill`.
+  pub fn fill(&mut self, src: &[u8]) -> Result<(), ()> {
+    self.trace(format!("fill {}", src.len()), |b| b.fill(src), |_| String::new())
+  }
+
+  /// Traces `IobufWrite::fill_be`.
+  pub fn fill_be<T: Prim>(&mut self, t: T) -> Result<(), ()> {
+    self.trace(format!("fill_be {}-bit 0x{:x}", prim_len::<T>() * 8, bits(t)),
+               |b| b.fill_be(t), |_| String::new())
+  }
+
+  /// Traces `IobufWrite::fill_le`.
+  pub fn fill_le<T: Prim>(&mut self, t: T) -> Result<(), ()> {
+    self.trace(format!("fill_le {}-bit 0x{:x}", prim_len::<T>() * 8, bits(t)),
+               |b| b.fill_le(t), |_| String::new())
+  }
+
+  /// Traces `MutIobuf::unsafe_poke`.
+  pub unsafe fn unsafe_poke(&mut self, pos: u32, src: &[u8]) {
+    let _ = self.trace(format!("unsafe_poke {} {}", pos, src.len()),
+                       |b| Ok::<(), ()>(b.unsafe_poke(pos, src)), |_| String::new());
+  }
+
+  /// Traces `MutIobuf::unsafe_poke_be`.
+  pub unsafe fn unsafe_poke_be<T: Prim>(&mut self, pos: u32, t: T) {
+    let _ = self.trace(format!("unsafe_poke_be {}-bit {} 0x{:x}", prim_len::<T>() * 8, pos, bits(t)),
+                       |b| Ok::<(), ()>(b.unsafe_poke_be(pos, t)), |_| String::new());
+  }
+
+  /// Traces `MutIobuf::unsafe_poke_le`.
+  pub unsafe fn unsafe_poke_le<T: Prim>(&mut self, pos: u32, t: T) {
+    let _ = self.trace(format!("unsafe_poke_le {}-bit {} 0x{:x}", prim_len::<T>() * 8, pos, bits(t)),
+                       |b| Ok::<(), ()>(b.unsafe_poke_le(pos, t)), |_| String::new());
+  }
+
+  /// Traces `MutIobuf::unsafe_fill`.
+  pub unsafe fn unsafe_fill(&mut self, src: &[u8]) {
+    let _ = self.trace(format!("unsafe_fill {}", src.len()),
+                       |b| Ok::<(), ()>(b.unsafe_fill(src)), |_| String::new());
+  }
+
+  /// Traces `MutIobuf::unsafe_fill_be`.
+  pub unsafe fn unsafe_fill_be<T: Prim>(&mut self, t: T) {
+    let _ = self.trace(format!("unsafe_fill_be {}-bit 0x{:x}", prim_len::<T>() * 8, bits(t)),
+                       |b| Ok::<(), ()>(b.unsafe_fill_be(t)), |_| String::new());
+  }
+
+  /// Traces `MutIobuf::unsafe_fill_le`.
+  pub unsafe fn unsafe_fill_le<T: Prim>(&mut self, t: T) {
+    let _ = self.trace(format!("unsafe_fill_le {}-bit 0x{:x}", prim_len::<T>() * 8, bits(t)),
+                       |b| Ok::<(), ()>(b.unsafe_fill_le(t)), |_| String::new());
+  }
+}
+
+impl<'a, W: Writer> Traced<RWIobuf<'a>, W> {
+  /// Traces `RWIobuf::fill_vectored`.
+  pub fn fill_vectored(&mut self, srcs: &[&[u8]]) -> Result<(), ()> {
+    let lens: Vec<String> = srcs.iter().map(|s| s.len().to_string()).collect();
+    self.trace(format!("fill_vectored [{}]", lens.connect(", ")),
+               |b| b.fill_vectored(srcs), |_| String::new())
+  }
+
+  /// Traces `RWIobuf::fill_reporting`.
+  pub fn fill_reporting(&mut self, src: &[u8]) -> Result<(), u32> {
+    self.trace(format!("fill_reporting {}", src.len()), |b| b.fill_reporting(src), |_| String::new())
+  }
+
+  /// Traces `RWIobuf::fill_json_escaped`. Only the string's length is
+  /// printed.
+  pub fn fill_json_escaped(&mut self, s: &str) -> Result<(), ()> {
+    self.trace(format!("fill_json_escaped {}", s.len()), |b| b.fill_json_escaped(s), |_| String::new())
+  }
+
+  /// Traces `RWIobuf::fill_fixed_be`.
+  pub fn fill_fixed_be(&mut self, x: f64, int_bits: u8, frac_bits: u8) -> Result<(), ()> {
+    self.trace(format!("fill_fixed_be Q{}.{} {}", int_bits, frac_bits, x),
+               |b| b.fill_fixed_be(x, int_bits, frac_bits), |_| String::new())
+  }
+
+  /// Traces `RWIobuf::fill_q16_16_be`.
+  pub fn fill_q16_16_be(&mut self, x: f64) -> Result<(), ()> {
+    self.trace(format!("fill_q16_16_be {}", x), |b| b.fill_q16_16_be(x), |_| String::new())
+  }
+
+  /// Traces `RWIobuf::fill_ip`.
+  pub fn fill_ip(&mut self, ip: IpAddr) -> Result<(), ()> {
+    self.trace(format!("fill_ip {}", ip), |b| b.fill_ip(ip), |_| String::new())
+  }
+
+  /// Traces `RWIobuf::fill_socket_addr`.
+  pub fn fill_socket_addr(&mut self, addr: SocketAddr) -> Result<(), ()> {
+    self.trace(format!("fill_socket_addr {}", addr), |b| b.fill_socket_addr(addr), |_| String::new())
+  }
+
+  /// Traces `RWIobuf::compact`.
+  pub fn compact(&mut self) {
+    let _ = self.trace("compact".to_string(), |b| Ok::<(), ()>(b.compact()), |_| String::new());
+  }
+
+  /// Traces `RWIobuf::unsafe_compact`.
+  pub unsafe fn unsafe_compact(&mut self) {
+    let _ = self.trace("unsafe_compact".to_string(), |b| Ok::<(), ()>(b.unsafe_compact()), |_| String::new());
+  }
+
+  /// Traces `RWIobuf::compact_if`.
+  pub fn compact_if<F: FnOnce(&RWIobuf<'a>) -> bool>(&mut self, should: F) -> bool {
+    self.trace("compact_if".to_string(),
+               |b| Ok::<bool, ()>(b.compact_if(should)), |&x| format!(" = {}", x)).unwrap()
+  }
+
+  /// Traces `RWIobuf::compact_if_lo_space_exceeds`.
+  pub fn compact_if_lo_space_exceeds(&mut self, threshold: u32) -> bool {
+    self.trace(format!("compact_if_lo_space_exceeds {}", threshold),
+               |b| Ok::<bool, ()>(b.compact_if_lo_space_exceeds(threshold)), |&x| format!(" = {}", x)).unwrap()
+  }
+
+  /// Traces `RWIobuf::reset_to_front`.
+  pub fn reset_to_front(&mut self) {
+    let _ = self.trace("reset_to_front".to_string(), |b| Ok::<(), ()>(b.reset_to_front()), |_| String::new());
+  }
+
+  /// Traces `RWIobuf::resize_zeroed`.
+  pub fn resize_zeroed(&mut self, len: u32) -> Result<(), ()> {
+    self.trace(format!("resize_zeroed {}", len), |b| b.resize_zeroed(len), |_| String::new())
+  }
+
+  /// Traces `RWIobuf::dedup`.
+  pub fn dedup(&mut self) -> u32 {
+    self.trace("dedup".to_string(), |b| Ok::<u32, ()>(b.dedup()), |&n| format!(" = {}", n)).unwrap()
+  }
+
+  /// Traces `RWIobuf::dedup_by`.
+  pub fn dedup_by<F: FnMut(u8, u8) -> bool>(&mut self, same: F) -> u32 {
+    self.trace("dedup_by".to_string(), |b| Ok::<u32, ()>(b.dedup_by(same)), |&n| format!(" = {}", n)).unwrap()
+  }
+
+  /// Traces `RWIobuf::fill_with`.
+  pub unsafe fn fill_with<F: FnOnce(&mut [u8])>(&mut self, len: u32, f: F) -> Result<(), ()> {
+    self.trace(format!("fill_with {}", len), |b| b.fill_with(len, f), |_| String::new())
+  }
+
+  /// Traces `RWIobuf::reserve_iobuf`.
+  pub fn reserve_iobuf(&mut self, len: u32) -> Result<RWIobuf<'a>, ()> {
+    self.trace(format!("reserve_iobuf {}", len), |b| b.reserve_iobuf(len), |_| String::new())
+  }
+
+  /// Traces `RWIobuf::fill_native_slice`.
+  pub fn fill_native_slice<T: Prim>(&mut self, src: &[T]) -> Result<(), ()> {
+    self.trace(format!("fill_native_slice {}-bit {}", prim_len::<T>() * 8, src.len()),
+               |b| b.fill_native_slice(src), |_| String::new())
+  }
+
+  /// Traces `RWIobuf::fill_exact_from_reader`.
+  pub fn fill_exact_from_reader<R: Reader>(&mut self, r: &mut R, n: u32) -> Result<u32, ()> {
+    self.trace(format!("fill_exact_from_reader {}", n),
+               |b| b.fill_exact_from_reader(r, n), |&x| format!(" = {}", x))
+  }
+
+  /// Traces `RWIobuf::fill_prim`.
+  pub fn fill_prim<T: Prim>(&mut self, t: T, big_endian: bool) -> Result<(), ()> {
+    self.trace(format!("fill_prim {}-bit 0x{:x} {}", prim_len::<T>() * 8, bits(t), if big_endian { "be" } else { "le" }),
+               |b| b.fill_prim(t, big_endian), |_| String::new())
+  }
+
+  /// Traces `RWIobuf::fill_biguint_be`.
+  #[cfg(feature = "num-bigint")]
+  pub fn fill_biguint_be(&mut self, n: &BigUint, len: u32) -> Result<(), ()> {
+    self.trace(format!("fill_biguint_be {} {}", n, len), |b| b.fill_biguint_be(n, len), |_| String::new())
+  }
+}
+
+#[test]
+fn scripted_trace() {
+  let mut out = Vec::new();
+  {
+    let mut b = Traced::new(RWIobuf::new(8), &mut out);
+    assert_eq!(b.fill_be(0x0304u16), Ok(()));
+    assert_eq!(b.fill_le(-1i8), Ok(()));
+    assert_eq!(b.fill(&[0; 6]), Err(()));
+    b.flip_lo();
+    assert_eq!(b.consume_be::<u16>(), Ok(0x0304));
+    assert_eq!(b.peek_be::<u8>(0), Ok(0xFF));
+    assert_eq!(b.peek_be::<u8>(1), Err(()));
+    assert_eq!(b.advance(1), Ok(()));
+    b.compact();
+    assert_eq!(b.fill_vectored(&[ b"GET", b" " ]), Ok(()));
+    b.flip_lo();
+    assert_eq!(b.consume_tag(b"GET"), Ok(()));
+    assert_eq!(b.consume_tag(b"PUT"), Err(TagError::Mismatch(0)));
+    assert_eq!(b.advance_upto(5), 1);
+  }
+
+  let trace = String::from_utf8(out).unwrap();
+  let lines: Vec<&str> = trace.lines().collect();
+  assert_eq!(lines, vec![
+    "fill_be 16-bit 0x304 @ [0, 8) -> [2, 8)",
+    "fill_le 8-bit 0xff @ [2, 8) -> [3, 8)",
+    "fill 6 @ [3, 8) FAILED",
+    "flip_lo @ [3, 8) -> [0, 3)",
+    "consume_be 16-bit @ [0, 3) -> [2, 3) = 0x304",
+    "peek_be 8-bit 0 @ [2, 3) -> [2, 3) = 0xff",
+    "peek_be 8-bit 1 @ [2, 3) FAILED",
+    "advance 1 @ [2, 3) -> [3, 3)",
+    "compact @ [3, 3) -> [0, 8)",
+    "fill_vectored [3, 1] @ [0, 8) -> [4, 8)",
+    "flip_lo @ [4, 8) -> [0, 4)",
+    "consume_tag 3 @ [0, 4) -> [3, 4)",
+    "consume_tag 3 @ [3, 4) FAILED",
+    "advance_upto 5 @ [3, 4) -> [4, 4) = 1",
+  ]);
+}
+
+#[test]
+fn nested_trace() {
+  use impls::ROIobuf;
+
+  let mut out = Vec::new();
+  {
+    let data = b"\x02\x05 abc \x03de \xAA\xBB";
+    let mut b = Traced::new(ROIobuf::from_slice(data), &mut out);
+    assert_eq!(b.consume_be::<u8>(), Ok(2));
+    assert_eq!(b.consume_repeat(2, |t| {
+      let len = try!(t.consume_be::<u8>()) as u32;
+      t.with_sub_window(0, len, |t| t.trim_ascii_whitespace())
+    }), Ok(vec![ (), () ]));
+    assert_eq!(b.with_rollback(|t| {
+      try!(t.advance(1));
+      t.advance(5)
+    }), Err(()));
+    assert!(b.split_to_owned(2).is_ok());
+    assert_eq!(b.consume_be_opt::<u8>(), None);
+    assert_eq!(b.extend_upto(5), 0);
+  }
+
+  let trace = String::from_utf8(out).unwrap();
+  let lines: Vec<&str> = trace.lines().collect();
+  assert_eq!(lines, vec![
+    "consume_be 8-bit @ [0, 13) -> [1, 13) = 0x2",
+    "consume_be 8-bit @ [1, 13) -> [2, 13) = 0x5",
+    "trim_ascii_whitespace @ [2, 7) -> [3, 6)",
+    "with_sub_window 0 5 @ [2, 13) -> [7, 13)",
+    "consume_be 8-bit @ [7, 13) -> [8, 13) = 0x3",
+    "trim_ascii_whitespace @ [8, 11) -> [8, 10)",
+    "with_sub_window 0 3 @ [8, 13) -> [11, 13)",
+    "consume_repeat 2 @ [1, 13) -> [11, 13)",
+    "advance 1 @ [11, 13) -> [12, 13)",
+    "advance 5 @ [12, 13) FAILED",
+    "with_rollback @ [11, 13) FAILED",
+    "split_to_owned 2 @ [11, 13) -> [13, 13)",
+    "consume_be_opt 8-bit @ [13, 13) FAILED",
+    "extend_upto 5 @ [13, 13) -> [13, 13) = 0",
+  ]);
+}
@@ -0,0 +1,102 @@
+use std::ops::{Deref, DerefMut};
+
+use iobuf::Iobuf;
+
+/// A guard which rolls an Iobuf's window and limits back to where they were
+/// when it was made, unless it's `commit`ted. See `Iobuf::transaction`.
+///
+/// The guard derefs to the Iobuf, so consume through it as usual.
+pub struct BufTransaction<'b, Buf: 'b> {
+  buf:   &'b mut Buf,
+  // `None` once committed or rolled back.
+  saved: Option<Buf>,
+}
+
+impl<'b, Buf: Iobuf> BufTransaction<'b, Buf> {
+  /// Snapshots `buf`'s window and limits.
+  #[inline]
+  pub fn new(buf: &'b mut Buf) -> BufTransaction<'b, Buf> {
+    let saved = buf.clone();
+    BufTransaction { buf: buf, saved: Some(saved) }
+  }
+
+  /// Keeps everything done to the window since the snapshot.
+  #[inline]
+  pub fn commit(mut self) {
+    self.saved = None;
+  }
+
+  /// Puts the window and limits back to the snapshot. This is what dropping
+  /// the guard does, too.
+  #[inline]
+  pub fn rollback(self) {}
+}
+
+impl<'b, Buf> Deref for BufTransaction<'b, Buf> {
+  type Target = Buf;
+
+  #[inline(always)]
+  fn deref(&self) -> &Buf { &*self.buf }
+}
+
+impl<'b, Buf> DerefMut for BufTransaction<'b, Buf> {
+  #[inline(always)]
+  fn deref_mut(&mut self) -> &mut Buf { &mut *self.buf }
+}
+
+#[unsafe_destructor]
+impl<'b, Buf> Drop for BufTransaction<'b, Buf> {
+  #[inline]
+  fn drop(&mut self) {
+    match self.saved.take() {
+      Some(saved) => *self.buf = saved,
+      None        => {},
+    }
+  }
+}
+
+#[test]
+fn commit_and_rollback() {
+  use impls::ROIobuf;
+
+  let data = [ 1, 2, 3, 4, 5, 6 ];
+  let mut b = ROIobuf::from_slice(&data);
+
+  {
+    let mut t = b.transaction();
+    assert_eq!(t.consume_be::<u16>(), Ok(0x0102));
+    t.commit();
+  }
+  assert_eq!(b.len(), 4);
+
+  {
+    let mut t = b.transaction();
+    assert_eq!(t.advance(3), Ok(()));
+    t.rollback();
+  }
+  assert_eq!(b.len(), 4);
+
+  {
+    let mut t = b.transaction();
+    assert_eq!(t.advance(1), Ok(()));
+    assert_eq!(t.resize(1), Ok(()));
+    t.narrow();
+    // Dropped without a commit: the limits come back too.
+  }
+  assert_eq!(b.len(), 4);
+  assert_eq!(b.cap(), 6);
+  assert_eq!(b.peek_be::<u32>(0), Ok(0x03040506));
+
+  // Nested transactions.
+  {
+    let mut outer = b.transaction();
+    assert_eq!(outer.advance(1), Ok(()));
+    {
+      let mut inner = outer.transaction();
+      assert_eq!(inner.advance(2), Ok(()));
+    }
+    assert_eq!(outer.len(), 3);
+    outer.commit();
+  }
+  assert_eq!(b.len(), 3);
+}
@@ -0,0 +1,92 @@
+use std::io::IoResult;
+use std::io::net::ip::SocketAddr;
+use std::io::net::udp::UdpSocket;
+
+use iobuf::Iobuf;
+use impls::RWIobuf;
+
+/// The result of receiving a datagram with `recv_from_into`.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Datagram {
+  /// A whole datagram of the given length was received from the given address.
+  Whole(u32, SocketAddr),
+  /// The datagram filled the whole window, so some of it may have been cut
+  /// off. The window was still advanced past the bytes that were received.
+  Truncated(u32, SocketAddr),
+}
+
+/// Receives a single datagram into the window, and advances the window past
+/// it.
+///
+/// The operating system silently throws away whatever part of a datagram
+/// doesn't fit. Since there's no telling whether a datagram which exactly fits
+/// the window was cut short, it's reported as `Truncated`. Make the window at
+/// least one byte longer than the largest datagram you expect.
+///
+/// ```rust
+/// use std::io::net::ip::{Ipv4Addr, SocketAddr};
+/// use std::io::net::udp::UdpSocket;
+/// use iobuf::{Datagram, Iobuf, ROIobuf, RWIobuf, recv_from_into, send_to_from};
+///
+/// let local = SocketAddr { ip: Ipv4Addr(127, 0, 0, 1), port: 0 };
+/// let mut a = UdpSocket::bind(local).unwrap();
+/// let mut b = UdpSocket::bind(local).unwrap();
+/// let b_addr = b.socket_name().unwrap();
+///
+/// let mut out = ROIobuf::from_str("hello");
+/// assert_eq!(send_to_from(&mut a, &mut out, b_addr).unwrap(), 5);
+/// assert!(out.is_empty());
+///
+/// let mut buf = RWIobuf::new(100);
+/// match recv_from_into(&mut b, &mut buf).unwrap() {
+///   Datagram::Whole(n, from) => {
+///     assert_eq!(n, 5);
+///     assert_eq!(from, a.socket_name().unwrap());
+///   }
+///   Datagram::Truncated(..) => panic!("truncated"),
+/// }
+///
+/// buf.flip_lo();
+/// unsafe { assert_eq!(buf.as_window_slice(), b"hello"); }
+/// ```
+pub fn recv_from_into(socket: &mut UdpSocket, buf: &mut RWIobuf) -> IoResult<Datagram> {
+  let len = buf.len();
+  let (n, addr) = try!(socket.recv_from(unsafe { buf.as_mut_window_slice() }));
+  let n = n as u32;
+  unsafe { buf.unsafe_advance(n) };
+  Ok(if n == len { Datagram::Truncated(n, addr) } else { Datagram::Whole(n, addr) })
+}
+
+/// Sends the window as a single datagram to `addr`, and advances the window
+/// past it. Returns the number of bytes sent.
+pub fn send_to_from<Buf: Iobuf>(socket: &mut UdpSocket, buf: &mut Buf, addr: SocketAddr) -> IoResult<u32> {
+  let len = buf.len();
+  try!(socket.send_to(unsafe { buf.as_window_slice() }, addr));
+  unsafe { buf.unsafe_advance(len) };
+  Ok(len)
+}
+
+#[test]
+fn udp_truncation() {
+  use std::io::net::ip::Ipv4Addr;
+  use impls::ROIobuf;
+
+  let local = SocketAddr { ip: Ipv4Addr(127, 0, 0, 1), port: 0 };
+  let mut a = UdpSocket::bind(local).unwrap();
+  let mut b = UdpSocket::bind(local).unwrap();
+  let a_addr = a.socket_name().unwrap();
+  let b_addr = b.socket_name().unwrap();
+
+  assert_eq!(send_to_from(&mut a, &mut ROIobuf::from_str("0123456789"), b_addr), Ok(10));
+  assert_eq!(send_to_from(&mut a, &mut ROIobuf::from_str("abcd"), b_addr), Ok(4));
+
+  let mut buf = RWIobuf::new(6);
+  assert_eq!(recv_from_into(&mut b, &mut buf), Ok(Datagram::Truncated(6, a_addr)));
+  assert!(buf.is_empty());
+
+  let mut buf = RWIobuf::new(6);
+  assert_eq!(recv_from_into(&mut b, &mut buf), Ok(Datagram::Whole(4, a_addr)));
+  assert_eq!(buf.len(), 2);
+  buf.flip_lo();
+  unsafe { assert_eq!(buf.as_window_slice(), b"abcd"); }
+}
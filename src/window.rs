@@ -0,0 +1,54 @@
+use std::ops::Deref;
+
+use iobuf::Iobuf;
+
+/// A borrow of an Iobuf's window as a slice, which remembers where the window
+/// starts in the underlying buffer. See `Iobuf::window_guard_positioned`.
+///
+/// This is for handing the window to a parser which works on plain slices, and
+/// translating the offsets it reports back into offsets into the buffer, for
+/// error messages and logging.
+pub struct PositionedWindow<'b, Buf: 'b> {
+  buf: &'b mut Buf,
+}
+
+impl<'b, Buf: Iobuf> PositionedWindow<'b, Buf> {
+  /// Borrows `buf`'s window. This is unsafe for the same reasons as
+  /// `Iobuf::window_guard_positioned`.
+  #[inline(always)]
+  pub unsafe fn new(buf: &'b mut Buf) -> PositionedWindow<'b, Buf> {
+    PositionedWindow { buf: buf }
+  }
+
+  /// The offset of the start of the window into the buffer returned by
+  /// `ptr()`, the same as `lo()`.
+  #[inline(always)]
+  pub fn start(&self) -> u32 { self.buf.lo() }
+
+  /// Translates an offset into the slice into an offset into the buffer.
+  #[inline(always)]
+  pub fn absolute(&self, pos: usize) -> u32 { self.buf.lo() + pos as u32 }
+}
+
+impl<'b, Buf: Iobuf> Deref for PositionedWindow<'b, Buf> {
+  type Target = [u8];
+
+  // Whoever made the guard promised nothing pokes into the window meanwhile.
+  #[inline(always)]
+  fn deref(&self) -> &[u8] { unsafe { self.buf.as_window_slice() } }
+}
+
+#[test]
+fn start_tracks_the_window() {
+  use impls::ROIobuf;
+
+  let mut b = ROIobuf::from_str("0123456789");
+  assert_eq!(b.advance(3), Ok(()));
+  assert_eq!(b.resize(4), Ok(()));
+
+  let w = unsafe { b.window_guard_positioned() };
+  assert_eq!(&*w, b"3456");
+  assert_eq!(w.start(), 3);
+  assert_eq!(w.absolute(0), 3);
+  assert_eq!(w.absolute(4), 7);
+}
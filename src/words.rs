@@ -0,0 +1,92 @@
+use std::marker::CovariantType;
+use std::mem;
+use std::num::Int;
+use std::ptr;
+
+use raw::{Prim, prim_len};
+
+/// An iterator over the primitives in an Iobuf's window, decoded as either
+/// big-endian or little-endian. See `Iobuf::iter_be` and `Iobuf::iter_le`.
+///
+/// Iteration stops at the last complete primitive. If the window's length
+/// isn't a multiple of the primitive's size, the leftover bytes at the end
+/// are skipped, and can be retrieved with `remainder`.
+pub struct WordIter<'b, T> {
+  data:       &'b [u8],
+  big_endian: bool,
+  marker:     CovariantType<T>,
+}
+
+impl<'b, T: Prim> WordIter<'b, T> {
+  /// Iterates over the primitives in `data`.
+  #[inline]
+  pub fn new(data: &'b [u8], big_endian: bool) -> WordIter<'b, T> {
+    WordIter {
+      data:       data,
+      big_endian: big_endian,
+      marker:     CovariantType,
+    }
+  }
+
+  /// The bytes at the end of the window that are too short to make up a
+  /// whole primitive. This is empty if the window's length is a multiple of
+  /// the primitive's size.
+  #[inline]
+  pub fn remainder(&self) -> &'b [u8] {
+    let len = self.data.len();
+    &self.data[len - len % prim_len::<T>()..]
+  }
+}
+
+impl<'b, T: Prim> Iterator for WordIter<'b, T> {
+  type Item = T;
+
+  #[inline]
+  fn next(&mut self) -> Option<T> {
+    let size = prim_len::<T>();
+    if self.data.len() < size { return None; }
+
+    let t = unsafe {
+      let mut t: T::Repr = mem::uninitialized();
+      ptr::copy_nonoverlapping_memory(
+        &mut t as *mut T::Repr as *mut u8,
+        self.data.as_ptr(),
+        size);
+      Prim::from_repr(if self.big_endian { Int::from_be(t) } else { Int::from_le(t) })
+    };
+
+    self.data = &self.data[size..];
+    Some(t)
+  }
+
+  #[inline]
+  fn size_hint(&self) -> (usize, Option<usize>) {
+    let n = self.data.len() / prim_len::<T>();
+    (n, Some(n))
+  }
+}
+
+#[test]
+fn iter_words() {
+  use iobuf::Iobuf;
+  use impls::ROIobuf;
+
+  let data = [ 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x02, 0xAA, 0xBB ];
+  let b = ROIobuf::from_slice(&data);
+
+  assert_eq!(b.iter_be::<u32>().collect::<Vec<u32>>(), vec!(1, 2));
+  assert_eq!(b.iter_le::<u32>().collect::<Vec<u32>>(), vec!(0x01000000, 0x02000000));
+  assert_eq!(b.iter_be::<u16>().collect::<Vec<u16>>(), vec!(0, 1, 0, 2, 0xAABB));
+  assert_eq!(b.iter_le::<u64>().collect::<Vec<u64>>(), vec!(0x02000000_01000000));
+  assert_eq!(b.iter_be::<u8>().count(), 10);
+
+  let mut it = b.iter_be::<u32>();
+  assert_eq!(it.size_hint(), (2, Some(2)));
+  assert_eq!(it.remainder(), [ 0xAA, 0xBB ]);
+  it.next();
+  it.next();
+  assert_eq!(it.next(), None);
+  assert_eq!(it.remainder(), [ 0xAA, 0xBB ]);
+
+  assert_eq!(b.iter_be::<u16>().remainder(), []);
+}
@@ -0,0 +1,261 @@
+//! Streaming zlib compression between Iobufs. Enabled with the `zlib` cargo
+//! feature.
+//!
+//! The compressor reads straight out of the source window and writes straight
+//! into the destination window, so no intermediate copies are made. Each call
+//! advances the source past the input it consumed, and the destination past
+//! the output it produced. When a call returns `NeedInput` or `NeedOutput`,
+//! refill (or `compact` and refill) the appropriate buffer and call it again.
+
+use std::mem;
+
+use libz;
+
+use iobuf::Iobuf;
+use impls::RWIobuf;
+
+/// What a call to `Inflater::inflate` or `Deflater::deflate` is waiting on.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum FlateStatus {
+  /// The end of the stream was reached. Any input after it was left in the
+  /// source window.
+  Finished,
+  /// All of the source window was consumed. Provide more input to continue.
+  NeedInput,
+  /// The destination window is full. Provide more space to continue.
+  NeedOutput,
+}
+
+/// Runs one step of a zlib stream over the windows of `src` and `dst`.
+unsafe fn step<Buf, F>(strm: &mut libz::z_stream, src: &mut Buf, dst: &mut RWIobuf, f: F) -> Result<FlateStatus, ()>
+    where Buf: Iobuf, F: FnOnce(&mut libz::z_stream) -> i32 {
+  let (in_len, out_len) = (src.len(), dst.len());
+
+  strm.next_in   = src.as_window_slice().as_ptr() as *mut u8;
+  strm.avail_in  = in_len;
+  strm.next_out  = dst.as_mut_window_slice().as_mut_ptr();
+  strm.avail_out = out_len;
+
+  let ret = f(strm);
+
+  let consumed = in_len  - strm.avail_in;
+  let produced = out_len - strm.avail_out;
+
+  strm.next_in  = 0 as *mut u8;
+  strm.next_out = 0 as *mut u8;
+
+  src.unsafe_advance(consumed);
+  dst.unsafe_advance(produced);
+
+  match ret {
+    libz::Z_STREAM_END => Ok(FlateStatus::Finished),
+    // Z_BUF_ERROR just means no progress could be made, and that's always
+    // because one of the windows is empty.
+    libz::Z_OK | libz::Z_BUF_ERROR =>
+      if dst.is_empty() { Ok(FlateStatus::NeedOutput) }
+      else              { Ok(FlateStatus::NeedInput)  },
+    _ => Err(()),
+  }
+}
+
+/// A zlib decompressor.
+///
+/// ```rust
+/// use iobuf::{Deflater, Inflater, FlateStatus, Iobuf, ROIobuf, RWIobuf};
+///
+/// let mut compressed = RWIobuf::new(128);
+/// let mut d = Deflater::new(6);
+/// let mut src = ROIobuf::from_str("hello, hello, hello, hello");
+/// assert_eq!(d.deflate(&mut src, &mut compressed, true), Ok(FlateStatus::Finished));
+/// compressed.flip_lo();
+///
+/// let mut out = RWIobuf::new(128);
+/// let mut i = Inflater::new();
+/// assert_eq!(i.inflate(&mut compressed, &mut out), Ok(FlateStatus::Finished));
+/// assert!(compressed.is_empty());
+/// out.flip_lo();
+///
+/// unsafe { assert_eq!(out.as_window_slice(), b"hello, hello, hello, hello"); }
+/// ```
+pub struct Inflater {
+  // zlib keeps a pointer back to the stream, so it can't move.
+  strm: Box<libz::z_stream>,
+}
+
+impl Inflater {
+  /// Creates a decompressor for a zlib-wrapped deflate stream.
+  pub fn new() -> Inflater {
+    unsafe {
+      let mut strm: Box<libz::z_stream> = Box::new(mem::zeroed());
+      let ret = libz::inflateInit_(&mut *strm,
+                                   libz::zlibVersion(),
+                                   mem::size_of::<libz::z_stream>() as i32);
+      if ret != libz::Z_OK { panic!("iobuf: inflateInit failed: {}", ret) }
+      Inflater { strm: strm }
+    }
+  }
+
+  /// Decompresses as much of `src`'s window as possible into `dst`'s window,
+  /// advancing `src` past the consumed input and `dst` past the produced
+  /// output.
+  ///
+  /// Returns `Err(())` if the input is not a valid zlib stream.
+  pub fn inflate<Buf: Iobuf>(&mut self, src: &mut Buf, dst: &mut RWIobuf) -> Result<FlateStatus, ()> {
+    unsafe {
+      step(&mut *self.strm, src, dst, |strm| libz::inflate(strm, libz::Z_NO_FLUSH))
+    }
+  }
+}
+
+impl Drop for Inflater {
+  fn drop(&mut self) {
+    unsafe { libz::inflateEnd(&mut *self.strm); }
+  }
+}
+
+/// A zlib compressor.
+pub struct Deflater {
+  // zlib keeps a pointer back to the stream, so it can't move.
+  strm: Box<libz::z_stream>,
+}
+
+impl Deflater {
+  /// Creates a compressor producing a zlib-wrapped deflate stream. `level`
+  /// ranges from 0 (no compression) to 9 (best compression).
+  pub fn new(level: u32) -> Deflater {
+    assert!(level <= 9);
+    unsafe {
+      let mut strm: Box<libz::z_stream> = Box::new(mem::zeroed());
+      let ret = libz::deflateInit_(&mut *strm,
+                                   level as i32,
+                                   libz::zlibVersion(),
+                                   mem::size_of::<libz::z_stream>() as i32);
+      if ret != libz::Z_OK { panic!("iobuf: deflateInit failed: {}", ret) }
+      Deflater { strm: strm }
+    }
+  }
+
+  /// Compresses as much of `src`'s window as possible into `dst`'s window,
+  /// advancing `src` past the consumed input and `dst` past the produced
+  /// output.
+  ///
+  /// Pass `finish = true` once `src` holds the last of the input. Keep calling
+  /// with `finish = true` (and more room in `dst`) until `Finished` is
+  /// returned.
+  pub fn deflate<Buf: Iobuf>(&mut self, src: &mut Buf, dst: &mut RWIobuf, finish: bool) -> Result<FlateStatus, ()> {
+    let flush = if finish { libz::Z_FINISH } else { libz::Z_NO_FLUSH };
+    unsafe {
+      step(&mut *self.strm, src, dst, |strm| libz::deflate(strm, flush))
+    }
+  }
+}
+
+impl Drop for Deflater {
+  fn drop(&mut self) {
+    unsafe { libz::deflateEnd(&mut *self.strm); }
+  }
+}
+
+#[cfg(test)]
+fn test_data() -> Vec<u8> {
+  range(0u32, 20000).map(|i| ((i * i) % 251) as u8 ^ (i / 97) as u8).collect()
+}
+
+#[cfg(test)]
+fn deflate_in_pieces(data: &[u8], in_step: usize, out_cap: usize) -> Vec<u8> {
+  use impls::ROIobuf;
+
+  let mut d   = Deflater::new(6);
+  let mut out = vec!();
+  let mut dst = RWIobuf::new(out_cap);
+
+  let mut pos = 0;
+  loop {
+    let end    = ::std::cmp::min(pos + in_step, data.len());
+    let finish = end == data.len();
+    let mut src = ROIobuf::from_slice(&data[pos..end]);
+
+    loop {
+      let status = d.deflate(&mut src, &mut dst, finish).unwrap();
+      dst.flip_lo();
+      out.push_all(unsafe { dst.as_window_slice() });
+      dst.reset();
+      match status {
+        FlateStatus::Finished   => return out,
+        FlateStatus::NeedOutput => {},
+        FlateStatus::NeedInput  => break,
+      }
+    }
+
+    assert!(src.is_empty());
+    pos = end;
+  }
+}
+
+#[cfg(test)]
+fn inflate_in_pieces(data: &[u8], in_step: usize, out_cap: usize) -> Vec<u8> {
+  use impls::ROIobuf;
+
+  let mut i   = Inflater::new();
+  let mut out = vec!();
+  let mut dst = RWIobuf::new(out_cap);
+
+  let mut pos = 0;
+  loop {
+    let end = ::std::cmp::min(pos + in_step, data.len());
+    let mut src = ROIobuf::from_slice(&data[pos..end]);
+
+    loop {
+      let status = i.inflate(&mut src, &mut dst).unwrap();
+      dst.flip_lo();
+      out.push_all(unsafe { dst.as_window_slice() });
+      dst.reset();
+      match status {
+        FlateStatus::Finished   => { assert!(src.is_empty()); return out },
+        FlateStatus::NeedOutput => {},
+        FlateStatus::NeedInput  => break,
+      }
+    }
+
+    assert!(end < data.len());
+    pos = end;
+  }
+}
+
+#[test]
+fn round_trip_in_pieces() {
+  let data = test_data();
+
+  for &(in_step, out_cap) in [ (1, 1), (7, 13), (100, 3), (4096, 4096), (20000, 100000) ].iter() {
+    let compressed = deflate_in_pieces(&data[], in_step, out_cap);
+    assert!(compressed.len() < data.len());
+
+    for &(in_step2, out_cap2) in [ (1, 1), (13, 7), (3, 100), (100000, 20000) ].iter() {
+      assert_eq!(inflate_in_pieces(&compressed[], in_step2, out_cap2), data);
+    }
+  }
+}
+
+#[test]
+fn inflate_garbage() {
+  use impls::ROIobuf;
+
+  let mut i   = Inflater::new();
+  let mut src = ROIobuf::from_str("this is not a zlib stream");
+  let mut dst = RWIobuf::new(100);
+  assert_eq!(i.inflate(&mut src, &mut dst), Err(()));
+}
+
+#[test]
+fn trailing_input_is_left_alone() {
+  use impls::ROIobuf;
+
+  let mut compressed = deflate_in_pieces(b"abc", 10, 10);
+  compressed.push_all(b"xyz");
+
+  let mut i   = Inflater::new();
+  let mut src = ROIobuf::from_slice(&compressed[]);
+  let mut dst = RWIobuf::new(100);
+  assert_eq!(i.inflate(&mut src, &mut dst), Ok(FlateStatus::Finished));
+  unsafe { assert_eq!(src.as_window_slice(), b"xyz"); }
+}